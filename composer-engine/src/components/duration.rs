@@ -77,6 +77,27 @@ impl NoteDuration {
         }
     }
 
+    /// The time signature denominator for the duration, ie. Quarter -> 4
+    pub fn to_denominator(&self) -> u8 {
+        match self {
+            NoteDuration::Whole => 1,
+            NoteDuration::Half => 2,
+            NoteDuration::Quarter => 4,
+            NoteDuration::Eighth => 8,
+            NoteDuration::Sixteenth => 16,
+            NoteDuration::ThirtySecond => 32,
+            NoteDuration::SixtyFourth => 64,
+            NoteDuration::HudredTwentyEighth => 128,
+        }
+    }
+
+    pub fn from_denominator(denominator: u8) -> Option<NoteDuration> {
+        NOTE_DURATIONS
+            .iter()
+            .find(|duration| duration.to_denominator() == denominator)
+            .copied()
+    }
+
     pub fn spacing_ratio(&self, ratio: f32, is_dotted: bool) -> f32 {
         let base = match self {
             NoteDuration::Whole => ratio * 2.0,
@@ -0,0 +1,40 @@
+use super::pitch::{Accidental, Pitch};
+use serde::{Deserialize, Serialize};
+
+/// A diatonic interval, the steps are needed as well as the semitones
+/// so that transposed pitches can be spelled correctly.
+/// ie. a major 2nd is 1 step and 2 semitones, a diminished 3rd is 2 steps and 2 semitones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Interval {
+    pub steps: i8,
+    pub semitones: i8,
+}
+
+impl Interval {
+    pub const UNISON: Interval = Interval {
+        steps: 0,
+        semitones: 0,
+    };
+
+    pub fn new(steps: i8, semitones: i8) -> Self {
+        Self { steps, semitones }
+    }
+
+    pub fn is_unison(&self) -> bool {
+        self.steps == 0 && self.semitones == 0
+    }
+
+    /// The same interval in the opposite direction
+    pub fn invert(&self) -> Self {
+        Self {
+            steps: -self.steps,
+            semitones: -self.semitones,
+        }
+    }
+
+    /// How far the interval moves around the circle of fifths
+    /// ie. a major 2nd is 2 fifths (C -> D), a perfect 5th is 1 fifth (C -> G)
+    pub fn fifths(&self) -> i8 {
+        Pitch::new(60, Accidental::Natural).transpose(self).fifths()
+    }
+}
@@ -1,8 +1,10 @@
 pub mod articulation;
 pub mod duration;
+pub mod interval;
 pub mod measurements;
 pub mod misc;
 pub mod pitch;
 pub mod text;
+pub mod tuning;
 pub mod units;
 pub mod velocity;
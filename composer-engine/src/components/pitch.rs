@@ -1,8 +1,16 @@
+use super::interval::Interval;
+use super::tuning::Tuning;
 use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 
 const C0: u8 = 12;
 
+/// half steps above C for each natural step (C, D, E, F, G, A, B)
+const STEP_HALF_STEPS: [u8; 7] = [0, 2, 4, 5, 7, 9, 11];
+
+/// position on the circle of fifths for each natural step (C, D, E, F, G, A, B)
+const STEP_FIFTHS: [i8; 7] = [0, 2, 4, -1, 1, 3, 5];
+
 #[wasm_bindgen]
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum Accidental {
@@ -11,11 +19,25 @@ pub enum Accidental {
     Natural,
     Flat,
     DoubleFlat,
+
+    // Stein-Zimmermann quarter tones
+    QuarterSharp,
+    ThreeQuarterSharp,
+    QuarterFlat,
+    ThreeQuarterFlat,
+
+    // Gould arrows, a quarter tone either side of the accidental
+    NaturalArrowUp,
+    NaturalArrowDown,
+    SharpArrowUp,
+    SharpArrowDown,
+    FlatArrowUp,
+    FlatArrowDown,
 }
 
 impl Accidental {
-    // TODO: Make this better by working it out within the context of a key
-    /// When there is no user defined accidental, we work it out from the pitch
+    /// When there is no user defined accidental and no key to spell within, we work it out from the pitch.
+    /// See `KeySignature::spell` for spelling within the context of a key.
     pub fn default(int: u8) -> Accidental {
         let step = (int - 12) % 12;
         match step {
@@ -24,6 +46,94 @@ impl Accidental {
         }
     }
 
+    /// The number of half steps the accidental alters a natural by.
+    /// Microtonal accidentals give the half steps of the MIDI number, see `to_cents`.
+    pub fn to_half_steps(&self) -> i8 {
+        match self {
+            Accidental::DoubleSharp => 2,
+            Accidental::Sharp | Accidental::ThreeQuarterSharp => 1,
+            Accidental::SharpArrowUp | Accidental::SharpArrowDown => 1,
+            Accidental::Natural | Accidental::QuarterSharp | Accidental::QuarterFlat => 0,
+            Accidental::NaturalArrowUp | Accidental::NaturalArrowDown => 0,
+            Accidental::Flat | Accidental::ThreeQuarterFlat => -1,
+            Accidental::FlatArrowUp | Accidental::FlatArrowDown => -1,
+            Accidental::DoubleFlat => -2,
+        }
+    }
+
+    /// The cents the accidental alters the MIDI number by, on top of the half steps
+    /// ie. a quarter sharp C is MIDI 60 +50 cents, a three quarter flat E is MIDI 63 -50 cents
+    pub fn to_cents(&self) -> i8 {
+        match self {
+            Accidental::QuarterSharp
+            | Accidental::ThreeQuarterSharp
+            | Accidental::NaturalArrowUp
+            | Accidental::SharpArrowUp
+            | Accidental::FlatArrowUp => 50,
+            Accidental::QuarterFlat
+            | Accidental::ThreeQuarterFlat
+            | Accidental::NaturalArrowDown
+            | Accidental::SharpArrowDown
+            | Accidental::FlatArrowDown => -50,
+            _ => 0,
+        }
+    }
+
+    pub fn is_microtonal(&self) -> bool {
+        self.to_cents() != 0
+    }
+
+    pub fn is_arrow(&self) -> bool {
+        matches!(
+            self,
+            Accidental::NaturalArrowUp
+                | Accidental::NaturalArrowDown
+                | Accidental::SharpArrowUp
+                | Accidental::SharpArrowDown
+                | Accidental::FlatArrowUp
+                | Accidental::FlatArrowDown
+        )
+    }
+
+    /// Find the accidental for a half step and cents alteration, preferring
+    /// Stein-Zimmermann symbols unless arrows are asked for or needed
+    pub fn from_parts(half_steps: i16, cents: i8, arrows: bool) -> Option<Accidental> {
+        if cents == 0 {
+            return Accidental::from_half_steps(half_steps);
+        }
+
+        if !arrows {
+            match (half_steps, cents) {
+                (0, 50) => return Some(Accidental::QuarterSharp),
+                (1, 50) => return Some(Accidental::ThreeQuarterSharp),
+                (0, -50) => return Some(Accidental::QuarterFlat),
+                (-1, -50) => return Some(Accidental::ThreeQuarterFlat),
+                _ => (),
+            };
+        }
+
+        match (half_steps, cents) {
+            (0, 50) => Some(Accidental::NaturalArrowUp),
+            (0, -50) => Some(Accidental::NaturalArrowDown),
+            (1, 50) => Some(Accidental::SharpArrowUp),
+            (1, -50) => Some(Accidental::SharpArrowDown),
+            (-1, 50) => Some(Accidental::FlatArrowUp),
+            (-1, -50) => Some(Accidental::FlatArrowDown),
+            _ => None,
+        }
+    }
+
+    pub fn from_half_steps(half_steps: i16) -> Option<Accidental> {
+        match half_steps {
+            2 => Some(Accidental::DoubleSharp),
+            1 => Some(Accidental::Sharp),
+            0 => Some(Accidental::Natural),
+            -1 => Some(Accidental::Flat),
+            -2 => Some(Accidental::DoubleFlat),
+            _ => None,
+        }
+    }
+
     /// Convert an accidental to a token
     pub fn to_token(&self) -> &str {
         match self {
@@ -32,6 +142,16 @@ impl Accidental {
             Accidental::Natural => "${natural}",
             Accidental::Flat => "${flat}",
             Accidental::DoubleFlat => "${double-flat}",
+            Accidental::QuarterSharp => "${quarter-sharp}",
+            Accidental::ThreeQuarterSharp => "${three-quarter-sharp}",
+            Accidental::QuarterFlat => "${quarter-flat}",
+            Accidental::ThreeQuarterFlat => "${three-quarter-flat}",
+            Accidental::NaturalArrowUp => "${natural-arrow-up}",
+            Accidental::NaturalArrowDown => "${natural-arrow-down}",
+            Accidental::SharpArrowUp => "${sharp-arrow-up}",
+            Accidental::SharpArrowDown => "${sharp-arrow-down}",
+            Accidental::FlatArrowUp => "${flat-arrow-up}",
+            Accidental::FlatArrowDown => "${flat-arrow-down}",
         }
     }
 
@@ -42,6 +162,16 @@ impl Accidental {
             Accidental::Natural => String::from("\u{E261}"),
             Accidental::Flat => String::from("\u{E260}"),
             Accidental::DoubleFlat => String::from("\u{E264}"),
+            Accidental::QuarterSharp => String::from("\u{E282}"),
+            Accidental::ThreeQuarterSharp => String::from("\u{E283}"),
+            Accidental::QuarterFlat => String::from("\u{E280}"),
+            Accidental::ThreeQuarterFlat => String::from("\u{E281}"),
+            Accidental::NaturalArrowUp => String::from("\u{E272}"),
+            Accidental::NaturalArrowDown => String::from("\u{E273}"),
+            Accidental::SharpArrowUp => String::from("\u{E274}"),
+            Accidental::SharpArrowDown => String::from("\u{E275}"),
+            Accidental::FlatArrowUp => String::from("\u{E270}"),
+            Accidental::FlatArrowDown => String::from("\u{E271}"),
         }
     }
 }
@@ -51,12 +181,27 @@ impl Accidental {
 pub struct Pitch {
     pub int: u8, // the midi number
     pub accidental: Accidental,
+    #[serde(default)]
+    pub cents: i8, // offset from the midi number, microtonal accidentals are +/-50
 }
 
 impl Pitch {
     /// Create a pitch from a MIDI number and accidental
     pub fn new(int: u8, accidental: Accidental) -> Self {
-        Pitch { int, accidental }
+        Pitch {
+            int,
+            accidental,
+            cents: accidental.to_cents(),
+        }
+    }
+
+    /// Create a pitch with a cents offset that differs from the accidental's own
+    pub fn with_cents(int: u8, accidental: Accidental, cents: i8) -> Self {
+        Pitch {
+            int,
+            accidental,
+            cents,
+        }
     }
 
     pub fn from_int(int: u8) -> Self {
@@ -66,14 +211,14 @@ impl Pitch {
     /// gets the base note pitch (natural) for an accidental note
     /// ie 61 (C#) -> 60 (C), 61 (D flat) -> 62 (D)
     fn base(&self) -> u8 {
-        let int = self.int;
-        match self.accidental {
-            Accidental::DoubleSharp => int - 2,
-            Accidental::Sharp => int - 1,
-            Accidental::Natural => int,
-            Accidental::Flat => int + 1,
-            Accidental::DoubleFlat => int + 2,
-        }
+        (self.int as i16 - self.accidental.to_half_steps() as i16) as u8
+    }
+
+    /// The same letter and octave with a different accidental
+    /// ie. C -> C quarter sharp, Eb -> E three quarter flat
+    pub fn with_accidental(&self, accidental: Accidental) -> Pitch {
+        let int = self.base() as i16 + accidental.to_half_steps() as i16;
+        Pitch::new(int.clamp(C0 as i16, 127) as u8, accidental)
     }
 
     pub fn half_steps(&self) -> u8 {
@@ -124,9 +269,120 @@ impl Pitch {
         )
     }
 
+    /// The frequency in equal temperament at A=440, see `Tuning` for other tunings
     pub fn to_frequency(&self) -> f64 {
-        let a: f64 = 440.0;
-        (a / 32.0) * (2.0_f64.powf((self.int as f64 - 9.0) / 12.0))
+        Tuning::new().to_frequency(self, None)
+    }
+
+    /// The natural on a step counted from C0 (C0 = 0, D0 = 1 ...)
+    pub fn natural(steps: i16) -> Pitch {
+        let octave = steps.div_euclid(7);
+        let step = steps.rem_euclid(7) as usize;
+        let int = C0 as i16 + (octave * 12) + STEP_HALF_STEPS[step] as i16;
+        Pitch::new(int.clamp(0, 127) as u8, Accidental::Natural)
+    }
+
+    /// Spell a MIDI number on a natural step counted from C0 (C0 = 0, D0 = 1 ...)
+    /// Returns None if the pitch can't be spelled on that step.
+    pub fn spell(int: u8, steps: i16) -> Option<Pitch> {
+        let octave = steps.div_euclid(7);
+        let step = steps.rem_euclid(7) as usize;
+        let natural = C0 as i16 + (octave * 12) + STEP_HALF_STEPS[step] as i16;
+        let accidental = Accidental::from_half_steps(int as i16 - natural)?;
+        Some(Pitch::new(int, accidental))
+    }
+
+    /// Get the pitch (in the middle C octave) at a position on the circle of fifths
+    /// ie. 0 -> C, 1 -> G, -3 -> Eb, 6 -> F#
+    pub fn from_fifths(fifths: i8) -> Pitch {
+        let natural = (fifths + 1).rem_euclid(7) - 1;
+        let step = STEP_FIFTHS
+            .iter()
+            .position(|step| *step == natural)
+            .unwrap();
+        let alteration = (fifths + 1).div_euclid(7);
+        let int = 60 + STEP_HALF_STEPS[step] as i8 + alteration;
+        Pitch::new(
+            int as u8,
+            Accidental::from_half_steps(alteration as i16).unwrap(),
+        )
+    }
+
+    /// All the ways to spell a MIDI number with a single accidental (or none)
+    /// ie. 61 -> [C#, Db], 60 -> [C, B#]
+    pub fn spellings(int: u8) -> Vec<Pitch> {
+        let mut output = Vec::new();
+        for accidental in [Accidental::Natural, Accidental::Sharp, Accidental::Flat] {
+            let natural = int as i16 - accidental.to_half_steps() as i16 - C0 as i16;
+            let octave = natural.div_euclid(12);
+            if let Some(step) = STEP_HALF_STEPS
+                .iter()
+                .position(|half_steps| *half_steps as i16 == natural.rem_euclid(12))
+            {
+                if let Some(pitch) = Pitch::spell(int, (octave * 7) + step as i16) {
+                    output.push(pitch);
+                }
+            }
+        }
+        output
+    }
+
+    /// Swap between the sharp and flat spelling of the same MIDI number
+    /// ie. C# -> Db, Db -> C#, E# -> F. Naturals are left as they are.
+    pub fn flip_enharmonic(&self) -> Pitch {
+        let target = match self.accidental {
+            Accidental::Sharp | Accidental::DoubleSharp => Accidental::Flat,
+            Accidental::Flat | Accidental::DoubleFlat => Accidental::Sharp,
+            _ => return self.clone(),
+        };
+
+        let spellings = Pitch::spellings(self.int);
+        match spellings.iter().find(|pitch| pitch.accidental == target) {
+            Some(pitch) => pitch.clone(),
+            None => spellings[0].clone(),
+        }
+    }
+
+    /// The spelling with the fewest accidentals, naturals where possible
+    /// ie. B# -> C, Fx -> G, C# -> C#
+    pub fn simplest(&self) -> Pitch {
+        let spellings = Pitch::spellings(self.int);
+        match self.accidental {
+            // spellings are ordered natural first so only change if a natural exists
+            Accidental::Sharp | Accidental::Flat => spellings
+                .into_iter()
+                .find(|pitch| pitch.accidental == Accidental::Natural)
+                .unwrap_or_else(|| self.clone()),
+            Accidental::Natural | Accidental::DoubleSharp | Accidental::DoubleFlat => {
+                spellings[0].clone()
+            }
+            _ => self.clone(),
+        }
+    }
+
+    /// Transpose the pitch by a diatonic interval, keeping the spelling
+    /// ie. F# up a major 2nd is G#, not Ab
+    pub fn transpose(&self, interval: &Interval) -> Pitch {
+        let int = (self.int as i16 + interval.semitones as i16).clamp(C0 as i16, 127) as u8;
+        let steps = (self.octave() as i16 * 7) + self.steps() as i16 + interval.steps as i16;
+        let pitch = match Pitch::spell(int, steps) {
+            Some(pitch) => pitch,
+            None => Pitch::from_int(int),
+        };
+
+        // keep any microtonal alteration on the new spelling
+        let accidental = Accidental::from_parts(
+            pitch.accidental.to_half_steps() as i16,
+            self.accidental.to_cents(),
+            self.accidental.is_arrow(),
+        )
+        .unwrap_or(pitch.accidental);
+        Pitch::with_cents(int, accidental, self.cents)
+    }
+
+    /// Position of the pitch on the circle of fifths (C = 0, G = 1, F = -1, F# = 6 ...)
+    pub fn fifths(&self) -> i8 {
+        STEP_FIFTHS[self.steps() as usize] + (7 * self.accidental.to_half_steps())
     }
 
     pub fn steps_between(a: &Pitch, b: &Pitch) -> i8 {
@@ -138,6 +394,7 @@ impl Pitch {
 
 #[cfg(test)]
 mod tests {
+    use crate::components::interval::Interval;
     use crate::components::pitch::Accidental;
     use crate::components::pitch::Pitch;
 
@@ -304,4 +561,175 @@ mod tests {
             -1
         );
     }
+
+    #[test]
+    fn transpose_1() {
+        // C up a major 2nd is D
+        assert_eq!(
+            Pitch::new(60, Accidental::Natural).transpose(&Interval::new(1, 2)),
+            Pitch::new(62, Accidental::Natural)
+        );
+    }
+
+    #[test]
+    fn transpose_2() {
+        // F# up a major 2nd is G#
+        assert_eq!(
+            Pitch::new(66, Accidental::Sharp).transpose(&Interval::new(1, 2)),
+            Pitch::new(68, Accidental::Sharp)
+        );
+    }
+
+    #[test]
+    fn transpose_3() {
+        // Eb up a perfect 5th is Bb
+        assert_eq!(
+            Pitch::new(63, Accidental::Flat).transpose(&Interval::new(4, 7)),
+            Pitch::new(70, Accidental::Flat)
+        );
+    }
+
+    #[test]
+    fn transpose_4() {
+        // B up a minor 3rd is D (across the octave)
+        assert_eq!(
+            Pitch::new(59, Accidental::Natural).transpose(&Interval::new(2, 3)),
+            Pitch::new(62, Accidental::Natural)
+        );
+    }
+
+    #[test]
+    fn transpose_5() {
+        // D down a major 2nd is C
+        assert_eq!(
+            Pitch::new(62, Accidental::Natural).transpose(&Interval::new(-1, -2)),
+            Pitch::new(60, Accidental::Natural)
+        );
+    }
+
+    #[test]
+    fn spellings_test() {
+        assert_eq!(
+            Pitch::spellings(61),
+            vec![
+                Pitch::new(61, Accidental::Sharp),
+                Pitch::new(61, Accidental::Flat)
+            ]
+        );
+        assert_eq!(
+            Pitch::spellings(60),
+            vec![
+                Pitch::new(60, Accidental::Natural),
+                Pitch::new(60, Accidental::Sharp)
+            ]
+        );
+        assert_eq!(
+            Pitch::spellings(64),
+            vec![
+                Pitch::new(64, Accidental::Natural),
+                Pitch::new(64, Accidental::Flat)
+            ]
+        );
+    }
+
+    #[test]
+    fn flip_enharmonic_test() {
+        assert_eq!(
+            Pitch::new(61, Accidental::Sharp).flip_enharmonic(),
+            Pitch::new(61, Accidental::Flat)
+        );
+        assert_eq!(
+            Pitch::new(61, Accidental::Flat).flip_enharmonic(),
+            Pitch::new(61, Accidental::Sharp)
+        );
+        assert_eq!(
+            Pitch::new(65, Accidental::Sharp).flip_enharmonic(),
+            Pitch::new(65, Accidental::Natural)
+        );
+        assert_eq!(
+            Pitch::new(62, Accidental::Natural).flip_enharmonic(),
+            Pitch::new(62, Accidental::Natural)
+        );
+    }
+
+    #[test]
+    fn microtonal_test() {
+        let pitch = Pitch::new(60, Accidental::QuarterSharp);
+        assert_eq!(pitch.cents, 50);
+        assert_eq!(pitch.letter(), "C");
+        assert_eq!(pitch.octave(), 4);
+
+        let pitch = Pitch::new(63, Accidental::ThreeQuarterFlat);
+        assert_eq!(pitch.cents, -50);
+        assert_eq!(pitch.letter(), "E");
+        assert_eq!(
+            Pitch::new(64, Accidental::Natural).with_accidental(Accidental::ThreeQuarterFlat),
+            pitch
+        );
+    }
+
+    #[test]
+    fn microtonal_frequency_test() {
+        let a = Pitch::new(69, Accidental::Natural).to_frequency();
+        let quarter = Pitch::new(69, Accidental::QuarterSharp).to_frequency();
+        let b_flat = Pitch::new(70, Accidental::Flat).to_frequency();
+        assert!((a - 440.0).abs() < 0.001);
+        assert!(quarter > a && quarter < b_flat);
+        assert!((quarter - 452.893).abs() < 0.001);
+    }
+
+    #[test]
+    fn microtonal_transpose_test() {
+        // F quarter sharp up a major 2nd is G quarter sharp
+        assert_eq!(
+            Pitch::new(65, Accidental::QuarterSharp).transpose(&Interval::new(1, 2)),
+            Pitch::new(67, Accidental::QuarterSharp)
+        );
+        // C quarter sharp up a major 3rd is E quarter sharp
+        assert_eq!(
+            Pitch::new(60, Accidental::NaturalArrowUp).transpose(&Interval::new(2, 4)),
+            Pitch::new(64, Accidental::NaturalArrowUp)
+        );
+        // D quarter flat up a major 2nd is E quarter flat
+        assert_eq!(
+            Pitch::new(62, Accidental::QuarterFlat).transpose(&Interval::new(1, 2)),
+            Pitch::new(64, Accidental::QuarterFlat)
+        );
+    }
+
+    #[test]
+    fn simplest_test() {
+        assert_eq!(
+            Pitch::new(60, Accidental::Sharp).simplest(),
+            Pitch::new(60, Accidental::Natural)
+        );
+        assert_eq!(
+            Pitch::new(67, Accidental::DoubleSharp).simplest(),
+            Pitch::new(67, Accidental::Natural)
+        );
+        assert_eq!(
+            Pitch::new(61, Accidental::Flat).simplest(),
+            Pitch::new(61, Accidental::Flat)
+        );
+        assert_eq!(
+            Pitch::new(66, Accidental::DoubleSharp).simplest(),
+            Pitch::new(66, Accidental::Sharp)
+        );
+    }
+
+    #[test]
+    fn from_fifths_test() {
+        assert_eq!(Pitch::from_fifths(0), Pitch::new(60, Accidental::Natural));
+        assert_eq!(Pitch::from_fifths(-3), Pitch::new(63, Accidental::Flat));
+        assert_eq!(Pitch::from_fifths(6), Pitch::new(66, Accidental::Sharp));
+        assert_eq!(Pitch::from_fifths(5), Pitch::new(71, Accidental::Natural));
+    }
+
+    #[test]
+    fn fifths_test() {
+        assert_eq!(Pitch::new(60, Accidental::Natural).fifths(), 0);
+        assert_eq!(Pitch::new(66, Accidental::Sharp).fifths(), 6);
+        assert_eq!(Pitch::new(70, Accidental::Flat).fifths(), -2);
+        assert_eq!(Pitch::new(71, Accidental::Natural).fifths(), 5);
+    }
 }
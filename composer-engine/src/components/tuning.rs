@@ -0,0 +1,155 @@
+use super::pitch::Pitch;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+/// Cents each fifth differs from an equal tempered fifth (700 cents)
+const PYTHAGOREAN_FIFTH: f64 = 1.955;
+const MEANTONE_FIFTH: f64 = -3.422;
+
+/// Position of A on the circle of fifths, the reference pitch is always an A
+const A_FIFTHS: i8 = 3;
+
+/// Cents each 5-limit just interval above the tonic differs from equal temperament
+const JUST_INTERVALS: [f64; 12] = [
+    0.0,    // 1/1
+    11.73,  // 16/15
+    3.91,   // 9/8
+    15.64,  // 6/5
+    -13.69, // 5/4
+    -1.96,  // 4/3
+    -9.78,  // 45/32
+    1.96,   // 3/2
+    13.69,  // 8/5
+    -15.64, // 5/3
+    17.60,  // 9/5
+    -11.73, // 15/8
+];
+
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum TuningSystem {
+    Equal,
+    Pythagorean,
+    QuarterCommaMeantone,
+    Just,   // relative to the tonic of the current key
+    Custom, // cents offsets from equal temperament for each pitch class
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tuning {
+    pub reference: f64, // frequency of A4
+    pub system: TuningSystem,
+    pub custom: [f64; 12], // C, C#, D ... B
+}
+
+impl Tuning {
+    pub fn new() -> Self {
+        Tuning {
+            reference: 440.0,
+            system: TuningSystem::Equal,
+            custom: [0.0; 12],
+        }
+    }
+
+    /// The cents a pitch differs from equal temperament in this tuning.
+    /// Pythagorean and meantone tunings are spelling aware so G# and Ab differ.
+    pub fn deviation(&self, pitch: &Pitch, tonic: Option<&Pitch>) -> f64 {
+        match self.system {
+            TuningSystem::Equal => 0.0,
+            TuningSystem::Pythagorean => (pitch.fifths() - A_FIFTHS) as f64 * PYTHAGOREAN_FIFTH,
+            TuningSystem::QuarterCommaMeantone => {
+                (pitch.fifths() - A_FIFTHS) as f64 * MEANTONE_FIFTH
+            }
+            TuningSystem::Just => match tonic {
+                Some(tonic) => {
+                    let interval = (pitch.int as i16 - tonic.int as i16).rem_euclid(12);
+                    JUST_INTERVALS[interval as usize]
+                }
+                None => 0.0,
+            },
+            TuningSystem::Custom => self.custom[(pitch.int % 12) as usize],
+        }
+    }
+
+    /// The MIDI number including any microtonal offset and tuning deviation
+    /// ie. A quarter sharp in equal temperament is 69.5
+    pub fn to_midi(&self, pitch: &Pitch, tonic: Option<&Pitch>) -> f64 {
+        let cents = pitch.cents as f64 + self.deviation(pitch, tonic);
+        pitch.int as f64 + (cents / 100.0)
+    }
+
+    pub fn to_frequency(&self, pitch: &Pitch, tonic: Option<&Pitch>) -> f64 {
+        self.reference * 2.0_f64.powf((self.to_midi(pitch, tonic) - 69.0) / 12.0)
+    }
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::pitch::{Accidental, Pitch};
+    use crate::components::tuning::{Tuning, TuningSystem};
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 0.01
+    }
+
+    #[test]
+    fn equal_test() {
+        let mut tuning = Tuning::new();
+        let a = Pitch::new(69, Accidental::Natural);
+        assert!(close(tuning.to_frequency(&a, None), 440.0));
+        tuning.reference = 415.0;
+        assert!(close(tuning.to_frequency(&a, None), 415.0));
+        assert!(close(
+            tuning.to_frequency(&Pitch::new(81, Accidental::Natural), None),
+            830.0
+        ));
+    }
+
+    #[test]
+    fn pythagorean_test() {
+        let mut tuning = Tuning::new();
+        tuning.system = TuningSystem::Pythagorean;
+        // E is a pure fifth above A
+        let e = Pitch::new(76, Accidental::Natural);
+        assert!(close(tuning.to_frequency(&e, None), 660.0));
+        // G# and Ab are different pitches
+        let g_sharp = tuning.to_midi(&Pitch::new(68, Accidental::Sharp), None);
+        let a_flat = tuning.to_midi(&Pitch::new(68, Accidental::Flat), None);
+        assert!(g_sharp > a_flat);
+    }
+
+    #[test]
+    fn meantone_test() {
+        let mut tuning = Tuning::new();
+        tuning.system = TuningSystem::QuarterCommaMeantone;
+        // the major third F-A is pure (5/4)
+        let f = tuning.to_frequency(&Pitch::new(65, Accidental::Natural), None);
+        assert!(close(440.0 / f, 1.25));
+    }
+
+    #[test]
+    fn just_test() {
+        let mut tuning = Tuning::new();
+        tuning.system = TuningSystem::Just;
+        let a = Pitch::new(69, Accidental::Natural);
+        let c_sharp = tuning.to_frequency(&Pitch::new(73, Accidental::Sharp), Some(&a));
+        let e = tuning.to_frequency(&Pitch::new(76, Accidental::Natural), Some(&a));
+        assert!(close(c_sharp / 440.0, 1.25));
+        assert!(close(e / 440.0, 1.5));
+    }
+
+    #[test]
+    fn custom_test() {
+        let mut tuning = Tuning::new();
+        tuning.system = TuningSystem::Custom;
+        tuning.custom[9] = -50.0;
+        let a = Pitch::new(69, Accidental::Natural);
+        assert!(close(tuning.to_midi(&a, None), 68.5));
+    }
+}
@@ -11,4 +11,8 @@ impl Velocity {
     pub fn new(int: u8) -> Self {
         Self { int }
     }
+
+    pub fn int(&self) -> u8 {
+        self.int
+    }
 }
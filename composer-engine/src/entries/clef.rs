@@ -3,6 +3,7 @@ use crate::components::misc::Tick;
 use crate::components::pitch::Pitch;
 use crate::score::tracks::Track;
 use crate::utils::shortid;
+use crate::Engine;
 use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 
@@ -25,6 +26,8 @@ pub struct Clef {
     pub draw_as: ClefDrawType,
     pub pitch: Pitch, // the pitch that the clef sits on
     pub offset: i8,   // visual offset from middle stave line
+    #[serde(default)]
+    pub octave: i8, // octave indication drawn with the clef (-1 = 8vb, 1 = 8va)
 }
 
 impl Clef {
@@ -35,20 +38,55 @@ impl Clef {
             draw_as,
             pitch: Pitch::from_int(pitch),
             offset,
+            octave: 0,
         }
     }
 
+    /// Create an octave transposing clef (ie. treble 8vb for guitar).
+    ///
+    /// The pitch is the sounding pitch the clef sits on so the octave is
+    /// already accounted for, the octave is only used to draw the clef.
+    pub fn new_octave(
+        tick: Tick,
+        pitch: u8,
+        offset: i8,
+        draw_as: ClefDrawType,
+        octave: i8,
+    ) -> Self {
+        let mut clef = Self::new(tick, pitch, offset, draw_as);
+        clef.octave = octave;
+        clef
+    }
+
     pub fn glyph(&self) -> Option<String> {
-        match self.draw_as {
-            ClefDrawType::Hidden => None,
-            ClefDrawType::G => Some(String::from("\u{E050}")),
-            ClefDrawType::F => Some(String::from("\u{E062}")),
-            ClefDrawType::C => Some(String::from("\u{E05C}")),
-            ClefDrawType::Percussion => Some(String::from("\u{E069}")),
+        match (&self.draw_as, self.octave) {
+            (ClefDrawType::Hidden, _) => None,
+            (ClefDrawType::G, -2) => Some(String::from("\u{E051}")),
+            (ClefDrawType::G, -1) => Some(String::from("\u{E052}")),
+            (ClefDrawType::G, 1) => Some(String::from("\u{E053}")),
+            (ClefDrawType::G, 2) => Some(String::from("\u{E054}")),
+            (ClefDrawType::G, _) => Some(String::from("\u{E050}")),
+            (ClefDrawType::F, -2) => Some(String::from("\u{E063}")),
+            (ClefDrawType::F, -1) => Some(String::from("\u{E064}")),
+            (ClefDrawType::F, 1) => Some(String::from("\u{E065}")),
+            (ClefDrawType::F, 2) => Some(String::from("\u{E066}")),
+            (ClefDrawType::F, _) => Some(String::from("\u{E062}")),
+            (ClefDrawType::C, -1) => Some(String::from("\u{E05D}")),
+            (ClefDrawType::C, _) => Some(String::from("\u{E05C}")),
+            (ClefDrawType::Percussion, _) => Some(String::from("\u{E069}")),
+        }
+    }
+
+    /// Clef changes are drawn smaller than the initial (first) clef on the stave
+    pub fn scale(&self, is_initial: bool) -> f32 {
+        if is_initial {
+            1.0
+        } else {
+            0.75
         }
     }
 
-    pub fn metrics(&self) -> BoundingBox {
+    pub fn metrics(&self, is_initial: bool) -> BoundingBox {
         match self.draw_as {
             ClefDrawType::Hidden => BoundingBox {
                 width: 0.0,
@@ -56,7 +94,7 @@ impl Clef {
                 padding: PaddingSpaces::new(0.0, 0.0, 0.0, 0.0),
             },
             _ => BoundingBox {
-                width: 2.8,
+                width: 2.8 * self.scale(is_initial),
                 height: 4.0,
                 padding: PaddingSpaces::new(0.0, 1.0, 0.0, 0.0),
             },
@@ -64,6 +102,58 @@ impl Clef {
     }
 }
 
+#[wasm_bindgen]
+impl Engine {
+    /// Create a clef (or clef change) on a stave, replacing any clef already at the tick
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_clef(
+        &mut self,
+        flow_key: &str,
+        stave_key: &str,
+        tick: Tick,
+        pitch: u8,
+        offset: i8,
+        draw_as: ClefDrawType,
+        octave: i8,
+    ) -> String {
+        let flow = self.score.flows.by_key.get(flow_key).unwrap();
+        let stave = flow.staves.get(stave_key).unwrap();
+        let master = self.score.tracks.get_mut(&stave.master).unwrap();
+
+        // remove old clef if defined
+        if let Some(old) = master.get_clef_at_tick(&tick) {
+            let key = old.key.clone();
+            master.remove(&key);
+        };
+
+        // insert the new clef
+        let clef = Clef::new_octave(tick, pitch, offset, draw_as, octave);
+        let key = clef.key.clone();
+        master.insert(Entry::Clef(clef));
+
+        self.emit();
+
+        key
+    }
+
+    /// Remove a clef change, the initial clef can be replaced but not removed
+    pub fn remove_clef(&mut self, flow_key: &str, stave_key: &str, clef_key: &str) {
+        let flow = self.score.flows.by_key.get(flow_key).unwrap();
+        let stave = flow.staves.get(stave_key).unwrap();
+        let master = self.score.tracks.get_mut(&stave.master).unwrap();
+
+        if let Some(Entry::Clef(clef)) = master.entries.by_key.get(clef_key) {
+            if master.is_initial_clef(clef) {
+                return;
+            }
+        }
+
+        master.remove(clef_key);
+
+        self.emit();
+    }
+}
+
 impl Track {
     /// Returns the time signature entry at a given tick if it exists
     pub fn get_clef_at_tick(&self, tick: &Tick) -> Option<&Clef> {
@@ -80,4 +170,121 @@ impl Track {
 
         None
     }
+
+    /// Is the clef the first on the track, it may be after the start of the flow
+    pub fn is_initial_clef(&self, clef: &Clef) -> bool {
+        !self
+            .entries
+            .by_key
+            .values()
+            .any(|entry| matches!(entry, Entry::Clef(other) if other.tick < clef.tick))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Clef, ClefDrawType};
+    use crate::entries::Entry;
+    use crate::score::players::PlayerType;
+    use crate::score::tracks::Track;
+    use crate::Engine;
+
+    /// An engine with a violin in the first flow, returns the flow and stave keys
+    fn violin() -> (Engine, String, String) {
+        let mut engine = Engine::tester();
+        let flow_key = engine.score.flows.order[0].clone();
+        let player_key = engine.create_player(PlayerType::Solo);
+        let instrument_key = engine.create_instrument("strings.violin");
+        engine.assign_instrument_to_player(&player_key, &instrument_key);
+        let stave_key = engine.score.instruments[&instrument_key].staves[0].clone();
+        (engine, flow_key, stave_key)
+    }
+
+    fn clefs(engine: &Engine, flow_key: &str, stave_key: &str) -> Vec<(u32, ClefDrawType)> {
+        let stave = &engine.score.flows.by_key[flow_key].staves[stave_key];
+        let master = &engine.score.tracks[&stave.master];
+        let mut output: Vec<(u32, ClefDrawType)> = master
+            .entries
+            .by_key
+            .values()
+            .filter_map(|entry| match entry {
+                Entry::Clef(clef) => Some((clef.tick, clef.draw_as.clone())),
+                _ => None,
+            })
+            .collect();
+        output.sort_by_key(|(tick, _)| *tick);
+        output
+    }
+
+    #[test]
+    fn glyph_test_1() {
+        let clef = Clef::new(0, 67, 2, ClefDrawType::G);
+        assert_eq!(clef.glyph(), Some(String::from("\u{E050}")));
+    }
+
+    #[test]
+    /// treble 8vb for guitar
+    fn glyph_test_2() {
+        let clef = Clef::new_octave(0, 55, 2, ClefDrawType::G, -1);
+        assert_eq!(clef.glyph(), Some(String::from("\u{E052}")));
+    }
+
+    #[test]
+    /// bass 8vb for contrabass
+    fn glyph_test_3() {
+        let clef = Clef::new_octave(0, 41, -2, ClefDrawType::F, -1);
+        assert_eq!(clef.glyph(), Some(String::from("\u{E064}")));
+    }
+
+    #[test]
+    /// clef changes are narrower than the initial clef
+    fn metrics_test() {
+        let initial = Clef::new(0, 67, 2, ClefDrawType::G);
+        let change = Clef::new(16, 53, -2, ClefDrawType::F);
+        assert!(change.metrics(false).width < initial.metrics(true).width);
+    }
+
+    #[test]
+    fn create_clef_test() {
+        let (mut engine, flow_key, stave_key) = violin();
+        engine.create_clef(&flow_key, &stave_key, 16, 60, 0, ClefDrawType::C, 0);
+        // a clef at the same tick is replaced
+        engine.create_clef(&flow_key, &stave_key, 16, 53, -2, ClefDrawType::F, 0);
+        assert_eq!(
+            clefs(&engine, &flow_key, &stave_key),
+            vec![(0, ClefDrawType::G), (16, ClefDrawType::F)]
+        );
+    }
+
+    #[test]
+    fn remove_clef_test() {
+        let (mut engine, flow_key, stave_key) = violin();
+        let change = engine.create_clef(&flow_key, &stave_key, 16, 53, -2, ClefDrawType::F, 0);
+        engine.remove_clef(&flow_key, &stave_key, &change);
+        assert_eq!(
+            clefs(&engine, &flow_key, &stave_key),
+            vec![(0, ClefDrawType::G)]
+        );
+
+        // the initial clef can't be removed
+        let initial = engine.create_clef(&flow_key, &stave_key, 0, 53, -2, ClefDrawType::F, 0);
+        engine.remove_clef(&flow_key, &stave_key, &initial);
+        assert_eq!(
+            clefs(&engine, &flow_key, &stave_key),
+            vec![(0, ClefDrawType::F)]
+        );
+    }
+
+    #[test]
+    /// the first clef is full size even if it isn't at the start of the flow
+    fn is_initial_clef_test() {
+        let mut track = Track::new();
+        let first = Clef::new(8, 67, 2, ClefDrawType::G);
+        let change = Clef::new(16, 53, -2, ClefDrawType::F);
+        track.insert(Entry::Clef(first.clone()));
+        track.insert(Entry::Clef(change.clone()));
+        assert!(track.is_initial_clef(&first));
+        assert!(!track.is_initial_clef(&change));
+        assert_eq!(first.scale(track.is_initial_clef(&first)), 1.0);
+    }
 }
@@ -1,8 +1,10 @@
 use super::clef::{Clef, ClefDrawType};
+use crate::components::interval::Interval;
 use crate::components::measurements::{BoundingBox, PaddingSpaces};
 use crate::components::misc::Tick;
 use crate::components::pitch::{Accidental, Pitch};
 use crate::entries::Entry;
+use crate::score::engrave::KeySignatureCancellation;
 use crate::score::tracks::Track;
 use crate::utils::shortid;
 use crate::Engine;
@@ -15,69 +17,119 @@ const SHARPS: [Pitch; 7] = [
     Pitch {
         int: 66,
         accidental: Accidental::Sharp,
+        cents: 0,
     },
     Pitch {
         int: 61,
         accidental: Accidental::Sharp,
+        cents: 0,
     },
     Pitch {
         int: 68,
         accidental: Accidental::Sharp,
+        cents: 0,
     },
     Pitch {
         int: 63,
         accidental: Accidental::Sharp,
+        cents: 0,
     },
     Pitch {
         int: 70,
         accidental: Accidental::Sharp,
+        cents: 0,
     },
     Pitch {
         int: 65,
         accidental: Accidental::Sharp,
+        cents: 0,
     },
     Pitch {
         int: 72,
         accidental: Accidental::Sharp,
+        cents: 0,
     },
 ];
 
+/// Space between cancellation naturals and the new key signature
+pub const CANCELLATION_GAP: f32 = 0.5;
+
 const FLATS: [Pitch; 7] = [
     Pitch {
         int: 70,
         accidental: Accidental::Flat,
+        cents: 0,
     },
     Pitch {
         int: 63,
         accidental: Accidental::Flat,
+        cents: 0,
     },
     Pitch {
         int: 68,
         accidental: Accidental::Flat,
+        cents: 0,
     },
     Pitch {
         int: 61,
         accidental: Accidental::Flat,
+        cents: 0,
     },
     Pitch {
         int: 66,
         accidental: Accidental::Flat,
+        cents: 0,
     },
     Pitch {
         int: 71,
         accidental: Accidental::Flat,
+        cents: 0,
     },
     Pitch {
         int: 64,
         accidental: Accidental::Flat,
+        cents: 0,
     },
 ];
 
 #[wasm_bindgen]
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum KeySignatureMode {
     Major,
     Minor,
+    Dorian,
+    Phrygian,
+    Lydian,
+    Mixolydian,
+    Locrian,
+}
+
+impl KeySignatureMode {
+    /// The tonic's position on the circle of fifths relative to the key signature
+    /// ie. with no sharps or flats, major is C (0), dorian is D (2)
+    pub fn tonic_fifths(&self) -> i8 {
+        match self {
+            KeySignatureMode::Major => 0,
+            KeySignatureMode::Minor => 3,
+            KeySignatureMode::Dorian => 2,
+            KeySignatureMode::Phrygian => 4,
+            KeySignatureMode::Lydian => -1,
+            KeySignatureMode::Mixolydian => 1,
+            KeySignatureMode::Locrian => 5,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            KeySignatureMode::Major => "major",
+            KeySignatureMode::Minor => "minor",
+            KeySignatureMode::Dorian => "dorian",
+            KeySignatureMode::Phrygian => "phrygian",
+            KeySignatureMode::Lydian => "lydian",
+            KeySignatureMode::Mixolydian => "mixolydian",
+            KeySignatureMode::Locrian => "locrian",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -86,6 +138,8 @@ pub struct KeySignature {
     pub tick: Tick,
     pub mode: KeySignatureMode,
     pub offset: i8,
+    #[serde(default)]
+    pub custom: Option<Vec<Pitch>>, // non-standard key signatures, in the order drawn
 }
 
 impl KeySignature {
@@ -95,12 +149,31 @@ impl KeySignature {
             tick,
             mode,
             offset,
+            custom: None,
+        }
+    }
+
+    /// Create a non-standard key signature (ie. F# + Bb)
+    pub fn new_custom(tick: Tick, pitches: Vec<Pitch>) -> Self {
+        Self {
+            key: shortid(),
+            tick,
+            mode: KeySignatureMode::Major,
+            offset: 0,
+            custom: Some(pitches),
         }
     }
 
     pub fn accidentals(&self) -> FxHashSet<&Pitch> {
         let mut output = FxHashSet::default();
 
+        if let Some(custom) = &self.custom {
+            for pitch in custom {
+                output.insert(pitch);
+            }
+            return output;
+        }
+
         if self.offset > 0 {
             for i in 0..self.offset.abs() {
                 output.insert(&SHARPS[i as usize]);
@@ -116,8 +189,118 @@ impl KeySignature {
         output
     }
 
+    /// The number of accidentals drawn in the key signature
+    pub fn count(&self) -> usize {
+        match &self.custom {
+            Some(custom) => custom.len(),
+            None => self.offset.unsigned_abs() as usize,
+        }
+    }
+
+    /// An empty key signature cancels the previous one
+    pub fn is_empty(&self) -> bool {
+        self.count() == 0
+    }
+
+    /// The tonic of the key, custom key signatures have no tonic
+    pub fn tonic(&self) -> Option<Pitch> {
+        match self.custom {
+            Some(_) => None,
+            None => Some(Pitch::from_fifths(self.offset + self.mode.tonic_fifths())),
+        }
+    }
+
+    /// ie. "E${flat} major", "D dorian"
+    pub fn name(&self) -> String {
+        match self.tonic() {
+            Some(tonic) => {
+                let accidental = match tonic.accidental {
+                    Accidental::Natural => "",
+                    _ => tonic.accidental.to_token(),
+                };
+                format!("{}{} {}", tonic.letter(), accidental, self.mode.name())
+            }
+            None => String::from("Custom"),
+        }
+    }
+
+    /// The written key signature for a transposing instrument.
+    /// Keys beyond 6 sharps or flats are respelled enharmonically (C# major -> Db major)
+    pub fn transpose(&self, interval: &Interval) -> KeySignature {
+        let mut offset = self.offset + interval.fifths();
+        if offset > 6 {
+            offset -= 12;
+        }
+        if offset < -6 {
+            offset += 12;
+        }
+
+        let custom = self.custom.as_ref().map(|custom| {
+            custom
+                .iter()
+                .map(|pitch| pitch.transpose(interval))
+                .collect()
+        });
+
+        KeySignature {
+            key: self.key.clone(),
+            tick: self.tick,
+            mode: self.mode.clone(),
+            offset: if custom.is_some() { 0 } else { offset },
+            custom,
+        }
+    }
+
+    /// Is the spelled pitch part of the key (including raised 6th & 7th in minor keys)
+    fn is_diatonic(&self, pitch: &Pitch) -> bool {
+        if self.custom.is_some() {
+            let altered = self
+                .accidentals()
+                .into_iter()
+                .find(|altered| altered.letter() == pitch.letter());
+            return match altered {
+                Some(altered) => altered.accidental == pitch.accidental,
+                None => pitch.accidental == Accidental::Natural,
+            };
+        }
+
+        let fifths = pitch.fifths() - self.offset;
+        match self.mode {
+            KeySignatureMode::Minor => (-1..=6).contains(&fifths) || fifths == 8,
+            _ => (-1..=5).contains(&fifths),
+        }
+    }
+
+    /// Spell a MIDI number idiomatically within the key.
+    ///
+    /// Notes in the key are spelled as such, chromatic notes take the spelling closest to
+    /// the key on the circle of fifths, leaning sharp when ascending and flat when descending
+    /// from the previous pitch.
+    pub fn spell(&self, int: u8, previous: Option<u8>) -> Pitch {
+        let spellings = Pitch::spellings(int);
+
+        if let Some(pitch) = spellings.iter().find(|pitch| self.is_diatonic(pitch)) {
+            return pitch.clone();
+        }
+
+        let center = match self.mode {
+            KeySignatureMode::Minor => self.offset + 3,
+            _ => self.offset + 2,
+        };
+        let center = match previous {
+            Some(previous) if previous < int => center + 3,
+            Some(previous) if previous > int => center - 3,
+            _ => center,
+        };
+
+        spellings
+            .into_iter()
+            .min_by_key(|pitch| (pitch.fifths() - center).abs())
+            .unwrap()
+    }
+
     pub fn metrics(&self) -> BoundingBox {
-        let width = self.offset.abs() as f32;
+        let width = self.count() as f32;
         let right_padding = if width > 0.0 { 1.0 } else { 0.0 };
 
         BoundingBox {
@@ -128,38 +311,139 @@ impl KeySignature {
     }
 
     pub fn pattern(&self, clef: &Clef) -> Option<[i8; 7]> {
+        match self.offset.cmp(&0) {
+            Ordering::Greater => KeySignature::pattern_for(clef, true),
+            Ordering::Less => KeySignature::pattern_for(clef, false),
+            Ordering::Equal => None,
+        }
+    }
+
+    /// The stave offsets of the sharps (or flats) in the order they are drawn.
+    ///
+    /// Each accidental sits within a 7 step window so the pattern keeps its shape for any
+    /// clef. The window starts on the G (for sharps) or the E (for flats) found between the
+    /// space above the stave and the space below the middle line. Tenor clef sharps are
+    /// conventionally drawn lower so start on the top line.
+    fn pattern_for(clef: &Clef, sharps: bool) -> Option<[i8; 7]> {
         match clef.draw_as {
-            ClefDrawType::C => match clef.offset {
-                -2 => match self.offset.cmp(&0) {
-                    Ordering::Greater => Some([2, -2, 1, -3, 0, -4, -1]),
-                    Ordering::Less => Some([-1, -4, 0, -3, 1, -2, 2]),
-                    Ordering::Equal => None,
-                },
-                0 => match self.offset.cmp(&0) {
-                    Ordering::Greater => Some([-3, 0, -4, -1, 2, -2, 1]),
-                    Ordering::Less => Some([1, -2, 2, -1, 3, 0, 4]),
-                    Ordering::Equal => None,
-                },
-                _ => None,
-            },
-            ClefDrawType::F => match clef.offset {
-                -2 => match self.offset.cmp(&0) {
-                    Ordering::Greater => Some([-2, 1, -3, 0, 3, -1, 2]),
-                    Ordering::Less => Some([2, -1, 3, 0, 4, 1, 5]),
-                    Ordering::Equal => None,
-                },
-                _ => None,
-            },
-            ClefDrawType::G => match clef.offset {
-                2 => match self.offset.cmp(&0) {
-                    Ordering::Greater => Some([-4, -1, -5, -2, 1, -3, 0]),
-                    Ordering::Less => Some([0, -3, 1, -2, 2, -1, 3]),
-                    Ordering::Equal => None,
-                },
-                _ => None,
-            },
-            ClefDrawType::Hidden => None,
-            ClefDrawType::Percussion => None,
+            ClefDrawType::Hidden | ClefDrawType::Percussion => return None,
+            _ => (),
+        };
+
+        let offset_of = |pitch: &Pitch| Pitch::steps_between(pitch, &clef.pitch) + clef.offset;
+        let normalise = |offset: i8, low: i8| low + (offset - low).rem_euclid(7);
+
+        let is_tenor = clef.draw_as == ClefDrawType::C && clef.offset == -2;
+        let (order, top) = match (sharps, is_tenor) {
+            (true, true) => (&SHARPS, -4),
+            (true, false) => (&SHARPS, normalise(offset_of(&Pitch::from_int(67)), -5)),
+            (false, _) => (&FLATS, normalise(offset_of(&Pitch::from_int(64)), -5)),
+        };
+
+        let mut output = [0; 7];
+        for (i, pitch) in order.iter().enumerate() {
+            output[i] = normalise(offset_of(pitch), top);
+        }
+        Some(output)
+    }
+
+    /// The altered pitches in the order they are drawn
+    pub fn ordered_accidentals(&self) -> Vec<Pitch> {
+        match &self.custom {
+            Some(custom) => custom.clone(),
+            None if self.offset > 0 => SHARPS.iter().take(self.count()).cloned().collect(),
+            None => FLATS.iter().take(self.count()).cloned().collect(),
+        }
+    }
+
+    /// The accidentals to draw along with their stave offsets, in the order they are drawn
+    pub fn positions(&self, clef: &Clef) -> Vec<(Accidental, i8)> {
+        let mut output = Vec::new();
+
+        // custom accidentals sit where they would in a standard key signature
+        for pitch in self.ordered_accidentals() {
+            let sharps = pitch.accidental.to_half_steps() > 0;
+            let order = if sharps { &SHARPS } else { &FLATS };
+            let index = order
+                .iter()
+                .position(|standard| standard.letter() == pitch.letter())
+                .unwrap();
+            if let Some(pattern) = KeySignature::pattern_for(clef, sharps) {
+                output.push((pitch.accidental, pattern[index]));
+            }
+        }
+
+        output
+    }
+
+    /// The previous key's accidentals that need cancelling with naturals, in the order they are drawn
+    pub fn cancellations(
+        &self,
+        previous: &KeySignature,
+        cancellation: &KeySignatureCancellation,
+    ) -> Vec<Pitch> {
+        let is_needed = match cancellation {
+            KeySignatureCancellation::Always => true,
+            KeySignatureCancellation::Never => false,
+            KeySignatureCancellation::ToCMajor => self.is_empty(),
+        };
+
+        if !is_needed {
+            return Vec::new();
+        }
+
+        let accidentals = self.accidentals();
+        previous
+            .ordered_accidentals()
+            .into_iter()
+            .filter(|pitch| {
+                !accidentals.iter().any(|current| {
+                    current.letter() == pitch.letter() && current.accidental == pitch.accidental
+                })
+            })
+            .collect()
+    }
+
+    /// The cancellation naturals to draw along with their stave offsets
+    pub fn cancellation_positions(
+        &self,
+        previous: &KeySignature,
+        cancellation: &KeySignatureCancellation,
+        clef: &Clef,
+    ) -> Vec<(Accidental, i8)> {
+        let cancellations = self.cancellations(previous, cancellation);
+        previous
+            .ordered_accidentals()
+            .iter()
+            .zip(previous.positions(clef))
+            .filter(|(pitch, _)| cancellations.contains(pitch))
+            .map(|(_, (_, offset))| (Accidental::Natural, offset))
+            .collect()
+    }
+
+    /// Width of the key signature including any cancellation naturals
+    pub fn metrics_with_cancellations(
+        &self,
+        previous: Option<&KeySignature>,
+        cancellation: &KeySignatureCancellation,
+    ) -> BoundingBox {
+        let naturals = match previous {
+            Some(previous) => self.cancellations(previous, cancellation).len() as f32,
+            None => 0.0,
+        };
+        let gap = if naturals > 0.0 && !self.is_empty() {
+            CANCELLATION_GAP
+        } else {
+            0.0
+        };
+
+        let width = naturals + gap + self.count() as f32;
+        let right_padding = if width > 0.0 { 1.0 } else { 0.0 };
+
+        BoundingBox {
+            width,
+            height: 4.0,
+            padding: PaddingSpaces::new(0.0, right_padding, 0.0, 0.0),
         }
     }
 
@@ -196,6 +480,57 @@ impl Engine {
 
         self.emit();
     }
+
+    /// Create a non-standard key signature from the altered pitches in the order they are drawn,
+    /// alterations are in half steps (ie. pitches: [66, 70], alterations: [1, -1] is F# + Bb).
+    /// Returns the new entry key, or None if the pitches and alterations don't match up.
+    pub fn create_custom_key_signature(
+        &mut self,
+        flow_key: &str,
+        tick: Tick,
+        pitches: Vec<u8>,
+        alterations: Vec<i8>,
+    ) -> Option<String> {
+        if pitches.len() != alterations.len() {
+            return None;
+        }
+
+        let pitches = pitches
+            .iter()
+            .zip(alterations.iter())
+            .map(|(int, alteration)| {
+                let accidental = Accidental::from_half_steps(*alteration as i16)?;
+                Some(Pitch::new(*int, accidental))
+            })
+            .collect::<Option<Vec<Pitch>>>()?;
+
+        let flow = self.score.flows.by_key.get_mut(flow_key).unwrap();
+        let master = self.score.tracks.get_mut(&flow.master).unwrap();
+
+        // remove old key signative if defined
+        if let Some(key_signature) = master.get_key_signature_at_tick(&tick) {
+            let key = key_signature.key.clone();
+            master.remove(&key);
+        };
+
+        let key_signature = KeySignature::new_custom(tick, pitches);
+        let key = key_signature.key.clone();
+        master.insert(Entry::KeySignature(key_signature));
+
+        self.emit();
+
+        Some(key)
+    }
+
+    /// Get the name of the key at a tick (ie. "E${flat} major")
+    pub fn get_key_signature_name(&self, flow_key: &str, tick: Tick) -> String {
+        let flow = self.score.flows.by_key.get(flow_key).unwrap();
+        let master = self.score.tracks.get(&flow.master).unwrap();
+        match master.get_key_signature_before_tick(tick + 1) {
+            Some(key_signature) => key_signature.name(),
+            None => KeySignature::new(0, KeySignatureMode::Major, 0).name(),
+        }
+    }
 }
 
 impl Track {
@@ -227,53 +562,272 @@ impl Track {
     }
 }
 
-// #[cfg(test)]
-// mod tests {
-//     use crate::components::pitch::Accidental;
-//     use crate::components::pitch::Pitch;
-//     use crate::entries::key_signature::KeySignature;
-//     use crate::entries::key_signature::KeySignatureMode;
-//     use std::collections::HashSet;
-
-//     #[test]
-//     fn accidentals_test_1() {
-//         let key = KeySignature::new(0, KeySignatureMode::Major, 0);
-//         assert_eq!(key.accidentals(), HashSet::new());
-//     }
-
-//     #[test]
-//     fn accidentals_test_2() {
-//         let key = KeySignature::new(0, KeySignatureMode::Major, 2);
-//         assert_eq!(
-//             key.accidentals(),
-//             hashset! {
-//                 &Pitch {
-//                     int: 66,
-//                     accidental: Accidental::Sharp,
-//                 },
-//                 &Pitch {
-//                     int: 61,
-//                     accidental: Accidental::Sharp,
-//                 }
-//             }
-//         );
-//     }
-
-//     #[test]
-//     fn accidentals_test_3() {
-//         let key = KeySignature::new(0, KeySignatureMode::Major, -2);
-//         assert_eq!(
-//             key.accidentals(),
-//             hashset! {
-//                 &Pitch {
-//                     int: 70,
-//                     accidental: Accidental::Flat,
-//                 },
-//                 &Pitch {
-//                     int: 63,
-//                     accidental: Accidental::Flat,
-//                 }
-//             }
-//         );
-//     }
-// }
+#[cfg(test)]
+mod tests {
+    use crate::components::pitch::{Accidental, Pitch};
+    use crate::entries::clef::{Clef, ClefDrawType};
+    use crate::entries::key_signature::{KeySignature, KeySignatureMode};
+    use crate::entries::Entry;
+    use crate::score::engrave::KeySignatureCancellation;
+    use crate::Engine;
+    use rustc_hash::FxHashSet;
+
+    #[test]
+    fn accidentals_test_1() {
+        let key = KeySignature::new(0, KeySignatureMode::Major, 0);
+        assert_eq!(key.accidentals(), FxHashSet::default());
+    }
+
+    #[test]
+    fn accidentals_test_2() {
+        let key = KeySignature::new(0, KeySignatureMode::Major, 2);
+        let expected = [
+            Pitch::new(66, Accidental::Sharp),
+            Pitch::new(61, Accidental::Sharp),
+        ];
+        assert_eq!(key.accidentals(), expected.iter().collect());
+    }
+
+    #[test]
+    fn accidentals_test_3() {
+        let key = KeySignature::new(0, KeySignatureMode::Major, -2);
+        let expected = [
+            Pitch::new(70, Accidental::Flat),
+            Pitch::new(63, Accidental::Flat),
+        ];
+        assert_eq!(key.accidentals(), expected.iter().collect());
+    }
+
+    #[test]
+    /// Ab in Eb major
+    fn spell_test_1() {
+        let key = KeySignature::new(0, KeySignatureMode::Major, -3);
+        assert_eq!(key.spell(68, None), Pitch::new(68, Accidental::Flat));
+    }
+
+    #[test]
+    /// F# in D major
+    fn spell_test_2() {
+        let key = KeySignature::new(0, KeySignatureMode::Major, 2);
+        assert_eq!(key.spell(66, None), Pitch::new(66, Accidental::Sharp));
+    }
+
+    #[test]
+    /// leading tone in A minor
+    fn spell_test_3() {
+        let key = KeySignature::new(0, KeySignatureMode::Minor, 0);
+        assert_eq!(key.spell(68, None), Pitch::new(68, Accidental::Sharp));
+    }
+
+    #[test]
+    /// E# in C# major
+    fn spell_test_4() {
+        let key = KeySignature::new(0, KeySignatureMode::Major, 7);
+        assert_eq!(key.spell(65, None), Pitch::new(65, Accidental::Sharp));
+    }
+
+    #[test]
+    /// chromatic notes follow the melodic direction
+    fn spell_test_5() {
+        let key = KeySignature::new(0, KeySignatureMode::Major, 0);
+        assert_eq!(key.spell(63, Some(62)), Pitch::new(63, Accidental::Sharp));
+        assert_eq!(key.spell(63, Some(64)), Pitch::new(63, Accidental::Flat));
+        assert_eq!(key.spell(63, None), Pitch::new(63, Accidental::Flat));
+    }
+
+    #[test]
+    /// modes share the spelling of their key signature
+    fn spell_test_7() {
+        let key = KeySignature::new(0, KeySignatureMode::Dorian, 0);
+        assert_eq!(key.spell(65, None), Pitch::new(65, Accidental::Natural));
+        assert_eq!(key.spell(70, None), Pitch::new(70, Accidental::Flat));
+    }
+
+    #[test]
+    /// custom key signatures spell their own accidentals
+    fn spell_test_8() {
+        let key = KeySignature::new_custom(
+            0,
+            vec![
+                Pitch::new(66, Accidental::Sharp),
+                Pitch::new(70, Accidental::Flat),
+            ],
+        );
+        assert_eq!(key.spell(66, None), Pitch::new(66, Accidental::Sharp));
+        assert_eq!(key.spell(70, None), Pitch::new(70, Accidental::Flat));
+        assert_eq!(key.spell(71, None), Pitch::new(71, Accidental::Natural));
+    }
+
+    #[test]
+    fn name_test() {
+        let key = KeySignature::new(0, KeySignatureMode::Major, -3);
+        assert_eq!(key.name(), "E${flat} major");
+        let key = KeySignature::new(0, KeySignatureMode::Dorian, 0);
+        assert_eq!(key.name(), "D dorian");
+        let key = KeySignature::new(0, KeySignatureMode::Minor, 3);
+        assert_eq!(key.name(), "F${sharp} minor");
+    }
+
+    #[test]
+    /// custom accidentals sit where they would in a standard key signature
+    fn positions_test() {
+        let clef = Clef::new(0, 67, 2, ClefDrawType::G);
+        let key = KeySignature::new_custom(
+            0,
+            vec![
+                Pitch::new(66, Accidental::Sharp),
+                Pitch::new(70, Accidental::Flat),
+            ],
+        );
+        assert_eq!(
+            key.positions(&clef),
+            vec![(Accidental::Sharp, -4), (Accidental::Flat, 0)]
+        );
+    }
+
+    fn patterns(pitch: u8, offset: i8, draw_as: ClefDrawType) -> (Vec<i8>, Vec<i8>) {
+        let clef = Clef::new(0, pitch, offset, draw_as);
+        let sharps = KeySignature::new(0, KeySignatureMode::Major, 7);
+        let flats = KeySignature::new(0, KeySignatureMode::Major, -7);
+        (
+            sharps
+                .positions(&clef)
+                .iter()
+                .map(|(_, offset)| *offset)
+                .collect(),
+            flats
+                .positions(&clef)
+                .iter()
+                .map(|(_, offset)| *offset)
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn pattern_treble() {
+        assert_eq!(
+            patterns(67, 2, ClefDrawType::G),
+            (vec![-4, -1, -5, -2, 1, -3, 0], vec![0, -3, 1, -2, 2, -1, 3])
+        );
+    }
+
+    #[test]
+    fn pattern_bass() {
+        assert_eq!(
+            patterns(53, -2, ClefDrawType::F),
+            (vec![-2, 1, -3, 0, 3, -1, 2], vec![2, -1, 3, 0, 4, 1, 5])
+        );
+    }
+
+    #[test]
+    fn pattern_alto() {
+        assert_eq!(
+            patterns(60, 0, ClefDrawType::C),
+            (vec![-3, 0, -4, -1, 2, -2, 1], vec![1, -2, 2, -1, 3, 0, 4])
+        );
+    }
+
+    #[test]
+    fn pattern_tenor() {
+        assert_eq!(
+            patterns(60, -2, ClefDrawType::C),
+            (vec![2, -2, 1, -3, 0, -4, -1], vec![-1, -4, 0, -3, 1, -2, 2])
+        );
+    }
+
+    #[test]
+    /// octave clefs keep the shape of the clef they are based on
+    fn pattern_treble_8vb() {
+        assert_eq!(
+            patterns(55, 2, ClefDrawType::G),
+            patterns(67, 2, ClefDrawType::G)
+        );
+    }
+
+    #[test]
+    fn pattern_soprano() {
+        assert_eq!(
+            patterns(60, 4, ClefDrawType::C),
+            (vec![1, 4, 0, 3, 6, 2, 5], vec![-2, -5, -1, -4, 0, -3, 1])
+        );
+    }
+
+    #[test]
+    fn pattern_percussion() {
+        assert_eq!(patterns(60, 0, ClefDrawType::Percussion), (vec![], vec![]));
+    }
+
+    #[test]
+    /// D major to G major cancels the C#
+    fn cancellations_test_1() {
+        let previous = KeySignature::new(0, KeySignatureMode::Major, 2);
+        let key = KeySignature::new(0, KeySignatureMode::Major, 1);
+        let result = key.cancellations(&previous, &KeySignatureCancellation::Always);
+        assert_eq!(result, vec![Pitch::new(61, Accidental::Sharp)]);
+        let result = key.cancellations(&previous, &KeySignatureCancellation::ToCMajor);
+        assert_eq!(result, Vec::new());
+    }
+
+    #[test]
+    /// A major to Bb major cancels all the sharps in order
+    fn cancellations_test_2() {
+        let previous = KeySignature::new(0, KeySignatureMode::Major, 3);
+        let key = KeySignature::new(0, KeySignatureMode::Major, -2);
+        let result = key.cancellations(&previous, &KeySignatureCancellation::Always);
+        let letters: Vec<String> = result.iter().map(|pitch| pitch.letter()).collect();
+        assert_eq!(letters, vec!["F", "C", "G"]);
+    }
+
+    #[test]
+    fn cancellations_test_3() {
+        let previous = KeySignature::new(0, KeySignatureMode::Major, 3);
+        let key = KeySignature::new(0, KeySignatureMode::Major, 0);
+        let result = key.cancellations(&previous, &KeySignatureCancellation::Never);
+        assert_eq!(result.len(), 0);
+        let result = key.cancellations(&previous, &KeySignatureCancellation::ToCMajor);
+        assert_eq!(result.len(), 3);
+    }
+
+    #[test]
+    /// diatonic notes ignore the melodic direction
+    fn spell_test_6() {
+        let key = KeySignature::new(0, KeySignatureMode::Major, -1);
+        assert_eq!(key.spell(70, Some(69)), Pitch::new(70, Accidental::Flat));
+    }
+
+    #[test]
+    fn create_custom_key_signature_test() {
+        let mut engine = Engine::tester();
+        let flow_key = engine.score.flows.order[0].clone();
+        let master = engine.score.flows.by_key[&flow_key].master.clone();
+
+        let key = engine
+            .create_custom_key_signature(&flow_key, 0, vec![66, 70], vec![1, -1])
+            .unwrap();
+        match &engine.score.tracks[&master].entries.by_key[&key] {
+            Entry::KeySignature(key_signature) => assert_eq!(
+                key_signature.custom,
+                Some(vec![
+                    Pitch::new(66, Accidental::Sharp),
+                    Pitch::new(70, Accidental::Flat)
+                ])
+            ),
+            _ => panic!("expected a key signature"),
+        }
+
+        // invalid input leaves the existing key signature alone
+        assert!(engine
+            .create_custom_key_signature(&flow_key, 0, vec![66, 70], vec![1, 3])
+            .is_none());
+        assert!(engine
+            .create_custom_key_signature(&flow_key, 0, vec![66, 70], vec![1])
+            .is_none());
+        assert_eq!(
+            engine.score.tracks[&master]
+                .get_key_signature_at_tick(&0)
+                .unwrap()
+                .key,
+            key
+        );
+    }
+}
@@ -1,11 +1,13 @@
 pub mod barline;
 pub mod clef;
 pub mod key_signature;
+pub mod octave_shift;
 pub mod time_signature;
 pub mod tone;
 
 use clef::Clef;
 use key_signature::KeySignature;
+use octave_shift::OctaveShift;
 use serde::{Deserialize, Serialize};
 use time_signature::TimeSignature;
 use tone::Tone;
@@ -19,6 +21,7 @@ pub enum Entry {
     Barline(Barline),
     Clef(Clef),
     KeySignature(KeySignature),
+    OctaveShift(OctaveShift),
     TimeSignature(TimeSignature),
     Tone(Tone),
 }
@@ -30,6 +33,7 @@ impl Entry {
             Entry::Barline(barline) => barline.key.clone(),
             Entry::Clef(clef) => clef.key.clone(),
             Entry::KeySignature(key_signature) => key_signature.key.clone(),
+            Entry::OctaveShift(octave_shift) => octave_shift.key.clone(),
             Entry::TimeSignature(time_signature) => time_signature.key.clone(),
             Entry::Tone(tone) => tone.key.clone(),
         }
@@ -41,6 +45,7 @@ impl Entry {
             Entry::Barline(barline) => barline.tick,
             Entry::Clef(clef) => clef.tick,
             Entry::KeySignature(key_signature) => key_signature.tick,
+            Entry::OctaveShift(octave_shift) => octave_shift.tick,
             Entry::TimeSignature(time_signature) => time_signature.tick,
             Entry::Tone(tone) => tone.tick,
         }
@@ -55,6 +60,7 @@ impl Entry {
             Entry::Barline(barline) => barline.tick = tick,
             Entry::Clef(clef) => clef.tick = tick,
             Entry::KeySignature(key_signature) => key_signature.tick = tick,
+            Entry::OctaveShift(octave_shift) => octave_shift.tick = tick,
             Entry::TimeSignature(time_signature) => time_signature.tick = tick,
             Entry::Tone(tone) => tone.tick = tick,
         }
@@ -0,0 +1,107 @@
+use super::Entry;
+use crate::components::misc::{Tick, Ticks};
+use crate::score::tracks::Track;
+use crate::utils::shortid;
+use crate::Engine;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::wasm_bindgen;
+
+/// Octave lines (8va, 8vb, 15ma, 15mb).
+///
+/// These only change where tones are written, the sounding pitch is untouched.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OctaveShift {
+    pub key: String,
+    pub tick: Tick,
+    pub duration: Ticks,
+    pub octave: i8, // 1 = 8va, -1 = 8vb, 2 = 15ma, -2 = 15mb
+}
+
+impl OctaveShift {
+    pub fn new(tick: Tick, duration: Ticks, octave: i8) -> Self {
+        Self {
+            key: shortid(),
+            tick,
+            duration,
+            octave,
+        }
+    }
+
+    /// The text drawn at the start of the line
+    pub fn glyph(&self) -> String {
+        match self.octave {
+            2 => String::from("\u{E515}"),
+            -1 => String::from("\u{E51C}"),
+            -2 => String::from("\u{E51D}"),
+            _ => String::from("\u{E511}"),
+        }
+    }
+
+    /// Approximate width of the glyph so the dashed line can follow it
+    pub fn glyph_width(&self) -> f32 {
+        match self.octave.abs() {
+            2 => 3.5,
+            _ => 2.5,
+        }
+    }
+
+    /// Is the line drawn above the stave
+    pub fn is_above(&self) -> bool {
+        self.octave > 0
+    }
+}
+
+#[wasm_bindgen]
+impl Engine {
+    /// Create an octave line on an instrument track
+    pub fn create_octave_shift(
+        &mut self,
+        track_key: &str,
+        tick: Tick,
+        duration: Ticks,
+        octave: i8,
+    ) -> String {
+        let track = self.score.tracks.get_mut(track_key).unwrap();
+
+        let octave_shift = OctaveShift::new(tick, duration, octave);
+        let key = octave_shift.key.clone();
+        track.insert(Entry::OctaveShift(octave_shift));
+
+        self.emit();
+
+        key
+    }
+
+    /// update octave line duration
+    pub fn set_octave_shift_duration(&mut self, track_key: &str, entry_key: &str, duration: Ticks) {
+        let track = self.score.tracks.get_mut(track_key).unwrap();
+        let entry = track.entries.by_key.get_mut(entry_key).unwrap();
+        if let Entry::OctaveShift(octave_shift) = entry {
+            octave_shift.duration = duration;
+        }
+
+        self.emit();
+    }
+
+    /// Remove the octave line
+    pub fn remove_octave_shift(&mut self, track_key: &str, entry_key: &str) {
+        let track = self.score.tracks.get_mut(track_key).unwrap();
+        track.remove(entry_key);
+        self.emit();
+    }
+}
+
+impl Track {
+    /// Returns the octave shift covering a given tick if it exists
+    pub fn get_octave_shift_at_tick(&self, at: &Tick) -> Option<&OctaveShift> {
+        for entry in self.entries.by_key.values() {
+            if let Entry::OctaveShift(octave_shift) = entry {
+                if octave_shift.tick <= *at && octave_shift.tick + octave_shift.duration > *at {
+                    return Some(octave_shift);
+                }
+            }
+        }
+
+        None
+    }
+}
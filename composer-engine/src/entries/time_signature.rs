@@ -1,11 +1,13 @@
-use crate::components::duration::NoteDuration;
+use crate::components::duration::{NoteDuration, NOTE_DURATIONS};
 use crate::components::measurements::{BoundingBox, PaddingSpaces};
 use crate::components::misc::{Tick, Ticks};
+use crate::components::units::Space;
 use crate::entries::Entry;
 use crate::score::tracks::Track;
 use crate::utils::shortid;
 use crate::Engine;
 use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
 use wasm_bindgen::prelude::*;
 
 pub enum TimeSignatureType {
@@ -25,6 +27,74 @@ pub enum TimeSignatureDrawType {
     Open,            // X
 }
 
+pub const DIGIT_WIDTH: Space = 1.5;
+pub const PLUS_WIDTH: Space = 1.2; // between additive numerators
+pub const PART_GAP: Space = 1.6; // between the fractions of a mixed signature
+pub const PARENS_WIDTH: Space = 1.0;
+
+/// A single fraction of a time signature, the numerator may be additive (3+2+2/8)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimeSignaturePart {
+    pub beats: Vec<u8>,
+    pub beat_type: NoteDuration,
+}
+
+impl TimeSignaturePart {
+    pub fn new(beats: Vec<u8>, beat_type: NoteDuration) -> Self {
+        Self { beats, beat_type }
+    }
+
+    pub fn total(&self) -> u16 {
+        self.beats.iter().map(|beats| *beats as u16).sum()
+    }
+
+    pub fn width(&self) -> Space {
+        let digits: usize = self.beats.iter().map(|beats| beats.to_string().len()).sum();
+        let pluses = self.beats.len().saturating_sub(1);
+        let numerator = (digits as f32 * DIGIT_WIDTH) + (pluses as f32 * PLUS_WIDTH);
+        let denominator = self.beat_type.to_denominator().to_string().len() as f32 * DIGIT_WIDTH;
+        numerator.max(denominator) + 0.2
+    }
+
+    /// Parse fractions joined with pluses, ie. "3+2+2/8" or "3/4+3/8"
+    pub fn parse_list(input: &str) -> Option<Vec<TimeSignaturePart>> {
+        let mut output = Vec::new();
+        let mut pending: Vec<u8> = Vec::new();
+
+        for token in input.split('+') {
+            match token.split_once('/') {
+                Some((beats, beat_type)) => {
+                    pending.push(beats.trim().parse().ok()?);
+                    let denominator = beat_type.trim().parse().ok()?;
+                    let beat_type = NoteDuration::from_denominator(denominator)?;
+                    output.push(TimeSignaturePart::new(pending, beat_type));
+                    pending = Vec::new();
+                }
+                None => pending.push(token.trim().parse().ok()?),
+            }
+        }
+
+        if output.is_empty() || !pending.is_empty() {
+            return None;
+        }
+
+        if output
+            .iter()
+            .flat_map(|part| &part.beats)
+            .any(|beats| *beats == 0)
+        {
+            return None;
+        }
+
+        Some(output)
+    }
+}
+
+fn parts_width(parts: &[TimeSignaturePart]) -> Space {
+    let widths: Space = parts.iter().map(|part| part.width()).sum();
+    widths + (parts.len().saturating_sub(1) as f32 * PART_GAP)
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TimeSignature {
     pub key: String,
@@ -33,6 +103,12 @@ pub struct TimeSignature {
     pub beat_type: NoteDuration,
     pub draw_type: TimeSignatureDrawType,
     pub groupings: Vec<u8>,
+    #[serde(default)]
+    pub parts: Vec<TimeSignaturePart>, // additive and mixed signatures, empty if beats/beat_type
+    #[serde(default)]
+    pub interchangeable: Option<Vec<TimeSignaturePart>>, // displayed only, ie. 3/4 (6/8)
+    #[serde(default)]
+    pub pickup: Ticks, // length of an irregular first bar (anacrusis at tick 0), 0 if a full bar
 }
 
 impl TimeSignature {
@@ -53,6 +129,79 @@ impl TimeSignature {
                 None => TimeSignature::default_groupings(beats),
             },
             draw_type,
+            parts: Vec::new(),
+            interchangeable: None,
+            pickup: 0,
+        }
+    }
+
+    /// Create an additive or mixed time signature. The bar is counted in the
+    /// shortest beat type of the parts, ie. 3/4+3/8 has the length of 9/8.
+    pub fn new_composite(
+        tick: Tick,
+        parts: Vec<TimeSignaturePart>,
+        interchangeable: Option<Vec<TimeSignaturePart>>,
+    ) -> Option<Self> {
+        let unit = parts
+            .iter()
+            .map(|part| part.beat_type.to_denominator())
+            .max()?;
+
+        let mut beats: u16 = 0;
+        let mut groupings: Vec<u8> = Vec::new();
+        for part in &parts {
+            let ratio = (unit / part.beat_type.to_denominator()) as u16;
+            beats += part.total() * ratio;
+
+            let part_groupings = if part.beats.len() > 1 {
+                part.beats.clone()
+            } else {
+                TimeSignature::default_groupings(part.beats[0])
+            };
+            for group in part_groupings {
+                groupings.push(u8::try_from(group as u16 * ratio).ok()?);
+            }
+        }
+
+        let mut time_signature = TimeSignature::new(
+            tick,
+            u8::try_from(beats).ok()?,
+            NoteDuration::from_denominator(unit)?,
+            TimeSignatureDrawType::Regular,
+            Some(groupings),
+        );
+        time_signature.parts = parts;
+        time_signature.interchangeable = interchangeable;
+        Some(time_signature)
+    }
+
+    /// Parse a time signature, ie. "7/8", "3+2+2/8", "3/4+3/8" or "3/4 (6/8)"
+    pub fn parse(tick: Tick, input: &str) -> Option<Self> {
+        let (primary, interchangeable) = match input.split_once('(') {
+            Some((primary, rest)) => {
+                let inner = rest.trim().strip_suffix(')')?;
+                (primary, Some(TimeSignaturePart::parse_list(inner)?))
+            }
+            None => (input, None),
+        };
+
+        let parts = TimeSignaturePart::parse_list(primary)?;
+        TimeSignature::new_composite(tick, parts, interchangeable)
+    }
+
+    /// Is the signature drawn as anything other than a single fraction
+    pub fn is_composite(&self) -> bool {
+        self.interchangeable.is_some()
+            || self.parts.len() > 1
+            || self.parts.iter().any(|part| part.beats.len() > 1)
+    }
+
+    /// The fractions as displayed
+    pub fn display_parts(&self) -> Vec<TimeSignaturePart> {
+        if self.parts.is_empty() {
+            vec![TimeSignaturePart::new(vec![self.beats], self.beat_type)]
+        } else {
+            self.parts.clone()
         }
     }
 
@@ -121,10 +270,99 @@ impl TimeSignature {
     pub fn distance_from_barline(&self, tick: Tick, subdivisions: Ticks) -> Ticks {
         match self.kind() {
             TimeSignatureType::Open => tick - self.tick,
-            _ => (tick - self.tick) % self.ticks_per_bar(subdivisions),
+            _ => {
+                if self.pickup == 0 {
+                    (tick - self.tick) % self.ticks_per_bar(subdivisions)
+                } else if tick < self.tick + self.pickup {
+                    tick - self.tick
+                } else {
+                    (tick - self.tick - self.pickup) % self.ticks_per_bar(subdivisions)
+                }
+            }
         }
     }
 
+    /// Position of the tick within the bar as if the bar were full length.
+    /// An anacrusis is counted from the end of the bar, other irregular bars from the start.
+    fn bar_offset(&self, tick: Tick, subdivisions: Ticks) -> Ticks {
+        let distance = self.distance_from_barline(tick, subdivisions);
+        let ticks_per_bar = self.ticks_per_bar(subdivisions);
+        let is_anacrusis = self.tick == 0 && self.pickup > 0 && self.pickup < ticks_per_bar;
+        if is_anacrusis && tick < self.pickup {
+            ticks_per_bar - self.pickup + distance
+        } else {
+            distance
+        }
+    }
+
+    /// The ticks needed to complete the last bar of a span starting at the time signature
+    pub fn fill(&self, span: Ticks, subdivisions: Ticks) -> Ticks {
+        if let TimeSignatureType::Open = self.kind() {
+            return 0;
+        }
+
+        if self.pickup > 0 && span <= self.pickup {
+            return self.pickup - span;
+        }
+
+        let ticks_per_bar = self.ticks_per_bar(subdivisions);
+        let overflow = (span - self.pickup) % ticks_per_bar;
+        if overflow == 0 {
+            0
+        } else {
+            ticks_per_bar - overflow
+        }
+    }
+
+    /// The meter of the bar starting at a tick, as used when parsing the bars of a flow.
+    /// Irregular bars are given a meter that fits their length and keep it as the pickup.
+    pub fn bar_at(&self, tick: Tick, subdivisions: Ticks) -> TimeSignature {
+        let mut output = self.clone();
+        output.tick = tick;
+        output.pickup = 0;
+
+        if self.pickup > 0 && tick == self.tick && self.kind_is_metered() {
+            let length = self.pickup;
+            let ticks_per_beat = self.ticks_per_beat(subdivisions);
+            let beat_type = if length.is_multiple_of(ticks_per_beat) {
+                Some(self.beat_type)
+            } else {
+                NOTE_DURATIONS.iter().copied().find(|duration| {
+                    let ticks = duration.to_ticks(subdivisions);
+                    ticks > 0 && ticks < ticks_per_beat && length.is_multiple_of(ticks)
+                })
+            };
+            let beat_type = beat_type.unwrap_or(NoteDuration::HudredTwentyEighth);
+            let beats = (length / beat_type.to_ticks(subdivisions).max(1)).min(255) as u8;
+
+            output.beats = beats;
+            output.beat_type = beat_type;
+            output.groupings = TimeSignature::default_groupings(beats);
+            output.parts = Vec::new();
+            output.interchangeable = None;
+            output.pickup = length;
+        }
+
+        output
+    }
+
+    fn kind_is_metered(&self) -> bool {
+        !matches!(self.kind(), TimeSignatureType::Open)
+    }
+
+    /// Is this the meter of an irregular bar, see `bar_at`
+    pub fn is_irregular(&self) -> bool {
+        self.pickup > 0
+    }
+
+    /// Do both signatures count the same bars, regardless of how they are drawn
+    pub fn is_same_meter(&self, other: &TimeSignature, subdivisions: Ticks) -> bool {
+        self.beats == other.beats
+            && self.beat_type.to_ticks(subdivisions) == other.beat_type.to_ticks(subdivisions)
+            && self.ticks_per_bar(subdivisions) == other.ticks_per_bar(subdivisions)
+            && self.groupings == other.groupings
+    }
+
     // Returns true if the tick is on a beat
     pub fn is_on_beat(&self, tick: Tick, subdivisions: Ticks) -> bool {
         self.is_on_beat_type(tick, &self.beat_type, subdivisions)
@@ -138,7 +376,12 @@ impl TimeSignature {
         subdivisions: Ticks,
     ) -> bool {
         let ticks_per_beat = beat_type.to_ticks(subdivisions);
-        ((tick - self.tick) % ticks_per_beat) == 0
+        if self.pickup == 0 {
+            ((tick - self.tick) % ticks_per_beat) == 0
+        } else {
+            self.bar_offset(tick, subdivisions)
+                .is_multiple_of(ticks_per_beat)
+        }
     }
 
     pub fn is_on_first_beat(&self, tick: Tick, subdivisions: Ticks) -> bool {
@@ -188,6 +431,18 @@ impl TimeSignature {
             };
         }
 
+        if self.draw_type == TimeSignatureDrawType::Regular && self.is_composite() {
+            let mut width = parts_width(&self.display_parts());
+            if let Some(interchangeable) = &self.interchangeable {
+                width += (PARENS_WIDTH * 2.0) + parts_width(interchangeable);
+            }
+            return BoundingBox {
+                width,
+                height: 4.0,
+                padding: PaddingSpaces::new(0.0, 2.0, 0.0, 0.0),
+            };
+        }
+
         let is_wide = self.beats > 9
             || self.beat_type.to_ticks(subdivisions) < NoteDuration::Eighth.to_ticks(subdivisions);
         if is_wide {
@@ -217,6 +472,139 @@ impl Engine {
         draw_type: TimeSignatureDrawType,
         groupings: Option<Vec<u8>>,
     ) {
+        let new = TimeSignature::new(tick, beats, beat_type, draw_type, groupings);
+        self.insert_time_signature(flow_key, new);
+    }
+
+    /// Set the length of the bar starting at a tick without changing the visible meter.
+    /// A length of 0 (or the full bar) removes the override.
+    pub fn set_bar_length(&mut self, flow_key: &str, tick: Tick, length: Ticks) {
+        let flow = self.score.flows.by_key.get(flow_key).unwrap();
+        let master = self.score.tracks.get(&flow.master).unwrap();
+
+        let mut new = match master.get_time_signature_at_tick(&tick) {
+            Some(time_signature) => time_signature.clone(),
+            None => {
+                // an irregular bar restarts the current meter, hidden
+                let mut new = master
+                    .get_time_signature_before_tick(tick)
+                    .cloned()
+                    .unwrap_or_else(TimeSignature::default);
+                new.key = shortid();
+                new.tick = tick;
+                new.draw_type = TimeSignatureDrawType::Hidden;
+                new
+            }
+        };
+
+        if length == 0 || length == new.ticks_per_bar(flow.subdivisions) {
+            self.clear_bar_length(flow_key, tick);
+            return;
+        }
+
+        new.pickup = length;
+        self.insert_time_signature(flow_key, new);
+    }
+
+    /// Set the length of an anacrusis at the start of the flow, 0 for none
+    pub fn set_pickup_bar(&mut self, flow_key: &str, length: Ticks) {
+        self.set_bar_length(flow_key, 0, length);
+    }
+
+    /// Create a time signature from a string, ie. "3+2+2/8", "3/4+3/8" or "3/4 (6/8)".
+    /// Returns false if the string can't be parsed.
+    pub fn create_time_signature_from_string(
+        &mut self,
+        flow_key: &str,
+        tick: Tick,
+        input: &str,
+    ) -> bool {
+        match TimeSignature::parse(tick, input) {
+            Some(new) => {
+                self.insert_time_signature(flow_key, new);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl Engine {
+    /// Remove the bar length override at a tick. A hidden signature that only restarted the
+    /// meter is removed and the bars it padded at the end of the span are trimmed again,
+    /// as long as nothing has been written there since.
+    fn clear_bar_length(&mut self, flow_key: &str, tick: Tick) {
+        let track_keys = self.get_flow_track_keys(flow_key);
+        let flow = self.score.flows.by_key.get_mut(flow_key).unwrap();
+        let master = self.score.tracks.get_mut(&flow.master).unwrap();
+
+        let mut current = match master.get_time_signature_at_tick(&tick) {
+            Some(time_signature) if time_signature.pickup > 0 => time_signature.clone(),
+            _ => return,
+        };
+        master.remove(&current.key);
+
+        let previous = master.get_time_signature_before_tick(tick).cloned();
+        let meter = match previous {
+            Some(previous)
+                if current.draw_type == TimeSignatureDrawType::Hidden
+                    && current.is_same_meter(&previous, flow.subdivisions) =>
+            {
+                previous
+            }
+            _ => {
+                current.pickup = 0;
+                master.insert(Entry::TimeSignature(current.clone()));
+                current
+            }
+        };
+
+        if !meter.kind_is_metered() {
+            return;
+        }
+
+        let next_tick = match master.get_time_signature_after_tick(meter.tick, flow.length) {
+            Some(entry) => entry.tick,
+            None => flow.length,
+        };
+        let span = next_tick - meter.tick;
+        let overflow = span.saturating_sub(meter.pickup) % meter.ticks_per_bar(flow.subdivisions);
+        if overflow == 0 {
+            return;
+        }
+
+        let is_empty = track_keys.iter().all(|track_key| {
+            let track = &self.score.tracks[track_key];
+            !track
+                .entries
+                .by_tick
+                .keys()
+                .any(|tick| *tick >= next_tick - overflow && *tick < next_tick)
+        });
+        if !is_empty {
+            // pad the span to whole bars instead
+            self.insert_time_signature(flow_key, meter);
+            return;
+        }
+
+        let flow = self.score.flows.by_key.get_mut(flow_key).unwrap();
+        let master = self.score.tracks.get_mut(&flow.master).unwrap();
+        let mut later: Vec<(String, Tick)> = Vec::new();
+        for tick in next_tick..flow.length {
+            if let Some(time_signature) = master.get_time_signature_at_tick(&tick) {
+                later.push((time_signature.key.clone(), time_signature.tick));
+            };
+        }
+        flow.length -= overflow;
+        for (key, tick) in later {
+            master.shift(&key, tick - overflow);
+        }
+
+        self.emit();
+    }
+
+    fn insert_time_signature(&mut self, flow_key: &str, new: TimeSignature) {
+        let tick = new.tick;
         let flow = self.score.flows.by_key.get_mut(flow_key).unwrap();
         let master = self.score.tracks.get_mut(&flow.master).unwrap();
 
@@ -226,29 +614,29 @@ impl Engine {
             master.remove(&key);
         };
 
-        // insert the new time signature
-        let new = TimeSignature::new(tick, beats, beat_type, draw_type, groupings);
-        let ticks_per_bar = new.ticks_per_bar(flow.subdivisions);
-        master.insert(Entry::TimeSignature(new));
-
         // calculate diff
         let next_tick = match master.get_time_signature_after_tick(tick, flow.length) {
             Some(entry) => entry.tick,
             None => flow.length,
         };
 
-        let overflow = (next_tick - tick) % ticks_per_bar;
-        let fill = ticks_per_bar - overflow;
+        let fill = new.fill(next_tick - tick, flow.subdivisions);
+
+        // insert the new time signature
+        master.insert(Entry::TimeSignature(new));
 
         if fill > 0 {
-            flow.length += fill;
+            let mut later: Vec<(String, Tick)> = Vec::new();
             for tick in tick + 1..flow.length {
                 if let Some(time_signature) = master.get_time_signature_at_tick(&tick) {
-                    let key = time_signature.key.clone();
-                    let tick = time_signature.tick + fill;
-                    master.shift(&key, tick);
+                    later.push((time_signature.key.clone(), time_signature.tick));
                 };
             }
+
+            flow.length += fill;
+            for (key, tick) in later {
+                master.shift(&key, tick + fill);
+            }
         }
 
         self.emit();
@@ -272,6 +660,16 @@ impl Track {
         None
     }
 
+    /// Returns the time signature entry before a given tick if it exists
+    pub fn get_time_signature_before_tick(&self, at: Tick) -> Option<&TimeSignature> {
+        self.entries
+            .by_tick
+            .keys()
+            .filter(|tick| **tick < at)
+            .filter_map(|tick| self.get_time_signature_at_tick(tick))
+            .max_by_key(|time_signature| time_signature.tick)
+    }
+
     /// Returns the next time signature entry *after* a given tick if it exists
     pub fn get_time_signature_after_tick(
         &self,
@@ -287,3 +685,220 @@ impl Track {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{TimeSignature, TimeSignatureDrawType};
+    use crate::components::duration::NoteDuration;
+    use crate::components::misc::Tick;
+    use crate::entries::key_signature::KeySignatureMode;
+    use crate::parse::get_bars::get_bars;
+    use crate::Engine;
+
+    /// A flow of 4 bars of 4/4
+    fn flow() -> (Engine, String) {
+        let mut engine = Engine::tester();
+        let flow_key = engine.score.flows.order[0].clone();
+        engine.create_time_signature(
+            &flow_key,
+            0,
+            4,
+            NoteDuration::Quarter,
+            TimeSignatureDrawType::Regular,
+            None,
+        );
+        (engine, flow_key)
+    }
+
+    fn bars(engine: &Engine, flow_key: &str) -> Vec<Tick> {
+        let flow = &engine.score.flows.by_key[flow_key];
+        let mut output: Vec<Tick> = get_bars(flow, &engine.score.tracks)
+            .keys()
+            .copied()
+            .collect();
+        output.sort_unstable();
+        output
+    }
+
+    #[test]
+    fn parse_additive_test() {
+        let time_signature = TimeSignature::parse(0, "3+2+2/8").unwrap();
+        assert_eq!(time_signature.beats, 7);
+        assert_eq!(time_signature.beat_type.to_denominator(), 8);
+        assert_eq!(time_signature.groupings, vec![3, 2, 2]);
+        assert_eq!(time_signature.ticks_per_bar(48), 7 * 24);
+        assert!(time_signature.is_composite());
+    }
+
+    #[test]
+    fn parse_mixed_test() {
+        let time_signature = TimeSignature::parse(0, "3/4+3/8").unwrap();
+        assert_eq!(time_signature.beats, 9);
+        assert_eq!(time_signature.beat_type.to_denominator(), 8);
+        assert_eq!(time_signature.groupings, vec![6, 3]);
+        assert_eq!(time_signature.ticks_per_bar(48), (3 * 48) + (3 * 24));
+    }
+
+    #[test]
+    fn parse_interchangeable_test() {
+        let time_signature = TimeSignature::parse(0, "3/4 (6/8)").unwrap();
+        assert_eq!(time_signature.beats, 3);
+        assert_eq!(time_signature.beat_type.to_denominator(), 4);
+        let interchangeable = time_signature.interchangeable.as_ref().unwrap();
+        assert_eq!(interchangeable[0].beats, vec![6]);
+        assert!(time_signature.is_composite());
+        assert!(time_signature.metrics(48).width > 3.0);
+    }
+
+    #[test]
+    fn parse_invalid_test() {
+        assert!(TimeSignature::parse(0, "3+2").is_none());
+        assert!(TimeSignature::parse(0, "3/5").is_none());
+        assert!(TimeSignature::parse(0, "0/4").is_none());
+        assert!(TimeSignature::parse(0, "3/4 (6/8").is_none());
+    }
+
+    #[test]
+    fn pickup_test() {
+        let mut time_signature = TimeSignature::parse(0, "4/4").unwrap();
+        time_signature.pickup = 48;
+        assert!(time_signature.is_on_first_beat(0, 48));
+        assert!(time_signature.is_on_first_beat(48, 48));
+        assert!(!time_signature.is_on_first_beat(96, 48));
+        assert!(time_signature.is_on_first_beat(48 + 192, 48));
+        assert_eq!(time_signature.fill(48, 48), 0);
+        assert_eq!(time_signature.fill(48 + 96, 48), 96);
+        assert_eq!(time_signature.fill(24, 48), 24);
+    }
+
+    #[test]
+    fn pickup_beats_test() {
+        // an eighth note anacrusis in 4/4 starts on the off beat
+        let mut time_signature = TimeSignature::parse(0, "4/4").unwrap();
+        time_signature.pickup = 24;
+        assert!(!time_signature.is_on_beat(0, 48));
+        assert!(time_signature.is_on_beat(24, 48));
+        assert!(time_signature.is_on_beat(72, 48));
+    }
+
+    #[test]
+    fn bar_at_test() {
+        let mut time_signature = TimeSignature::parse(0, "4/4").unwrap();
+        time_signature.pickup = 72;
+        let pickup = time_signature.bar_at(0, 48);
+        assert_eq!(pickup.ticks_per_bar(48), 72);
+        assert_eq!(pickup.beats, 3);
+        assert_eq!(pickup.beat_type.to_denominator(), 8);
+        assert!(pickup.is_irregular());
+
+        let full = time_signature.bar_at(72, 48);
+        assert_eq!(full.ticks_per_bar(48), 192);
+        assert!(!full.is_irregular());
+    }
+
+    #[test]
+    fn parse_simple_test() {
+        let time_signature = TimeSignature::parse(0, "4/4").unwrap();
+        assert!(!time_signature.is_composite());
+        assert_eq!(time_signature.metrics(48).width, 1.7);
+    }
+
+    #[test]
+    fn set_pickup_bar_test() {
+        let (mut engine, flow_key) = flow();
+        engine.set_pickup_bar(&flow_key, 48);
+
+        // the flow grows so the last bar is still whole
+        assert_eq!(engine.score.flows.by_key[&flow_key].length, 48 + 4 * 192);
+        assert_eq!(bars(&engine, &flow_key), vec![0, 48, 240, 432, 624]);
+    }
+
+    #[test]
+    fn set_bar_length_test() {
+        let (mut engine, flow_key) = flow();
+        engine.set_bar_length(&flow_key, 192, 96);
+
+        let flow = &engine.score.flows.by_key[&flow_key];
+        let master = &engine.score.tracks[&flow.master];
+        let irregular = master.get_time_signature_at_tick(&192).unwrap();
+        assert_eq!(irregular.pickup, 96);
+        assert_eq!(irregular.draw_type, TimeSignatureDrawType::Hidden);
+        assert_eq!(bars(&engine, &flow_key), vec![0, 192, 288, 480, 672]);
+
+        // the full length of the bar removes the override and the padding it added
+        engine.set_bar_length(&flow_key, 192, 192);
+        let flow = &engine.score.flows.by_key[&flow_key];
+        let master = &engine.score.tracks[&flow.master];
+        assert!(master.get_time_signature_at_tick(&192).is_none());
+        assert_eq!(flow.length, 768);
+        assert_eq!(bars(&engine, &flow_key), vec![0, 192, 384, 576]);
+
+        // so a later change of meter isn't overridden
+        engine.create_time_signature(
+            &flow_key,
+            0,
+            3,
+            NoteDuration::Quarter,
+            TimeSignatureDrawType::Regular,
+            None,
+        );
+        assert_eq!(bars(&engine, &flow_key), vec![0, 144, 288, 432, 576, 720]);
+
+        // a length of 0 also clears the override
+        engine.set_bar_length(&flow_key, 144, 48);
+        engine.set_bar_length(&flow_key, 144, 0);
+        let flow = &engine.score.flows.by_key[&flow_key];
+        assert_eq!(flow.length, 864);
+        assert_eq!(bars(&engine, &flow_key), vec![0, 144, 288, 432, 576, 720]);
+    }
+
+    #[test]
+    fn clear_bar_length_test() {
+        // nothing is trimmed from under written music
+        let (mut engine, flow_key) = flow();
+        engine.set_bar_length(&flow_key, 192, 96);
+        let flow = &engine.score.flows.by_key[&flow_key];
+        let master = flow.master.clone();
+        engine.create_key_signature(&flow_key, 800, KeySignatureMode::Major, 1);
+        engine.set_bar_length(&flow_key, 192, 0);
+        let flow = &engine.score.flows.by_key[&flow_key];
+        assert!(engine.score.tracks[&master]
+            .get_time_signature_at_tick(&192)
+            .is_none());
+        assert_eq!(flow.length, 960);
+        assert_eq!(bars(&engine, &flow_key), vec![0, 192, 384, 576, 768]);
+    }
+
+    #[test]
+    fn get_timestamp_test() {
+        let (mut engine, flow_key) = flow();
+        assert_eq!(engine.get_timestamp(&flow_key, 0), "1:1:0.000");
+        assert_eq!(engine.get_timestamp(&flow_key, 48 + 24), "1:2:1.000");
+        assert_eq!(engine.get_timestamp(&flow_key, 192), "2:1:0.000");
+
+        // an anacrusis is bar 0, counted from the end of the bar
+        engine.set_pickup_bar(&flow_key, 48);
+        assert_eq!(engine.get_timestamp(&flow_key, 0), "0:4:0.000");
+        assert_eq!(engine.get_timestamp(&flow_key, 48), "1:1:0.000");
+        assert_eq!(engine.get_timestamp(&flow_key, 48 + 72), "1:2:1.000");
+    }
+
+    #[test]
+    fn get_time_signature_before_tick_test() {
+        let (mut engine, flow_key) = flow();
+        engine.create_time_signature(
+            &flow_key,
+            192,
+            3,
+            NoteDuration::Quarter,
+            TimeSignatureDrawType::Regular,
+            None,
+        );
+
+        let flow = &engine.score.flows.by_key[&flow_key];
+        let master = &engine.score.tracks[&flow.master];
+        assert!(master.get_time_signature_before_tick(0).is_none());
+        assert_eq!(master.get_time_signature_before_tick(192).unwrap().beats, 4);
+        assert_eq!(master.get_time_signature_before_tick(193).unwrap().beats, 3);
+    }
+}
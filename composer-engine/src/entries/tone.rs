@@ -2,6 +2,7 @@ use crate::components::articulation::Articulation;
 use crate::components::misc::{Tick, Ticks};
 use crate::components::pitch::{Accidental, Pitch};
 use crate::components::velocity::Velocity;
+use crate::entries::key_signature::{KeySignature, KeySignatureMode};
 use crate::entries::Entry;
 use crate::score::tracks::Track;
 use crate::utils::shortid;
@@ -10,6 +11,15 @@ use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::wasm_bindgen;
 use wasm_bindgen::JsValue;
 
+/// The ways a tone can be respelled, the MIDI number is always kept
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Respell {
+    Enharmonic, // flip between sharp and flat
+    Natural,    // the simplest spelling, naturals where possible
+    Key,        // as if entered within the active key
+}
+
 /// These represent the audiable tones of the music.
 /// They are never directly drawn in the score.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,6 +30,7 @@ pub struct Tone {
     pub pitch: Pitch,
     pub velocity: Velocity,
     pub articulation: Articulation,
+    pub string: Option<u8>, // user defined string for tablature (1 = highest)
 }
 
 impl Tone {
@@ -38,6 +49,7 @@ impl Tone {
             pitch,
             velocity,
             articulation,
+            string: None,
         }
     }
 
@@ -53,6 +65,31 @@ impl Tone {
     }
 }
 
+impl Engine {
+    /// Spell a MIDI number within the key active at the tick, using the previous
+    /// tone in the track to decide the melodic direction.
+    pub fn spell_tone_pitch(&self, track_key: &str, tick: Tick, int: u8) -> Pitch {
+        let key_signature = self
+            .get_flow_master_for_track(track_key)
+            .and_then(|master| master.get_key_signature_before_tick(tick + 1))
+            .cloned()
+            .unwrap_or_else(|| KeySignature::new(0, KeySignatureMode::Major, 0));
+
+        let track = self.score.tracks.get(track_key).unwrap();
+        let previous = track.get_tone_before_tick(tick).map(|tone| tone.pitch.int);
+
+        key_signature.spell(int, previous)
+    }
+
+    fn respell_pitch(&self, track_key: &str, tone: &Tone, respell: Respell) -> Pitch {
+        match respell {
+            Respell::Enharmonic => tone.pitch.flip_enharmonic(),
+            Respell::Natural => tone.pitch.simplest(),
+            Respell::Key => self.spell_tone_pitch(track_key, tone.tick, tone.pitch.int),
+        }
+    }
+}
+
 #[wasm_bindgen]
 impl Engine {
     /// Create a tone
@@ -69,13 +106,14 @@ impl Engine {
     ) -> String {
         // we want to be able to return this at the end
         let key = shortid();
-        let track = self.score.tracks.get_mut(track_key).unwrap();
 
         let pitch = match accidental {
             Some(accidental) => Pitch::new(pitch, accidental),
-            None => Pitch::from_int(pitch),
+            None => self.spell_tone_pitch(track_key, tick, pitch),
         };
 
+        let track = self.score.tracks.get_mut(track_key).unwrap();
+
         // we are now done with the entry, insert it back in
         track.insert(Entry::Tone(Tone::new(
             key.clone(),
@@ -93,10 +131,104 @@ impl Engine {
 
     /// update tone pitch
     pub fn set_tone_pitch(&mut self, track_key: &str, entry_key: &str, pitch: u8) {
+        let tick = match self
+            .score
+            .tracks
+            .get(track_key)
+            .unwrap()
+            .entries
+            .by_key
+            .get(entry_key)
+        {
+            Some(entry) => entry.tick(),
+            None => return,
+        };
+        let pitch = self.spell_tone_pitch(track_key, tick, pitch);
+
+        let track = self.score.tracks.get_mut(track_key).unwrap();
+        let entry = track.entries.by_key.get_mut(entry_key).unwrap();
+        if let Entry::Tone(tone) = entry {
+            tone.pitch = pitch;
+        }
+
+        self.emit();
+    }
+
+    /// update tone accidental, keeping the MIDI number (ignored if it can't be spelled that way)
+    pub fn set_tone_accidental(
+        &mut self,
+        track_key: &str,
+        entry_key: &str,
+        accidental: Accidental,
+    ) {
+        let track = self.score.tracks.get_mut(track_key).unwrap();
+        let entry = track.entries.by_key.get_mut(entry_key).unwrap();
+        if let Entry::Tone(tone) = entry {
+            // microtonal accidentals alter the written note rather than respell it
+            if accidental.is_microtonal() || tone.pitch.accidental.is_microtonal() {
+                tone.pitch = tone.pitch.with_accidental(accidental);
+            } else {
+                let spellings = Pitch::spellings(tone.pitch.int);
+                if let Some(pitch) = spellings
+                    .into_iter()
+                    .find(|pitch| pitch.accidental == accidental)
+                {
+                    tone.pitch = pitch;
+                }
+            }
+        }
+
+        self.emit();
+    }
+
+    /// Fine tune a tone by an offset in cents on top of any microtonal accidental, this
+    /// doesn't change how it is written
+    pub fn set_tone_cents(&mut self, track_key: &str, entry_key: &str, cents: i8) {
         let track = self.score.tracks.get_mut(track_key).unwrap();
         let entry = track.entries.by_key.get_mut(entry_key).unwrap();
         if let Entry::Tone(tone) = entry {
-            tone.pitch = Pitch::from_int(pitch);
+            tone.pitch.cents = tone.pitch.accidental.to_cents() + cents.clamp(-100, 100);
+        }
+
+        self.emit();
+    }
+
+    /// Respell a tone
+    pub fn respell_tone(&mut self, track_key: &str, entry_key: &str, respell: Respell) {
+        let track = self.score.tracks.get(track_key).unwrap();
+        let pitch = match track.entries.by_key.get(entry_key) {
+            Some(Entry::Tone(tone)) => self.respell_pitch(track_key, tone, respell),
+            _ => return,
+        };
+
+        let track = self.score.tracks.get_mut(track_key).unwrap();
+        if let Some(Entry::Tone(tone)) = track.entries.by_key.get_mut(entry_key) {
+            tone.pitch = pitch;
+        }
+
+        self.emit();
+    }
+
+    /// Respell all the tones starting within a range of ticks
+    pub fn respell_tones(&mut self, track_key: &str, start: Tick, end: Tick, respell: Respell) {
+        let track = self.score.tracks.get(track_key).unwrap();
+        let mut pitches: Vec<(String, Pitch)> = Vec::new();
+        for entry in track.entries.by_key.values() {
+            if let Entry::Tone(tone) = entry {
+                if tone.tick >= start && tone.tick < end {
+                    pitches.push((
+                        tone.key.clone(),
+                        self.respell_pitch(track_key, tone, respell),
+                    ));
+                }
+            }
+        }
+
+        let track = self.score.tracks.get_mut(track_key).unwrap();
+        for (key, pitch) in pitches {
+            if let Some(Entry::Tone(tone)) = track.entries.by_key.get_mut(&key) {
+                tone.pitch = pitch;
+            }
         }
 
         self.emit();
@@ -113,6 +245,17 @@ impl Engine {
         self.emit();
     }
 
+    /// update the string a tone is played on in tablature, None to assign automatically
+    pub fn set_tone_string(&mut self, track_key: &str, entry_key: &str, string: Option<u8>) {
+        let track = self.score.tracks.get_mut(track_key).unwrap();
+        let entry = track.entries.by_key.get_mut(entry_key).unwrap();
+        if let Entry::Tone(tone) = entry {
+            tone.string = string;
+        }
+
+        self.emit();
+    }
+
     /// move the tone
     pub fn shift_tone(&mut self, track_key: &str, entry_key: &str, new_tick: Tick) {
         let track = self.score.tracks.get_mut(track_key).unwrap();
@@ -138,15 +281,11 @@ impl Engine {
             let pitch = tone.pitch.clone();
             let velocity = tone.velocity.clone();
             let articulation = tone.articulation.clone();
+            let string = tone.string;
             tone.duration = slice_at - tone.tick;
-            track.insert(Entry::Tone(Tone::new(
-                shortid(),
-                slice_at,
-                diff,
-                pitch,
-                velocity,
-                articulation,
-            )));
+            let mut tail = Tone::new(shortid(), slice_at, diff, pitch, velocity, articulation);
+            tail.string = string;
+            track.insert(Entry::Tone(tail));
         }
 
         self.emit();
@@ -206,4 +345,149 @@ impl Track {
 
         output
     }
+
+    /// Returns the last tone starting before the given tick, the highest if there are several
+    pub fn get_tone_before_tick(&self, at: Tick) -> Option<&Tone> {
+        self.entries
+            .by_tick
+            .keys()
+            .filter(|tick| **tick < at)
+            .filter_map(|tick| {
+                let tones = self.get_tones_at_tick(tick);
+                tones.into_iter().max_by_key(|tone| tone.pitch.int)
+            })
+            .max_by_key(|tone| tone.tick)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Respell, Tone};
+    use crate::components::articulation::Articulation;
+    use crate::components::pitch::{Accidental, Pitch};
+    use crate::components::tuning::Tuning;
+    use crate::components::velocity::Velocity;
+    use crate::entries::Entry;
+    use crate::export::accidentals::alteration;
+    use crate::score::players::PlayerType;
+    use crate::score::tracks::Track;
+    use crate::Engine;
+
+    #[test]
+    fn get_tone_before_tick_test() {
+        let mut track = Track::new();
+        for (key, tick, int) in [("a", 0, 60), ("b", 16, 64), ("c", 16, 67), ("d", 32, 72)] {
+            track.insert(Entry::Tone(Tone::new(
+                String::from(key),
+                tick,
+                16,
+                Pitch::from_int(int),
+                Velocity::new(100),
+                Articulation::None,
+            )));
+        }
+
+        assert!(track.get_tone_before_tick(0).is_none());
+        assert_eq!(track.get_tone_before_tick(16).unwrap().key, "a");
+        // the highest of a chord
+        assert_eq!(track.get_tone_before_tick(32).unwrap().key, "c");
+        assert_eq!(track.get_tone_before_tick(1000).unwrap().key, "d");
+    }
+
+    #[test]
+    fn respell_tones_test() {
+        let mut engine = Engine::tester();
+        let flow_key = engine.score.flows.order[0].clone();
+        let player_key = engine.create_player(PlayerType::Solo);
+        let instrument_key = engine.create_instrument("strings.violin");
+        engine.assign_instrument_to_player(&player_key, &instrument_key);
+        let stave_key = engine.score.instruments[&instrument_key].staves[0].clone();
+        let track_key = engine.score.flows.by_key[&flow_key].staves[&stave_key].tracks[0].clone();
+
+        let keys: Vec<String> = [0, 16, 32]
+            .iter()
+            .map(|tick| {
+                engine.create_tone(
+                    &track_key,
+                    *tick,
+                    16,
+                    61,
+                    Some(Accidental::Sharp),
+                    100,
+                    Articulation::None,
+                )
+            })
+            .collect();
+
+        engine.respell_tones(&track_key, 16, 32, Respell::Enharmonic);
+
+        let accidentals: Vec<Accidental> = keys
+            .iter()
+            .map(
+                |key| match &engine.score.tracks[&track_key].entries.by_key[key] {
+                    Entry::Tone(tone) => tone.pitch.accidental,
+                    _ => panic!("expected a tone"),
+                },
+            )
+            .collect();
+
+        // the range is end exclusive
+        assert_eq!(
+            accidentals,
+            vec![Accidental::Sharp, Accidental::Flat, Accidental::Sharp]
+        );
+    }
+
+    #[test]
+    fn set_tone_cents_test() {
+        let mut engine = Engine::tester();
+        let flow_key = engine.score.flows.order[0].clone();
+        let player_key = engine.create_player(PlayerType::Solo);
+        let instrument_key = engine.create_instrument("strings.violin");
+        engine.assign_instrument_to_player(&player_key, &instrument_key);
+        let stave_key = engine.score.instruments[&instrument_key].staves[0].clone();
+        let track_key = engine.score.flows.by_key[&flow_key].staves[&stave_key].tracks[0].clone();
+
+        let quarter_sharp = engine.create_tone(
+            &track_key,
+            0,
+            96,
+            64,
+            Some(Accidental::QuarterSharp),
+            100,
+            Articulation::None,
+        );
+        let sharp = engine.create_tone(
+            &track_key,
+            96,
+            96,
+            61,
+            Some(Accidental::Sharp),
+            100,
+            Articulation::None,
+        );
+        engine.set_tone_cents(&track_key, &quarter_sharp, 10);
+        engine.set_tone_cents(&track_key, &sharp, 10);
+
+        // the fine tuning is added to the microtone for playback
+        let pitch =
+            |entry_key: &str| match &engine.score.tracks[&track_key].entries.by_key[entry_key] {
+                Entry::Tone(tone) => tone.pitch.clone(),
+                _ => panic!("expected a tone"),
+            };
+        let tuning = Tuning::new();
+        assert!((tuning.to_midi(&pitch(&quarter_sharp), None) - 64.6).abs() < 0.001);
+        assert!((tuning.to_midi(&pitch(&sharp), None) - 61.1).abs() < 0.001);
+
+        // but only the accidental is written
+        assert_eq!(alteration(&pitch(&quarter_sharp)), 50);
+        assert_eq!(alteration(&pitch(&sharp)), 100);
+        let abc = engine.export_abc(&flow_key, &instrument_key).unwrap();
+        assert!(abc.contains("^/E4 ^C4 |"));
+        let lilypond = engine.export_lilypond(&flow_key);
+        assert!(lilypond.contains("eih'2 cis'2 |"));
+        let musicxml = engine.export_musicxml(&flow_key);
+        assert!(musicxml.contains("<alter>0.5</alter>"));
+        assert!(musicxml.contains("<alter>1</alter>"));
+    }
 }
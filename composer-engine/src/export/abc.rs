@@ -0,0 +1,401 @@
+use super::accidentals::{alteration, Accidentals};
+use crate::components::misc::{Tick, Ticks};
+use crate::components::pitch::Pitch;
+use crate::entries::barline::BarlineDrawType;
+use crate::entries::clef::{Clef, ClefDrawType};
+use crate::entries::key_signature::{KeySignature, KeySignatureMode};
+use crate::entries::time_signature::{TimeSignature, TimeSignatureDrawType};
+use crate::parse::get_bars::get_bars;
+use crate::parse::get_beams::get_beams_in_track;
+use crate::parse::get_written_durations::Notation;
+use crate::utils::gcd;
+use crate::Engine;
+use rustc_hash::FxHashMap;
+use wasm_bindgen::prelude::wasm_bindgen;
+
+const BARS_PER_LINE: usize = 4;
+
+/// The accidental for an alteration in cents, microtones as fractions of a half step
+pub fn accidental(cents: i16) -> &'static str {
+    match cents {
+        -200 => "__",
+        -150 => "_3/2",
+        -100 => "_",
+        -50 => "_/",
+        50 => "^/",
+        100 => "^",
+        150 => "^3/2",
+        200 => "^^",
+        _ => "=",
+    }
+}
+
+/// The note name without accidental, ie. C4 -> C, C5 -> c, C6 -> c', C3 -> C,
+pub fn note_name(pitch: &Pitch) -> String {
+    let octave = pitch.octave() as i8;
+    if octave >= 5 {
+        format!(
+            "{}{}",
+            pitch.letter().to_lowercase(),
+            "'".repeat((octave - 5) as usize)
+        )
+    } else {
+        format!("{}{}", pitch.letter(), ",".repeat((4 - octave) as usize))
+    }
+}
+
+/// The note name with an accidental if one is needed in the bar
+fn spell(accidentals: &mut Accidentals, pitch: &Pitch) -> String {
+    match accidentals.is_written(pitch) {
+        true => format!("{}{}", accidental(alteration(pitch)), note_name(pitch)),
+        false => note_name(pitch),
+    }
+}
+
+/// A length in unit note lengths, ie. 2, 3/2 or / (a half), nothing for a single unit
+pub fn length(ticks: Ticks, unit: Ticks) -> String {
+    let divisor = gcd(ticks, unit).max(1);
+    match (ticks / divisor, unit / divisor) {
+        (1, 1) => String::new(),
+        (num, 1) => num.to_string(),
+        (1, 2) => String::from("/"),
+        (1, den) => format!("/{}", den),
+        (num, den) => format!("{}/{}", num, den),
+    }
+}
+
+/// The unit note length for a meter as ticks and the field value, 1/16 below 3/4 otherwise 1/8
+pub fn unit(meter: &TimeSignature, subdivisions: Ticks) -> (Ticks, &'static str) {
+    let whole = subdivisions * 4;
+    if meter.beats > 0 && meter.ticks_per_bar(subdivisions) * 4 < whole * 3 {
+        (whole / 16, "1/16")
+    } else {
+        (whole / 8, "1/8")
+    }
+}
+
+/// The meter field, ie. "6/8", "C", "(2+2+3)/8" or "none"
+pub fn meter(time_signature: &TimeSignature) -> String {
+    if time_signature.beats == 0 {
+        return String::from("none");
+    }
+
+    match time_signature.draw_type {
+        TimeSignatureDrawType::CommonTime => String::from("C"),
+        TimeSignatureDrawType::SplitCommonTime => String::from("C|"),
+        _ => {
+            let parts: Vec<String> = time_signature
+                .display_parts()
+                .iter()
+                .map(|part| {
+                    let beats: Vec<String> =
+                        part.beats.iter().map(|beats| beats.to_string()).collect();
+                    match beats.len() {
+                        1 => format!("{}/{}", beats[0], part.beat_type.to_denominator()),
+                        _ => format!("({})/{}", beats.join("+"), part.beat_type.to_denominator()),
+                    }
+                })
+                .collect();
+            parts.join("+")
+        }
+    }
+}
+
+/// The key field, ie. "G", "F#m", "D dor" or "C exp ^f _b" for custom keys
+pub fn key(key_signature: &KeySignature) -> String {
+    match key_signature.tonic() {
+        Some(tonic) => {
+            let accidental = match alteration(&tonic) {
+                100 => "#",
+                -100 => "b",
+                _ => "",
+            };
+            let mode = match key_signature.mode {
+                KeySignatureMode::Major => "",
+                KeySignatureMode::Minor => "m",
+                KeySignatureMode::Dorian => " dor",
+                KeySignatureMode::Phrygian => " phr",
+                KeySignatureMode::Lydian => " lyd",
+                KeySignatureMode::Mixolydian => " mix",
+                KeySignatureMode::Locrian => " loc",
+            };
+            format!("{}{}{}", tonic.letter(), accidental, mode)
+        }
+        None => {
+            let accidentals: Vec<String> = key_signature
+                .ordered_accidentals()
+                .iter()
+                .map(|pitch| {
+                    format!(
+                        "{}{}",
+                        accidental(alteration(pitch)),
+                        pitch.letter().to_lowercase()
+                    )
+                })
+                .collect();
+            format!("C exp {}", accidentals.join(" "))
+        }
+    }
+}
+
+fn clef(clef: &Clef) -> Option<&str> {
+    match clef.draw_as {
+        ClefDrawType::F => Some("bass"),
+        ClefDrawType::C if clef.offset == -2 => Some("tenor"),
+        ClefDrawType::C => Some("alto"),
+        ClefDrawType::Percussion => Some("perc"),
+        _ => None,
+    }
+}
+
+fn barline(barline_type: &BarlineDrawType) -> &str {
+    match barline_type {
+        BarlineDrawType::Single => "|",
+        BarlineDrawType::Double => "||",
+        BarlineDrawType::Final => "|]",
+        BarlineDrawType::StartRepeat => "|:",
+        BarlineDrawType::EndRepeat => ":|",
+        BarlineDrawType::EndStartRepeat => "::",
+    }
+}
+
+#[wasm_bindgen]
+impl Engine {
+    /// Export the first voice of a single stave instrument as an ABC tune at concert pitch.
+    /// Returns nothing if the instrument isn't in the flow or has more than one stave.
+    pub fn export_abc(&self, flow_key: &str, instrument_key: &str) -> Option<String> {
+        let (flow, instruments, _, _) = self.get_flow_instruments(flow_key);
+        let instrument = instruments
+            .into_iter()
+            .find(|instrument| instrument.key == instrument_key)?;
+        if instrument.staves.len() != 1 {
+            return None;
+        }
+
+        let master = self.score.tracks.get(&flow.master).unwrap();
+        let stave = flow.staves.get(&instrument.staves[0]).unwrap();
+        let stave_master = self.score.tracks.get(&stave.master).unwrap();
+        let track = self.score.tracks.get(&stave.tracks[0]).unwrap();
+
+        let subdivisions = flow.subdivisions;
+        let bars = get_bars(flow, &self.score.tracks);
+        let notation = track.to_notation_track(&flow.length, &bars, subdivisions);
+        let mut notations: Vec<&Notation> = notation.track.values().collect();
+        notations.sort_by_key(|entry| entry.tick);
+
+        // notes beamed together are written without a space between them
+        let mut beamed: FxHashMap<Tick, usize> = FxHashMap::default();
+        for (i, beam) in get_beams_in_track(&notation, &bars, subdivisions)
+            .iter()
+            .enumerate()
+        {
+            for tick in beam.ticks.keys() {
+                beamed.insert(*tick, i);
+            }
+        }
+
+        let opening_meter = master
+            .get_time_signature_at_tick(&0)
+            .cloned()
+            .unwrap_or_else(TimeSignature::default);
+        let opening_key = master
+            .get_key_signature_at_tick(&0)
+            .cloned()
+            .unwrap_or_else(|| KeySignature::new(0, KeySignatureMode::Major, 0));
+        let (unit_ticks, unit_field) = unit(&opening_meter, subdivisions);
+
+        let mut output: Vec<String> = vec![String::from("X:1")];
+        for title in [self.title(), flow.title.clone()] {
+            if !title.is_empty() {
+                output.push(format!("T:{}", title));
+            }
+        }
+        let composer = self.composer();
+        if !composer.is_empty() {
+            output.push(format!("C:{}", composer));
+        }
+        output.push(format!("M:{}", meter(&opening_meter)));
+        output.push(format!("L:{}", unit_field));
+        let clef = stave_master.get_clef_at_tick(&0).and_then(clef);
+        output.push(match clef {
+            Some(clef) => format!("K:{} clef={}", key(&opening_key), clef),
+            None => format!("K:{}", key(&opening_key)),
+        });
+
+        let mut accidentals = Accidentals::new(&opening_key);
+        let mut line = String::new();
+        let mut count: usize = 0;
+
+        if let Some(entry) = master.get_barline_at_tick(&0) {
+            line.push_str(barline(&entry.barline_type));
+        }
+
+        for (i, notation) in notations.iter().enumerate() {
+            let tick = notation.tick;
+
+            if tick > 0 && bars.contains_key(&tick) {
+                let bar = match master.get_barline_at_tick(&tick) {
+                    Some(entry) => barline(&entry.barline_type),
+                    None => "|",
+                };
+                line = format!("{} {}", line.trim_end(), bar);
+                accidentals.clear();
+                count += 1;
+                if count.is_multiple_of(BARS_PER_LINE) {
+                    output.push(line);
+                    line = String::new();
+                } else {
+                    line.push(' ');
+                }
+            }
+
+            if tick > 0 {
+                if let Some(entry) = master.get_time_signature_at_tick(&tick) {
+                    if entry.draw_type != TimeSignatureDrawType::Hidden {
+                        line.push_str(&format!("[M:{}]", meter(entry)));
+                    }
+                }
+                if let Some(entry) = master.get_key_signature_at_tick(&tick) {
+                    accidentals.set_key(entry);
+                    line.push_str(&format!("[K:{}]", key(entry)));
+                }
+            }
+
+            let bar = bars.get(&tick);
+            let is_whole_bar = bar.is_some_and(|bar| {
+                !bar.is_irregular()
+                    && bar.beats > 0
+                    && notation.duration == bar.ticks_per_bar(subdivisions)
+            });
+
+            let mut tones = notation.tones.clone();
+            tones.sort_by_key(|tone| tone.pitch.int);
+            let notes: Vec<String> = tones
+                .iter()
+                .map(|tone| {
+                    let tie = match notation.ties.contains(&tone.key) {
+                        true => "-",
+                        false => "",
+                    };
+                    format!("{}{}", spell(&mut accidentals, &tone.pitch), tie)
+                })
+                .collect();
+
+            let duration = length(notation.duration, unit_ticks);
+            line.push_str(&match notes.len() {
+                0 if is_whole_bar => String::from("Z"),
+                0 => format!("z{}", duration),
+                1 => format!("{}{}", notes[0], duration),
+                _ => format!("[{}]{}", notes.join(""), duration),
+            });
+
+            let is_beamed = match (beamed.get(&tick), notations.get(i + 1)) {
+                (Some(beam), Some(next)) => beamed.get(&next.tick) == Some(beam),
+                _ => false,
+            };
+            if !is_beamed {
+                line.push(' ');
+            }
+        }
+
+        let end = match master.get_barline_at_tick(&flow.length) {
+            Some(entry) => barline(&entry.barline_type),
+            None => "|",
+        };
+        output.push(format!("{} {}", line.trim_end(), end).trim().to_string());
+        output.push(String::new());
+
+        Some(output.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{key, length, meter, note_name, spell, unit};
+    use crate::components::pitch::{Accidental, Pitch};
+    use crate::entries::key_signature::{KeySignature, KeySignatureMode};
+    use crate::entries::time_signature::TimeSignature;
+    use crate::export::accidentals::Accidentals;
+    use crate::import::abc::{parse_key, parse_meter};
+
+    #[test]
+    fn note_name_test() {
+        assert_eq!(note_name(&Pitch::new(60, Accidental::Natural)), "C");
+        assert_eq!(note_name(&Pitch::new(72, Accidental::Natural)), "c");
+        assert_eq!(note_name(&Pitch::new(86, Accidental::Natural)), "d'");
+        assert_eq!(note_name(&Pitch::new(59, Accidental::Natural)), "B,");
+        assert_eq!(note_name(&Pitch::new(36, Accidental::Natural)), "C,,");
+    }
+
+    #[test]
+    fn length_test() {
+        assert_eq!(length(24, 24), "");
+        assert_eq!(length(48, 24), "2");
+        assert_eq!(length(12, 24), "/");
+        assert_eq!(length(6, 24), "/4");
+        assert_eq!(length(36, 24), "3/2");
+        assert_eq!(length(16, 24), "2/3");
+    }
+
+    #[test]
+    fn unit_test() {
+        let meter = TimeSignature::parse(0, "6/8").unwrap();
+        assert_eq!(unit(&meter, 48), (24, "1/8"));
+        let meter = TimeSignature::parse(0, "2/4").unwrap();
+        assert_eq!(unit(&meter, 48), (12, "1/16"));
+    }
+
+    #[test]
+    fn meter_test() {
+        for input in ["6/8", "C", "C|", "(2+2+3)/8", "none"] {
+            assert_eq!(meter(&parse_meter(input).unwrap()), input);
+        }
+    }
+
+    #[test]
+    fn key_test() {
+        assert_eq!(key(&KeySignature::new(0, KeySignatureMode::Major, 1)), "G");
+        assert_eq!(
+            key(&KeySignature::new(0, KeySignatureMode::Minor, 3)),
+            "F#m"
+        );
+        assert_eq!(
+            key(&KeySignature::new(0, KeySignatureMode::Mixolydian, -3)),
+            "Bb mix"
+        );
+        for input in ["D dor", "Ebm", "C exp ^f _b"] {
+            let (key_signature, _) = parse_key(input).unwrap();
+            assert_eq!(key(&key_signature), input);
+        }
+    }
+
+    #[test]
+    fn accidentals_test() {
+        let mut accidentals = Accidentals::new(&KeySignature::new(0, KeySignatureMode::Major, 1));
+        assert_eq!(
+            spell(&mut accidentals, &Pitch::new(78, Accidental::Sharp)),
+            "f"
+        );
+        assert_eq!(
+            spell(&mut accidentals, &Pitch::new(77, Accidental::Natural)),
+            "=f"
+        );
+        assert_eq!(
+            spell(&mut accidentals, &Pitch::new(77, Accidental::Natural)),
+            "f"
+        );
+        assert_eq!(
+            spell(&mut accidentals, &Pitch::new(66, Accidental::Sharp)),
+            "F"
+        );
+        assert_eq!(
+            spell(&mut accidentals, &Pitch::new(60, Accidental::QuarterSharp)),
+            "^/C"
+        );
+        accidentals.clear();
+        assert_eq!(
+            spell(&mut accidentals, &Pitch::new(78, Accidental::Sharp)),
+            "f"
+        );
+    }
+}
@@ -0,0 +1,75 @@
+use crate::components::pitch::Pitch;
+use crate::entries::key_signature::KeySignature;
+use rustc_hash::FxHashMap;
+
+/// The written alteration of a pitch in cents, ie. C# -> 100, E quarter flat -> -50.
+/// Only the accidental is written, any fine tuning is left to playback.
+pub fn alteration(pitch: &Pitch) -> i16 {
+    pitch.accidental.to_half_steps() as i16 * 100 + pitch.accidental.to_cents() as i16
+}
+
+/// Accidentals in force within a bar, for formats that need to know which are written
+pub struct Accidentals {
+    key: FxHashMap<u8, i16>,       // alterations in the key by step
+    bar: FxHashMap<(u8, u8), i16>, // alterations written in the bar by step and octave
+}
+
+impl Accidentals {
+    pub fn new(key_signature: &KeySignature) -> Self {
+        let mut output = Self {
+            key: FxHashMap::default(),
+            bar: FxHashMap::default(),
+        };
+        output.set_key(key_signature);
+        output
+    }
+
+    pub fn set_key(&mut self, key_signature: &KeySignature) {
+        self.key = key_signature
+            .accidentals()
+            .into_iter()
+            .map(|pitch| (pitch.steps(), alteration(pitch)))
+            .collect();
+        self.bar.clear();
+    }
+
+    /// Accidentals written in a bar don't carry over to the next
+    pub fn clear(&mut self) {
+        self.bar.clear();
+    }
+
+    /// Is an accidental written for the pitch, ie. it differs from the key or earlier in the bar
+    pub fn is_written(&mut self, pitch: &Pitch) -> bool {
+        let cents = alteration(pitch);
+        let position = (pitch.steps(), pitch.octave());
+        let current = match self.bar.get(&position) {
+            Some(cents) => *cents,
+            None => self.key.get(&pitch.steps()).copied().unwrap_or(0),
+        };
+
+        if cents != current {
+            self.bar.insert(position, cents);
+        }
+        cents != current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Accidentals;
+    use crate::components::pitch::{Accidental, Pitch};
+    use crate::entries::key_signature::{KeySignature, KeySignatureMode};
+
+    #[test]
+    fn is_written_test() {
+        let mut accidentals = Accidentals::new(&KeySignature::new(0, KeySignatureMode::Major, 1));
+        assert!(!accidentals.is_written(&Pitch::new(78, Accidental::Sharp)));
+        assert!(accidentals.is_written(&Pitch::new(77, Accidental::Natural)));
+        assert!(!accidentals.is_written(&Pitch::new(77, Accidental::Natural)));
+        // other octaves are unaffected
+        assert!(!accidentals.is_written(&Pitch::new(66, Accidental::Sharp)));
+        assert!(accidentals.is_written(&Pitch::new(60, Accidental::QuarterSharp)));
+        accidentals.clear();
+        assert!(!accidentals.is_written(&Pitch::new(78, Accidental::Sharp)));
+    }
+}
@@ -0,0 +1,729 @@
+use super::accidentals::{alteration, Accidentals};
+use crate::components::duration::{NoteDuration, NOTE_DURATIONS};
+use crate::components::interval::Interval;
+use crate::components::misc::{Tick, Ticks};
+use crate::components::pitch::Pitch;
+use crate::entries::barline::BarlineDrawType;
+use crate::entries::clef::{Clef, ClefDrawType};
+use crate::entries::key_signature::{KeySignature, KeySignatureMode};
+use crate::entries::time_signature::{TimeSignature, TimeSignatureDrawType};
+use crate::parse::get_bars::get_bars;
+use crate::parse::get_written_durations::Notation;
+use crate::score::instruments::defs::{get_def, InstrumentType};
+use crate::score::tracks::Track;
+use crate::Engine;
+use wasm_bindgen::prelude::wasm_bindgen;
+
+/// Cells per line of an embossed page
+const LINE_WIDTH: usize = 40;
+
+/// North American ASCII braille, indexed by the dots of a six dot cell
+const BRF: &str = " A1B'K2L@CIF/MSP\"E3H9O6R^DJG>NTQ,*5<-U8V.%[$+X!&;:4\\0Z7(_?W]#Y)=";
+
+/// Note names C to B as eighths, the other values add dots 3 and 6
+const STEPS: [&str; 7] = ["145", "15", "124", "1245", "125", "24", "245"];
+const UPPER_DIGITS: [&str; 10] = [
+    "245", "1", "12", "14", "145", "15", "124", "1245", "125", "24",
+];
+const LOWER_DIGITS: [&str; 10] = [
+    "356", "2", "23", "25", "256", "26", "235", "2356", "236", "35",
+];
+const OCTAVES: [&str; 9] = ["4 4", "4", "45", "456", "5", "46", "56", "6", "6 6"];
+const INTERVALS: [&str; 7] = ["36", "34", "346", "3456", "35", "356", "25"]; // octave, 2nd .. 7th
+const LETTERS: [&str; 26] = [
+    "1", "12", "14", "145", "15", "124", "1245", "125", "24", "245", "13", "123", "134", "1345",
+    "135", "1234", "12345", "1235", "234", "2345", "136", "1236", "2456", "1346", "13456", "1356",
+];
+
+const NUMBER: &str = "3456";
+const DOT: &str = "3";
+const TIE: &str = "4 14";
+const CHORD_TIE: &str = "46 14";
+const IN_ACCORD: &str = "126 345";
+const RIGHT_HAND: &str = "46 345";
+const LEFT_HAND: &str = "456 345";
+
+/// Cells from dot numbers, cells are separated by spaces ie. "126 345" -> ⠣⠜
+pub fn braille(dots: &str) -> String {
+    dots.split(' ')
+        .map(|cell| {
+            let bits = cell
+                .chars()
+                .filter_map(|dot| dot.to_digit(10))
+                .fold(0, |bits, dot| bits | 1 << (dot - 1));
+            char::from_u32(0x2800 + bits).unwrap()
+        })
+        .collect()
+}
+
+/// Convert unicode braille to ASCII braille for embossers (BRF)
+pub fn to_brf(input: &str) -> String {
+    input
+        .chars()
+        .map(|char| match char as u32 {
+            code @ 0x2800..=0x283F => BRF.as_bytes()[(code - 0x2800) as usize] as char,
+            _ => char,
+        })
+        .collect()
+}
+
+/// Uncontracted text for titles, letters and digits only
+pub fn text(input: &str) -> String {
+    let mut output = String::new();
+    let mut is_number = false;
+    for char in input.to_lowercase().chars() {
+        match char {
+            'a'..='z' => {
+                output.push_str(&braille(LETTERS[char as usize - 'a' as usize]));
+                is_number = false;
+            }
+            '0'..='9' => {
+                if !is_number {
+                    output.push_str(&braille(NUMBER));
+                    is_number = true;
+                }
+                let digit = char.to_digit(10).unwrap() as usize;
+                output.push_str(&braille(LETTERS[(digit + 9) % 10]));
+            }
+            _ => {
+                if char.is_whitespace() {
+                    output.push(' ');
+                }
+                is_number = false;
+            }
+        }
+    }
+    output.trim().to_string()
+}
+
+/// A number with the number sign, in upper or lower cells
+pub fn number(value: u32, lower: bool) -> String {
+    let digits = if lower { LOWER_DIGITS } else { UPPER_DIGITS };
+    value
+        .to_string()
+        .chars()
+        .map(|digit| braille(digits[digit.to_digit(10).unwrap() as usize]))
+        .collect()
+}
+
+/// The note sign for a step in a value, 16ths and shorter share the signs of longer values
+pub fn note(step: u8, duration: NoteDuration) -> String {
+    let extra = match duration {
+        NoteDuration::Whole | NoteDuration::Sixteenth => "36",
+        NoteDuration::Half | NoteDuration::ThirtySecond => "3",
+        NoteDuration::Quarter | NoteDuration::SixtyFourth => "6",
+        NoteDuration::Eighth | NoteDuration::HudredTwentyEighth => "",
+    };
+    braille(&format!("{}{}", STEPS[step as usize], extra))
+}
+
+pub fn rest(duration: NoteDuration) -> String {
+    braille(match duration {
+        NoteDuration::Whole | NoteDuration::Sixteenth => "134",
+        NoteDuration::Half | NoteDuration::ThirtySecond => "136",
+        NoteDuration::Quarter | NoteDuration::SixtyFourth => "1236",
+        NoteDuration::Eighth | NoteDuration::HudredTwentyEighth => "1346",
+    })
+}
+
+/// The octave mark, C4 is in the 4th octave
+pub fn octave(pitch: &Pitch) -> String {
+    braille(OCTAVES[(pitch.octave() as usize).min(8)])
+}
+
+/// Position on the stave counted in steps from C0
+fn position(pitch: &Pitch) -> i16 {
+    pitch.octave() as i16 * 7 + pitch.steps() as i16
+}
+
+/// An octave mark is needed after a sixth or more, or a fourth or fifth into another octave
+pub fn needs_octave(previous: Option<&Pitch>, pitch: &Pitch) -> bool {
+    match previous {
+        None => true,
+        Some(previous) => {
+            let steps = (position(pitch) - position(previous)).abs();
+            steps >= 5 || (steps >= 3 && previous.octave() != pitch.octave())
+        }
+    }
+}
+
+pub fn accidental(cents: i16) -> String {
+    braille(match cents {
+        -200 => "126 126",
+        -150 => "456 126",
+        -100 => "126",
+        -50 => "4 126",
+        50 => "4 146",
+        100 => "146",
+        150 => "456 146",
+        200 => "146 146",
+        _ => "16",
+    })
+}
+
+/// Sharps or flats repeated up to three, otherwise counted ie. ⠼⠙⠩ for four sharps
+pub fn key(key_signature: &KeySignature) -> String {
+    let pitches = key_signature.ordered_accidentals();
+    if key_signature.custom.is_some() {
+        return pitches
+            .iter()
+            .map(|pitch| accidental(alteration(pitch)))
+            .collect();
+    }
+
+    let sign = if key_signature.offset > 0 {
+        "146"
+    } else {
+        "126"
+    };
+    match key_signature.offset.unsigned_abs() {
+        0 => String::new(),
+        count @ 1..=3 => braille(sign).repeat(count as usize),
+        count => format!(
+            "{}{}{}",
+            braille(NUMBER),
+            number(count as u32, false),
+            braille(sign)
+        ),
+    }
+}
+
+pub fn time(time_signature: &TimeSignature) -> String {
+    if time_signature.beats == 0 || time_signature.draw_type == TimeSignatureDrawType::Hidden {
+        return String::new();
+    }
+
+    match time_signature.draw_type {
+        TimeSignatureDrawType::CommonTime => braille("46 14"),
+        TimeSignatureDrawType::SplitCommonTime => braille("456 14"),
+        _ => {
+            let parts: Vec<String> = time_signature
+                .display_parts()
+                .iter()
+                .map(|part| {
+                    let beats: Vec<String> = part
+                        .beats
+                        .iter()
+                        .map(|beats| number(*beats as u32, false))
+                        .collect();
+                    format!(
+                        "{}{}{}",
+                        braille(NUMBER),
+                        beats.join(&braille("3456")),
+                        number(part.beat_type.to_denominator() as u32, true)
+                    )
+                })
+                .collect();
+            parts.join("")
+        }
+    }
+}
+
+pub fn clef(clef: &Clef) -> Option<String> {
+    let line = 3 - clef.offset / 2;
+    let dots = match (&clef.draw_as, line) {
+        (ClefDrawType::G, 2) => "345 34 123",
+        (ClefDrawType::F, 4) => "345 3456 123",
+        (ClefDrawType::C, 3) => "345 346 123",
+        (ClefDrawType::C, 4) => "345 346 5 123",
+        _ => return None,
+    };
+    Some(braille(dots))
+}
+
+fn barline(barline_type: &BarlineDrawType) -> Option<(&str, &str)> {
+    // (at the end of the bar, at the start of the next)
+    match barline_type {
+        BarlineDrawType::Single => None,
+        BarlineDrawType::Double => Some(("126 13 3", "")),
+        BarlineDrawType::Final => Some(("126 13", "")),
+        BarlineDrawType::StartRepeat => Some(("", "126 2356")),
+        BarlineDrawType::EndRepeat => Some(("126 23", "")),
+        BarlineDrawType::EndStartRepeat => Some(("126 23", "126 2356")),
+    }
+}
+
+/// The value of a notation, unwritable durations use the longest value that fits
+fn value(notation: &Notation, subdivisions: Ticks) -> (NoteDuration, bool) {
+    match notation.base_to_note_duration(subdivisions) {
+        Some(duration) => (duration, notation.is_dotted(subdivisions)),
+        None => {
+            let duration = NOTE_DURATIONS
+                .iter()
+                .copied()
+                .find(|duration| duration.to_ticks(subdivisions) <= notation.duration)
+                .unwrap_or(NoteDuration::HudredTwentyEighth);
+            (duration, false)
+        }
+    }
+}
+
+/// Everything needed to write the music of a stave
+struct Context<'a> {
+    master: &'a Track,
+    stave_master: &'a Track,
+    starts: Vec<Tick>,
+    length: Ticks,
+    subdivisions: Ticks,
+    transposition: Interval,
+    reads_up: bool, // chords are written from the lowest note, ie. in the bass clef
+}
+
+impl Context<'_> {
+    fn key_at(&self, tick: Tick) -> KeySignature {
+        self.master
+            .get_key_signature_before_tick(tick + 1)
+            .cloned()
+            .unwrap_or_else(|| KeySignature::new(0, KeySignatureMode::Major, 0))
+            .transpose(&self.transposition)
+    }
+
+    /// A voice in a bar, returning the last written note for the next octave mark
+    fn voice(
+        &self,
+        notations: &[&Notation],
+        start: Tick,
+        end: Tick,
+        mut previous: Option<Pitch>,
+    ) -> (String, Option<Pitch>) {
+        let mut output = String::new();
+        let mut accidentals = Accidentals::new(&self.key_at(start));
+
+        for notation in notations
+            .iter()
+            .filter(|notation| notation.tick >= start && notation.tick < end)
+        {
+            let tick = notation.tick;
+            if tick > start {
+                if let Some(entry) = self.stave_master.get_clef_at_tick(&tick) {
+                    if let Some(clef) = clef(entry) {
+                        output.push_str(&clef);
+                        previous = None;
+                    }
+                }
+            }
+
+            let (duration, dotted) = value(notation, self.subdivisions);
+            let dot = if dotted { braille(DOT) } else { String::new() };
+            let whole_bar = tick == start && notation.duration == end - start;
+
+            let mut tones: Vec<_> = notation.tones.iter().collect();
+            tones.sort_by_key(|tone| tone.pitch.int);
+            if !self.reads_up {
+                tones.reverse();
+            }
+
+            let main = match tones.first() {
+                Some(tone) => tone,
+                None => {
+                    match whole_bar {
+                        true => output.push_str(&rest(NoteDuration::Whole)),
+                        false => output.push_str(&format!("{}{}", rest(duration), dot)),
+                    }
+                    continue;
+                }
+            };
+
+            let pitch = main.pitch.transpose(&self.transposition);
+            if accidentals.is_written(&pitch) {
+                output.push_str(&accidental(alteration(&pitch)));
+            }
+            if needs_octave(previous.as_ref(), &pitch) {
+                output.push_str(&octave(&pitch));
+            }
+            output.push_str(&note(pitch.steps(), duration));
+            output.push_str(&dot);
+
+            let all_tied = tones.iter().all(|tone| notation.ties.contains(&tone.key));
+            if tones.len() == 1 && all_tied {
+                output.push_str(&braille(TIE));
+            }
+
+            // intervals are counted from the written note
+            for tone in tones.iter().skip(1) {
+                let other = tone.pitch.transpose(&self.transposition);
+                if accidentals.is_written(&other) {
+                    output.push_str(&accidental(alteration(&other)));
+                }
+                let steps = (position(&other) - position(&pitch)).abs();
+                if steps > 7 {
+                    output.push_str(&octave(&other));
+                }
+                output.push_str(&braille(INTERVALS[(steps % 7) as usize]));
+                if !all_tied && notation.ties.contains(&tone.key) {
+                    output.push_str(&braille(TIE));
+                }
+            }
+            if tones.len() > 1 && all_tied {
+                output.push_str(&braille(CHORD_TIE));
+            }
+
+            previous = Some(pitch);
+        }
+
+        (output, previous)
+    }
+
+    /// A bar of all the voices of a stave, joined with in-accord signs
+    fn bar(
+        &self,
+        voices: &[Vec<&Notation>],
+        index: usize,
+        previous: Option<Pitch>,
+    ) -> (String, Option<Pitch>) {
+        let start = self.starts[index];
+        let end = self.starts.get(index + 1).copied().unwrap_or(self.length);
+        let mut output = String::new();
+
+        if index > 0 {
+            if let Some(entry) = self.master.get_key_signature_at_tick(&start) {
+                output.push_str(&key(&entry.transpose(&self.transposition)));
+            }
+            if let Some(entry) = self.master.get_time_signature_at_tick(&start) {
+                output.push_str(&time(entry));
+            }
+            if !output.is_empty() {
+                output.push(' ');
+            }
+            if let Some(entry) = self.stave_master.get_clef_at_tick(&start) {
+                if let Some(clef) = clef(entry) {
+                    output.push_str(&clef);
+                }
+            }
+        }
+
+        let barline_at = |tick: Tick| {
+            self.master
+                .get_barline_at_tick(&tick)
+                .and_then(|entry| barline(&entry.barline_type))
+        };
+        if let Some((_, opening)) = barline_at(start) {
+            output.push_str(&braille(opening));
+        }
+
+        let mut last = previous;
+        for (i, voice) in voices.iter().enumerate() {
+            if i > 0 {
+                output.push_str(&braille(IN_ACCORD));
+            }
+            // the first note after an in-accord sign is always given an octave mark
+            let (music, voice_last) =
+                self.voice(voice, start, end, if i == 0 { last.clone() } else { None });
+            output.push_str(&music);
+            if i == 0 {
+                last = voice_last;
+            }
+        }
+
+        if let Some((closing, _)) = barline_at(end) {
+            output.push_str(&braille(closing));
+        }
+
+        (output.replace('\u{2800}', ""), last)
+    }
+
+    /// The music of a stave, bars separated by spaces and wrapped at the line width.
+    /// The first note of each line has an octave mark.
+    fn music(&self, voices: &[Vec<&Notation>], mut line: String) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut previous = None;
+
+        for index in 0..self.starts.len() {
+            let (mut bar, mut last) = self.bar(voices, index, previous.clone());
+            if !line.is_empty() && line.chars().count() + 1 + bar.chars().count() > LINE_WIDTH {
+                lines.push(line);
+                line = String::new();
+                (bar, last) = self.bar(voices, index, None);
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&bar);
+            previous = last;
+        }
+
+        if !line.is_empty() {
+            lines.push(line);
+        }
+        lines
+    }
+}
+
+#[wasm_bindgen]
+impl Engine {
+    /// Export the written music of an instrument in a flow in music braille, as
+    /// unicode braille or as ASCII braille (BRF) for embossing.
+    /// Returns nothing if the instrument isn't in the flow.
+    pub fn export_braille(
+        &self,
+        flow_key: &str,
+        instrument_key: &str,
+        ascii: bool,
+    ) -> Option<String> {
+        let (flow, instruments, _, _) = self.get_flow_instruments(flow_key);
+        let instrument = instruments
+            .into_iter()
+            .find(|instrument| instrument.key == instrument_key)?;
+        let master = self.score.tracks.get(&flow.master).unwrap();
+        let bars = get_bars(flow, &self.score.tracks);
+        let mut starts: Vec<Tick> = bars.keys().copied().collect();
+        starts.sort_unstable();
+
+        let transposition = match get_def(&instrument.id) {
+            Some(def) => def.transposition,
+            None => Interval::UNISON,
+        };
+
+        let mut output: Vec<String> = Vec::new();
+        for title in [self.title(), flow.title.clone(), instrument.name()] {
+            let title = text(&title);
+            if !title.is_empty() {
+                output.push(title);
+            }
+        }
+
+        // the opening key and time signature on a line of their own
+        let mut signatures = String::new();
+        if !matches!(instrument.instrument_type, InstrumentType::Percussive) {
+            if let Some(entry) = master.get_key_signature_at_tick(&0) {
+                signatures.push_str(&key(&entry.transpose(&transposition)));
+            }
+        }
+        if let Some(entry) = master.get_time_signature_at_tick(&0) {
+            signatures.push_str(&time(entry));
+        }
+        if !signatures.is_empty() {
+            output.push(signatures);
+        }
+
+        let is_keyboard = instrument.staves.len() > 1;
+        for (i, stave_key) in instrument.staves.iter().enumerate() {
+            let stave = flow.staves.get(stave_key).unwrap();
+            let stave_master = self.score.tracks.get(&stave.master).unwrap();
+            let opening_clef = stave_master.get_clef_at_tick(&0);
+
+            let notations: Vec<_> = stave
+                .tracks
+                .iter()
+                .map(|track_key| {
+                    let track = self.score.tracks.get(track_key).unwrap();
+                    track.to_notation_track(&flow.length, &bars, flow.subdivisions)
+                })
+                .collect();
+            let voices: Vec<Vec<&Notation>> = notations
+                .iter()
+                .map(|notation| {
+                    let mut entries: Vec<&Notation> = notation.track.values().collect();
+                    entries.sort_by_key(|entry| entry.tick);
+                    entries
+                })
+                .collect();
+
+            let context = Context {
+                master,
+                stave_master,
+                starts: starts.clone(),
+                length: flow.length,
+                subdivisions: flow.subdivisions,
+                transposition,
+                reads_up: opening_clef.is_some_and(|clef| clef.draw_as == ClefDrawType::F),
+            };
+
+            let mut opening = String::new();
+            if is_keyboard {
+                opening.push_str(&braille(if i == 0 { RIGHT_HAND } else { LEFT_HAND }));
+            }
+            if let Some(clef) = opening_clef.and_then(clef) {
+                opening.push_str(&clef);
+            }
+            output.extend(context.music(&voices, opening));
+        }
+
+        output.push(String::new());
+        let output = output.join("\n");
+        Some(if ascii { to_brf(&output) } else { output })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        accidental, braille, key, needs_octave, note, number, octave, rest, text, time, to_brf,
+    };
+    use crate::components::articulation::Articulation;
+    use crate::components::duration::NoteDuration;
+    use crate::components::misc::Tick;
+    use crate::components::pitch::{Accidental, Pitch};
+    use crate::entries::key_signature::{KeySignature, KeySignatureMode};
+    use crate::entries::time_signature::{TimeSignature, TimeSignatureDrawType};
+    use crate::score::players::PlayerType;
+    use crate::score::tracks::Track;
+    use crate::Engine;
+
+    #[test]
+    fn braille_test() {
+        assert_eq!(braille("126 345"), "⠣⠜");
+        assert_eq!(braille("123456"), "⠿");
+        assert_eq!(to_brf("⠣⠜ ⠿"), "<> =");
+    }
+
+    #[test]
+    fn note_test() {
+        assert_eq!(note(0, NoteDuration::Eighth), "⠙");
+        assert_eq!(note(0, NoteDuration::Quarter), "⠹");
+        assert_eq!(note(4, NoteDuration::Half), "⠗");
+        assert_eq!(note(6, NoteDuration::Whole), "⠾");
+        assert_eq!(note(1, NoteDuration::Sixteenth), "⠵");
+        assert_eq!(rest(NoteDuration::Quarter), "⠧");
+    }
+
+    #[test]
+    fn octave_test() {
+        let c4 = Pitch::new(60, Accidental::Natural);
+        let f4 = Pitch::new(65, Accidental::Natural);
+        let a4 = Pitch::new(69, Accidental::Natural);
+        let b3 = Pitch::new(59, Accidental::Natural);
+        let g3 = Pitch::new(55, Accidental::Natural);
+        assert_eq!(octave(&c4), "⠐");
+        assert_eq!(octave(&b3), "⠸");
+        assert!(needs_octave(None, &c4));
+        // a third or less never needs a mark
+        assert!(!needs_octave(Some(&c4), &b3));
+        // a fourth or fifth only when the octave changes
+        assert!(!needs_octave(Some(&c4), &f4));
+        assert!(needs_octave(Some(&c4), &g3));
+        // a sixth or more always does
+        assert!(needs_octave(Some(&c4), &a4));
+    }
+
+    #[test]
+    fn signatures_test() {
+        assert_eq!(key(&KeySignature::new(0, KeySignatureMode::Major, 2)), "⠩⠩");
+        assert_eq!(
+            key(&KeySignature::new(0, KeySignatureMode::Major, -4)),
+            "⠼⠙⠣"
+        );
+        assert_eq!(time(&TimeSignature::parse(0, "3/4").unwrap()), "⠼⠉⠲");
+        assert_eq!(time(&TimeSignature::parse(0, "12/8").unwrap()), "⠼⠁⠃⠦");
+        assert_eq!(number(6, true), "⠖");
+        assert_eq!(accidental(-100), "⠣");
+        assert_eq!(accidental(0), "⠡");
+    }
+
+    #[test]
+    fn text_test() {
+        assert_eq!(text("Flute 2"), "⠋⠇⠥⠞⠑ ⠼⠃");
+    }
+
+    /// A flow of 4/4 in C with an instrument, returning its stave and track keys
+    fn score(id: &str) -> (Engine, String, String, Vec<(String, String)>) {
+        let mut engine = Engine::tester();
+        let flow_key = engine.score.flows.order[0].clone();
+        engine.create_time_signature(
+            &flow_key,
+            0,
+            4,
+            NoteDuration::Quarter,
+            TimeSignatureDrawType::Regular,
+            None,
+        );
+        engine.create_key_signature(&flow_key, 0, KeySignatureMode::Major, 0);
+        let player_key = engine.create_player(PlayerType::Solo);
+        let instrument_key = engine.create_instrument(id);
+        engine.assign_instrument_to_player(&player_key, &instrument_key);
+        let staves = engine.score.instruments[&instrument_key]
+            .staves
+            .iter()
+            .map(|stave_key| {
+                let stave = &engine.score.flows.by_key[&flow_key].staves[stave_key];
+                (stave_key.clone(), stave.tracks[0].clone())
+            })
+            .collect();
+        (engine, flow_key, instrument_key, staves)
+    }
+
+    fn tone(engine: &mut Engine, track_key: &str, tick: Tick, duration: Tick, pitch: u8) {
+        engine.create_tone(
+            track_key,
+            tick,
+            duration,
+            pitch,
+            None,
+            100,
+            Articulation::None,
+        );
+    }
+
+    #[test]
+    fn export_braille_test() {
+        let (mut engine, flow_key, instrument_key, staves) = score("strings.violin");
+        let track_key = &staves[0].1;
+        tone(&mut engine, track_key, 0, 48, 60);
+        tone(&mut engine, track_key, 48, 72, 62);
+        tone(&mut engine, track_key, 120, 24, 64);
+        tone(&mut engine, track_key, 144, 96, 65); // tied over the bar
+        for pitch in [60, 64, 67] {
+            tone(&mut engine, track_key, 240, 96, pitch);
+        }
+        engine.create_key_signature(&flow_key, 384, KeySignatureMode::Major, 1);
+        tone(&mut engine, track_key, 384, 192, 66);
+        let output = engine
+            .export_braille(&flow_key, &instrument_key, false)
+            .unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[..2], ["⠧⠊⠕⠇⠊⠝", "⠼⠙⠲"]);
+
+        // the dotted quarter across the middle of the bar and the note over the barline are
+        // tied, the chord reads down from its top note in the treble clef, and the key
+        // changes at the third bar
+        assert_eq!(
+            lines[2],
+            ["⠜⠌⠇", "⠐⠹⠱⠈⠉⠑⠋⠻⠈⠉", "⠻⠳⠬⠔⠨⠉⠳⠬⠔⠧", "⠩", "⠿", "⠍"].join(" ")
+        );
+    }
+
+    #[test]
+    fn export_braille_voices_test() {
+        let (mut engine, flow_key, instrument_key, staves) = score("strings.violoncello");
+        let (stave_key, track_key) = &staves[0];
+        for pitch in [48, 52, 55] {
+            tone(&mut engine, track_key, 0, 192, pitch);
+        }
+        let track = Track::new();
+        let second = track.key.clone();
+        engine.score.tracks.insert(second.clone(), track);
+        let flow = engine.score.flows.by_key.get_mut(&flow_key).unwrap();
+        flow.staves
+            .get_mut(stave_key)
+            .unwrap()
+            .tracks
+            .push(second.clone());
+        tone(&mut engine, &second, 192, 96, 43);
+        tone(&mut engine, &second, 288, 96, 45);
+        tone(&mut engine, track_key, 192, 192, 59);
+        for bar in 2..12 {
+            tone(&mut engine, track_key, bar * 192, 96, 48);
+            tone(&mut engine, track_key, bar * 192 + 96, 96, 50);
+        }
+        engine.set_flow_length(&flow_key, 192 * 12);
+        let output = engine
+            .export_braille(&flow_key, &instrument_key, false)
+            .unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+
+        // chords read up from the lowest note in the bass clef, voices are joined in accord
+        // and each line starts with an octave mark
+        assert_eq!(
+            lines[2..],
+            [
+                "⠜⠼⠇ ⠸⠽⠬⠔⠣⠜⠍ ⠸⠾⠣⠜⠘⠗⠎ ⠸⠝⠕⠣⠜⠍ ⠝⠕⠣⠜⠍ ⠝⠕⠣⠜⠍",
+                "⠸⠝⠕⠣⠜⠍ ⠝⠕⠣⠜⠍ ⠝⠕⠣⠜⠍ ⠝⠕⠣⠜⠍ ⠝⠕⠣⠜⠍ ⠝⠕⠣⠜⠍",
+                "⠸⠝⠕⠣⠜⠍",
+            ]
+        );
+        let ascii = engine
+            .export_braille(&flow_key, &instrument_key, true)
+            .unwrap();
+        assert_eq!(ascii, to_brf(&output));
+    }
+}
@@ -0,0 +1,487 @@
+use super::accidentals::alteration;
+use crate::components::articulation::Articulation;
+use crate::components::interval::Interval;
+use crate::components::misc::{Tick, Ticks};
+use crate::components::pitch::{Accidental, Pitch};
+use crate::entries::barline::BarlineDrawType;
+use crate::entries::clef::{Clef, ClefDrawType};
+use crate::entries::key_signature::KeySignature;
+use crate::entries::time_signature::{TimeSignature, TimeSignatureDrawType};
+use crate::parse::get_bars::{get_bars, Bars};
+use crate::parse::get_written_durations::Notation;
+use crate::score::instruments::defs::get_def;
+use crate::score::stave::Stave;
+use crate::score::tracks::Track;
+use crate::utils::gcd;
+use crate::Engine;
+use wasm_bindgen::prelude::wasm_bindgen;
+
+const VERSION: &str = "2.24.0";
+const VOICES: [&str; 4] = ["\\voiceOne", "\\voiceTwo", "\\voiceThree", "\\voiceFour"];
+
+/// A length in ticks as a reduced fraction of a whole note, ie. 3/4
+pub fn fraction(ticks: Ticks, subdivisions: Ticks) -> String {
+    let whole = subdivisions * 4;
+    let divisor = gcd(ticks, whole).max(1);
+    format!("{}/{}", ticks / divisor, whole / divisor)
+}
+
+/// Escape text for use in a LilyPond string
+pub fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// The absolute pitch name in Dutch note names, ie. C4 -> c', Bb2 -> bes,
+pub fn pitch_name(pitch: &Pitch) -> String {
+    let cents = alteration(pitch);
+    let accidental = match cents {
+        -200 => "eses",
+        -150 => "eseh",
+        -100 => "es",
+        -50 => "eh",
+        50 => "ih",
+        100 => "is",
+        150 => "isih",
+        200 => "isis",
+        _ => "",
+    };
+
+    let octave = pitch.octave() as i8 - 3;
+    let marks = if octave > 0 {
+        "'".repeat(octave as usize)
+    } else {
+        ",".repeat(octave.unsigned_abs() as usize)
+    };
+
+    format!("{}{}{}", pitch.letter().to_lowercase(), accidental, marks)
+}
+
+/// The duration of a notation, falling back to a scaled quarter if it isn't writable
+pub fn duration(notation: &Notation, subdivisions: Ticks) -> String {
+    match notation.base_to_note_duration(subdivisions) {
+        Some(base) => {
+            let dot = if notation.is_dotted(subdivisions) {
+                "."
+            } else {
+                ""
+            };
+            format!("{}{}", base.to_denominator(), dot)
+        }
+        None => format!("4*{}", fraction(notation.duration * 4, subdivisions)),
+    }
+}
+
+pub fn clef_name(clef: &Clef) -> Option<String> {
+    let line = 3 - clef.offset / 2;
+    let name = match (&clef.draw_as, line) {
+        (ClefDrawType::Hidden, _) => return None,
+        (ClefDrawType::Percussion, _) => "percussion",
+        (ClefDrawType::G, 1) => "french",
+        (ClefDrawType::G, _) => "treble",
+        (ClefDrawType::F, 3) => "varbaritone",
+        (ClefDrawType::F, 5) => "subbass",
+        (ClefDrawType::F, _) => "bass",
+        (ClefDrawType::C, 1) => "soprano",
+        (ClefDrawType::C, 2) => "mezzosoprano",
+        (ClefDrawType::C, 4) => "tenor",
+        (ClefDrawType::C, 5) => "baritone",
+        (ClefDrawType::C, _) => "alto",
+    };
+    Some(match clef.octave {
+        0 => String::from(name),
+        octave if octave > 0 => format!("\"{}^{}\"", name, 1 + 7 * octave),
+        octave => format!("\"{}_{}\"", name, 1 + 7 * octave.abs()),
+    })
+}
+
+fn key(key_signature: &KeySignature) -> String {
+    match key_signature.tonic() {
+        Some(tonic) => format!(
+            "\\key {} \\{}",
+            pitch_name(&tonic).trim_end_matches('\''),
+            key_signature.mode.name()
+        ),
+        None => {
+            let alterations: Vec<String> = key_signature
+                .ordered_accidentals()
+                .iter()
+                .map(|pitch| {
+                    format!(
+                        "({} . {}/2)",
+                        pitch.steps(),
+                        pitch.accidental.to_half_steps()
+                    )
+                })
+                .collect();
+            format!("\\set Staff.keyAlterations = #`({})", alterations.join(" "))
+        }
+    }
+}
+
+fn time(time_signature: &TimeSignature) -> Option<String> {
+    if time_signature.beats == 0 {
+        return None;
+    }
+
+    let parts = time_signature.display_parts();
+    let output = if time_signature.parts.len() > 1 || parts.iter().any(|part| part.beats.len() > 1)
+    {
+        let parts: Vec<String> = parts
+            .iter()
+            .map(|part| {
+                let beats: Vec<String> = part.beats.iter().map(|beats| beats.to_string()).collect();
+                format!("({} {})", beats.join(" "), part.beat_type.to_denominator())
+            })
+            .collect();
+        format!("\\compoundMeter #'({})", parts.join(" "))
+    } else {
+        let time = format!(
+            "\\time {}/{}",
+            time_signature.beats,
+            time_signature.beat_type.to_denominator()
+        );
+        match time_signature.draw_type {
+            TimeSignatureDrawType::CommonTime | TimeSignatureDrawType::SplitCommonTime => {
+                format!("\\defaultTimeSignature {}", time)
+            }
+            _ => format!("\\numericTimeSignature {}", time),
+        }
+    };
+
+    match time_signature.draw_type {
+        TimeSignatureDrawType::Hidden => {
+            Some(format!("\\once \\omit Staff.TimeSignature {}", output))
+        }
+        _ => Some(output),
+    }
+}
+
+fn barline(barline_type: &BarlineDrawType) -> &str {
+    match barline_type {
+        BarlineDrawType::Single => "|",
+        BarlineDrawType::Double => "||",
+        BarlineDrawType::Final => "|.",
+        BarlineDrawType::StartRepeat => ".|:",
+        BarlineDrawType::EndRepeat => ":|.",
+        BarlineDrawType::EndStartRepeat => ":|.|:",
+    }
+}
+
+fn articulation(articulation: &Articulation) -> &str {
+    match articulation {
+        Articulation::None => "",
+        Articulation::Staccato => "-.",
+        Articulation::Staccatissimo => "-!",
+        Articulation::Tenuto => "--",
+        Articulation::StaccatoTenuto => "-_",
+    }
+}
+
+/// Everything needed to write the music of a voice
+struct Context<'a> {
+    master: &'a Track,
+    stave_master: &'a Track,
+    bars: &'a Bars,
+    length: Ticks,
+    subdivisions: Ticks,
+    transposition: Interval,
+}
+
+impl Context<'_> {
+    /// Time, key and clef changes and barlines before the music at a tick
+    fn changes(&self, tick: Tick, output: &mut Vec<String>) {
+        if let Some(entry) = self.master.get_barline_at_tick(&tick) {
+            if tick > 0 {
+                output.push(format!("\\bar \"{}\"", barline(&entry.barline_type)));
+            }
+        }
+
+        if let Some(entry) = self.master.get_time_signature_at_tick(&tick) {
+            if let Some(time) = time(entry) {
+                output.push(time);
+            }
+        }
+
+        if let Some(bar) = self.bars.get(&tick) {
+            if bar.is_irregular() && tick == 0 {
+                output.push(format!(
+                    "\\partial 1*{}",
+                    fraction(bar.pickup, self.subdivisions)
+                ));
+            } else if bar.is_irregular() {
+                output.push(format!(
+                    "\\set Timing.measureLength = #(ly:make-moment {})",
+                    fraction(bar.pickup, self.subdivisions)
+                ));
+            } else if tick > 0 {
+                // restore the length after an irregular bar
+                let previous = self
+                    .bars
+                    .iter()
+                    .filter(|(start, _)| **start < tick)
+                    .max_by_key(|(start, _)| **start);
+                if previous.is_some_and(|(start, bar)| *start > 0 && bar.is_irregular()) {
+                    output.push(format!(
+                        "\\set Timing.measureLength = #(ly:make-moment {})",
+                        fraction(bar.ticks_per_bar(self.subdivisions), self.subdivisions)
+                    ));
+                }
+            }
+        }
+
+        if let Some(entry) = self.master.get_key_signature_at_tick(&tick) {
+            output.push(key(&entry.transpose(&self.transposition)));
+        }
+
+        if let Some(entry) = self.stave_master.get_clef_at_tick(&tick) {
+            if let Some(name) = clef_name(entry) {
+                output.push(format!("\\clef {}", name));
+            }
+        }
+    }
+
+    /// The music of a voice, a bar per line. Changes are only written in the first voice.
+    fn music(&self, notations: &[&Notation], is_first: bool) -> Vec<String> {
+        let mut lines: Vec<String> = Vec::new();
+        let mut line: Vec<String> = Vec::new();
+        let mut starts: Vec<&Tick> = self.bars.keys().collect();
+        starts.sort();
+
+        for (i, notation) in notations.iter().enumerate() {
+            let tick = notation.tick;
+
+            if tick > 0 && self.bars.contains_key(&tick) {
+                line.push(String::from("|"));
+                lines.push(line.join(" "));
+                line = Vec::new();
+            }
+
+            if is_first {
+                self.changes(tick, &mut line);
+            }
+
+            let is_continued = i > 0 && notations[i - 1].has_tie();
+            let tie = if notation.has_tie() { "~" } else { "" };
+
+            let bar_end = starts
+                .iter()
+                .find(|start| ***start > tick)
+                .map(|start| **start)
+                .unwrap_or(self.length);
+            let is_whole_bar = self.bars.contains_key(&tick) && tick + notation.duration == bar_end;
+
+            let mut tones = notation.tones.clone();
+            tones.sort_by_key(|tone| tone.pitch.int);
+            let pitches: Vec<String> = tones
+                .iter()
+                .map(|tone| pitch_name(&tone.pitch.transpose(&self.transposition)))
+                .collect();
+            let articulation = match tones.first() {
+                Some(tone) if !is_continued => articulation(&tone.articulation),
+                _ => "",
+            };
+
+            let duration = duration(notation, self.subdivisions);
+            line.push(match pitches.len() {
+                0 if is_whole_bar => {
+                    format!("R1*{}", fraction(notation.duration, self.subdivisions))
+                }
+                0 => format!("r{}", duration),
+                1 => format!("{}{}{}{}", pitches[0], duration, articulation, tie),
+                _ => format!("<{}>{}{}{}", pitches.join(" "), duration, articulation, tie),
+            });
+        }
+
+        if is_first {
+            if let Some(entry) = self.master.get_barline_at_tick(&self.length) {
+                line.push(format!("\\bar \"{}\"", barline(&entry.barline_type)));
+            }
+        }
+
+        if !line.is_empty() {
+            lines.push(line.join(" "));
+        }
+
+        lines
+    }
+}
+
+fn indent(lines: Vec<String>, depth: usize) -> Vec<String> {
+    lines
+        .into_iter()
+        .map(|line| format!("{}{}", "  ".repeat(depth), line))
+        .collect()
+}
+
+#[wasm_bindgen]
+impl Engine {
+    /// Export a flow as LilyPond source with absolute pitches.
+    /// Transposing instruments are written at written pitch with a \transposition.
+    pub fn export_lilypond(&self, flow_key: &str) -> String {
+        let (flow, instruments, _, _) = self.get_flow_instruments(flow_key);
+        let master = self.score.tracks.get(&flow.master).unwrap();
+        let bars = get_bars(flow, &self.score.tracks);
+
+        let mut output: Vec<String> = vec![format!("\\version \"{}\"", VERSION), String::new()];
+
+        output.push(String::from("\\header {"));
+        for (field, value) in [
+            ("title", self.title()),
+            ("composer", self.composer()),
+            ("arranger", self.arranger()),
+            ("copyright", self.copyright()),
+        ] {
+            if !value.is_empty() {
+                output.push(format!("  {} = \"{}\"", field, escape(&value)));
+            }
+        }
+        output.push(String::from("}"));
+        output.push(String::new());
+
+        output.push(String::from("\\score {"));
+        if !flow.title.is_empty() {
+            output.push(format!(
+                "  \\header {{ piece = \"{}\" }}",
+                escape(&flow.title)
+            ));
+        }
+        output.push(String::from("  <<"));
+
+        for instrument in instruments {
+            let transposition = match get_def(&instrument.id) {
+                Some(def) => def.transposition,
+                None => Interval::UNISON,
+            };
+            let names = format!(
+                "\\with {{ instrumentName = \"{}\" shortInstrumentName = \"{}\" }}",
+                escape(&instrument.name()),
+                escape(&instrument.short_name)
+            );
+
+            let staves: Vec<&Stave> = instrument
+                .staves
+                .iter()
+                .map(|key| flow.staves.get(key).unwrap())
+                .collect();
+
+            let mut lines: Vec<String> = Vec::new();
+            for stave in &staves {
+                let context = Context {
+                    master,
+                    stave_master: self.score.tracks.get(&stave.master).unwrap(),
+                    bars: &bars,
+                    length: flow.length,
+                    subdivisions: flow.subdivisions,
+                    transposition,
+                };
+
+                let header = match staves.len() {
+                    1 => format!("\\new Staff {} <<", names),
+                    _ => String::from("\\new Staff <<"),
+                };
+                lines.push(header);
+
+                for (i, track_key) in stave.tracks.iter().enumerate() {
+                    let track = self.score.tracks.get(track_key).unwrap();
+                    let notation = track.to_notation_track(&flow.length, &bars, flow.subdivisions);
+                    let mut notations: Vec<&Notation> = notation.track.values().collect();
+                    notations.sort_by_key(|entry| entry.tick);
+
+                    lines.push(String::from("  \\new Voice {"));
+                    if i == 0 && !transposition.is_unison() {
+                        let sounding =
+                            Pitch::new(60, Accidental::Natural).transpose(&transposition.invert());
+                        lines.push(format!("    \\transposition {}", pitch_name(&sounding)));
+                    }
+                    if stave.tracks.len() > 1 {
+                        if let Some(voice) = VOICES.get(i) {
+                            lines.push(format!("    {}", voice));
+                        }
+                    }
+                    lines.extend(indent(context.music(&notations, i == 0), 2));
+                    lines.push(String::from("  }"));
+                }
+
+                lines.push(String::from(">>"));
+            }
+
+            if staves.len() > 1 {
+                output.push(format!("    \\new PianoStaff {} <<", names));
+                output.extend(indent(lines, 3));
+                output.push(String::from("    >>"));
+            } else {
+                output.extend(indent(lines, 2));
+            }
+        }
+
+        output.push(String::from("  >>"));
+        output.push(String::from("  \\layout { }"));
+        output.push(String::from("}"));
+        output.push(String::new());
+
+        output.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{clef_name, duration, fraction, pitch_name};
+    use crate::components::pitch::{Accidental, Pitch};
+    use crate::entries::clef::{Clef, ClefDrawType};
+    use crate::parse::get_written_durations::Notation;
+    use rustc_hash::FxHashSet;
+
+    #[test]
+    fn pitch_name_test() {
+        assert_eq!(pitch_name(&Pitch::new(60, Accidental::Natural)), "c'");
+        assert_eq!(pitch_name(&Pitch::new(46, Accidental::Flat)), "bes,");
+        assert_eq!(pitch_name(&Pitch::new(57, Accidental::Natural)), "a");
+        assert_eq!(pitch_name(&Pitch::new(60, Accidental::Sharp)), "bis");
+        assert_eq!(
+            pitch_name(&Pitch::new(74, Accidental::DoubleSharp)),
+            "cisis''"
+        );
+        assert_eq!(
+            pitch_name(&Pitch::new(60, Accidental::QuarterSharp)),
+            "cih'"
+        );
+        assert_eq!(
+            pitch_name(&Pitch::new(63, Accidental::ThreeQuarterFlat)),
+            "eeseh'"
+        );
+    }
+
+    #[test]
+    fn duration_test() {
+        let notation = |duration| Notation {
+            tick: 0,
+            tones: Vec::new(),
+            duration,
+            ties: FxHashSet::default(),
+        };
+        assert_eq!(duration(&notation(48), 48), "4");
+        assert_eq!(duration(&notation(72), 48), "4.");
+        assert_eq!(duration(&notation(192), 48), "1");
+        assert_eq!(duration(&notation(6), 48), "32");
+        assert_eq!(duration(&notation(16), 48), "4*1/3");
+    }
+
+    #[test]
+    fn fraction_test() {
+        assert_eq!(fraction(144, 48), "3/4");
+        assert_eq!(fraction(72, 48), "3/8");
+        assert_eq!(fraction(192, 48), "1/1");
+    }
+
+    #[test]
+    fn clef_name_test() {
+        let treble = Clef::new(0, 67, 2, ClefDrawType::G);
+        assert_eq!(clef_name(&treble), Some(String::from("treble")));
+        let bass = Clef::new(0, 53, -2, ClefDrawType::F);
+        assert_eq!(clef_name(&bass), Some(String::from("bass")));
+        let tenor = Clef::new(0, 60, -2, ClefDrawType::C);
+        assert_eq!(clef_name(&tenor), Some(String::from("tenor")));
+        let guitar = Clef::new_octave(0, 55, 2, ClefDrawType::G, -1);
+        assert_eq!(clef_name(&guitar), Some(String::from("\"treble_8\"")));
+    }
+}
@@ -0,0 +1,686 @@
+use super::accidentals::Accidentals;
+use super::xml::{attr, XmlWriter};
+use crate::components::interval::Interval;
+use crate::components::misc::{Tick, Ticks};
+use crate::components::pitch::Accidental;
+use crate::entries::barline::BarlineDrawType;
+use crate::entries::clef::{Clef, ClefDrawType};
+use crate::entries::key_signature::{KeySignature, KeySignatureMode};
+use crate::entries::time_signature::{TimeSignature, TimeSignatureDrawType};
+use crate::parse::get_bars::get_bars;
+use crate::parse::get_beams::{get_beams_in_track, Beams};
+use crate::parse::get_written_durations::Notation;
+use crate::score::instruments::defs::{get_def, InstrumentType};
+use crate::score::instruments::Instrument;
+use crate::score::stave::Stave;
+use crate::score::tracks::Track;
+use crate::Engine;
+use wasm_bindgen::prelude::wasm_bindgen;
+
+const PROLOG: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<?xml-model href=\"https://music-encoding.org/schema/5.0/mei-all.rng\" type=\"application/xml\" schematypens=\"http://relaxng.org/ns/structure/1.0\"?>
+";
+const NAMESPACE: &str = "http://www.music-encoding.org/ns/mei";
+const VERSION: &str = "5.0";
+
+/// The written accidental, ie. "s", "ff" or "1qs"
+pub fn accid(accidental: &Accidental) -> &'static str {
+    match accidental {
+        Accidental::DoubleSharp => "x",
+        Accidental::Sharp => "s",
+        Accidental::Natural => "n",
+        Accidental::Flat => "f",
+        Accidental::DoubleFlat => "ff",
+        Accidental::QuarterSharp => "1qs",
+        Accidental::ThreeQuarterSharp => "3qs",
+        Accidental::QuarterFlat => "1qf",
+        Accidental::ThreeQuarterFlat => "3qf",
+        Accidental::NaturalArrowUp => "nu",
+        Accidental::NaturalArrowDown => "nd",
+        Accidental::SharpArrowUp => "su",
+        Accidental::SharpArrowDown => "sd",
+        Accidental::FlatArrowUp => "fu",
+        Accidental::FlatArrowDown => "fd",
+    }
+}
+
+/// The key signature attribute, ie. "0", "2s" or "3f"
+pub fn key_sig(key_signature: &KeySignature) -> String {
+    match key_signature.offset {
+        0 => String::from("0"),
+        offset if offset > 0 => format!("{}s", offset),
+        offset => format!("{}f", offset.abs()),
+    }
+}
+
+/// The tie attribute of a note, "i" (initial), "m" (medial) or "t" (terminal)
+pub fn tie(start: bool, stop: bool) -> Option<&'static str> {
+    match (start, stop) {
+        (true, false) => Some("i"),
+        (true, true) => Some("m"),
+        (false, true) => Some("t"),
+        (false, false) => None,
+    }
+}
+
+fn write_clef(writer: &mut XmlWriter, clef: &Clef) {
+    let shape = match clef.draw_as {
+        ClefDrawType::Hidden => return,
+        ClefDrawType::Percussion => "perc",
+        ClefDrawType::G => "G",
+        ClefDrawType::F => "F",
+        ClefDrawType::C => "C",
+    };
+    let mut attributes = vec![attr("shape", shape)];
+    if clef.draw_as != ClefDrawType::Percussion {
+        attributes.push(attr("line", 3 - clef.offset / 2));
+    }
+    if clef.octave != 0 {
+        attributes.push(attr("dis", 1 + 7 * clef.octave.abs()));
+        let place = if clef.octave > 0 { "above" } else { "below" };
+        attributes.push(attr("dis.place", place));
+    }
+    writer.empty("clef", &attributes);
+}
+
+fn write_key(writer: &mut XmlWriter, key_signature: &KeySignature) {
+    match &key_signature.custom {
+        Some(_) => {
+            writer.open("keySig", &[]);
+            for pitch in key_signature.ordered_accidentals() {
+                writer.empty(
+                    "keyAccid",
+                    &[
+                        attr("pname", pitch.letter().to_lowercase()),
+                        attr("accid", accid(&pitch.accidental)),
+                    ],
+                );
+            }
+            writer.close();
+        }
+        None => writer.empty(
+            "keySig",
+            &[
+                attr("sig", key_sig(key_signature)),
+                attr("mode", key_signature.mode.name()),
+            ],
+        ),
+    }
+}
+
+fn write_meter(writer: &mut XmlWriter, time_signature: &TimeSignature) {
+    if time_signature.beats == 0 {
+        return;
+    }
+
+    let mut common = Vec::new();
+    match time_signature.draw_type {
+        TimeSignatureDrawType::Hidden => common.push(attr("visible", "false")),
+        TimeSignatureDrawType::CommonTime => common.push(attr("sym", "common")),
+        TimeSignatureDrawType::SplitCommonTime => common.push(attr("sym", "cut")),
+        _ => (),
+    }
+
+    let parts = time_signature.display_parts();
+    if parts.len() > 1 {
+        writer.open("meterSigGrp", &[attr("func", "mixed")]);
+    }
+    for part in &parts {
+        let beats: Vec<String> = part.beats.iter().map(|beats| beats.to_string()).collect();
+        let mut attributes = vec![
+            attr("count", beats.join("+")),
+            attr("unit", part.beat_type.to_denominator()),
+        ];
+        attributes.extend(common.iter().cloned());
+        writer.empty("meterSig", &attributes);
+    }
+    if parts.len() > 1 {
+        writer.close();
+    }
+}
+
+/// Barlines at the end of a measure, start repeats are written on the next measure
+fn right(barline_type: &BarlineDrawType) -> Option<&'static str> {
+    match barline_type {
+        BarlineDrawType::Single | BarlineDrawType::StartRepeat => None,
+        BarlineDrawType::Double => Some("dbl"),
+        BarlineDrawType::Final => Some("end"),
+        BarlineDrawType::EndRepeat => Some("rptend"),
+        BarlineDrawType::EndStartRepeat => Some("rptboth"),
+    }
+}
+
+/// A track of a stave, with its notation in order
+struct Layer<'a> {
+    notations: Vec<&'a Notation>,
+    beams: Beams,
+}
+
+struct Staff<'a> {
+    n: usize,
+    stave: &'a Stave,
+    layers: Vec<Layer<'a>>,
+}
+
+struct Part<'a> {
+    instrument: &'a Instrument,
+    staves: Vec<Staff<'a>>,
+    transposition: Interval,
+}
+
+#[wasm_bindgen]
+impl Engine {
+    /// Export a flow as an MEI 5.0 document, a staff per stave and a layer per track.
+    /// Pitches are written as displayed for transposing instruments.
+    pub fn export_mei(&self, flow_key: &str) -> String {
+        let (flow, instruments, _, _) = self.get_flow_instruments(flow_key);
+        let master = self.score.tracks.get(&flow.master).unwrap();
+        let subdivisions = flow.subdivisions;
+
+        let bars = get_bars(flow, &self.score.tracks);
+        let mut starts: Vec<Tick> = bars.keys().copied().collect();
+        starts.sort_unstable();
+
+        let mut notations = Vec::new();
+        for instrument in &instruments {
+            for stave_key in &instrument.staves {
+                let stave = flow.staves.get(stave_key).unwrap();
+                for track_key in &stave.tracks {
+                    let track = self.score.tracks.get(track_key).unwrap();
+                    notations.push((
+                        track_key.clone(),
+                        track.to_notation_track(&flow.length, &bars, subdivisions),
+                    ));
+                }
+            }
+        }
+
+        let mut parts: Vec<Part> = Vec::new();
+        let mut n = 0;
+        for instrument in &instruments {
+            let mut staves = Vec::new();
+            for stave_key in &instrument.staves {
+                let stave = flow.staves.get(stave_key).unwrap();
+                let layers = stave
+                    .tracks
+                    .iter()
+                    .map(|track_key| {
+                        let (_, notation) =
+                            notations.iter().find(|(key, _)| key == track_key).unwrap();
+                        let mut entries: Vec<&Notation> = notation.track.values().collect();
+                        entries.sort_by_key(|entry| entry.tick);
+                        Layer {
+                            notations: entries,
+                            beams: get_beams_in_track(notation, &bars, subdivisions),
+                        }
+                    })
+                    .collect();
+                n += 1;
+                staves.push(Staff { n, stave, layers });
+            }
+
+            parts.push(Part {
+                instrument,
+                staves,
+                transposition: match get_def(&instrument.id) {
+                    Some(def) => def.transposition,
+                    None => Interval::UNISON,
+                },
+            });
+        }
+
+        let mut writer = XmlWriter::new(PROLOG);
+        writer.open(
+            "mei",
+            &[attr("xmlns", NAMESPACE), attr("meiversion", VERSION)],
+        );
+
+        writer.open("meiHead", &[]);
+        writer.open("fileDesc", &[]);
+        writer.open("titleStmt", &[]);
+        writer.text("title", &[], &self.title());
+        let composer = self.composer();
+        if !composer.is_empty() {
+            writer.text("composer", &[], &composer);
+        }
+        let arranger = self.arranger();
+        if !arranger.is_empty() {
+            writer.text("arranger", &[], &arranger);
+        }
+        writer.close();
+        let copyright = self.copyright();
+        if copyright.is_empty() {
+            writer.empty("pubStmt", &[]);
+        } else {
+            writer.open("pubStmt", &[]);
+            writer.open("availability", &[]);
+            writer.text("useRestrict", &[], &copyright);
+            writer.close();
+            writer.close();
+        }
+        writer.close();
+        writer.close();
+
+        writer.open("music", &[]);
+        writer.open("body", &[]);
+        let mut attributes = Vec::new();
+        if !flow.title.is_empty() {
+            attributes.push(attr("label", &flow.title));
+        }
+        writer.open("mdiv", &attributes);
+        writer.open("score", &[]);
+
+        // the opening score definition
+        let opening_key = master
+            .get_key_signature_at_tick(&0)
+            .cloned()
+            .unwrap_or_else(|| KeySignature::new(0, KeySignatureMode::Major, 0));
+        writer.open("scoreDef", &[]);
+        if let Some(time_signature) = master.get_time_signature_at_tick(&0) {
+            write_meter(&mut writer, time_signature);
+        }
+        writer.open("staffGrp", &[]);
+        for part in &parts {
+            let is_group = part.staves.len() > 1;
+            if is_group {
+                writer.open(
+                    "staffGrp",
+                    &[attr("symbol", "brace"), attr("bar.thru", "true")],
+                );
+                writer.text("label", &[], &part.instrument.name());
+                writer.text("labelAbbr", &[], &part.instrument.short_name);
+            }
+            for staff in &part.staves {
+                let mut attributes = vec![
+                    attr("n", staff.n),
+                    attr("lines", staff.stave.lines.len()),
+                    attr("ppq", subdivisions),
+                ];
+                if !part.transposition.is_unison() {
+                    // MEI gives the interval from written to sounding pitch
+                    let interval = part.transposition.invert();
+                    attributes.push(attr("trans.diat", interval.steps));
+                    attributes.push(attr("trans.semi", interval.semitones));
+                }
+                writer.open("staffDef", &attributes);
+                if !is_group {
+                    writer.text("label", &[], &part.instrument.name());
+                    writer.text("labelAbbr", &[], &part.instrument.short_name);
+                }
+                let stave_master = self.score.tracks.get(&staff.stave.master).unwrap();
+                if let Some(clef) = stave_master.get_clef_at_tick(&0) {
+                    write_clef(&mut writer, clef);
+                }
+                if !matches!(part.instrument.instrument_type, InstrumentType::Percussive) {
+                    write_key(&mut writer, &opening_key.transpose(&part.transposition));
+                }
+                writer.close();
+            }
+            if is_group {
+                writer.close();
+            }
+        }
+        writer.close();
+        writer.close();
+
+        writer.open("section", &[]);
+        let pickup = starts
+            .first()
+            .is_some_and(|start| bars[start].is_irregular());
+
+        for (i, start) in starts.iter().enumerate() {
+            let start = *start;
+            let end = starts.get(i + 1).copied().unwrap_or(flow.length);
+            let bar = &bars[&start];
+
+            // changes of meter and key between measures
+            if i > 0 {
+                let time_signature = master
+                    .get_time_signature_at_tick(&start)
+                    .filter(|entry| entry.draw_type != TimeSignatureDrawType::Hidden);
+                let key_signature = master.get_key_signature_at_tick(&start);
+                if time_signature.is_some() || key_signature.is_some() {
+                    writer.open("scoreDef", &[]);
+                    if let Some(time_signature) = time_signature {
+                        write_meter(&mut writer, time_signature);
+                    }
+                    if let Some(key_signature) = key_signature {
+                        writer.open("staffGrp", &[]);
+                        for part in &parts {
+                            if matches!(part.instrument.instrument_type, InstrumentType::Percussive)
+                            {
+                                continue;
+                            }
+                            for staff in &part.staves {
+                                writer.open("staffDef", &[attr("n", staff.n)]);
+                                write_key(
+                                    &mut writer,
+                                    &key_signature.transpose(&part.transposition),
+                                );
+                                writer.close();
+                            }
+                        }
+                        writer.close();
+                    }
+                    writer.close();
+                }
+            }
+
+            let number = if pickup { i } else { i + 1 };
+            let mut attributes = vec![attr("n", number)];
+            if bar.is_irregular() {
+                attributes.push(attr("metcon", "false"));
+            }
+            if let Some(entry) = master.get_barline_at_tick(&start) {
+                if entry.barline_type == BarlineDrawType::StartRepeat {
+                    attributes.push(attr("left", "rptstart"));
+                }
+            }
+            if let Some(entry) = master.get_barline_at_tick(&end) {
+                if let Some(right) = right(&entry.barline_type) {
+                    attributes.push(attr("right", right));
+                }
+            }
+            writer.open("measure", &attributes);
+
+            for part in &parts {
+                for staff in &part.staves {
+                    writer.open("staff", &[attr("n", staff.n)]);
+                    let stave_master = self.score.tracks.get(&staff.stave.master).unwrap();
+                    for (l, layer) in staff.layers.iter().enumerate() {
+                        writer.open("layer", &[attr("n", l + 1)]);
+                        self.write_layer(
+                            &mut writer,
+                            part,
+                            layer,
+                            // clef changes are written in the first layer
+                            if l == 0 { Some(stave_master) } else { None },
+                            master,
+                            start,
+                            end,
+                            subdivisions,
+                        );
+                        writer.close();
+                    }
+                    writer.close();
+                }
+            }
+
+            writer.close();
+        }
+
+        writer.finish()
+    }
+}
+
+impl Engine {
+    #[allow(clippy::too_many_arguments)]
+    fn write_layer(
+        &self,
+        writer: &mut XmlWriter,
+        part: &Part,
+        layer: &Layer,
+        stave_master: Option<&Track>,
+        master: &Track,
+        start: Tick,
+        end: Tick,
+        subdivisions: Ticks,
+    ) {
+        let key_signature = master
+            .get_key_signature_before_tick(start + 1)
+            .cloned()
+            .unwrap_or_else(|| KeySignature::new(0, KeySignatureMode::Major, 0));
+        let mut accidentals = Accidentals::new(&key_signature.transpose(&part.transposition));
+        let mut open_beam: Option<usize> = None;
+
+        for (n, notation) in layer.notations.iter().enumerate() {
+            let tick = notation.tick;
+            if tick < start || tick >= end {
+                continue;
+            }
+
+            if let Some(stave_master) = stave_master {
+                if tick > 0 {
+                    if let Some(clef) = stave_master.get_clef_at_tick(&tick) {
+                        write_clef(writer, clef);
+                    }
+                }
+            }
+            if tick > start {
+                if let Some(key_signature) = master.get_key_signature_at_tick(&tick) {
+                    let key_signature = key_signature.transpose(&part.transposition);
+                    // mid bar key changes are written with the clef changes in the first layer
+                    let is_percussive =
+                        matches!(part.instrument.instrument_type, InstrumentType::Percussive);
+                    if stave_master.is_some() && !is_percussive {
+                        write_key(writer, &key_signature);
+                    }
+                    accidentals.set_key(&key_signature);
+                }
+            }
+
+            let beam = layer
+                .beams
+                .iter()
+                .position(|beam| beam.ticks.len() > 1 && beam.ticks.contains_key(&tick));
+            if beam.is_some() && open_beam != beam {
+                writer.open("beam", &[]);
+                open_beam = beam;
+            }
+
+            let whole_bar = notation.is_rest() && tick == start && notation.duration == end - start;
+            let mut attributes = Vec::new();
+            if let Some(duration) = notation.base_to_note_duration(subdivisions) {
+                attributes.push(attr("dur", duration.to_denominator()));
+                if notation.is_dotted(subdivisions) {
+                    attributes.push(attr("dots", 1));
+                }
+            }
+            attributes.push(attr("dur.ppq", notation.duration));
+
+            let previous = match n {
+                0 => None,
+                _ => Some(layer.notations[n - 1]),
+            };
+            let mut tones: Vec<_> = notation.tones.iter().collect();
+            tones.sort_by_key(|tone| tone.pitch.int);
+
+            let notes: Vec<Vec<(&str, String)>> = tones
+                .iter()
+                .map(|tone| {
+                    let pitch = tone.pitch.transpose(&part.transposition);
+                    let mut attributes = vec![
+                        attr("pname", pitch.letter().to_lowercase()),
+                        attr("oct", pitch.octave()),
+                    ];
+                    if accidentals.is_written(&pitch) {
+                        attributes.push(attr("accid", accid(&pitch.accidental)));
+                    } else if pitch.accidental != Accidental::Natural {
+                        let gestural = match pitch.accidental {
+                            Accidental::DoubleSharp => "ss",
+                            _ => accid(&pitch.accidental),
+                        };
+                        attributes.push(attr("accid.ges", gestural));
+                    }
+                    let stop = previous.is_some_and(|previous| previous.ties.contains(&tone.key));
+                    if let Some(tie) = tie(notation.ties.contains(&tone.key), stop) {
+                        attributes.push(attr("tie", tie));
+                    }
+                    attributes
+                })
+                .collect();
+
+            match notes.len() {
+                0 if whole_bar => writer.empty("mRest", &[]),
+                0 => writer.empty("rest", &attributes),
+                1 => {
+                    let mut note = attributes;
+                    note.extend(notes[0].iter().cloned());
+                    writer.empty("note", &note);
+                }
+                _ => {
+                    writer.open("chord", &attributes);
+                    for note in &notes {
+                        writer.empty("note", note);
+                    }
+                    writer.close();
+                }
+            }
+
+            let next = layer.notations.get(n + 1).map(|next| next.tick);
+            if let Some(beam) = open_beam {
+                let continues = next
+                    .is_some_and(|next| next < end && layer.beams[beam].ticks.contains_key(&next));
+                if !continues {
+                    writer.close();
+                    open_beam = None;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{accid, key_sig, right, tie};
+    use crate::components::articulation::Articulation;
+    use crate::components::pitch::Accidental;
+    use crate::entries::barline::BarlineDrawType;
+    use crate::entries::key_signature::{KeySignature, KeySignatureMode};
+    use crate::score::players::PlayerType;
+    use crate::Engine;
+
+    #[test]
+    fn accid_test() {
+        assert_eq!(accid(&Accidental::Sharp), "s");
+        assert_eq!(accid(&Accidental::DoubleSharp), "x");
+        assert_eq!(accid(&Accidental::ThreeQuarterFlat), "3qf");
+    }
+
+    #[test]
+    fn key_sig_test() {
+        assert_eq!(
+            key_sig(&KeySignature::new(0, KeySignatureMode::Major, 0)),
+            "0"
+        );
+        assert_eq!(
+            key_sig(&KeySignature::new(0, KeySignatureMode::Major, 2)),
+            "2s"
+        );
+        assert_eq!(
+            key_sig(&KeySignature::new(0, KeySignatureMode::Minor, -3)),
+            "3f"
+        );
+    }
+
+    #[test]
+    fn tie_test() {
+        assert_eq!(tie(true, false), Some("i"));
+        assert_eq!(tie(true, true), Some("m"));
+        assert_eq!(tie(false, true), Some("t"));
+        assert_eq!(tie(false, false), None);
+    }
+
+    #[test]
+    fn right_test() {
+        assert_eq!(right(&BarlineDrawType::Final), Some("end"));
+        assert_eq!(right(&BarlineDrawType::EndStartRepeat), Some("rptboth"));
+        assert_eq!(right(&BarlineDrawType::StartRepeat), None);
+    }
+
+    #[test]
+    fn mid_bar_key_change_test() {
+        let mut engine = Engine::tester();
+        let flow_key = engine.score.flows.order[0].clone();
+        let player_key = engine.create_player(PlayerType::Solo);
+        let instrument_key = engine.create_instrument("strings.violin");
+        engine.assign_instrument_to_player(&player_key, &instrument_key);
+        let stave_key = engine.score.instruments[&instrument_key].staves[0].clone();
+        let track_key = engine.score.flows.by_key[&flow_key].staves[&stave_key].tracks[0].clone();
+
+        engine.create_key_signature(&flow_key, 96, KeySignatureMode::Major, 2);
+        for tick in [0, 96] {
+            engine.create_tone(
+                &track_key,
+                tick,
+                96,
+                66,
+                Some(Accidental::Sharp),
+                100,
+                Articulation::None,
+            );
+        }
+
+        let mei = engine.export_mei(&flow_key);
+        let layer = &mei[mei.find("<layer").unwrap()..mei.find("</layer>").unwrap()];
+        let key = layer.find(r#"<keySig sig="2s" mode="major"/>"#).unwrap();
+        // the sharp is written before the change and is part of the key after it
+        assert!(layer[..key].contains(r#"accid="s""#));
+        assert!(layer[key..].contains(r#"accid.ges="s""#));
+        assert!(!layer[key..].contains(r#"accid="s""#));
+    }
+
+    #[test]
+    fn export_mei_test() {
+        let mut engine = Engine::tester();
+        let flow_key = engine.score.flows.order[0].clone();
+        engine.create_key_signature(&flow_key, 0, KeySignatureMode::Major, 0);
+        let player_key = engine.create_player(PlayerType::Solo);
+        let instrument_key = engine.create_instrument("strings.violin");
+        engine.assign_instrument_to_player(&player_key, &instrument_key);
+        let stave_key = engine.score.instruments[&instrument_key].staves[0].clone();
+        let track_key = engine.score.flows.by_key[&flow_key].staves[&stave_key].tracks[0].clone();
+
+        // a chord, then a note tied through the next bar into the third
+        for (tick, duration, pitch) in [(0, 96, 60), (0, 96, 64), (0, 96, 67), (96, 384, 62)] {
+            engine.create_tone(
+                &track_key,
+                tick,
+                duration,
+                pitch,
+                None,
+                100,
+                Articulation::None,
+            );
+        }
+        engine.create_barline(&flow_key, 768, BarlineDrawType::Final);
+
+        let mei = engine.export_mei(&flow_key);
+        assert!(mei.contains(r#"<keySig sig="0" mode="major"/>"#));
+        let section = &mei[mei.find("<section>").unwrap()..mei.find("</section>").unwrap()];
+        let measures: Vec<&str> = section
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .filter(|line| {
+                !["<staff", "</staff", "<layer", "</layer"]
+                    .iter()
+                    .any(|tag| line.starts_with(tag))
+            })
+            .collect();
+        assert_eq!(
+            measures,
+            vec![
+                "<section>",
+                r#"<measure n="1">"#,
+                r#"<chord dur="2" dur.ppq="96">"#,
+                r#"<note pname="c" oct="4"/>"#,
+                r#"<note pname="e" oct="4"/>"#,
+                r#"<note pname="g" oct="4"/>"#,
+                "</chord>",
+                r#"<note dur="2" dur.ppq="96" pname="d" oct="4" tie="i"/>"#,
+                "</measure>",
+                r#"<measure n="2">"#,
+                r#"<note dur="1" dur.ppq="192" pname="d" oct="4" tie="m"/>"#,
+                "</measure>",
+                r#"<measure n="3">"#,
+                r#"<note dur="2" dur.ppq="96" pname="d" oct="4" tie="t"/>"#,
+                r#"<rest dur="2" dur.ppq="96"/>"#,
+                "</measure>",
+                r#"<measure n="4" right="end">"#,
+                "<mRest/>",
+                "</measure>",
+            ]
+        );
+    }
+}
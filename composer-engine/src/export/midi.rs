@@ -0,0 +1,570 @@
+use crate::components::misc::{Tick, Ticks};
+use crate::entries::barline::BarlineDrawType;
+use crate::entries::key_signature::KeySignatureMode;
+use crate::entries::Entry;
+use crate::score::instruments::defs::InstrumentType;
+use crate::score::tracks::Track;
+use crate::Engine;
+use wasm_bindgen::prelude::wasm_bindgen;
+
+/// The master track has no tempo marks yet so everything plays at 120bpm
+const DEFAULT_TEMPO: u32 = 500_000; // microseconds per quarter
+
+const DRUM_CHANNEL: u8 = 9;
+const MELODIC_CHANNELS: usize = 15;
+const PITCH_BEND_CENTER: u16 = 8192;
+const PITCH_BEND_RANGE: f64 = 2.0; // semitones, the General MIDI default
+
+/// (tick, off, note, bend, velocity) of a tone as played
+type PlayedNote = (Tick, Tick, u8, u16, u8);
+
+/// Events at the same tick are written in this order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum EventOrder {
+    Meta,
+    Setup,
+    NoteOff,
+    PitchBend,
+    NoteOn,
+}
+
+#[derive(Debug)]
+struct MidiEvent {
+    tick: Tick,
+    order: EventOrder,
+    data: Vec<u8>,
+}
+
+impl MidiEvent {
+    fn new(tick: Tick, order: EventOrder, data: Vec<u8>) -> Self {
+        Self { tick, order, data }
+    }
+
+    fn meta(tick: Tick, kind: u8, content: &[u8]) -> Self {
+        let mut data = vec![0xFF, kind];
+        write_variable_length(content.len() as u32, &mut data);
+        data.extend_from_slice(content);
+        Self::new(tick, EventOrder::Meta, data)
+    }
+}
+
+pub fn write_variable_length(value: u32, output: &mut Vec<u8>) {
+    let mut bytes = vec![(value & 0x7F) as u8];
+    let mut value = value >> 7;
+    while value > 0 {
+        bytes.push(((value & 0x7F) as u8) | 0x80);
+        value >>= 7;
+    }
+    bytes.reverse();
+    output.extend(bytes);
+}
+
+fn write_chunk(kind: &[u8; 4], content: &[u8], output: &mut Vec<u8>) {
+    output.extend_from_slice(kind);
+    output.extend_from_slice(&(content.len() as u32).to_be_bytes());
+    output.extend_from_slice(content);
+}
+
+fn write_track(mut events: Vec<MidiEvent>, output: &mut Vec<u8>) {
+    events.sort_by_key(|event| (event.tick, event.order));
+
+    let mut content: Vec<u8> = Vec::new();
+    let mut previous: Tick = 0;
+    for event in &events {
+        write_variable_length(event.tick - previous, &mut content);
+        content.extend_from_slice(&event.data);
+        previous = event.tick;
+    }
+
+    // end of track
+    content.extend_from_slice(&[0x00, 0xFF, 0x2F, 0x00]);
+
+    write_chunk(b"MTrk", &content, output);
+}
+
+/// The General MIDI program for an instrument id
+pub fn program_for_id(id: &str) -> u8 {
+    match id {
+        "brass.bass-trombone" | "brass.trombone" => 57,
+        "brass.horn.f" => 60,
+        "brass.trumpet.b-flat" | "brass.trumpet.c" => 56,
+        "brass.tuba" => 58,
+        "guitar.acoustic" => 24,
+        "guitar.bass" => 33,
+        "guitar.distortion" => 30,
+        "pitched-percussion.glockenspiel" => 9,
+        "pitched-percussion.harp" => 46,
+        "pitched-percussion.marimba" => 12,
+        "pitched-percussion.timpani" => 47,
+        "pitched-percussion.vibraphone" => 11,
+        "pitched-percussion.xylophone" => 13,
+        "keyboard.celesta" => 8,
+        "keyboard.piano" => 0,
+        "strings.contrabass" => 43,
+        "strings.viola" => 41,
+        "strings.violin" => 40,
+        "strings.violoncello" => 42,
+        "woodwinds.alto-flute" | "woodwinds.flute" => 73,
+        "woodwinds.piccolo" => 72,
+        "woodwinds.alto-sxophone" => 65,
+        "woodwinds.bassoon" | "woodwinds.contrabassoon" => 70,
+        "woodwinds.bass-clarinet" | "woodwinds.clarinet.a" | "woodwinds.clarinet.b-flat" => 71,
+        "woodwinds.english-horn" => 69,
+        "woodwinds.oboe" => 68,
+        _ => 0,
+    }
+}
+
+/// The ranges of the flow in the order they are played. Without unrolling this
+/// is the whole flow, else each repeated section is played twice.
+pub fn get_playback_segments(master: &Track, length: Ticks, unroll: bool) -> Vec<(Tick, Tick)> {
+    if !unroll {
+        return vec![(0, length)];
+    }
+
+    let mut repeats: Vec<(Tick, BarlineDrawType)> = master
+        .entries
+        .by_key
+        .values()
+        .filter_map(|entry| match entry {
+            Entry::Barline(barline) => Some((barline.tick, barline.barline_type.clone())),
+            _ => None,
+        })
+        .collect();
+    repeats.sort_by_key(|(tick, _)| *tick);
+
+    let mut output = Vec::new();
+    let mut start: Tick = 0;
+    let mut repeat_start: Tick = 0;
+
+    for (tick, barline_type) in repeats {
+        let is_end = matches!(
+            barline_type,
+            BarlineDrawType::EndRepeat | BarlineDrawType::EndStartRepeat
+        );
+        let is_start = matches!(
+            barline_type,
+            BarlineDrawType::StartRepeat | BarlineDrawType::EndStartRepeat
+        );
+
+        if is_end && tick <= length {
+            output.push((start, tick));
+            output.push((repeat_start, tick));
+            start = tick;
+        }
+
+        if is_start {
+            repeat_start = tick;
+        }
+    }
+
+    if start < length {
+        output.push((start, length));
+    }
+
+    output
+}
+
+/// Take the next channel for a melodic instrument, skipping the drum channel
+fn next_melodic_channel(next: &mut u8) -> u8 {
+    let channel = *next;
+    *next = (*next + 1) % 16;
+    if *next == DRUM_CHANNEL {
+        *next += 1;
+    }
+    channel
+}
+
+/// Share the melodic channels between instruments given how many differently bent tones
+/// each has. Every instrument gets a channel, any left over are dealt out in turn to those
+/// that need more.
+fn share_channels(needs: &[usize]) -> Vec<usize> {
+    let mut counts: Vec<usize> = needs.iter().map(|need| (*need).min(1)).collect();
+    let mut spare = MELODIC_CHANNELS.saturating_sub(counts.iter().sum());
+    while spare > 0 {
+        let mut is_shared = false;
+        for (count, need) in counts.iter_mut().zip(needs) {
+            if spare > 0 && *count < *need {
+                *count += 1;
+                spare -= 1;
+                is_shared = true;
+            }
+        }
+        if !is_shared {
+            break;
+        }
+    }
+    counts
+}
+
+/// Map a tick in the flow to each tick it is played at, along with the end of its segment
+fn map_tick(segments: &[(Tick, Tick)], tick: Tick) -> Vec<(Tick, Tick)> {
+    let mut output = Vec::new();
+    let mut offset: Tick = 0;
+    for (start, end) in segments {
+        if tick >= *start && tick < *end {
+            output.push((offset + tick - start, offset + end - start));
+        }
+        offset += end - start;
+    }
+    output
+}
+
+#[wasm_bindgen]
+impl Engine {
+    /// Export a flow as a type 1 Standard MIDI File
+    pub fn export_midi(&self, flow_key: &str, unroll_repeats: bool) -> Vec<u8> {
+        let (flow, instruments, _, _) = self.get_flow_instruments(flow_key);
+        let master = self.score.tracks.get(&flow.master).unwrap();
+        let segments = get_playback_segments(master, flow.length, unroll_repeats);
+
+        let mut tracks: Vec<u8> = Vec::new();
+
+        // conductor track
+        let mut events = vec![
+            MidiEvent::meta(0, 0x03, flow.title.as_bytes()),
+            MidiEvent::meta(0, 0x51, &DEFAULT_TEMPO.to_be_bytes()[1..]),
+        ];
+        for entry in master.entries.by_key.values() {
+            let content = match entry {
+                Entry::TimeSignature(time_signature) if time_signature.beats > 0 => (
+                    0x58,
+                    vec![
+                        time_signature.beats,
+                        time_signature.beat_type.to_denominator().trailing_zeros() as u8,
+                        24,
+                        8,
+                    ],
+                ),
+                Entry::KeySignature(key_signature) if key_signature.custom.is_none() => {
+                    let minor = matches!(key_signature.mode, KeySignatureMode::Minor);
+                    (0x59, vec![key_signature.offset as u8, minor as u8])
+                }
+                _ => continue,
+            };
+            for (tick, _) in map_tick(&segments, entry.tick()) {
+                events.push(MidiEvent::meta(tick, content.0, &content.1));
+            }
+        }
+        write_track(events, &mut tracks);
+
+        let has_solo = instruments.iter().any(|instrument| instrument.solo);
+
+        // the tones of each instrument as played, and the bends they use
+        let mut parts: Vec<(Vec<PlayedNote>, Vec<u16>)> = Vec::new();
+        for instrument in &instruments {
+            let is_percussive = matches!(instrument.instrument_type, InstrumentType::Percussive);
+            let is_audible = !instrument.mute && (!has_solo || instrument.solo);
+
+            let mut notes: Vec<PlayedNote> = Vec::new();
+            for stave_key in &instrument.staves {
+                let stave = flow.staves.get(stave_key).unwrap();
+                for track_key in &stave.tracks {
+                    let track = self.score.tracks.get(track_key).unwrap();
+                    for entry in track.entries.by_key.values() {
+                        let tone = match entry {
+                            Entry::Tone(tone) if is_audible => tone,
+                            _ => continue,
+                        };
+
+                        let tuned = self.get_tuned_pitch(track_key, tone.tick, &tone.pitch);
+                        let note = tuned.round().clamp(0.0, 127.0) as u8;
+                        let bend = (tuned - note as f64) / PITCH_BEND_RANGE;
+                        let bend = (PITCH_BEND_CENTER as f64 * (1.0 + bend)).clamp(0.0, 16383.0);
+                        let bend = if is_percussive {
+                            PITCH_BEND_CENTER
+                        } else {
+                            bend as u16
+                        };
+                        let velocity = tone.velocity.int().clamp(1, 127);
+
+                        for (tick, end) in map_tick(&segments, tone.tick) {
+                            let off = (tick + tone.duration).min(end);
+                            notes.push((tick, off, note, bend, velocity));
+                        }
+                    }
+                }
+            }
+            notes.sort_unstable();
+
+            // pitch bend applies to the whole channel so tones that are bent differently
+            // (ie. a chord mixing quarter tones and semitones) are each given a channel.
+            // Unbent tones keep the instrument's first channel, then the most used bends.
+            let mut bends: Vec<(u16, usize)> = Vec::new();
+            for (_, _, _, bend, _) in &notes {
+                match bends.iter_mut().find(|(entry, _)| entry == bend) {
+                    Some((_, count)) => *count += 1,
+                    None => bends.push((*bend, 1)),
+                }
+            }
+            bends.sort_by_key(|(bend, count)| {
+                (*bend != PITCH_BEND_CENTER, std::cmp::Reverse(*count), *bend)
+            });
+            let mut bends: Vec<u16> = bends.into_iter().map(|(bend, _)| bend).collect();
+            if bends.is_empty() {
+                bends.push(PITCH_BEND_CENTER);
+            }
+
+            parts.push((notes, bends));
+        }
+
+        // when there are too few channels the remaining bends share the nearest channel
+        let needs: Vec<usize> = instruments
+            .iter()
+            .zip(&parts)
+            .map(|(instrument, (_, bends))| {
+                if matches!(instrument.instrument_type, InstrumentType::Percussive) {
+                    0
+                } else {
+                    bends.len()
+                }
+            })
+            .collect();
+        let counts = share_channels(&needs);
+
+        let mut next_channel: u8 = 0;
+        for ((instrument, (notes, bends)), count) in instruments.iter().zip(parts).zip(counts) {
+            let is_percussive = matches!(instrument.instrument_type, InstrumentType::Percussive);
+
+            let channels: Vec<u8> = if is_percussive {
+                vec![DRUM_CHANNEL]
+            } else {
+                (0..count.max(1))
+                    .map(|_| next_melodic_channel(&mut next_channel))
+                    .collect()
+            };
+
+            let name = match &instrument.count {
+                Some(count) => format!("{} {}", instrument.long_name, count),
+                None => instrument.long_name.clone(),
+            };
+
+            let mut events = vec![MidiEvent::meta(0, 0x03, name.as_bytes())];
+            for (channel, bend) in channels.iter().zip(bends.iter()) {
+                events.push(MidiEvent::new(
+                    0,
+                    EventOrder::Setup,
+                    vec![0xC0 | channel, program_for_id(&instrument.id)],
+                ));
+                events.push(MidiEvent::new(
+                    0,
+                    EventOrder::Setup,
+                    vec![0xB0 | channel, 7, instrument.volume.min(127)],
+                ));
+                if !is_percussive {
+                    events.push(MidiEvent::new(
+                        0,
+                        EventOrder::PitchBend,
+                        vec![0xE0 | channel, (bend & 0x7F) as u8, (bend >> 7) as u8],
+                    ));
+                }
+            }
+
+            let played = &bends[..channels.len().min(bends.len())];
+            for (tick, off, note, bend, velocity) in notes {
+                let i = match played.iter().position(|entry| *entry == bend) {
+                    Some(i) => i,
+                    None => (0..played.len())
+                        .min_by_key(|i| (played[*i] as i32 - bend as i32).abs())
+                        .unwrap_or(0),
+                };
+                let channel = channels[i];
+                events.push(MidiEvent::new(
+                    tick,
+                    EventOrder::NoteOn,
+                    vec![0x90 | channel, note, velocity],
+                ));
+                events.push(MidiEvent::new(
+                    off,
+                    EventOrder::NoteOff,
+                    vec![0x80 | channel, note, 0],
+                ));
+            }
+
+            write_track(events, &mut tracks);
+        }
+
+        let mut header: Vec<u8> = Vec::new();
+        header.extend_from_slice(&1_u16.to_be_bytes()); // type 1
+        header.extend_from_slice(&(instruments.len() as u16 + 1).to_be_bytes());
+        header.extend_from_slice(&(flow.subdivisions as u16).to_be_bytes()); // ticks per quarter
+
+        let mut output: Vec<u8> = Vec::new();
+        write_chunk(b"MThd", &header, &mut output);
+        output.extend(tracks);
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        get_playback_segments, map_tick, share_channels, write_track, write_variable_length,
+        MidiEvent,
+    };
+    use crate::components::articulation::Articulation;
+    use crate::components::pitch::Accidental;
+    use crate::components::tuning::TuningSystem;
+    use crate::entries::barline::{Barline, BarlineDrawType};
+    use crate::entries::Entry;
+    use crate::import::midi::parse_smf;
+    use crate::score::players::PlayerType;
+    use crate::score::tracks::Track;
+    use crate::Engine;
+
+    #[test]
+    fn variable_length_test() {
+        let cases: [(u32, Vec<u8>); 5] = [
+            (0, vec![0x00]),
+            (0x7F, vec![0x7F]),
+            (0x80, vec![0x81, 0x00]),
+            (0x2000, vec![0xC0, 0x00]),
+            (0x0FFFFFFF, vec![0xFF, 0xFF, 0xFF, 0x7F]),
+        ];
+        for (value, expected) in cases {
+            let mut output = Vec::new();
+            write_variable_length(value, &mut output);
+            assert_eq!(output, expected);
+        }
+    }
+
+    #[test]
+    fn write_track_test() {
+        let events = vec![
+            MidiEvent::new(48, super::EventOrder::NoteOff, vec![0x80, 60, 0]),
+            MidiEvent::new(0, super::EventOrder::NoteOn, vec![0x90, 60, 100]),
+        ];
+        let mut output = Vec::new();
+        write_track(events, &mut output);
+        assert_eq!(
+            output,
+            vec![
+                b'M', b'T', b'r', b'k', 0, 0, 0, 12, 0, 0x90, 60, 100, 48, 0x80, 60, 0, 0, 0xFF,
+                0x2F, 0
+            ]
+        );
+    }
+
+    #[test]
+    fn segments_test() {
+        let mut master = Track::new();
+        master.insert(Entry::Barline(Barline::new(
+            192,
+            BarlineDrawType::StartRepeat,
+        )));
+        master.insert(Entry::Barline(Barline::new(
+            384,
+            BarlineDrawType::EndRepeat,
+        )));
+
+        assert_eq!(get_playback_segments(&master, 576, false), vec![(0, 576)]);
+        assert_eq!(
+            get_playback_segments(&master, 576, true),
+            vec![(0, 384), (192, 384), (384, 576)]
+        );
+
+        let segments = get_playback_segments(&master, 576, true);
+        assert_eq!(map_tick(&segments, 0), vec![(0, 384)]);
+        assert_eq!(map_tick(&segments, 200), vec![(200, 384), (392, 576)]);
+        assert_eq!(map_tick(&segments, 400), vec![(592, 768)]);
+    }
+
+    #[test]
+    fn mixed_bend_chord_test() {
+        let mut engine = Engine::tester();
+        let flow_key = engine.score.flows.order[0].clone();
+        let player_key = engine.create_player(PlayerType::Solo);
+        let instrument_key = engine.create_instrument("strings.violin");
+        engine.assign_instrument_to_player(&player_key, &instrument_key);
+        let stave_key = engine.score.instruments[&instrument_key].staves[0].clone();
+        let track_key = engine.score.flows.by_key[&flow_key].staves[&stave_key].tracks[0].clone();
+
+        // E natural with a quarter sharp C, played as C# bent down a quarter tone
+        for (pitch, accidental) in [(64, Accidental::Natural), (60, Accidental::QuarterSharp)] {
+            engine.create_tone(
+                &track_key,
+                0,
+                48,
+                pitch,
+                Some(accidental),
+                100,
+                Articulation::None,
+            );
+        }
+
+        let bytes = engine.export_midi(&flow_key, false);
+        let smf = parse_smf(&bytes).unwrap();
+        let mut notes: Vec<(u8, u8)> = smf.tracks[1]
+            .notes
+            .iter()
+            .map(|note| (note.pitch, note.channel))
+            .collect();
+        notes.sort_unstable();
+        assert_eq!(notes, vec![(61, 1), (64, 0)]);
+
+        let has_bend = |bend: [u8; 3]| bytes.windows(3).any(|window| window == bend);
+        assert!(has_bend([0xE0, 0x00, 0x40])); // centered
+        assert!(has_bend([0xE1, 0x00, 0x30])); // down a quarter tone
+    }
+
+    #[test]
+    fn shared_channels_test() {
+        let mut engine = Engine::tester();
+        engine.set_tuning_system(TuningSystem::Just);
+        let flow_key = engine.score.flows.order[0].clone();
+        let player_key = engine.create_player(PlayerType::Solo);
+        for _ in 0..2 {
+            let instrument_key = engine.create_instrument("strings.violin");
+            engine.assign_instrument_to_player(&player_key, &instrument_key);
+            let stave_key = engine.score.instruments[&instrument_key].staves[0].clone();
+            let track_key =
+                engine.score.flows.by_key[&flow_key].staves[&stave_key].tracks[0].clone();
+
+            // a chromatic scale is bent differently on every degree
+            for i in 0..12 {
+                engine.create_tone(
+                    &track_key,
+                    i * 48,
+                    48,
+                    60 + i as u8,
+                    None,
+                    100,
+                    Articulation::None,
+                );
+            }
+        }
+
+        let smf = parse_smf(&engine.export_midi(&flow_key, false)).unwrap();
+        let channels = |track: usize| {
+            let mut channels: Vec<u8> = smf.tracks[track]
+                .programs
+                .iter()
+                .map(|(_, channel, _)| *channel)
+                .collect();
+            channels.sort_unstable();
+            channels
+        };
+        let (first, second) = (channels(1), channels(2));
+
+        // all 15 melodic channels are shared out without overlapping
+        assert_eq!(first.len() + second.len(), 15);
+        assert!(first.len().abs_diff(second.len()) <= 1);
+        let mut all: Vec<u8> = first.iter().chain(second.iter()).copied().collect();
+        all.sort_unstable();
+        all.dedup();
+        assert_eq!(all.len(), 15);
+        assert!(!all.contains(&9));
+
+        // every note is played on one of its own instrument's channels
+        for (track, channels) in [(1, &first), (2, &second)] {
+            assert_eq!(smf.tracks[track].notes.len(), 12);
+            assert!(smf.tracks[track]
+                .notes
+                .iter()
+                .all(|note| channels.contains(&note.channel)));
+        }
+
+        assert_eq!(share_channels(&[1, 12, 0, 3]), vec![1, 11, 0, 3]);
+        assert_eq!(share_channels(&[1; 20]), vec![1; 20]);
+    }
+}
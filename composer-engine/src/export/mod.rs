@@ -0,0 +1,8 @@
+pub mod abc;
+pub mod accidentals;
+pub mod braille;
+pub mod lilypond;
+pub mod mei;
+pub mod midi;
+pub mod musicxml;
+pub mod xml;
@@ -0,0 +1,702 @@
+use super::accidentals::alteration;
+use super::xml::{attr, XmlWriter};
+use crate::components::duration::NoteDuration;
+use crate::components::interval::Interval;
+use crate::components::misc::{Tick, Ticks};
+use crate::components::pitch::Pitch;
+use crate::entries::barline::BarlineDrawType;
+use crate::entries::clef::{Clef, ClefDrawType};
+use crate::entries::key_signature::KeySignature;
+use crate::entries::time_signature::{TimeSignature, TimeSignatureDrawType, TimeSignaturePart};
+use crate::parse::get_bars::{get_bars, Bars};
+use crate::parse::get_beams::{get_beams_in_track, Beam, Beams};
+use crate::parse::get_written_durations::Notation;
+use crate::score::instruments::defs::{get_def, InstrumentType};
+use crate::score::instruments::Instrument;
+use crate::score::stave::Stave;
+use crate::score::tracks::Track;
+use crate::Engine;
+use wasm_bindgen::prelude::wasm_bindgen;
+
+const PROLOG: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>
+<!DOCTYPE score-partwise PUBLIC \"-//Recordare//DTD MusicXML 4.0 Partwise//EN\" \"http://www.musicxml.org/dtds/partwise.dtd\">
+";
+
+pub fn note_type(duration: NoteDuration) -> &'static str {
+    match duration {
+        NoteDuration::Whole => "whole",
+        NoteDuration::Half => "half",
+        NoteDuration::Quarter => "quarter",
+        NoteDuration::Eighth => "eighth",
+        NoteDuration::Sixteenth => "16th",
+        NoteDuration::ThirtySecond => "32nd",
+        NoteDuration::SixtyFourth => "64th",
+        NoteDuration::HudredTwentyEighth => "128th",
+    }
+}
+
+/// The chromatic alteration of a pitch, microtones are given as decimals (ie. 0.5)
+pub fn alter(pitch: &Pitch) -> String {
+    let cents = alteration(pitch);
+    if cents % 100 == 0 {
+        (cents / 100).to_string()
+    } else {
+        (cents as f32 / 100.0).to_string()
+    }
+}
+
+/// The beam value at each level for a note in a beam, ie. ["begin", "forward hook"]
+pub fn beam_types(beam: &Beam, tick: Tick) -> Vec<&'static str> {
+    let mut ticks: Vec<&Tick> = beam.ticks.keys().collect();
+    ticks.sort();
+
+    let count = match beam.ticks.get(&tick) {
+        Some(count) => *count,
+        None => return Vec::new(),
+    };
+    let i = ticks.iter().position(|entry| **entry == tick).unwrap();
+    let previous = i.checked_sub(1).map(|i| beam.ticks[ticks[i]]);
+    let next = ticks.get(i + 1).map(|next| beam.ticks[*next]);
+
+    (1..=count)
+        .map(|level| {
+            let joins_previous = previous.is_some_and(|count| count >= level);
+            let joins_next = next.is_some_and(|count| count >= level);
+            match (joins_previous, joins_next) {
+                (true, true) => "continue",
+                (false, true) => "begin",
+                (true, false) => "end",
+                (false, false) => match previous {
+                    None => "forward hook",
+                    Some(_) => "backward hook",
+                },
+            }
+        })
+        .collect()
+}
+
+/// Names use tokens for accidentals (ie. "Clarinet in B${flat}"), other applications need the symbol
+fn plain_text(input: &str) -> String {
+    input.replace("${flat}", "♭").replace("${sharp}", "♯")
+}
+
+fn write_time(writer: &mut XmlWriter, time_signature: &TimeSignature, hidden: bool) {
+    let mut attributes = Vec::new();
+    match time_signature.draw_type {
+        TimeSignatureDrawType::CommonTime => attributes.push(attr("symbol", "common")),
+        TimeSignatureDrawType::SplitCommonTime => attributes.push(attr("symbol", "cut")),
+        _ => (),
+    }
+    if hidden {
+        attributes.push(attr("print-object", "no"));
+    }
+
+    writer.open("time", &attributes);
+    if time_signature.beats == 0 {
+        writer.empty("senza-misura", &[]);
+    } else {
+        write_time_parts(writer, &time_signature.display_parts());
+        if let Some(interchangeable) = &time_signature.interchangeable {
+            writer.open("interchangeable", &[]);
+            writer.text("time-relation", &[], "parentheses");
+            write_time_parts(writer, interchangeable);
+            writer.close();
+        }
+    }
+    writer.close();
+}
+
+fn write_time_parts(writer: &mut XmlWriter, parts: &[TimeSignaturePart]) {
+    for part in parts {
+        let beats: Vec<String> = part.beats.iter().map(|beats| beats.to_string()).collect();
+        writer.text("beats", &[], &beats.join("+"));
+        writer.text(
+            "beat-type",
+            &[],
+            &part.beat_type.to_denominator().to_string(),
+        );
+    }
+}
+
+fn write_key(writer: &mut XmlWriter, key_signature: &KeySignature, transposition: &Interval) {
+    let key_signature = key_signature.transpose(transposition);
+    writer.open("key", &[]);
+    match &key_signature.custom {
+        Some(pitches) => {
+            for pitch in pitches {
+                writer.text("key-step", &[], &pitch.letter());
+                writer.text("key-alter", &[], &alter(pitch));
+            }
+        }
+        None => {
+            writer.text("fifths", &[], &key_signature.offset.to_string());
+            writer.text("mode", &[], key_signature.mode.name());
+        }
+    }
+    writer.close();
+}
+
+fn write_clef(writer: &mut XmlWriter, clef: &Clef, number: Option<usize>) {
+    let attributes = match number {
+        Some(number) => vec![attr("number", number)],
+        None => Vec::new(),
+    };
+    writer.open("clef", &attributes);
+    match clef.draw_as {
+        ClefDrawType::Hidden => writer.text("sign", &[], "none"),
+        ClefDrawType::Percussion => writer.text("sign", &[], "percussion"),
+        ClefDrawType::G | ClefDrawType::F | ClefDrawType::C => {
+            let sign = match clef.draw_as {
+                ClefDrawType::G => "G",
+                ClefDrawType::F => "F",
+                _ => "C",
+            };
+            writer.text("sign", &[], sign);
+            writer.text("line", &[], &(3 - clef.offset / 2).to_string());
+        }
+    }
+    if clef.octave != 0 {
+        writer.text("clef-octave-change", &[], &clef.octave.to_string());
+    }
+    writer.close();
+}
+
+fn write_barline(writer: &mut XmlWriter, barline_type: &BarlineDrawType, left: bool) {
+    let location = if left { "left" } else { "right" };
+    let (style, repeat) = match barline_type {
+        BarlineDrawType::Single => ("regular", None),
+        BarlineDrawType::Double => ("light-light", None),
+        BarlineDrawType::Final => ("light-heavy", None),
+        BarlineDrawType::StartRepeat => ("heavy-light", Some("forward")),
+        BarlineDrawType::EndRepeat => ("light-heavy", Some("backward")),
+        BarlineDrawType::EndStartRepeat => {
+            if left {
+                ("heavy-light", Some("forward"))
+            } else {
+                ("light-heavy", Some("backward"))
+            }
+        }
+    };
+    writer.open("barline", &[attr("location", location)]);
+    writer.text("bar-style", &[], style);
+    if let Some(direction) = repeat {
+        writer.empty("repeat", &[attr("direction", direction)]);
+    }
+    writer.close();
+}
+
+/// A voice of a part, with its notation in order
+struct Voice<'a> {
+    number: usize,
+    staff: usize,
+    notations: Vec<&'a Notation>,
+    beams: Beams,
+}
+
+struct Part<'a> {
+    instrument: &'a Instrument,
+    staves: Vec<&'a Stave>,
+    voices: Vec<Voice<'a>>,
+    transposition: Interval,
+}
+
+#[wasm_bindgen]
+impl Engine {
+    /// Export a flow as a MusicXML 4.0 partwise document.
+    /// Pitches are written as displayed for transposing instruments.
+    pub fn export_musicxml(&self, flow_key: &str) -> String {
+        let flow = self.score.flows.by_key.get(flow_key).unwrap();
+        let master = self.score.tracks.get(&flow.master).unwrap();
+        let subdivisions = flow.subdivisions;
+
+        let bars = get_bars(flow, &self.score.tracks);
+        let mut starts: Vec<Tick> = bars.keys().copied().collect();
+        starts.sort_unstable();
+
+        // the notation of every track, as engraved
+        let mut notations = Vec::new();
+        for stave in flow.staves.values() {
+            for track_key in &stave.tracks {
+                let track = self.score.tracks.get(track_key).unwrap();
+                notations.push((
+                    track_key.clone(),
+                    track.to_notation_track(&flow.length, &bars, subdivisions),
+                ));
+            }
+        }
+
+        let mut players: Vec<(String, String, Vec<&Instrument>)> = Vec::new();
+        for player_key in &self.score.players.order {
+            if flow.players.contains(player_key) {
+                let player = self.score.players.by_key.get(player_key).unwrap();
+                let instruments = player
+                    .instruments
+                    .iter()
+                    .map(|key| self.score.instruments.get(key).unwrap())
+                    .collect();
+                players.push((
+                    player_key.clone(),
+                    self.get_player_name(player_key),
+                    instruments,
+                ));
+            }
+        }
+
+        let mut parts: Vec<Part> = Vec::new();
+        for (_, _, instruments) in &players {
+            for instrument in instruments {
+                let staves: Vec<&Stave> = instrument
+                    .staves
+                    .iter()
+                    .map(|key| flow.staves.get(key).unwrap())
+                    .collect();
+
+                let mut voices = Vec::new();
+                for (i, stave) in staves.iter().enumerate() {
+                    for track_key in &stave.tracks {
+                        let (_, notation) =
+                            notations.iter().find(|(key, _)| key == track_key).unwrap();
+                        let mut entries: Vec<&Notation> = notation.track.values().collect();
+                        entries.sort_by_key(|entry| entry.tick);
+                        voices.push(Voice {
+                            number: voices.len() + 1,
+                            staff: i + 1,
+                            notations: entries,
+                            beams: get_beams_in_track(notation, &bars, subdivisions),
+                        });
+                    }
+                }
+
+                let transposition = match get_def(&instrument.id) {
+                    Some(def) => def.transposition,
+                    None => Interval::UNISON,
+                };
+
+                parts.push(Part {
+                    instrument,
+                    staves,
+                    voices,
+                    transposition,
+                });
+            }
+        }
+
+        let mut writer = XmlWriter::new(PROLOG);
+        writer.open("score-partwise", &[attr("version", "4.0")]);
+
+        let title = self.title();
+        if !title.is_empty() {
+            writer.open("work", &[]);
+            writer.text("work-title", &[], &title);
+            writer.close();
+        }
+        if !flow.title.is_empty() {
+            writer.text("movement-title", &[], &flow.title);
+        }
+        let composer = self.composer();
+        if !composer.is_empty() {
+            writer.open("identification", &[]);
+            writer.text("creator", &[attr("type", "composer")], &composer);
+            writer.close();
+        }
+
+        writer.open("part-list", &[]);
+        let mut i = 0;
+        for (player_key, player_name, instruments) in &players {
+            for instrument in instruments {
+                i += 1;
+                let id = format!("P{}", i);
+                writer.open("score-part", &[attr("id", &id)]);
+                writer.text("part-name", &[], &plain_text(&instrument.name()));
+                writer.text(
+                    "part-abbreviation",
+                    &[],
+                    &plain_text(&instrument.short_name),
+                );
+                writer.open("score-instrument", &[attr("id", format!("{}-I1", id))]);
+                writer.text("instrument-name", &[], &plain_text(&instrument.long_name));
+                writer.close();
+                writer.open("player", &[attr("id", format!("{}-{}", id, player_key))]);
+                writer.text("player-name", &[], &plain_text(player_name));
+                writer.close();
+                writer.close();
+            }
+        }
+        writer.close();
+
+        for (i, part) in parts.iter().enumerate() {
+            writer.open("part", &[attr("id", format!("P{}", i + 1))]);
+            self.write_part(
+                &mut writer,
+                part,
+                master,
+                &bars,
+                &starts,
+                flow.length,
+                subdivisions,
+            );
+            writer.close();
+        }
+
+        writer.finish()
+    }
+}
+
+impl Engine {
+    #[allow(clippy::too_many_arguments)]
+    fn write_part(
+        &self,
+        writer: &mut XmlWriter,
+        part: &Part,
+        master: &Track,
+        bars: &Bars,
+        starts: &[Tick],
+        length: Ticks,
+        subdivisions: Ticks,
+    ) {
+        let staff_count = part.staves.len();
+        let pickup = starts
+            .first()
+            .is_some_and(|start| bars[start].is_irregular());
+        let mut current_meter: Option<(u8, u8)> = None;
+
+        for (i, start) in starts.iter().enumerate() {
+            let start = *start;
+            let end = starts.get(i + 1).copied().unwrap_or(length);
+            let bar = &bars[&start];
+            let number = if pickup { i } else { i + 1 };
+
+            let mut attributes = vec![attr("number", number)];
+            if i == 0 && pickup {
+                attributes.push(attr("implicit", "yes"));
+            }
+            writer.open("measure", &attributes);
+
+            if let Some(barline) = master.get_barline_at_tick(&start) {
+                if matches!(
+                    barline.barline_type,
+                    BarlineDrawType::StartRepeat | BarlineDrawType::EndStartRepeat
+                ) {
+                    write_barline(writer, &barline.barline_type, true);
+                }
+            }
+
+            // attributes at the start of the bar
+            let key_signature = master.get_key_signature_at_tick(&start);
+            let time_signature = master.get_time_signature_at_tick(&start);
+            let meter = (bar.beats, bar.beat_type.to_denominator());
+            let clefs: Vec<(usize, &Clef)> = part
+                .staves
+                .iter()
+                .enumerate()
+                .filter_map(|(i, stave)| {
+                    let track = self.score.tracks.get(&stave.master).unwrap();
+                    track.get_clef_at_tick(&start).map(|clef| (i + 1, clef))
+                })
+                .collect();
+            let time = match time_signature {
+                Some(entry) if entry.draw_type != TimeSignatureDrawType::Hidden => {
+                    current_meter = Some((entry.beats, entry.beat_type.to_denominator()));
+                    Some((entry, false))
+                }
+                _ if time_signature.is_some() || current_meter != Some(meter) => {
+                    current_meter = Some(meter);
+                    Some((bar, true))
+                }
+                _ => None,
+            };
+
+            if i == 0 || key_signature.is_some() || time.is_some() || !clefs.is_empty() {
+                writer.open("attributes", &[]);
+                if i == 0 {
+                    writer.text("divisions", &[], &subdivisions.to_string());
+                }
+                if let Some(key_signature) = key_signature {
+                    if !matches!(part.instrument.instrument_type, InstrumentType::Percussive) {
+                        write_key(writer, key_signature, &part.transposition);
+                    }
+                }
+                if let Some((time_signature, hidden)) = time {
+                    write_time(writer, time_signature, hidden);
+                }
+                if i == 0 && staff_count > 1 {
+                    writer.text("staves", &[], &staff_count.to_string());
+                }
+                for (number, clef) in &clefs {
+                    let number = if staff_count > 1 { Some(*number) } else { None };
+                    write_clef(writer, clef, number);
+                }
+                if i == 0 {
+                    for (i, stave) in part.staves.iter().enumerate() {
+                        if stave.lines.len() != 5 {
+                            let attributes = if staff_count > 1 {
+                                vec![attr("number", i + 1)]
+                            } else {
+                                Vec::new()
+                            };
+                            writer.open("staff-details", &attributes);
+                            writer.text("staff-lines", &[], &stave.lines.len().to_string());
+                            writer.close();
+                        }
+                    }
+                    if !part.transposition.is_unison() {
+                        // MusicXML gives the interval from written to sounding pitch
+                        let interval = part.transposition.invert();
+                        writer.open("transpose", &[]);
+                        writer.text("diatonic", &[], &interval.steps.to_string());
+                        writer.text("chromatic", &[], &interval.semitones.to_string());
+                        writer.close();
+                    }
+                }
+                writer.close();
+            }
+
+            for (v, voice) in part.voices.iter().enumerate() {
+                if v > 0 {
+                    writer.open("backup", &[]);
+                    writer.text("duration", &[], &(end - start).to_string());
+                    writer.close();
+                }
+
+                // mid bar changes are written in the first voice of each stave
+                let first_in_stave = !part.voices[..v]
+                    .iter()
+                    .any(|other| other.staff == voice.staff);
+                let stave_master = self
+                    .score
+                    .tracks
+                    .get(&part.staves[voice.staff - 1].master)
+                    .unwrap();
+
+                for (n, notation) in voice.notations.iter().enumerate() {
+                    if notation.tick < start || notation.tick >= end {
+                        continue;
+                    }
+
+                    if first_in_stave && notation.tick > start {
+                        let clef = stave_master.get_clef_at_tick(&notation.tick);
+                        let key_signature = if voice.staff == 1 {
+                            master.get_key_signature_at_tick(&notation.tick)
+                        } else {
+                            None
+                        };
+                        if clef.is_some() || key_signature.is_some() {
+                            writer.open("attributes", &[]);
+                            if let Some(key_signature) = key_signature {
+                                write_key(writer, key_signature, &part.transposition);
+                            }
+                            if let Some(clef) = clef {
+                                let number = if staff_count > 1 {
+                                    Some(voice.staff)
+                                } else {
+                                    None
+                                };
+                                write_clef(writer, clef, number);
+                            }
+                            writer.close();
+                        }
+                    }
+
+                    let previous = match n {
+                        0 => None,
+                        _ => Some(voice.notations[n - 1]),
+                    };
+                    let whole_bar = notation.is_rest()
+                        && notation.tick == start
+                        && notation.duration == end - start;
+                    self.write_notation(
+                        writer,
+                        part,
+                        voice,
+                        notation,
+                        previous,
+                        whole_bar,
+                        staff_count,
+                        subdivisions,
+                    );
+                }
+            }
+
+            if let Some(barline) = master.get_barline_at_tick(&end) {
+                if !matches!(barline.barline_type, BarlineDrawType::StartRepeat) {
+                    write_barline(writer, &barline.barline_type, false);
+                }
+            }
+
+            writer.close();
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn write_notation(
+        &self,
+        writer: &mut XmlWriter,
+        part: &Part,
+        voice: &Voice,
+        notation: &Notation,
+        previous: Option<&Notation>,
+        whole_bar: bool,
+        staff_count: usize,
+        subdivisions: Ticks,
+    ) {
+        let beam = voice
+            .beams
+            .iter()
+            .find(|beam| beam.ticks.contains_key(&notation.tick));
+        let beam_types = match beam {
+            Some(beam) => beam_types(beam, notation.tick),
+            None => Vec::new(),
+        };
+
+        let mut tones: Vec<_> = notation.tones.iter().collect();
+        tones.sort_by_key(|tone| tone.pitch.int);
+
+        // a rest is written as a note with no tones
+        let count = tones.len().max(1);
+        for i in 0..count {
+            let tone = tones.get(i);
+            writer.open("note", &[]);
+            if i > 0 {
+                writer.empty("chord", &[]);
+            }
+
+            let tie_start = tone.is_some_and(|tone| notation.ties.contains(&tone.key));
+            let tie_stop = tone.is_some_and(|tone| {
+                previous.is_some_and(|previous| previous.ties.contains(&tone.key))
+            });
+
+            match tone {
+                None => {
+                    if whole_bar {
+                        writer.empty("rest", &[attr("measure", "yes")]);
+                    } else {
+                        writer.empty("rest", &[]);
+                    }
+                }
+                Some(tone) => {
+                    let pitch = tone.pitch.transpose(&part.transposition);
+                    match part.instrument.instrument_type {
+                        InstrumentType::Percussive => {
+                            writer.open("unpitched", &[]);
+                            writer.text("display-step", &[], &pitch.letter());
+                            writer.text("display-octave", &[], &pitch.octave().to_string());
+                        }
+                        InstrumentType::Melodic => {
+                            writer.open("pitch", &[]);
+                            writer.text("step", &[], &pitch.letter());
+                            let alter = alter(&pitch);
+                            if alter != "0" {
+                                writer.text("alter", &[], &alter);
+                            }
+                            writer.text("octave", &[], &pitch.octave().to_string());
+                        }
+                    }
+                    writer.close();
+                }
+            }
+
+            writer.text("duration", &[], &notation.duration.to_string());
+            if tie_stop {
+                writer.empty("tie", &[attr("type", "stop")]);
+            }
+            if tie_start {
+                writer.empty("tie", &[attr("type", "start")]);
+            }
+            writer.text("voice", &[], &voice.number.to_string());
+            if !whole_bar {
+                if let Some(duration) = notation.base_to_note_duration(subdivisions) {
+                    writer.text("type", &[], note_type(duration));
+                }
+                if notation.is_dotted(subdivisions) {
+                    writer.empty("dot", &[]);
+                }
+            }
+            if staff_count > 1 {
+                writer.text("staff", &[], &voice.staff.to_string());
+            }
+            if i == 0 {
+                for (level, value) in beam_types.iter().enumerate() {
+                    writer.text("beam", &[attr("number", level + 1)], value);
+                }
+            }
+            if tie_start || tie_stop {
+                writer.open("notations", &[]);
+                if tie_stop {
+                    writer.empty("tied", &[attr("type", "stop")]);
+                }
+                if tie_start {
+                    writer.empty("tied", &[attr("type", "start")]);
+                }
+                writer.close();
+            }
+            writer.close();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{alter, beam_types};
+    use crate::components::articulation::Articulation;
+    use crate::components::pitch::{Accidental, Pitch};
+    use crate::entries::key_signature::KeySignatureMode;
+    use crate::parse::get_beams::Beam;
+    use crate::score::players::PlayerType;
+    use crate::Engine;
+
+    #[test]
+    fn alter_test() {
+        assert_eq!(alter(&Pitch::new(60, Accidental::Natural)), "0");
+        assert_eq!(alter(&Pitch::new(61, Accidental::Sharp)), "1");
+        assert_eq!(alter(&Pitch::new(58, Accidental::DoubleFlat)), "-2");
+        assert_eq!(alter(&Pitch::new(60, Accidental::QuarterSharp)), "0.5");
+        assert_eq!(alter(&Pitch::new(63, Accidental::ThreeQuarterFlat)), "-1.5");
+    }
+
+    #[test]
+    fn beam_types_test() {
+        // dotted eighth, sixteenth, eighth
+        let mut beam = Beam::new();
+        beam.ticks.insert(0, 1);
+        beam.ticks.insert(18, 2);
+        beam.ticks.insert(24, 1);
+        beam.start = 0;
+        beam.stop = 24;
+
+        assert_eq!(beam_types(&beam, 0), vec!["begin"]);
+        assert_eq!(beam_types(&beam, 18), vec!["continue", "backward hook"]);
+        assert_eq!(beam_types(&beam, 24), vec!["end"]);
+    }
+
+    #[test]
+    fn transposing_instrument_test() {
+        let mut engine = Engine::tester();
+        let flow_key = engine.score.flows.order[0].clone();
+        let player_key = engine.create_player(PlayerType::Solo);
+        let instrument_key = engine.create_instrument("woodwinds.clarinet.b-flat");
+        engine.assign_instrument_to_player(&player_key, &instrument_key);
+        let stave_key = engine.score.instruments[&instrument_key].staves[0].clone();
+        let track_key = engine.score.flows.by_key[&flow_key].staves[&stave_key].tracks[0].clone();
+
+        engine.create_key_signature(&flow_key, 0, KeySignatureMode::Major, -1);
+        engine.create_tone(
+            &track_key,
+            0,
+            192,
+            58,
+            Some(Accidental::Flat),
+            100,
+            Articulation::None,
+        );
+
+        let xml = engine.export_musicxml(&flow_key);
+        assert!(xml.contains("<part-name>Clarinet in B♭</part-name>"));
+        // written a major 2nd above concert pitch
+        assert!(xml.contains("<key>\n          <fifths>1</fifths>\n          <mode>major</mode>"));
+        assert!(xml.contains(
+            "<transpose>\n          <diatonic>-1</diatonic>\n          <chromatic>-2</chromatic>"
+        ));
+        assert!(xml.contains("<step>C</step>\n          <octave>4</octave>"));
+        assert!(!xml.contains("<alter>"));
+    }
+}
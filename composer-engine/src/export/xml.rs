@@ -0,0 +1,111 @@
+/// Escape text for use in XML content and attribute values
+pub fn escape(value: &str) -> String {
+    let mut output = String::with_capacity(value.len());
+    for char in value.chars() {
+        match char {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&apos;"),
+            _ => output.push(char),
+        }
+    }
+    output
+}
+
+/// An attribute from any displayable value, ie. attr("number", 1)
+pub fn attr(name: &str, value: impl ToString) -> (&str, String) {
+    (name, value.to_string())
+}
+
+/// A minimal indenting XML writer, attributes are given as (name, value) pairs
+pub struct XmlWriter {
+    output: String,
+    stack: Vec<String>,
+}
+
+impl XmlWriter {
+    pub fn new(prolog: &str) -> Self {
+        Self {
+            output: String::from(prolog),
+            stack: Vec::new(),
+        }
+    }
+
+    fn indent(&mut self) {
+        for _ in 0..self.stack.len() {
+            self.output.push_str("  ");
+        }
+    }
+
+    fn tag(&mut self, name: &str, attributes: &[(&str, String)]) {
+        self.output.push('<');
+        self.output.push_str(name);
+        for (key, value) in attributes {
+            self.output
+                .push_str(&format!(" {}=\"{}\"", key, escape(value)));
+        }
+    }
+
+    pub fn open(&mut self, name: &str, attributes: &[(&str, String)]) {
+        self.indent();
+        self.tag(name, attributes);
+        self.output.push_str(">\n");
+        self.stack.push(String::from(name));
+    }
+
+    pub fn close(&mut self) {
+        let name = self.stack.pop().unwrap();
+        self.indent();
+        self.output.push_str(&format!("</{}>\n", name));
+    }
+
+    /// An element with no content, ie. <chord/>
+    pub fn empty(&mut self, name: &str, attributes: &[(&str, String)]) {
+        self.indent();
+        self.tag(name, attributes);
+        self.output.push_str("/>\n");
+    }
+
+    /// An element containing only text, ie. <step>C</step>
+    pub fn text(&mut self, name: &str, attributes: &[(&str, String)], value: &str) {
+        self.indent();
+        self.tag(name, attributes);
+        self.output
+            .push_str(&format!(">{}</{}>\n", escape(value), name));
+    }
+
+    pub fn finish(mut self) -> String {
+        while !self.stack.is_empty() {
+            self.close();
+        }
+        self.output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape, XmlWriter};
+
+    #[test]
+    fn escape_test() {
+        assert_eq!(
+            escape("Flute & \"Piccolo\" <1>"),
+            "Flute &amp; &quot;Piccolo&quot; &lt;1&gt;"
+        );
+    }
+
+    #[test]
+    fn writer_test() {
+        let mut writer = XmlWriter::new("");
+        writer.open("note", &[("id", String::from("a"))]);
+        writer.empty("chord", &[]);
+        writer.text("step", &[], "C");
+        let output = writer.finish();
+        assert_eq!(
+            output,
+            "<note id=\"a\">\n  <chord/>\n  <step>C</step>\n</note>\n"
+        );
+    }
+}
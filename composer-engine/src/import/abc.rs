@@ -0,0 +1,1029 @@
+use crate::components::articulation::Articulation;
+use crate::components::duration::NoteDuration;
+use crate::components::misc::{Tick, Ticks};
+use crate::components::pitch::{Accidental, Pitch};
+use crate::entries::barline::BarlineDrawType;
+use crate::entries::key_signature::{KeySignature, KeySignatureMode};
+use crate::entries::time_signature::{TimeSignature, TimeSignatureDrawType};
+use crate::entries::Entry;
+use crate::score::players::PlayerType;
+use crate::Engine;
+use rustc_hash::FxHashMap;
+use std::convert::TryFrom;
+use wasm_bindgen::prelude::wasm_bindgen;
+
+const LETTERS: [char; 7] = ['C', 'D', 'E', 'F', 'G', 'A', 'B'];
+const DECORATIONS: [char; 11] = ['.', '~', 'H', 'L', 'M', 'O', 'P', 'S', 'T', 'u', 'v'];
+const MAX_BROKEN_DOTS: u32 = 3; // ie. a>>>b
+const LENGTH_ERROR: &str = "Note length is out of range";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AbcClef {
+    Treble,
+    Alto,
+    Bass,
+}
+
+#[derive(Debug, Clone)]
+pub struct AbcTone {
+    pub tick: Tick,
+    pub duration: Ticks,
+    pub pitch: Pitch,
+}
+
+/// A single tune with ties merged into single tones
+#[derive(Debug, Default)]
+pub struct AbcTune {
+    pub title: Option<String>,
+    pub composer: Option<String>,
+    pub clef: Option<AbcClef>,
+    pub length: Tick,
+    pub time_signatures: Vec<TimeSignature>,
+    pub key_signatures: Vec<KeySignature>,
+    pub barlines: Vec<(Tick, BarlineDrawType)>,
+    pub tones: Vec<AbcTone>,
+}
+
+/// An element of the tune body before ties and broken rhythms are resolved
+#[derive(Debug)]
+enum Element {
+    Note(Vec<(Pitch, bool)>, Ticks), // (pitch, tied to the next note), duration
+    Rest(Ticks),
+    BarRest(u32), // multiple whole bar rests
+    Bar(BarlineDrawType),
+    Meter(TimeSignature),
+    Key(KeySignature),
+}
+
+/// Parse a meter field, ie. "6/8", "C", "C|", "(2+3+2)/8" or "none"
+pub fn parse_meter(input: &str) -> Option<TimeSignature> {
+    let input = input.trim();
+    match input {
+        "" | "none" => Some(TimeSignature::default()),
+        "C" => Some(TimeSignature::new(
+            0,
+            4,
+            NoteDuration::Quarter,
+            TimeSignatureDrawType::CommonTime,
+            None,
+        )),
+        "C|" => Some(TimeSignature::new(
+            0,
+            2,
+            NoteDuration::Half,
+            TimeSignatureDrawType::SplitCommonTime,
+            None,
+        )),
+        _ => TimeSignature::parse(0, &input.replace(['(', ')'], "")),
+    }
+}
+
+/// Parse a unit note length field, ie. "1/8" as (1, 8)
+pub fn parse_unit(input: &str) -> Option<(u64, u64)> {
+    let (num, den) = input.trim().split_once('/')?;
+    let num: u64 = num.trim().parse().ok()?;
+    let den: u64 = den.trim().parse().ok()?;
+    if num == 0 || den == 0 {
+        return None;
+    }
+    Some((num, den))
+}
+
+/// The default unit note length for a meter, 1/16 below 3/4 otherwise 1/8
+fn default_unit(meter: &TimeSignature) -> (u64, u64) {
+    let whole = NoteDuration::Whole.to_ticks(16);
+    if meter.beats > 0 && meter.ticks_per_bar(16) * 4 < whole * 3 {
+        (1, 16)
+    } else {
+        (1, 8)
+    }
+}
+
+fn to_mode(input: &str) -> Option<KeySignatureMode> {
+    let input = input.to_lowercase();
+    let prefix: String = input.chars().take(3).collect();
+    match prefix.as_str() {
+        "" | "maj" | "ion" => Some(KeySignatureMode::Major),
+        "m" | "min" | "aeo" => Some(KeySignatureMode::Minor),
+        "dor" => Some(KeySignatureMode::Dorian),
+        "phr" => Some(KeySignatureMode::Phrygian),
+        "lyd" => Some(KeySignatureMode::Lydian),
+        "mix" => Some(KeySignatureMode::Mixolydian),
+        "loc" => Some(KeySignatureMode::Locrian),
+        _ => None,
+    }
+}
+
+/// Read an accidental at the start of the chars, ie. "^", "__", "=" or "^/"
+/// Returns the accidental and the number of chars read.
+fn read_accidental(chars: &[char]) -> Option<(Accidental, usize)> {
+    let accidental = match chars {
+        ['^', '^', ..] => return Some((Accidental::DoubleSharp, 2)),
+        ['_', '_', ..] => return Some((Accidental::DoubleFlat, 2)),
+        ['=', ..] => return Some((Accidental::Natural, 1)),
+        ['^', ..] => Accidental::Sharp,
+        ['_', ..] => Accidental::Flat,
+        _ => return None,
+    };
+
+    // microtones are written as a fraction of a half step, ie. ^/ or _3/2
+    let is_sharp = accidental == Accidental::Sharp;
+    match chars[1..] {
+        ['/', ..] => Some(match is_sharp {
+            true => (Accidental::QuarterSharp, 2),
+            false => (Accidental::QuarterFlat, 2),
+        }),
+        ['3', '/', '2', ..] => Some(match is_sharp {
+            true => (Accidental::ThreeQuarterSharp, 4),
+            false => (Accidental::ThreeQuarterFlat, 4),
+        }),
+        _ => Some((accidental, 1)),
+    }
+}
+
+/// Parse a key field, ie. "G", "F#m", "D dor", "Bb exp ^f" or "none"
+pub fn parse_key(input: &str) -> Option<(KeySignature, Option<AbcClef>)> {
+    let mut clef = None;
+    let mut explicit = false;
+    let mut tokens: Vec<&str> = Vec::new();
+    for token in input.split_whitespace() {
+        match token {
+            "clef=treble" | "treble" => clef = Some(AbcClef::Treble),
+            "clef=alto" | "alto" => clef = Some(AbcClef::Alto),
+            "clef=bass" | "bass" => clef = Some(AbcClef::Bass),
+            "exp" => explicit = true,
+            _ if token.contains('=') && !token.starts_with('=') => (),
+            _ => tokens.push(token),
+        }
+    }
+
+    let mut tokens = tokens.into_iter().peekable();
+    let mut key = match tokens.peek() {
+        None | Some(&"none") => {
+            tokens.next();
+            KeySignature::new(0, KeySignatureMode::Major, 0)
+        }
+        Some(first) if first.starts_with(|char: char| LETTERS.contains(&char)) => {
+            let first = tokens.next().unwrap();
+            let mut chars = first.chars();
+            let letter = chars.next().unwrap();
+            let rest: String = chars.collect();
+            let (accidental, rest) = match rest.as_str() {
+                rest if rest.starts_with('#') => (Accidental::Sharp, &rest[1..]),
+                rest if rest.starts_with('b') => (Accidental::Flat, &rest[1..]),
+                rest => (Accidental::Natural, rest),
+            };
+            let mode = match to_mode(rest) {
+                Some(KeySignatureMode::Major) if rest.is_empty() => {
+                    match tokens.peek().and_then(|token| to_mode(token)) {
+                        Some(mode) => {
+                            tokens.next();
+                            mode
+                        }
+                        None => KeySignatureMode::Major,
+                    }
+                }
+                Some(mode) => mode,
+                None => return None,
+            };
+            let step = LETTERS.iter().position(|entry| *entry == letter).unwrap();
+            let tonic = Pitch::natural(28 + step as i16).with_accidental(accidental);
+            let offset = tonic.fifths() - mode.tonic_fifths();
+            if !(-7..=7).contains(&offset) {
+                return None;
+            }
+            KeySignature::new(0, mode, offset)
+        }
+        Some(_) => KeySignature::new(0, KeySignatureMode::Major, 0),
+    };
+
+    // explicit accidentals modify the key, or replace it with "exp"
+    let mut alterations: Vec<Pitch> = Vec::new();
+    for token in tokens {
+        let chars: Vec<char> = token.chars().collect();
+        let (accidental, length) = match read_accidental(&chars) {
+            Some(accidental) => accidental,
+            None => continue,
+        };
+        let letter = match chars.get(length) {
+            Some(letter) => letter.to_ascii_uppercase(),
+            None => continue,
+        };
+        let step = match LETTERS.iter().position(|entry| *entry == letter) {
+            Some(step) => step,
+            None => continue,
+        };
+        alterations.push(Pitch::natural(28 + step as i16).with_accidental(accidental));
+    }
+
+    if explicit || !alterations.is_empty() {
+        let mut pitches: Vec<Pitch> = match explicit {
+            true => Vec::new(),
+            false => key.ordered_accidentals(),
+        };
+        for pitch in alterations {
+            pitches.retain(|entry| entry.steps() != pitch.steps());
+            if pitch.accidental != Accidental::Natural {
+                pitches.push(pitch);
+            }
+        }
+        key = KeySignature::new_custom(0, pitches);
+    }
+
+    Some((key, clef))
+}
+
+/// Read a length multiplier, ie. "3", "/", "//", "3/2" as a fraction
+fn read_length(chars: &[char], i: &mut usize) -> Result<(u64, u64), String> {
+    let read_number = |i: &mut usize| {
+        let start = *i;
+        while *i < chars.len() && chars[*i].is_ascii_digit() {
+            *i += 1;
+        }
+        if start == *i {
+            return Ok(None);
+        }
+        chars[start..*i]
+            .iter()
+            .collect::<String>()
+            .parse::<u64>()
+            .map(Some)
+            .map_err(|_| String::from(LENGTH_ERROR))
+    };
+
+    let num = read_number(i)?.unwrap_or(1);
+    let mut den: u64 = 1;
+    while *i < chars.len() && chars[*i] == '/' {
+        *i += 1;
+        den = den
+            .checked_mul(read_number(i)?.unwrap_or(2))
+            .ok_or_else(|| String::from(LENGTH_ERROR))?;
+    }
+    Ok((num, den.max(1)))
+}
+
+/// State while reading the body of a tune
+struct Body {
+    elements: Vec<Element>,
+    whole: Ticks,
+    unit: (u64, u64),
+    key: FxHashMap<u8, Accidental>,  // alterations in the key by step
+    bar: FxHashMap<i16, Accidental>, // accidentals in the bar by steps from C0
+    broken: Option<(u64, u64)>,      // multiplier for the next note of a broken rhythm
+    tuplet: Option<(u32, u64, u64)>, // (notes remaining, p notes in the time of q)
+    clef: Option<AbcClef>,
+}
+
+impl Body {
+    fn new(whole: Ticks, unit: (u64, u64)) -> Self {
+        Self {
+            elements: Vec::new(),
+            whole,
+            unit,
+            key: FxHashMap::default(),
+            bar: FxHashMap::default(),
+            broken: None,
+            tuplet: None,
+            clef: None,
+        }
+    }
+
+    fn set_key(&mut self, key: KeySignature) {
+        self.key = key
+            .accidentals()
+            .into_iter()
+            .map(|pitch| (pitch.steps(), pitch.accidental))
+            .collect();
+        self.elements.push(Element::Key(key));
+    }
+
+    /// Handle a field in the body (or an inline field), only meter, unit and key change the music
+    fn field(&mut self, name: char, value: &str) {
+        match name {
+            'M' => {
+                if let Some(meter) = parse_meter(value) {
+                    self.elements.push(Element::Meter(meter));
+                }
+            }
+            'L' => {
+                if let Some(unit) = parse_unit(value) {
+                    self.unit = unit;
+                }
+            }
+            'K' => {
+                if let Some((key, clef)) = parse_key(value) {
+                    self.set_key(key);
+                    if clef.is_some() {
+                        self.clef = clef;
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// The ticks of a length in unit note lengths, scaled by any tuplet or broken rhythm
+    fn duration(&mut self, (num, den): (u64, u64)) -> Result<Ticks, String> {
+        let mut num = (self.whole as u64)
+            .checked_mul(self.unit.0)
+            .and_then(|value| value.checked_mul(num));
+        let mut den = self.unit.1.checked_mul(den);
+
+        if let Some((remaining, p, q)) = self.tuplet {
+            num = num.and_then(|value| value.checked_mul(q));
+            den = den.and_then(|value| value.checked_mul(p));
+            self.tuplet = match remaining {
+                1 => None,
+                _ => Some((remaining - 1, p, q)),
+            };
+        }
+
+        if let Some((broken_num, broken_den)) = self.broken.take() {
+            num = num.and_then(|value| value.checked_mul(broken_num));
+            den = den.and_then(|value| value.checked_mul(broken_den));
+        }
+
+        match (num, den) {
+            (Some(num), Some(den)) if den > 0 => {
+                Ticks::try_from(num / den).map_err(|_| String::from(LENGTH_ERROR))
+            }
+            _ => Err(String::from(LENGTH_ERROR)),
+        }
+    }
+
+    /// Read the pitch of a note at i, ie. "^c'", the length follows
+    fn note(&mut self, chars: &[char], i: &mut usize) -> Option<Pitch> {
+        let explicit = match read_accidental(&chars[*i..]) {
+            Some((accidental, length)) => {
+                *i += length;
+                Some(accidental)
+            }
+            None => None,
+        };
+
+        let letter = *chars.get(*i)?;
+        let step = LETTERS
+            .iter()
+            .position(|entry| *entry == letter.to_ascii_uppercase())?;
+        *i += 1;
+
+        let mut octave: i16 = if letter.is_ascii_lowercase() { 5 } else { 4 };
+        while *i < chars.len() {
+            match chars[*i] {
+                '\'' => octave += 1,
+                ',' => octave -= 1,
+                _ => break,
+            }
+            *i += 1;
+        }
+
+        // accidentals last until the end of the bar, otherwise the key applies
+        let steps = octave * 7 + step as i16;
+        let accidental = match explicit {
+            Some(accidental) => {
+                self.bar.insert(steps, accidental);
+                accidental
+            }
+            None => match self.bar.get(&steps) {
+                Some(accidental) => *accidental,
+                None => self
+                    .key
+                    .get(&(step as u8))
+                    .copied()
+                    .unwrap_or(Accidental::Natural),
+            },
+        };
+
+        let natural = Pitch::natural(steps);
+        let int = (natural.int as i16 + accidental.to_half_steps() as i16).clamp(0, 127);
+
+        Some(Pitch::new(int as u8, accidental))
+    }
+
+    /// Apply a broken rhythm (> or <) to the last note and the next
+    fn broken_rhythm(&mut self, dots: u32, is_forward: bool) -> Result<(), String> {
+        let den = 2u64.pow(dots.min(MAX_BROKEN_DOTS));
+        let (last, next) = match is_forward {
+            true => ((den * 2 - 1, den), (1, den)),
+            false => ((1, den), (den * 2 - 1, den)),
+        };
+        if let Some(Element::Note(_, duration) | Element::Rest(duration)) = self.elements.last_mut()
+        {
+            *duration = Ticks::try_from(*duration as u64 * last.0 / last.1)
+                .map_err(|_| String::from(LENGTH_ERROR))?;
+        }
+        self.broken = Some(next);
+        Ok(())
+    }
+
+    fn line(&mut self, line: &str) -> Result<(), String> {
+        let chars: Vec<char> = line.chars().collect();
+        let mut i = 0;
+
+        while i < chars.len() {
+            let char = chars[i];
+            match char {
+                '"' | '!' | '+' => {
+                    // annotations, chord symbols and decorations
+                    i += 1;
+                    while i < chars.len() && chars[i] != char {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                '{' => {
+                    // grace notes are skipped
+                    while i < chars.len() && chars[i] != '}' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                '(' if chars.get(i + 1).is_some_and(|next| next.is_ascii_digit()) => {
+                    i += 1;
+                    let mut numbers: Vec<Option<u64>> = Vec::new();
+                    loop {
+                        let start = i;
+                        while i < chars.len() && chars[i].is_ascii_digit() {
+                            i += 1;
+                        }
+                        numbers.push(chars[start..i].iter().collect::<String>().parse().ok());
+                        if i < chars.len() && chars[i] == ':' && numbers.len() < 3 {
+                            i += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                    let p = numbers[0].unwrap_or(3).max(1);
+                    let q = match numbers.get(1).copied().flatten() {
+                        Some(q) => q,
+                        None => match p {
+                            2 | 4 | 8 => 3,
+                            _ => 2,
+                        },
+                    };
+                    let r = numbers.get(2).copied().flatten().unwrap_or(p);
+                    self.tuplet = Some((r as u32, p, q));
+                }
+                '-' => {
+                    if let Some(Element::Note(pitches, _)) = self.elements.last_mut() {
+                        for (_, tie) in pitches.iter_mut() {
+                            *tie = true;
+                        }
+                    }
+                    i += 1;
+                }
+                '>' | '<' => {
+                    let mut dots = 0;
+                    while i < chars.len() && chars[i] == char {
+                        dots += 1;
+                        i += 1;
+                    }
+                    self.broken_rhythm(dots, char == '>')?;
+                }
+                '[' if chars.get(i + 2) == Some(&':')
+                    && chars
+                        .get(i + 1)
+                        .is_some_and(|next| next.is_ascii_alphabetic()) =>
+                {
+                    // inline field, ie. [K:G]
+                    let start = i + 3;
+                    let mut end = start;
+                    while end < chars.len() && chars[end] != ']' {
+                        end += 1;
+                    }
+                    let value: String = chars[start..end.min(chars.len())].iter().collect();
+                    self.field(chars[i + 1], &value);
+                    i = end + 1;
+                }
+                '[' if chars.get(i + 1).is_some_and(|next| next.is_ascii_digit()) => {
+                    // ending, ie. [1
+                    i += 1;
+                    while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == ',') {
+                        i += 1;
+                    }
+                }
+                '[' if chars.get(i + 1) != Some(&'|') => {
+                    // chord, the length of the first note is used for the chord
+                    i += 1;
+                    let mut pitches: Vec<(Pitch, bool)> = Vec::new();
+                    let mut length = None;
+                    while i < chars.len() && chars[i] != ']' {
+                        if chars[i] == '-' {
+                            if let Some((_, tie)) = pitches.last_mut() {
+                                *tie = true;
+                            }
+                            i += 1;
+                            continue;
+                        }
+                        match self.note(&chars, &mut i) {
+                            Some(pitch) => {
+                                let note_length = read_length(&chars, &mut i)?;
+                                length.get_or_insert(note_length);
+                                pitches.push((pitch, false));
+                            }
+                            None => i += 1,
+                        }
+                    }
+                    i += 1;
+                    let (num, den) = length.unwrap_or((1, 1));
+                    let (multiplier_num, multiplier_den) = read_length(&chars, &mut i)?;
+                    if !pitches.is_empty() {
+                        let length = num
+                            .checked_mul(multiplier_num)
+                            .zip(den.checked_mul(multiplier_den))
+                            .ok_or_else(|| String::from(LENGTH_ERROR))?;
+                        let duration = self.duration(length)?;
+                        self.elements.push(Element::Note(pitches, duration));
+                    }
+                }
+                '|' | ':' | '[' => {
+                    let start = i;
+                    while i < chars.len()
+                        && (matches!(chars[i], '|' | ':' | ']')
+                            || (chars[i] == '[' && chars.get(i + 1) == Some(&'|')))
+                    {
+                        i += 1;
+                    }
+                    let token: String = chars[start..i].iter().collect();
+                    // endings after the barline, ie. :|2
+                    while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == ',') {
+                        i += 1;
+                    }
+                    let barline = if token.contains("::")
+                        || (token.len() > 1 && token.starts_with(':') && token.ends_with(':'))
+                    {
+                        BarlineDrawType::EndStartRepeat
+                    } else if token.starts_with(':') {
+                        BarlineDrawType::EndRepeat
+                    } else if token.ends_with(':') {
+                        BarlineDrawType::StartRepeat
+                    } else if token.contains(']') {
+                        BarlineDrawType::Final
+                    } else if token == "||" || token == "[|" {
+                        BarlineDrawType::Double
+                    } else {
+                        BarlineDrawType::Single
+                    };
+                    self.bar.clear();
+                    self.elements.push(Element::Bar(barline));
+                }
+                'z' | 'x' => {
+                    i += 1;
+                    let length = read_length(&chars, &mut i)?;
+                    let duration = self.duration(length)?;
+                    self.elements.push(Element::Rest(duration));
+                }
+                'Z' | 'X' => {
+                    i += 1;
+                    let (bars, _) = read_length(&chars, &mut i)?;
+                    let bars = u32::try_from(bars).map_err(|_| String::from(LENGTH_ERROR))?;
+                    self.elements.push(Element::BarRest(bars));
+                }
+                _ if DECORATIONS.contains(&char) => i += 1,
+                _ => match self.note(&chars, &mut i) {
+                    Some(pitch) => {
+                        let length = read_length(&chars, &mut i)?;
+                        let duration = self.duration(length)?;
+                        self.elements
+                            .push(Element::Note(vec![(pitch, false)], duration));
+                    }
+                    None => i += 1,
+                },
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Place the elements in time, merging tied notes and finding the pickup bar
+fn resolve(tune: &mut AbcTune, elements: Vec<Element>, subdivisions: Ticks) -> Result<(), String> {
+    let too_long = || String::from("The tune is too long");
+    let mut tick: Tick = 0;
+    let mut meter = TimeSignature::new(
+        0,
+        4,
+        NoteDuration::Quarter,
+        TimeSignatureDrawType::Regular,
+        None,
+    );
+    let mut ties: FxHashMap<u8, usize> = FxHashMap::default();
+    let mut first_bar: Option<Tick> = None;
+
+    for element in elements {
+        match element {
+            Element::Note(pitches, duration) => {
+                let mut next: FxHashMap<u8, usize> = FxHashMap::default();
+                for (pitch, tie) in pitches {
+                    let index = match ties.get(&pitch.int) {
+                        Some(index)
+                            if tune.tones[*index]
+                                .tick
+                                .checked_add(tune.tones[*index].duration)
+                                == Some(tick) =>
+                        {
+                            tune.tones[*index].duration = tune.tones[*index]
+                                .duration
+                                .checked_add(duration)
+                                .ok_or_else(too_long)?;
+                            *index
+                        }
+                        _ => {
+                            tune.tones.push(AbcTone {
+                                tick,
+                                duration,
+                                pitch: pitch.clone(),
+                            });
+                            tune.tones.len() - 1
+                        }
+                    };
+                    if tie {
+                        next.insert(pitch.int, index);
+                    }
+                }
+                ties = next;
+                tick = tick.checked_add(duration).ok_or_else(too_long)?;
+            }
+            Element::Rest(duration) => {
+                ties.clear();
+                tick = tick.checked_add(duration).ok_or_else(too_long)?;
+            }
+            Element::BarRest(bars) => {
+                ties.clear();
+                let ticks_per_bar = match meter.beats {
+                    0 => subdivisions * 4,
+                    _ => meter.ticks_per_bar(subdivisions),
+                };
+                tick = ticks_per_bar
+                    .checked_mul(bars)
+                    .and_then(|ticks| tick.checked_add(ticks))
+                    .ok_or_else(too_long)?;
+            }
+            Element::Bar(barline) => {
+                if tick > 0 && first_bar.is_none() {
+                    first_bar = Some(tick);
+                }
+                if barline != BarlineDrawType::Single {
+                    tune.barlines.retain(|(entry, _)| *entry != tick);
+                    tune.barlines.push((tick, barline));
+                }
+            }
+            Element::Meter(mut time_signature) => {
+                time_signature.tick = tick;
+                tune.time_signatures.retain(|entry| entry.tick != tick);
+                meter = time_signature.clone();
+                tune.time_signatures.push(time_signature);
+            }
+            Element::Key(mut key_signature) => {
+                key_signature.tick = tick;
+                tune.key_signatures.retain(|entry| entry.tick != tick);
+                tune.key_signatures.push(key_signature);
+            }
+        }
+    }
+
+    // a short first bar in the opening meter is an anacrusis
+    if let Some(first_bar) = first_bar {
+        let is_same_meter = tune
+            .time_signatures
+            .iter()
+            .all(|entry| entry.tick == 0 || entry.tick >= first_bar);
+        if let Some(opening) = tune.time_signatures.first_mut() {
+            if opening.beats > 0 && is_same_meter && first_bar < opening.ticks_per_bar(subdivisions)
+            {
+                opening.pickup = first_bar;
+            }
+        }
+    }
+
+    // complete the last bar
+    tune.length = match tune.time_signatures.last() {
+        Some(last) => tick
+            .checked_add(last.fill(tick - last.tick, subdivisions))
+            .ok_or_else(too_long)?,
+        None => tick,
+    };
+
+    Ok(())
+}
+
+/// Parse the first tune of an ABC file. Only the first voice of a multi voice tune is read.
+pub fn parse_abc(input: &str, subdivisions: Ticks) -> Result<AbcTune, String> {
+    let mut tune = AbcTune::default();
+    let mut meter: Option<TimeSignature> = None;
+    let mut unit: Option<(u64, u64)> = None;
+    let mut body: Option<Body> = None;
+    let mut voice: Option<String> = None;
+    let mut is_active = true;
+
+    for line in input.lines() {
+        if line.starts_with("%%") {
+            continue;
+        }
+        let is_blank = line.trim().is_empty();
+        let line = match line.split_once('%') {
+            Some((line, _)) => line,
+            None => line,
+        };
+        let trimmed = line.trim();
+
+        let chars: Vec<char> = trimmed.chars().collect();
+        let field = match chars[..] {
+            [name, ':', ..] if name.is_ascii_alphabetic() => {
+                Some((name, chars[2..].iter().collect::<String>()))
+            }
+            _ => None,
+        };
+
+        match &mut body {
+            None => match field {
+                Some(('T', value)) => {
+                    tune.title.get_or_insert_with(|| String::from(value.trim()));
+                }
+                Some(('C', value)) => {
+                    tune.composer
+                        .get_or_insert_with(|| String::from(value.trim()));
+                }
+                Some(('M', value)) => meter = parse_meter(&value),
+                Some(('L', value)) => unit = parse_unit(&value),
+                Some(('K', value)) => {
+                    let (key, clef) = match parse_key(&value) {
+                        Some(parsed) => parsed,
+                        None => return Err(format!("Unknown key \"{}\"", value.trim())),
+                    };
+                    let meter = meter.take().unwrap_or_else(TimeSignature::default);
+                    let unit = unit.unwrap_or_else(|| default_unit(&meter));
+
+                    let mut new = Body::new(subdivisions * 4, unit);
+                    new.clef = clef;
+                    new.elements.push(Element::Meter(meter));
+                    new.set_key(key);
+                    body = Some(new);
+                }
+                _ => (),
+            },
+            Some(body) => {
+                if is_blank {
+                    // a blank line ends the tune
+                    break;
+                }
+                match field {
+                    Some(('X', _)) => break,
+                    Some(('V', value)) => {
+                        let id = value.split_whitespace().next().unwrap_or("");
+                        let first = voice.get_or_insert_with(|| String::from(id));
+                        is_active = first == id;
+                    }
+                    Some((name, value)) if is_active => body.field(name, &value),
+                    None if is_active => body.line(line)?,
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    let body = match body {
+        Some(body) => body,
+        None => return Err(String::from("The tune has no K: field")),
+    };
+
+    tune.clef = body.clef;
+    resolve(&mut tune, body.elements, subdivisions)?;
+
+    Ok(tune)
+}
+
+#[wasm_bindgen]
+impl Engine {
+    /// Import the first tune of an ABC file as a new flow with a single melodic instrument.
+    /// Returns the new flow key, or nothing if the tune can't be read.
+    pub fn import_abc(&mut self, input: &str) -> Option<String> {
+        let subdivisions = self
+            .score
+            .flows
+            .by_key
+            .values()
+            .next()
+            .map(|flow| flow.subdivisions)
+            .unwrap_or(48);
+
+        let tune = parse_abc(input, subdivisions).ok()?;
+
+        let flow_key = self.create_flow();
+        if let Some(title) = &tune.title {
+            self.rename_flow(&flow_key, title);
+        }
+        let length = match tune.length {
+            0 => subdivisions * 4,
+            length => length,
+        };
+        self.set_flow_length(&flow_key, length);
+
+        // the tune sets the length of the first bar and the last, so insert directly
+        let flow = self.score.flows.by_key.get(&flow_key).unwrap();
+        let master = self.score.tracks.get_mut(&flow.master).unwrap();
+        for time_signature in &tune.time_signatures {
+            if let Some(old) = master.get_time_signature_at_tick(&time_signature.tick) {
+                let key = old.key.clone();
+                master.remove(&key);
+            }
+            master.insert(Entry::TimeSignature(time_signature.clone()));
+        }
+
+        for key_signature in &tune.key_signatures {
+            match &key_signature.custom {
+                Some(pitches) => {
+                    self.create_custom_key_signature(
+                        &flow_key,
+                        key_signature.tick,
+                        pitches.iter().map(|pitch| pitch.int).collect(),
+                        pitches
+                            .iter()
+                            .map(|pitch| pitch.accidental.to_half_steps())
+                            .collect(),
+                    );
+                }
+                None => self.create_key_signature(
+                    &flow_key,
+                    key_signature.tick,
+                    key_signature.mode.clone(),
+                    key_signature.offset,
+                ),
+            }
+        }
+
+        for (tick, barline) in &tune.barlines {
+            self.create_barline(&flow_key, *tick, barline.clone());
+        }
+
+        let id = match tune.clef {
+            Some(AbcClef::Bass) => "strings.violoncello",
+            Some(AbcClef::Alto) => "strings.viola",
+            _ => "strings.violin",
+        };
+        let player_key = self.create_player(PlayerType::Solo);
+        let instrument_key = self.create_instrument(id);
+        self.assign_instrument_to_player(&player_key, &instrument_key);
+
+        let instrument = self.score.instruments.get(&instrument_key).unwrap();
+        let flow = self.score.flows.by_key.get(&flow_key).unwrap();
+        let stave = flow.staves.get(&instrument.staves[0]).unwrap();
+        let track_key = stave.tracks[0].clone();
+
+        for tone in &tune.tones {
+            if tone.duration == 0 {
+                continue;
+            }
+            self.create_tone(
+                &track_key,
+                tone.tick,
+                tone.duration,
+                tone.pitch.int,
+                Some(tone.pitch.accidental),
+                100,
+                Articulation::None,
+            );
+        }
+
+        self.emit();
+
+        Some(flow_key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_abc, parse_key, parse_meter, AbcClef};
+    use crate::components::pitch::Accidental;
+    use crate::entries::barline::BarlineDrawType;
+    use crate::entries::key_signature::KeySignatureMode;
+    use crate::entries::time_signature::TimeSignatureDrawType;
+
+    const TUNE: &str = "X:1
+T:The Kesh
+T:The Kesh Jig
+C:Trad.
+M:6/8
+L:1/8
+K:G
+|:GAG GAB|ABA ABd|edd gdd|edB dBA:|
+";
+
+    #[test]
+    fn parse_key_test() {
+        let (key, clef) = parse_key("G").unwrap();
+        assert_eq!((key.offset, key.mode), (1, KeySignatureMode::Major));
+        assert_eq!(clef, None);
+        let (key, _) = parse_key("F#m").unwrap();
+        assert_eq!((key.offset, key.mode), (3, KeySignatureMode::Minor));
+        let (key, _) = parse_key("D dor").unwrap();
+        assert_eq!((key.offset, key.mode), (0, KeySignatureMode::Dorian));
+        let (key, _) = parse_key("Bbmix").unwrap();
+        assert_eq!((key.offset, key.mode), (-3, KeySignatureMode::Mixolydian));
+        let (key, clef) = parse_key("Amin clef=bass").unwrap();
+        assert_eq!((key.offset, key.mode), (0, KeySignatureMode::Minor));
+        assert_eq!(clef, Some(AbcClef::Bass));
+        let (key, _) = parse_key("D exp ^f _b").unwrap();
+        let custom = key.custom.unwrap();
+        assert_eq!(custom.len(), 2);
+        assert_eq!(custom[1].accidental, Accidental::Flat);
+        assert!(parse_key("Cb#").is_none());
+    }
+
+    #[test]
+    fn parse_meter_test() {
+        let meter = parse_meter("C|").unwrap();
+        assert_eq!((meter.beats, meter.ticks_per_bar(48)), (2, 192));
+        assert_eq!(meter.draw_type, TimeSignatureDrawType::SplitCommonTime);
+        let meter = parse_meter("(2+2+3)/8").unwrap();
+        assert_eq!(meter.ticks_per_bar(48), 168);
+        assert!(meter.is_composite());
+        assert_eq!(parse_meter("none").unwrap().beats, 0);
+    }
+
+    #[test]
+    fn parse_abc_test() {
+        let tune = parse_abc(TUNE, 48).unwrap();
+        assert_eq!(tune.title, Some(String::from("The Kesh")));
+        assert_eq!(tune.composer, Some(String::from("Trad.")));
+        assert_eq!(tune.length, 144 * 4);
+        assert_eq!(tune.tones.len(), 24);
+        assert_eq!((tune.tones[0].pitch.int, tune.tones[0].duration), (67, 24));
+        // d is D5
+        assert_eq!(tune.tones[11].pitch.int, 74);
+        assert_eq!(
+            tune.barlines,
+            vec![
+                (0, BarlineDrawType::StartRepeat),
+                (576, BarlineDrawType::EndRepeat)
+            ]
+        );
+        assert_eq!(tune.key_signatures[0].offset, 1);
+        assert_eq!(tune.time_signatures[0].pickup, 0);
+    }
+
+    #[test]
+    fn parse_abc_notes_test() {
+        let tune = parse_abc(
+            "X:1\nM:4/4\nL:1/4\nK:F\nC | ^c B =B2- | =B/2>c/2 (3def [CEG] | z4 |]\n",
+            48,
+        )
+        .unwrap();
+        let notes: Vec<(u32, u32, u8, Accidental)> = tune
+            .tones
+            .iter()
+            .map(|tone| {
+                (
+                    tone.tick,
+                    tone.duration,
+                    tone.pitch.int,
+                    tone.pitch.accidental,
+                )
+            })
+            .collect();
+        assert_eq!(
+            notes,
+            vec![
+                (0, 48, 60, Accidental::Natural),
+                (48, 48, 73, Accidental::Sharp),
+                (96, 48, 70, Accidental::Flat),
+                (144, 132, 71, Accidental::Natural), // tied over the bar, b is natural until the bar
+                (276, 12, 72, Accidental::Natural),
+                (288, 32, 74, Accidental::Natural),
+                (320, 32, 76, Accidental::Natural),
+                (352, 32, 77, Accidental::Natural),
+                (384, 48, 60, Accidental::Natural),
+                (384, 48, 64, Accidental::Natural),
+                (384, 48, 67, Accidental::Natural),
+            ]
+        );
+        // anacrusis of a quarter
+        assert_eq!(tune.time_signatures[0].pickup, 48);
+        assert_eq!(tune.length, 48 + 192 * 3);
+        assert_eq!(tune.barlines, vec![(624, BarlineDrawType::Final)]);
+    }
+
+    #[test]
+    fn parse_abc_invalid_test() {
+        assert!(parse_abc("X:1\nT:No key\nabc|\n", 48).is_err());
+
+        // absurd lengths are errors rather than overflows
+        for body in [
+            "c99999999999999999",
+            "c99999999999999999999999",
+            "c/4294967296/4294967296/4294967296",
+            "Z99999999999",
+            "Z4294967295",
+            "[CEG]99999999999",
+        ] {
+            let input = format!("X:1\nM:4/4\nL:1/4\nK:C\n{}|\n", body);
+            assert!(parse_abc(&input, 48).is_err(), "{}", body);
+        }
+
+        // extra broken rhythm dots are capped at three
+        let input = format!("X:1\nM:4/4\nL:1/4\nK:C\nc{}d|\n", ">".repeat(70));
+        let tune = parse_abc(&input, 48).unwrap();
+        let durations: Vec<u32> = tune.tones.iter().map(|tone| tone.duration).collect();
+        assert_eq!(durations, vec![90, 6]);
+    }
+}
@@ -0,0 +1,675 @@
+use crate::components::articulation::Articulation;
+use crate::components::duration::NoteDuration;
+use crate::components::misc::{Tick, Ticks};
+use crate::entries::key_signature::KeySignatureMode;
+use crate::entries::time_signature::TimeSignatureDrawType;
+use crate::score::players::PlayerType;
+use crate::Engine;
+use wasm_bindgen::prelude::wasm_bindgen;
+
+const DRUM_CHANNEL: u8 = 9;
+
+/// Longest file accepted, in quarter notes
+const MAX_QUARTERS: u32 = 100_000;
+
+/// Starting positions for the hand separation heuristic
+const RIGHT_HAND: f32 = 67.0;
+const LEFT_HAND: f32 = 48.0;
+const HAND_SPAN: u8 = 14;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SmfNote {
+    pub tick: u32,
+    pub duration: u32,
+    pub pitch: u8,
+    pub velocity: u8,
+    pub channel: u8,
+}
+
+#[derive(Debug, Default)]
+pub struct SmfTrack {
+    pub name: Option<String>,
+    pub programs: Vec<(u32, u8, u8)>, // (tick, channel, program)
+    pub notes: Vec<SmfNote>,
+    pub time_signatures: Vec<(u32, u8, u8)>, // (tick, numerator, denominator)
+    pub key_signatures: Vec<(u32, i8, bool)>, // (tick, sharps/flats, is minor)
+}
+
+#[derive(Debug)]
+pub struct Smf {
+    pub division: u16, // ticks per quarter
+    pub tracks: Vec<SmfTrack>,
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    fn is_done(&self) -> bool {
+        self.position >= self.bytes.len()
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        let byte = *self.bytes.get(self.position)?;
+        self.position += 1;
+        Some(byte)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.position).copied()
+    }
+
+    fn take(&mut self, length: usize) -> Option<&'a [u8]> {
+        let slice = self.bytes.get(self.position..self.position + length)?;
+        self.position += length;
+        Some(slice)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        let bytes = self.take(2)?;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        let bytes = self.take(4)?;
+        Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn variable_length(&mut self) -> Option<u32> {
+        let mut value: u32 = 0;
+        for _ in 0..4 {
+            let byte = self.byte()?;
+            value = (value << 7) | (byte & 0x7F) as u32;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+}
+
+fn parse_track(bytes: &[u8]) -> Result<SmfTrack, String> {
+    let mut reader = Reader::new(bytes);
+    let mut output = SmfTrack::default();
+    let mut open: Vec<(u8, u8, u32, u8)> = Vec::new(); // (channel, pitch, tick, velocity)
+
+    let error = || String::from("Unexpected end of track");
+
+    let mut tick: u32 = 0;
+    let mut running: Option<u8> = None;
+
+    while !reader.is_done() {
+        let delta = reader.variable_length().ok_or_else(error)?;
+        tick = tick
+            .checked_add(delta)
+            .ok_or_else(|| String::from("Track is too long"))?;
+
+        let mut status = reader.peek().ok_or_else(error)?;
+        if status & 0x80 == 0 {
+            status = running.ok_or_else(|| String::from("Missing running status"))?;
+        } else {
+            reader.byte();
+        }
+
+        match status {
+            0xFF => {
+                running = None;
+                let kind = reader.byte().ok_or_else(error)?;
+                let length = reader.variable_length().ok_or_else(error)?;
+                let data = reader.take(length as usize).ok_or_else(error)?;
+                match kind {
+                    0x03 => output.name = Some(String::from_utf8_lossy(data).to_string()),
+                    0x58 if data.len() >= 2 => {
+                        output.time_signatures.push((
+                            tick,
+                            data[0],
+                            1_u8.checked_shl(data[1] as u32).unwrap_or(4),
+                        ));
+                    }
+                    0x59 if data.len() >= 2 => {
+                        output
+                            .key_signatures
+                            .push((tick, data[0] as i8, data[1] == 1));
+                    }
+                    0x2F => break,
+                    _ => (),
+                }
+            }
+            0xF0 | 0xF7 => {
+                running = None;
+                let length = reader.variable_length().ok_or_else(error)?;
+                reader.take(length as usize).ok_or_else(error)?;
+            }
+            _ => {
+                running = Some(status);
+                let channel = status & 0x0F;
+                match status & 0xF0 {
+                    0x80 | 0x90 => {
+                        let pitch = reader.byte().ok_or_else(error)?;
+                        let velocity = reader.byte().ok_or_else(error)?;
+                        if status & 0xF0 == 0x90 && velocity > 0 {
+                            open.push((channel, pitch, tick, velocity));
+                        } else if let Some(i) = open
+                            .iter()
+                            .position(|note| note.0 == channel && note.1 == pitch)
+                        {
+                            let (channel, pitch, start, velocity) = open.remove(i);
+                            output.notes.push(SmfNote {
+                                tick: start,
+                                duration: tick - start,
+                                pitch,
+                                velocity,
+                                channel,
+                            });
+                        }
+                    }
+                    0xC0 => {
+                        let program = reader.byte().ok_or_else(error)?;
+                        output.programs.push((tick, channel, program));
+                    }
+                    0xD0 => {
+                        reader.byte().ok_or_else(error)?;
+                    }
+                    _ => {
+                        reader.take(2).ok_or_else(error)?;
+                    }
+                }
+            }
+        }
+    }
+
+    output.notes.sort_by_key(|note| (note.tick, note.pitch));
+    Ok(output)
+}
+
+/// Parse a Standard MIDI File (type 0 or 1)
+pub fn parse_smf(bytes: &[u8]) -> Result<Smf, String> {
+    let mut reader = Reader::new(bytes);
+
+    if reader.take(4) != Some(b"MThd") {
+        return Err(String::from("Not a MIDI file"));
+    }
+
+    let header = reader.u32().ok_or("Missing header")?;
+    let _format = reader.u16().ok_or("Missing header")?;
+    let count = reader.u16().ok_or("Missing header")?;
+    let division = reader.u16().ok_or("Missing header")?;
+    reader.take(header.saturating_sub(6) as usize);
+
+    if division & 0x8000 != 0 {
+        return Err(String::from("SMPTE timing is not supported"));
+    }
+
+    if division == 0 {
+        return Err(String::from("Invalid division"));
+    }
+
+    let mut tracks = Vec::new();
+    for _ in 0..count {
+        let kind = reader.take(4).ok_or("Missing track")?;
+        let length = reader.u32().ok_or("Missing track")?;
+        let data = reader.take(length as usize).ok_or("Truncated track")?;
+        if kind == b"MTrk" {
+            tracks.push(parse_track(data)?);
+        }
+    }
+
+    Ok(Smf { division, tracks })
+}
+
+/// Convert a file tick into a flow tick snapped to the nearest grid line
+pub fn quantise(tick: u32, division: u16, subdivisions: Ticks, grid: Ticks) -> Tick {
+    let tick = ((tick as f64 * subdivisions as f64) / division as f64).round() as Tick;
+    if grid <= 1 {
+        return tick;
+    }
+    (tick.saturating_add(grid / 2) / grid) * grid
+}
+
+/// Guess an instrument def from a General MIDI program (0 based)
+pub fn def_for_program(program: u8) -> &'static str {
+    match program {
+        8 => "keyboard.celesta",
+        9 | 10 | 14 => "pitched-percussion.glockenspiel",
+        11 => "pitched-percussion.vibraphone",
+        12 => "pitched-percussion.marimba",
+        13 => "pitched-percussion.xylophone",
+        24 | 25 => "guitar.acoustic",
+        26..=31 => "guitar.distortion",
+        32..=39 => "guitar.bass",
+        40 | 44 | 45 | 48..=51 => "strings.violin",
+        41 => "strings.viola",
+        42 => "strings.violoncello",
+        43 => "strings.contrabass",
+        46 => "pitched-percussion.harp",
+        47 => "pitched-percussion.timpani",
+        56 | 59 => "brass.trumpet.b-flat",
+        57 => "brass.trombone",
+        58 => "brass.tuba",
+        60..=63 => "brass.horn.f",
+        64..=67 => "woodwinds.alto-sxophone",
+        68 => "woodwinds.oboe",
+        69 => "woodwinds.english-horn",
+        70 => "woodwinds.bassoon",
+        71 => "woodwinds.clarinet.b-flat",
+        72 => "woodwinds.piccolo",
+        73..=79 => "woodwinds.flute",
+        _ => "keyboard.piano",
+    }
+}
+
+/// Guess an unpitched percussion def from a General MIDI drum note
+pub fn def_for_drum(pitch: u8) -> &'static str {
+    match pitch {
+        35 | 36 => "unpitched-percussion.kick",
+        42 | 44 | 46 => "unpitched-percussion.hi-hat",
+        49 | 52 | 55 | 57 => "unpitched-percussion.crash-cymbal",
+        _ => "unpitched-percussion.snare",
+    }
+}
+
+/// Decide which notes belong to the upper stave. With a split point notes at or above it go
+/// up, otherwise notes are given to the closest hand, chords wider than a hand are split at
+/// their largest gap.
+pub fn split_hands(notes: &[(Tick, u8)], split_point: Option<u8>) -> Vec<bool> {
+    if let Some(split_point) = split_point {
+        return notes
+            .iter()
+            .map(|(_, pitch)| *pitch >= split_point)
+            .collect();
+    }
+
+    let mut output = vec![false; notes.len()];
+    let mut right = RIGHT_HAND;
+    let mut left = LEFT_HAND;
+
+    let mut order: Vec<usize> = (0..notes.len()).collect();
+    order.sort_by_key(|i| notes[*i]);
+
+    for chord in order.chunk_by(|a, b| notes[*a].0 == notes[*b].0) {
+        let pitches: Vec<u8> = chord.iter().map(|i| notes[*i].1).collect();
+        let lowest = pitches[0];
+        let highest = pitches[pitches.len() - 1];
+
+        // index of the first note in the right hand
+        let split = if highest - lowest <= HAND_SPAN {
+            let mean =
+                pitches.iter().map(|pitch| *pitch as f32).sum::<f32>() / pitches.len() as f32;
+            if (mean - right).abs() <= (mean - left).abs() {
+                0
+            } else {
+                pitches.len()
+            }
+        } else {
+            let mut largest = (0, 0);
+            for i in 1..pitches.len() {
+                let gap = pitches[i] - pitches[i - 1];
+                if gap > largest.0 {
+                    largest = (gap, i);
+                }
+            }
+            largest.1
+        };
+
+        for (n, i) in chord.iter().enumerate() {
+            output[*i] = n >= split;
+        }
+
+        if split < pitches.len() {
+            right = (right * 0.5) + (pitches[split] as f32 * 0.5);
+        }
+        if split > 0 {
+            left = (left * 0.5) + (pitches[split - 1] as f32 * 0.5);
+        }
+    }
+
+    output
+}
+
+#[wasm_bindgen]
+impl Engine {
+    /// Import a Standard MIDI File into a new flow. Notes are quantised to the grid (in flow
+    /// ticks) and piano parts are split at the split point, or by hand if none is given.
+    /// Returns the new flow key, or nothing if the file can't be read or is unreasonably long.
+    pub fn import_midi(
+        &mut self,
+        bytes: &[u8],
+        grid: Ticks,
+        split_point: Option<u8>,
+    ) -> Option<String> {
+        let smf = parse_smf(bytes).ok()?;
+
+        let limit = smf.division as u32 * MAX_QUARTERS;
+        let is_too_long = smf.tracks.iter().any(|track| {
+            track
+                .notes
+                .iter()
+                .any(|note| note.tick.saturating_add(note.duration) > limit)
+                || track.time_signatures.iter().any(|entry| entry.0 > limit)
+                || track.key_signatures.iter().any(|entry| entry.0 > limit)
+        });
+        if is_too_long {
+            return None;
+        }
+
+        let flow_key = self.create_flow();
+        let subdivisions = self.get_flow_subdivisions(&flow_key);
+        let division = smf.division;
+        let to_tick = |tick: u32| quantise(tick, division, subdivisions, grid);
+
+        if let Some(name) = smf.tracks.first().and_then(|track| track.name.clone()) {
+            self.rename_flow(&flow_key, &name);
+        }
+
+        // find the flow length before creating time signatures so bars are padded
+        let mut length: Tick = subdivisions * 4;
+        for track in &smf.tracks {
+            for note in &track.notes {
+                length = length.max(to_tick(note.tick + note.duration));
+            }
+        }
+        self.set_flow_length(&flow_key, length);
+
+        // master track
+        let mut time_signatures: Vec<(Tick, u8, u8)> = Vec::new();
+        let mut key_signatures: Vec<(Tick, i8, bool)> = Vec::new();
+        for track in &smf.tracks {
+            for (tick, beats, beat_type) in &track.time_signatures {
+                time_signatures.push((to_tick(*tick), *beats, *beat_type));
+            }
+            for (tick, offset, minor) in &track.key_signatures {
+                key_signatures.push((to_tick(*tick), *offset, *minor));
+            }
+        }
+        time_signatures.sort_by_key(|entry| entry.0);
+        key_signatures.sort_by_key(|entry| entry.0);
+
+        for (tick, beats, beat_type) in time_signatures {
+            if let Some(beat_type) = NoteDuration::from_denominator(beat_type) {
+                self.create_time_signature(
+                    &flow_key,
+                    tick,
+                    beats,
+                    beat_type,
+                    TimeSignatureDrawType::Regular,
+                    None,
+                );
+            }
+        }
+
+        if !key_signatures.iter().any(|(tick, _, _)| *tick == 0) {
+            self.create_key_signature(&flow_key, 0, KeySignatureMode::Major, 0);
+        }
+        for (tick, offset, minor) in key_signatures {
+            let mode = match minor {
+                true => KeySignatureMode::Minor,
+                false => KeySignatureMode::Major,
+            };
+            self.create_key_signature(&flow_key, tick, mode, offset.clamp(-7, 7));
+        }
+
+        // group the notes into instruments by track and channel (or drum sound)
+        let mut parts: Vec<(&'static str, Vec<&SmfNote>)> = Vec::new();
+        for track in &smf.tracks {
+            let mut channels: Vec<u8> = track.notes.iter().map(|note| note.channel).collect();
+            channels.sort_unstable();
+            channels.dedup();
+
+            for channel in channels {
+                let notes = track.notes.iter().filter(|note| note.channel == channel);
+                if channel == DRUM_CHANNEL {
+                    for note in notes {
+                        let id = def_for_drum(note.pitch);
+                        match parts.iter_mut().find(|(def, _)| *def == id) {
+                            Some((_, notes)) => notes.push(note),
+                            None => parts.push((id, vec![note])),
+                        }
+                    }
+                } else {
+                    let program = track
+                        .programs
+                        .iter()
+                        .filter(|(_, program_channel, _)| *program_channel == channel)
+                        .map(|(_, _, program)| *program)
+                        .next()
+                        .unwrap_or(0);
+                    parts.push((def_for_program(program), notes.collect()));
+                }
+            }
+        }
+
+        for (id, notes) in parts {
+            let player_key = self.create_player(PlayerType::Solo);
+            let instrument_key = self.create_instrument(id);
+            self.assign_instrument_to_player(&player_key, &instrument_key);
+
+            let instrument = self.score.instruments.get(&instrument_key).unwrap();
+            let flow = self.score.flows.by_key.get(&flow_key).unwrap();
+            let track_keys: Vec<String> = instrument
+                .staves
+                .iter()
+                .map(|stave_key| flow.staves.get(stave_key).unwrap().tracks[0].clone())
+                .collect();
+
+            let ticks: Vec<(Tick, u8)> = notes
+                .iter()
+                .map(|note| (to_tick(note.tick), note.pitch))
+                .collect();
+            let upper = match track_keys.len() {
+                1 => vec![true; notes.len()],
+                _ => split_hands(&ticks, split_point),
+            };
+
+            for (i, note) in notes.iter().enumerate() {
+                let tick = ticks[i].0;
+                let end = to_tick(note.tick + note.duration);
+                let duration = end.saturating_sub(tick).max(grid.max(1));
+                let track_key = match upper[i] {
+                    true => &track_keys[0],
+                    false => &track_keys[track_keys.len() - 1],
+                };
+                self.create_tone(
+                    track_key,
+                    tick,
+                    duration,
+                    note.pitch,
+                    None,
+                    note.velocity,
+                    Articulation::None,
+                );
+            }
+        }
+
+        self.emit();
+
+        Some(flow_key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_smf, quantise, split_hands, SmfNote};
+    use crate::entries::key_signature::KeySignatureMode;
+    use crate::entries::Entry;
+    use crate::Engine;
+
+    /// A type 1 file at 480 ppq from its track data
+    fn smf(tracks: &[&[u8]]) -> Vec<u8> {
+        let mut bytes = vec![b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, 1, 0];
+        bytes.push(tracks.len() as u8);
+        bytes.extend_from_slice(&480_u16.to_be_bytes());
+        for track in tracks {
+            bytes.extend_from_slice(b"MTrk");
+            bytes.extend_from_slice(&(track.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(track);
+        }
+        bytes
+    }
+
+    #[test]
+    fn parse_smf_test() {
+        let bytes: Vec<u8> = vec![
+            b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, 1, 0, 1, 0, 96, // header
+            b'M', b'T', b'r', b'k', 0, 0, 0, 29, // track
+            0x00, 0xFF, 0x58, 0x04, 3, 2, 24, 8, // 3/4
+            0x00, 0xC0, 40, // violin
+            0x00, 0x90, 60, 100, // note on
+            0x60, 62, 90, // running status note on
+            0x00, 60, 0, // note off as velocity 0
+            0x60, 0x80, 62, 0, // note off
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+        let smf = parse_smf(&bytes).unwrap();
+        assert_eq!(smf.division, 96);
+        let track = &smf.tracks[0];
+        assert_eq!(track.time_signatures, vec![(0, 3, 4)]);
+        assert_eq!(track.programs, vec![(0, 0, 40)]);
+        assert_eq!(
+            track.notes,
+            vec![
+                SmfNote {
+                    tick: 0,
+                    duration: 96,
+                    pitch: 60,
+                    velocity: 100,
+                    channel: 0
+                },
+                SmfNote {
+                    tick: 96,
+                    duration: 96,
+                    pitch: 62,
+                    velocity: 90,
+                    channel: 0
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_smf_invalid_test() {
+        assert!(parse_smf(b"RIFF").is_err());
+
+        let header = [b'M', b'T', b'h', b'd', 0, 0, 0, 6, 0, 1, 0, 1];
+        let mut bytes = header.to_vec();
+        bytes.extend_from_slice(&[0, 0]); // no ticks per quarter
+        assert_eq!(parse_smf(&bytes).unwrap_err(), "Invalid division");
+
+        // deltas adding up to more than u32::MAX
+        let mut track: Vec<u8> = Vec::new();
+        for _ in 0..17 {
+            track.extend_from_slice(&[0xFF, 0xFF, 0xFF, 0x7F, 0xFF, 0x01, 0x00]);
+        }
+        let mut bytes = header.to_vec();
+        bytes.extend_from_slice(&[0, 96, b'M', b'T', b'r', b'k']);
+        bytes.extend_from_slice(&(track.len() as u32).to_be_bytes());
+        bytes.extend(track);
+        assert_eq!(parse_smf(&bytes).unwrap_err(), "Track is too long");
+    }
+
+    #[test]
+    fn import_midi_test() {
+        let conductor: &[u8] = &[
+            0x00, 0xFF, 0x03, 0x04, b'S', b'o', b'n', b'g', // name
+            0x00, 0xFF, 0x58, 0x04, 3, 2, 24, 8, // 3/4
+            0x00, 0xFF, 0x59, 0x02, 0xFF, 0x01, // d minor
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+        let piano: &[u8] = &[
+            0x00, 0xC0, 0, // piano
+            0x00, 0x90, 72, 100, // c5 and c3 together
+            0x00, 0x90, 48, 100, //
+            0x83, 0x56, 0x80, 72, 0, // released slightly early at 470
+            0x00, 0x80, 48, 0, //
+            0x14, 0x90, 76, 90, // e5 slightly late at 490
+            0x83, 0x60, 0x80, 76, 0, //
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+
+        let mut engine = Engine::tester();
+        let flow_key = engine
+            .import_midi(&smf(&[conductor, piano]), 12, None)
+            .unwrap();
+        let flow = &engine.score.flows.by_key[&flow_key];
+        assert_eq!(flow.title, "Song");
+
+        assert_eq!(engine.score.players.order.len(), 1);
+        assert_eq!(engine.score.instruments.len(), 1);
+        let instrument = engine.score.instruments.values().next().unwrap();
+        assert_eq!(instrument.id, "keyboard.piano");
+        assert_eq!(instrument.staves.len(), 2);
+
+        let master = &engine.score.tracks[&flow.master];
+        let time_signature = master.get_time_signature_at_tick(&0).unwrap();
+        assert_eq!(time_signature.beats, 3);
+        let key_signature = master.get_key_signature_at_tick(&0).unwrap();
+        assert_eq!(
+            (key_signature.mode.clone(), key_signature.offset),
+            (KeySignatureMode::Minor, -1)
+        );
+
+        // the chord is split between the hands and everything is snapped to sixteenths
+        let tones = |stave: usize| {
+            let stave_key = &instrument.staves[stave];
+            let track_key = &flow.staves[stave_key].tracks[0];
+            let mut tones: Vec<(u32, u32, u8)> = engine.score.tracks[track_key]
+                .entries
+                .by_key
+                .values()
+                .filter_map(|entry| match entry {
+                    Entry::Tone(tone) => Some((tone.tick, tone.duration, tone.pitch.int)),
+                    _ => None,
+                })
+                .collect();
+            tones.sort_unstable();
+            tones
+        };
+        assert_eq!(tones(0), vec![(0, 48, 72), (48, 48, 76)]);
+        assert_eq!(tones(1), vec![(0, 48, 48)]);
+    }
+
+    #[test]
+    fn import_midi_too_long_test() {
+        // a single note at a division of one lasting millions of quarters
+        let mut bytes = smf(&[&[
+            0x00, 0x90, 60, 100, //
+            0xFF, 0xFF, 0xFF, 0x7F, 0x80, 60, 0, //
+            0x00, 0xFF, 0x2F, 0x00,
+        ]]);
+        bytes[12..14].copy_from_slice(&1_u16.to_be_bytes());
+
+        let mut engine = Engine::tester();
+        let flows = engine.score.flows.order.len();
+        assert!(engine.import_midi(&bytes, 12, None).is_none());
+        assert_eq!(engine.score.flows.order.len(), flows);
+        assert_eq!(quantise(0x0FFF_FFFF, 1, 48, 12), 4_294_967_292);
+    }
+
+    #[test]
+    fn quantise_test() {
+        // 480 ppq into 48 subdivisions on a sixteenth grid
+        assert_eq!(quantise(0, 480, 48, 12), 0);
+        assert_eq!(quantise(470, 480, 48, 12), 48);
+        assert_eq!(quantise(130, 480, 48, 12), 12);
+        assert_eq!(quantise(130, 480, 48, 1), 13);
+    }
+
+    #[test]
+    fn split_hands_test() {
+        let notes = vec![(0, 72), (0, 40), (48, 64), (48, 67), (96, 43)];
+        assert_eq!(
+            split_hands(&notes, Some(60)),
+            vec![true, false, true, true, false]
+        );
+        // the chord at 0 is split, the third at 48 goes to the right hand
+        assert_eq!(
+            split_hands(&notes, None),
+            vec![true, false, true, true, false]
+        );
+    }
+}
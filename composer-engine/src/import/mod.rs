@@ -0,0 +1,3 @@
+pub mod abc;
+pub mod midi;
+pub mod musicxml;
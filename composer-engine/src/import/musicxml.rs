@@ -0,0 +1,1322 @@
+use crate::components::articulation::Articulation;
+use crate::components::interval::Interval;
+use crate::components::misc::{Tick, Ticks};
+use crate::components::pitch::{Accidental, Pitch};
+use crate::entries::barline::BarlineDrawType;
+use crate::entries::clef::ClefDrawType;
+use crate::entries::key_signature::KeySignatureMode;
+use crate::entries::time_signature::{TimeSignature, TimeSignatureDrawType};
+use crate::entries::Entry;
+use crate::score::instruments::defs::INSTRUMENT_DEFS;
+use crate::score::players::PlayerType;
+use crate::score::tracks::Track;
+use crate::utils::shortid;
+use crate::Engine;
+use roxmltree::{Document, Node, ParsingOptions};
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+/// MusicXML dynamics are a percentage of forte, which is velocity 90
+const FORTE: f32 = 90.0;
+const DEFAULT_VELOCITY: u8 = 80;
+
+const STEPS: [&str; 7] = ["C", "D", "E", "F", "G", "A", "B"];
+
+/// Words that don't help tell instruments apart
+const STOP_WORDS: [&str; 14] = [
+    "in",
+    "the",
+    "wind",
+    "winds",
+    "woodwinds",
+    "reed",
+    "flutes",
+    "strings",
+    "brass",
+    "keyboard",
+    "grand",
+    "pitched",
+    "unpitched",
+    "percussion",
+];
+
+/// Common names for instruments that are named differently in the defs
+const ALIASES: [(&str, &str); 4] = [
+    ("double bass", "contrabass"),
+    ("cello", "violoncello"),
+    ("french horn", "horn"),
+    ("cor anglais", "english horn"),
+];
+
+/// Something in the file that couldn't be imported, it was skipped
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ImportWarning {
+    pub part: Option<String>,
+    pub measure: Option<String>,
+    pub element: String,
+    pub message: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Warnings {
+    pub entries: Vec<ImportWarning>,
+    seen: FxHashSet<(Option<String>, String)>,
+}
+
+impl Warnings {
+    /// Record a warning, only the first of each element in a part is kept
+    pub fn push(
+        &mut self,
+        part: Option<&str>,
+        measure: Option<&str>,
+        element: &str,
+        message: &str,
+    ) {
+        let part = part.map(String::from);
+        if self.seen.insert((part.clone(), String::from(element))) {
+            self.entries.push(ImportWarning {
+                part,
+                measure: measure.map(String::from),
+                element: String::from(element),
+                message: String::from(message),
+            });
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum XmlKey {
+    Standard(i8, KeySignatureMode),
+    Custom(Vec<Pitch>),
+}
+
+#[derive(Debug)]
+pub struct XmlClef {
+    pub tick: Tick,
+    pub staff: usize,
+    pub draw_as: ClefDrawType,
+    pub pitch: u8,
+    pub offset: i8,
+    pub octave: i8,
+}
+
+#[derive(Debug)]
+pub struct XmlNote {
+    pub tick: Tick,
+    pub duration: Ticks,
+    pub pitch: Option<Pitch>, // None for rests
+    pub voice: String,
+    pub staff: usize,
+    pub tie_start: bool,
+    pub tie_stop: bool,
+    pub velocity: u8,
+    pub articulation: Articulation,
+}
+
+/// A measure of a part, ticks are relative to the start of the measure
+#[derive(Debug, Default)]
+pub struct XmlMeasure {
+    pub number: String,
+    pub implicit: bool,
+    pub length: Ticks,
+    pub time: Option<TimeSignature>,
+    pub keys: Vec<(Tick, XmlKey)>,
+    pub clefs: Vec<XmlClef>,
+    pub barlines: Vec<(bool, BarlineDrawType)>, // (is left, type)
+    pub notes: Vec<XmlNote>,
+}
+
+#[derive(Debug)]
+pub struct XmlPart {
+    pub id: String,
+    pub names: Vec<String>, // part name, instrument name and sound id
+    pub staves: usize,
+    pub transpose: Interval, // written to sounding
+    pub measures: Vec<XmlMeasure>,
+}
+
+#[derive(Debug, Default)]
+pub struct XmlScore {
+    pub title: Option<String>,
+    pub movement: Option<String>,
+    pub composer: Option<String>,
+    pub parts: Vec<XmlPart>,
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name(name))
+}
+
+fn child_text<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    child(node, name)
+        .and_then(|child| child.text())
+        .map(|text| text.trim())
+}
+
+fn child_number<T: std::str::FromStr>(node: Node, name: &str) -> Option<T> {
+    child_text(node, name)?.parse().ok()
+}
+
+/// Convert a MusicXML duration into flow ticks
+fn to_ticks(duration: f64, divisions: f64, subdivisions: Ticks) -> Ticks {
+    ((duration * subdivisions as f64) / divisions)
+        .round()
+        .max(0.0) as Ticks
+}
+
+/// A pitch from its step, alteration (which may be microtonal) and octave
+pub fn to_pitch(step: &str, alter: f32, octave: i16) -> Option<Pitch> {
+    // MIDI pitches run from C-1 to G9
+    if !(-1..=9).contains(&octave) {
+        return None;
+    }
+    let index = STEPS.iter().position(|entry| *entry == step)?;
+    let natural = Pitch::natural(octave * 7 + index as i16);
+    let half_steps = alter.trunc() as i16;
+    let cents = ((alter - alter.trunc()) * 100.0).round() as i8;
+    let accidental = Accidental::from_parts(half_steps, cents, false)?;
+    let int = natural.int as i16 + half_steps;
+    if !(0..=127).contains(&int) {
+        return None;
+    }
+    Some(Pitch::new(int as u8, accidental))
+}
+
+fn to_mode(mode: Option<&str>) -> KeySignatureMode {
+    match mode {
+        Some("minor") | Some("aeolian") => KeySignatureMode::Minor,
+        Some("dorian") => KeySignatureMode::Dorian,
+        Some("phrygian") => KeySignatureMode::Phrygian,
+        Some("lydian") => KeySignatureMode::Lydian,
+        Some("mixolydian") => KeySignatureMode::Mixolydian,
+        Some("locrian") => KeySignatureMode::Locrian,
+        _ => KeySignatureMode::Major,
+    }
+}
+
+/// Break a name into comparable words, ie. "Trumpet in Bb" -> ["trumpet", "bflat"]
+fn tokens(input: &str) -> Vec<String> {
+    let words = input
+        .to_lowercase()
+        .replace("${flat}", "b")
+        .replace('♭', "b")
+        .replace(
+            |char: char| !char.is_alphanumeric() && char != '#' && char != '♯',
+            " ",
+        );
+    let mut input = format!(
+        " {} ",
+        words.split_whitespace().collect::<Vec<&str>>().join(" ")
+    );
+    for (alias, name) in ALIASES {
+        input = input.replace(&format!(" {} ", alias), &format!(" {} ", name));
+    }
+
+    input
+        .split_whitespace()
+        .filter(|token| !token.is_empty() && !STOP_WORDS.contains(token))
+        .map(|token| {
+            let mut chars = token.chars();
+            match (chars.next(), chars.as_str()) {
+                (Some(letter @ 'a'..='g'), "b" | "flat") => format!("{}flat", letter),
+                (Some(letter @ 'a'..='g'), "#" | "♯" | "sharp") => format!("{}sharp", letter),
+                _ => String::from(token),
+            }
+        })
+        .collect()
+}
+
+/// Find the instrument def that best matches the names (and sound id) of a part
+pub fn match_instrument(names: &[String]) -> Option<&'static str> {
+    let words: Vec<String> = names.iter().flat_map(|name| tokens(name)).collect();
+
+    let mut best: Option<(&'static str, usize, usize)> = None; // (id, matches, size)
+    for def in INSTRUMENT_DEFS.iter() {
+        let (_, path) = def.id.split_once('.').unwrap_or(("", def.id));
+        let mut candidates = tokens(&path.replace("-flat", "flat"));
+        candidates.extend(tokens(def.long_name));
+        candidates.sort();
+        candidates.dedup();
+
+        let matches = candidates
+            .iter()
+            .filter(|token| words.contains(token))
+            .count();
+        if matches == 0 {
+            continue;
+        }
+
+        let is_better = match best {
+            None => true,
+            Some((_, best_matches, best_size)) => {
+                matches > best_matches || (matches == best_matches && candidates.len() < best_size)
+            }
+        };
+        if is_better {
+            best = Some((def.id, matches, candidates.len()));
+        }
+    }
+
+    best.map(|(id, _, _)| id)
+}
+
+fn parse_time(node: Node) -> Option<TimeSignature> {
+    if child(node, "senza-misura").is_some() {
+        return None;
+    }
+
+    let mut fractions: Vec<String> = Vec::new();
+    let mut interchangeable: Vec<String> = Vec::new();
+    for (target, parent) in [
+        (&mut fractions, Some(node)),
+        (&mut interchangeable, child(node, "interchangeable")),
+    ] {
+        if let Some(parent) = parent {
+            let beats = parent
+                .children()
+                .filter(|child| child.has_tag_name("beats"));
+            let beat_types = parent
+                .children()
+                .filter(|child| child.has_tag_name("beat-type"));
+            for (beats, beat_type) in beats.zip(beat_types) {
+                target.push(format!(
+                    "{}/{}",
+                    beats.text()?.trim(),
+                    beat_type.text()?.trim()
+                ));
+            }
+        }
+    }
+
+    let mut input = fractions.join("+");
+    if !interchangeable.is_empty() {
+        input = format!("{} ({})", input, interchangeable.join("+"));
+    }
+
+    let mut time_signature = TimeSignature::parse(0, &input)?;
+    time_signature.draw_type = match node.attribute("symbol") {
+        _ if node.attribute("print-object") == Some("no") => TimeSignatureDrawType::Hidden,
+        Some("common") if input == "4/4" => TimeSignatureDrawType::CommonTime,
+        Some("cut") if input == "2/2" => TimeSignatureDrawType::SplitCommonTime,
+        _ => TimeSignatureDrawType::Regular,
+    };
+    Some(time_signature)
+}
+
+fn parse_key(node: Node, warnings: &mut Warnings, part: &str, measure: &str) -> Option<XmlKey> {
+    if let Some(fifths) = child_number::<i32>(node, "fifths") {
+        if !(-7..=7).contains(&fifths) {
+            warnings.push(
+                Some(part),
+                Some(measure),
+                "fifths",
+                "Key signatures are limited to 7 sharps or flats",
+            );
+        }
+        return Some(XmlKey::Standard(
+            fifths.clamp(-7, 7) as i8,
+            to_mode(child_text(node, "mode")),
+        ));
+    }
+
+    let steps = node
+        .children()
+        .filter(|child| child.has_tag_name("key-step"));
+    let alters = node
+        .children()
+        .filter(|child| child.has_tag_name("key-alter"));
+    let mut pitches = Vec::new();
+    for (step, alter) in steps.zip(alters) {
+        let alter: f32 = alter.text()?.trim().parse().ok()?;
+        pitches.push(to_pitch(step.text()?.trim(), alter, 4)?);
+    }
+    Some(XmlKey::Custom(pitches))
+}
+
+fn parse_clef(node: Node, tick: Tick) -> Option<XmlClef> {
+    // clefs sit on the lines of a 5 line stave and shift by at most a few octaves
+    let line: i8 = child_number::<i8>(node, "line").unwrap_or(0).clamp(0, 5);
+    let octave: i8 = child_number::<i8>(node, "clef-octave-change")
+        .unwrap_or(0)
+        .clamp(-3, 3);
+    let (draw_as, pitch, line) = match child_text(node, "sign")? {
+        "G" => (ClefDrawType::G, 67, if line == 0 { 2 } else { line }),
+        "F" => (ClefDrawType::F, 53, if line == 0 { 4 } else { line }),
+        "C" => (ClefDrawType::C, 60, if line == 0 { 3 } else { line }),
+        "percussion" => (ClefDrawType::Percussion, 60, 3),
+        "none" => (ClefDrawType::Hidden, 67, 2),
+        _ => return None,
+    };
+    Some(XmlClef {
+        tick,
+        staff: node
+            .attribute("number")
+            .and_then(|n| n.parse().ok())
+            .unwrap_or(1),
+        draw_as,
+        pitch: (pitch + octave * 12) as u8,
+        offset: (3 - line) * 2,
+        octave,
+    })
+}
+
+fn parse_barline(node: Node) -> Result<(bool, BarlineDrawType), String> {
+    let left = node.attribute("location") == Some("left");
+    let repeat = child(node, "repeat").and_then(|repeat| repeat.attribute("direction"));
+    let style = child_text(node, "bar-style").unwrap_or("regular");
+    match (repeat, style) {
+        (Some("forward"), _) => Ok((left, BarlineDrawType::StartRepeat)),
+        (Some(_), _) => Ok((left, BarlineDrawType::EndRepeat)),
+        (None, "regular") => Ok((left, BarlineDrawType::Single)),
+        (None, "light-light") => Ok((left, BarlineDrawType::Double)),
+        (None, "light-heavy") => Ok((left, BarlineDrawType::Final)),
+        (None, style) => Err(format!("Bar style '{}' is not supported", style)),
+    }
+}
+
+/// The pitch of a note, None for a rest or nothing if it can't be read
+fn parse_note_pitch(node: Node) -> Option<Option<Pitch>> {
+    if child(node, "rest").is_some() {
+        return Some(None);
+    }
+    if let Some(pitch) = child(node, "pitch") {
+        let alter: f32 = child_number(pitch, "alter").unwrap_or(0.0);
+        let octave: i16 = child_number(pitch, "octave")?;
+        return to_pitch(child_text(pitch, "step")?, alter, octave).map(Some);
+    }
+    if let Some(unpitched) = child(node, "unpitched") {
+        let octave: i16 = child_number(unpitched, "display-octave").unwrap_or(4);
+        let step = child_text(unpitched, "display-step").unwrap_or("C");
+        return to_pitch(step, 0.0, octave).map(Some);
+    }
+    None
+}
+
+fn parse_articulation(
+    node: Node,
+    warnings: &mut Warnings,
+    part: &str,
+    measure: &str,
+) -> Articulation {
+    let mut staccato = false;
+    let mut staccatissimo = false;
+    let mut tenuto = false;
+
+    for notations in node
+        .children()
+        .filter(|child| child.has_tag_name("notations"))
+    {
+        for notation in notations.children().filter(|child| child.is_element()) {
+            match notation.tag_name().name() {
+                "tied" => (),
+                "articulations" => {
+                    for articulation in notation.children().filter(|child| child.is_element()) {
+                        match articulation.tag_name().name() {
+                            "staccato" => staccato = true,
+                            "staccatissimo" | "spiccato" => staccatissimo = true,
+                            "tenuto" => tenuto = true,
+                            "detached-legato" => {
+                                staccato = true;
+                                tenuto = true;
+                            }
+                            name => warnings.push(
+                                Some(part),
+                                Some(measure),
+                                name,
+                                "Articulation is not supported",
+                            ),
+                        }
+                    }
+                }
+                name => warnings.push(Some(part), Some(measure), name, "Notation is not supported"),
+            }
+        }
+    }
+
+    match (staccato, staccatissimo, tenuto) {
+        (_, true, _) => Articulation::Staccatissimo,
+        (true, _, true) => Articulation::StaccatoTenuto,
+        (true, _, _) => Articulation::Staccato,
+        (_, _, true) => Articulation::Tenuto,
+        _ => Articulation::None,
+    }
+}
+
+fn parse_measure(
+    node: Node,
+    part: &mut XmlPart,
+    divisions: &mut f64,
+    subdivisions: Ticks,
+    warnings: &mut Warnings,
+) -> XmlMeasure {
+    let mut measure = XmlMeasure {
+        number: String::from(node.attribute("number").unwrap_or("")),
+        implicit: node.attribute("implicit") == Some("yes"),
+        ..XmlMeasure::default()
+    };
+    let number = measure.number.clone();
+    let id = part.id.clone();
+
+    let mut position: Tick = 0;
+    let mut chord_position: Tick = 0;
+
+    for element in node.children().filter(|child| child.is_element()) {
+        match element.tag_name().name() {
+            "attributes" => {
+                for attribute in element.children().filter(|child| child.is_element()) {
+                    match attribute.tag_name().name() {
+                        "divisions" => {
+                            match attribute
+                                .text()
+                                .and_then(|text| text.trim().parse::<f64>().ok())
+                            {
+                                Some(value) if value > 0.0 && value.is_finite() => {
+                                    *divisions = value
+                                }
+                                _ => warnings.push(
+                                    Some(&id),
+                                    Some(&number),
+                                    "divisions",
+                                    "Divisions must be a positive number",
+                                ),
+                            }
+                        }
+                        "key" => match parse_key(attribute, warnings, &id, &number) {
+                            Some(key) => measure.keys.push((position, key)),
+                            None => warnings.push(
+                                Some(&id),
+                                Some(&number),
+                                "key",
+                                "Key signature could not be read",
+                            ),
+                        },
+                        "time" => match parse_time(attribute) {
+                            Some(time) => measure.time = Some(time),
+                            None => warnings.push(
+                                Some(&id),
+                                Some(&number),
+                                "time",
+                                "Time signature is not supported",
+                            ),
+                        },
+                        "staves" => {
+                            if let Some(staves) =
+                                attribute.text().and_then(|text| text.trim().parse().ok())
+                            {
+                                part.staves = staves;
+                            }
+                        }
+                        "clef" => match parse_clef(attribute, position) {
+                            Some(clef) => measure.clefs.push(clef),
+                            None => warnings.push(
+                                Some(&id),
+                                Some(&number),
+                                "clef",
+                                "Clef sign is not supported",
+                            ),
+                        },
+                        "transpose" => {
+                            let diatonic: i8 = child_number(attribute, "diatonic").unwrap_or(0);
+                            let chromatic: f32 =
+                                child_number(attribute, "chromatic").unwrap_or(0.0);
+                            let octave: i8 = child_number(attribute, "octave-change").unwrap_or(0);
+                            let chromatic = chromatic.round();
+                            let steps = octave
+                                .checked_mul(7)
+                                .and_then(|steps| steps.checked_add(diatonic));
+                            let semitones =
+                                if (i8::MIN as f32..=i8::MAX as f32).contains(&chromatic) {
+                                    octave.checked_mul(12).and_then(|semitones| {
+                                        semitones.checked_add(chromatic as i8)
+                                    })
+                                } else {
+                                    None
+                                };
+                            match (steps, semitones) {
+                                (Some(steps), Some(semitones)) => {
+                                    part.transpose = Interval::new(steps, semitones)
+                                }
+                                _ => warnings.push(
+                                    Some(&id),
+                                    Some(&number),
+                                    "transpose",
+                                    "Transposition is out of range",
+                                ),
+                            }
+                        }
+                        name => warnings.push(
+                            Some(&id),
+                            Some(&number),
+                            name,
+                            "Attribute is not supported",
+                        ),
+                    }
+                }
+            }
+            "note" => {
+                let duration: f64 = child_number(element, "duration").unwrap_or(0.0);
+                let duration = to_ticks(duration, *divisions, subdivisions);
+
+                if child(element, "grace").is_some() {
+                    warnings.push(
+                        Some(&id),
+                        Some(&number),
+                        "grace",
+                        "Grace notes are not supported",
+                    );
+                    continue;
+                }
+                if child(element, "cue").is_some() {
+                    warnings.push(
+                        Some(&id),
+                        Some(&number),
+                        "cue",
+                        "Cue notes are not supported",
+                    );
+                    continue;
+                }
+                if child(element, "time-modification").is_some() {
+                    warnings.push(
+                        Some(&id),
+                        Some(&number),
+                        "time-modification",
+                        "Tuplets are imported at their played duration",
+                    );
+                }
+                if child(element, "lyric").is_some() {
+                    warnings.push(
+                        Some(&id),
+                        Some(&number),
+                        "lyric",
+                        "Lyrics are not supported",
+                    );
+                }
+
+                let tick = match child(element, "chord") {
+                    Some(_) => chord_position,
+                    None => {
+                        chord_position = position;
+                        position = position.saturating_add(duration);
+                        chord_position
+                    }
+                };
+
+                let pitch = match parse_note_pitch(element) {
+                    Some(pitch) => pitch,
+                    None => {
+                        warnings.push(Some(&id), Some(&number), "note", "Pitch could not be read");
+                        continue;
+                    }
+                };
+
+                let ties = element.children().filter(|child| child.has_tag_name("tie"));
+                let mut tie_start = false;
+                let mut tie_stop = false;
+                for tie in ties {
+                    match tie.attribute("type") {
+                        Some("start") => tie_start = true,
+                        Some("stop") => tie_stop = true,
+                        _ => (),
+                    }
+                }
+
+                let velocity = match element
+                    .attribute("dynamics")
+                    .and_then(|value| value.parse::<f32>().ok())
+                {
+                    Some(dynamics) => ((dynamics / 100.0) * FORTE).round().clamp(1.0, 127.0) as u8,
+                    None => DEFAULT_VELOCITY,
+                };
+
+                measure.notes.push(XmlNote {
+                    tick,
+                    duration,
+                    pitch,
+                    voice: String::from(child_text(element, "voice").unwrap_or("1")),
+                    staff: child_number(element, "staff").unwrap_or(1),
+                    tie_start,
+                    tie_stop,
+                    velocity,
+                    articulation: parse_articulation(element, warnings, &id, &number),
+                });
+            }
+            "backup" => {
+                let duration: f64 = child_number(element, "duration").unwrap_or(0.0);
+                position = position.saturating_sub(to_ticks(duration, *divisions, subdivisions));
+            }
+            "forward" => {
+                let duration: f64 = child_number(element, "duration").unwrap_or(0.0);
+                position = position.saturating_add(to_ticks(duration, *divisions, subdivisions));
+            }
+            "barline" => {
+                if child(element, "ending").is_some() {
+                    warnings.push(
+                        Some(&id),
+                        Some(&number),
+                        "ending",
+                        "Repeat endings are not supported",
+                    );
+                }
+                match parse_barline(element) {
+                    Ok(barline) => measure.barlines.push(barline),
+                    Err(message) => warnings.push(Some(&id), Some(&number), "bar-style", &message),
+                }
+            }
+            name => warnings.push(Some(&id), Some(&number), name, "Element is not supported"),
+        }
+
+        measure.length = measure.length.max(position);
+    }
+
+    measure
+}
+
+/// Parse a partwise MusicXML document, ticks are converted to the given subdivisions
+pub fn parse_musicxml(
+    xml: &str,
+    subdivisions: Ticks,
+    warnings: &mut Warnings,
+) -> Result<XmlScore, String> {
+    let options = ParsingOptions {
+        allow_dtd: true,
+        ..ParsingOptions::default()
+    };
+    let document = Document::parse_with_options(xml, options).map_err(|error| error.to_string())?;
+    let root = document.root_element();
+
+    if root.has_tag_name("score-timewise") {
+        return Err(String::from("Timewise MusicXML is not supported"));
+    }
+    if !root.has_tag_name("score-partwise") {
+        return Err(String::from("Not a MusicXML file"));
+    }
+
+    let mut output = XmlScore {
+        title: child(root, "work")
+            .and_then(|work| child_text(work, "work-title"))
+            .map(String::from),
+        movement: child_text(root, "movement-title").map(String::from),
+        composer: child(root, "identification").and_then(|identification| {
+            identification
+                .children()
+                .find(|child| {
+                    child.has_tag_name("creator") && child.attribute("type") == Some("composer")
+                })
+                .and_then(|creator| creator.text())
+                .map(String::from)
+        }),
+        parts: Vec::new(),
+    };
+
+    let mut names: FxHashMap<String, Vec<String>> = FxHashMap::default();
+    if let Some(part_list) = child(root, "part-list") {
+        for element in part_list.children().filter(|child| child.is_element()) {
+            match element.tag_name().name() {
+                "score-part" => {
+                    let id = String::from(element.attribute("id").unwrap_or(""));
+                    let mut entries = Vec::new();
+                    for name in ["part-name", "part-abbreviation"] {
+                        if let Some(name) = child_text(element, name) {
+                            entries.push(String::from(name));
+                        }
+                    }
+                    for instrument in element
+                        .children()
+                        .filter(|child| child.has_tag_name("score-instrument"))
+                    {
+                        for name in ["instrument-name", "instrument-sound"] {
+                            if let Some(name) = child_text(instrument, name) {
+                                entries.push(String::from(name));
+                            }
+                        }
+                    }
+                    names.insert(id, entries);
+                }
+                name => warnings.push(None, None, name, "Part list element is not supported"),
+            }
+        }
+    }
+
+    for node in root.children().filter(|child| child.has_tag_name("part")) {
+        let id = String::from(node.attribute("id").unwrap_or(""));
+        let mut part = XmlPart {
+            names: names.remove(&id).unwrap_or_default(),
+            id,
+            staves: 1,
+            transpose: Interval::UNISON,
+            measures: Vec::new(),
+        };
+
+        let mut divisions: f64 = 1.0;
+        for measure in node
+            .children()
+            .filter(|child| child.has_tag_name("measure"))
+        {
+            let measure = parse_measure(measure, &mut part, &mut divisions, subdivisions, warnings);
+            part.measures.push(measure);
+        }
+
+        output.parts.push(part);
+    }
+
+    Ok(output)
+}
+
+impl Engine {
+    fn build_musicxml(&mut self, flow_key: &str, score: &XmlScore, warnings: &mut Warnings) {
+        let subdivisions = self.get_flow_subdivisions(flow_key);
+
+        if let Some(title) = &score.title {
+            self.set_title(title.clone());
+        }
+        if let Some(movement) = &score.movement {
+            self.rename_flow(flow_key, movement);
+        }
+        if let Some(composer) = &score.composer {
+            self.set_composer(composer.clone());
+        }
+
+        // measures are lined up by position, the longest part sets the length of each
+        let count = score
+            .parts
+            .iter()
+            .map(|part| part.measures.len())
+            .max()
+            .unwrap_or(0);
+        let mut starts: Vec<Tick> = Vec::with_capacity(count);
+        let mut lengths: Vec<Ticks> = Vec::with_capacity(count);
+        let mut meter = TimeSignature::parse(0, "4/4").unwrap();
+        let mut length: Tick = 0;
+        for i in 0..count {
+            if let Some(time) = score
+                .parts
+                .first()
+                .and_then(|part| part.measures.get(i)?.time.as_ref())
+            {
+                meter = time.clone();
+            }
+            let longest = score
+                .parts
+                .iter()
+                .filter_map(|part| part.measures.get(i))
+                .map(|measure| measure.length)
+                .max()
+                .unwrap_or(0);
+            let bar = if longest == 0 {
+                meter.ticks_per_bar(subdivisions)
+            } else {
+                longest
+            };
+            starts.push(length);
+            lengths.push(bar);
+            length = length.saturating_add(bar);
+        }
+        self.set_flow_length(flow_key, length.max(subdivisions * 4));
+
+        // the first part holds the meter, keys and barlines for the flow
+        if let Some(first) = score.parts.first() {
+            let flow = self.score.flows.by_key.get(flow_key).unwrap();
+            let master_key = flow.master.clone();
+            let mut current = TimeSignature::parse(0, "4/4").unwrap();
+
+            for (i, measure) in first.measures.iter().enumerate() {
+                let start = starts[i];
+                let master = self.score.tracks.get_mut(&master_key).unwrap();
+
+                let new = match &measure.time {
+                    Some(time) => {
+                        current = time.clone();
+                        Some(time.clone())
+                    }
+                    None if lengths[i] != current.ticks_per_bar(subdivisions) => {
+                        let mut hidden = current.clone();
+                        hidden.draw_type = TimeSignatureDrawType::Hidden;
+                        Some(hidden)
+                    }
+                    None => None,
+                };
+                if let Some(mut new) = new {
+                    new.key = shortid();
+                    new.tick = start;
+                    if lengths[i] != new.ticks_per_bar(subdivisions) {
+                        new.pickup = lengths[i];
+                    }
+                    if let Some(old) = master.get_time_signature_at_tick(&start) {
+                        let key = old.key.clone();
+                        master.remove(&key);
+                    }
+                    master.insert(Entry::TimeSignature(new));
+                }
+
+                for (tick, key) in &measure.keys {
+                    let tick = match start.checked_add(*tick) {
+                        Some(tick) => tick,
+                        None => {
+                            warnings.push(
+                                Some(&first.id),
+                                None,
+                                "key",
+                                "Key signature is beyond the end of the flow",
+                            );
+                            continue;
+                        }
+                    };
+                    match key {
+                        XmlKey::Standard(fifths, mode) => {
+                            let offset = fifths
+                                .checked_add(first.transpose.fifths())
+                                .unwrap_or(*fifths);
+                            self.create_key_signature(
+                                flow_key,
+                                tick,
+                                mode.clone(),
+                                offset.clamp(-7, 7),
+                            );
+                        }
+                        XmlKey::Custom(pitches) => {
+                            let pitches: Vec<Pitch> = pitches
+                                .iter()
+                                .map(|pitch| pitch.transpose(&first.transpose))
+                                .collect();
+                            self.create_custom_key_signature(
+                                flow_key,
+                                tick,
+                                pitches.iter().map(|pitch| pitch.int).collect(),
+                                pitches
+                                    .iter()
+                                    .map(|pitch| pitch.accidental.to_half_steps())
+                                    .collect(),
+                            );
+                        }
+                    }
+                }
+
+                for (left, barline) in &measure.barlines {
+                    let tick = if *left {
+                        start
+                    } else {
+                        match start.checked_add(lengths[i]) {
+                            Some(tick) => tick,
+                            None => continue,
+                        }
+                    };
+                    let master = self.score.tracks.get(&master_key).unwrap();
+                    let existing = master
+                        .get_barline_at_tick(&tick)
+                        .map(|entry| entry.barline_type.clone());
+                    let barline = match (existing, barline) {
+                        (Some(BarlineDrawType::EndRepeat), BarlineDrawType::StartRepeat)
+                        | (Some(BarlineDrawType::StartRepeat), BarlineDrawType::EndRepeat) => {
+                            BarlineDrawType::EndStartRepeat
+                        }
+                        (_, BarlineDrawType::Single) => continue,
+                        (_, barline) => barline.clone(),
+                    };
+                    self.create_barline(flow_key, tick, barline);
+                }
+            }
+        }
+
+        for part in &score.parts {
+            let id = match match_instrument(&part.names) {
+                Some(id) => id,
+                None => {
+                    warnings.push(
+                        Some(&part.id),
+                        None,
+                        "score-part",
+                        "No matching instrument was found, a piano was used instead",
+                    );
+                    "keyboard.piano"
+                }
+            };
+
+            let player_key = self.create_player(PlayerType::Solo);
+            let instrument_key = self.create_instrument(id);
+            self.assign_instrument_to_player(&player_key, &instrument_key);
+
+            let stave_keys = self
+                .score
+                .instruments
+                .get(&instrument_key)
+                .unwrap()
+                .staves
+                .clone();
+            if part.staves > stave_keys.len() {
+                warnings.push(
+                    Some(&part.id),
+                    None,
+                    "staves",
+                    "The part has more staves than the instrument, extra staves were merged into the last",
+                );
+            }
+            let stave_at = |staff: usize| &stave_keys[(staff.max(1) - 1).min(stave_keys.len() - 1)];
+
+            // a track for each voice of each stave, in the order they are first used
+            let mut voices: Vec<(String, String, String)> = Vec::new(); // (stave, voice, track)
+            let mut tones: Vec<(String, Tick, Ticks, Pitch, u8, Articulation)> = Vec::new();
+            let mut ties: FxHashMap<(String, u8), usize> = FxHashMap::default();
+
+            for (i, measure) in part.measures.iter().enumerate() {
+                let start = match starts.get(i) {
+                    Some(start) => *start,
+                    None => break,
+                };
+
+                for clef in &measure.clefs {
+                    if clef.staff > stave_keys.len() {
+                        continue;
+                    }
+                    let tick = match start.checked_add(clef.tick) {
+                        Some(tick) => tick,
+                        None => {
+                            warnings.push(
+                                Some(&part.id),
+                                None,
+                                "clef",
+                                "Clef is beyond the end of the flow",
+                            );
+                            continue;
+                        }
+                    };
+                    self.create_clef(
+                        flow_key,
+                        stave_at(clef.staff),
+                        tick,
+                        clef.pitch,
+                        clef.offset,
+                        clef.draw_as.clone(),
+                        clef.octave,
+                    );
+                }
+
+                for note in &measure.notes {
+                    let pitch = match &note.pitch {
+                        Some(pitch) => pitch.transpose(&part.transpose),
+                        None => continue,
+                    };
+
+                    let stave_key = stave_at(note.staff);
+                    let track_key = match voices
+                        .iter()
+                        .find(|(stave, voice, _)| stave == stave_key && *voice == note.voice)
+                    {
+                        Some((_, _, track_key)) => track_key.clone(),
+                        None => {
+                            let flow = self.score.flows.by_key.get_mut(flow_key).unwrap();
+                            let stave = flow.staves.get_mut(stave_key).unwrap();
+                            let used = voices
+                                .iter()
+                                .filter(|(stave, _, _)| stave == stave_key)
+                                .count();
+                            let track_key = match stave.tracks.get(used) {
+                                Some(track_key) => track_key.clone(),
+                                None => {
+                                    let track = Track::new();
+                                    let track_key = track.key.clone();
+                                    stave.tracks.push(track_key.clone());
+                                    self.score.tracks.insert(track_key.clone(), track);
+                                    track_key
+                                }
+                            };
+                            voices.push((stave_key.clone(), note.voice.clone(), track_key.clone()));
+                            track_key
+                        }
+                    };
+
+                    let tick = match start.checked_add(note.tick) {
+                        Some(tick) => tick,
+                        None => {
+                            warnings.push(
+                                Some(&part.id),
+                                None,
+                                "note",
+                                "Note is beyond the end of the flow",
+                            );
+                            continue;
+                        }
+                    };
+                    let tied = ties.remove(&(track_key.clone(), pitch.int));
+                    let index = match tied {
+                        Some(index)
+                            if note.tie_stop
+                                && tones[index].1.checked_add(tones[index].2) == Some(tick) =>
+                        {
+                            tones[index].2 = tones[index].2.saturating_add(note.duration);
+                            index
+                        }
+                        _ => {
+                            tones.push((
+                                track_key.clone(),
+                                tick,
+                                note.duration,
+                                pitch.clone(),
+                                note.velocity,
+                                note.articulation.clone(),
+                            ));
+                            tones.len() - 1
+                        }
+                    };
+                    if note.tie_start {
+                        ties.insert((track_key, pitch.int), index);
+                    }
+                }
+            }
+
+            for (track_key, tick, duration, pitch, velocity, articulation) in tones {
+                if duration == 0 {
+                    continue;
+                }
+                self.create_tone(
+                    &track_key,
+                    tick,
+                    duration,
+                    pitch.int,
+                    Some(pitch.accidental),
+                    velocity,
+                    articulation,
+                );
+            }
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Engine {
+    /// Replace the score with a partwise MusicXML document.
+    /// Returns a list of warnings for anything in the file that couldn't be imported,
+    /// if the file can't be read at all the score is left unchanged.
+    pub fn import_musicxml(&mut self, xml: &str) -> JsValue {
+        let mut warnings = Warnings::default();
+        let subdivisions = self
+            .score
+            .flows
+            .by_key
+            .values()
+            .next()
+            .map(|flow| flow.subdivisions)
+            .unwrap_or(48);
+
+        match parse_musicxml(xml, subdivisions, &mut warnings) {
+            Ok(score) => {
+                let flow_key = self.reset_score();
+                self.build_musicxml(&flow_key, &score, &mut warnings);
+                self.emit();
+            }
+            Err(message) => warnings.push(None, None, "score-partwise", &message),
+        }
+
+        serde_wasm_bindgen::to_value(&warnings.entries).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{match_instrument, parse_clef, parse_musicxml, to_pitch, Warnings, XmlKey};
+    use crate::components::pitch::Accidental;
+    use crate::entries::barline::BarlineDrawType;
+    use crate::entries::key_signature::KeySignatureMode;
+    use crate::Engine;
+    use roxmltree::Document;
+
+    const XML: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="no"?>
+<!DOCTYPE score-partwise PUBLIC "-//Recordare//DTD MusicXML 4.0 Partwise//EN" "http://www.musicxml.org/dtds/partwise.dtd">
+<score-partwise version="4.0">
+  <work><work-title>Sonata</work-title></work>
+  <part-list>
+    <score-part id="P1">
+      <part-name>Clarinet in Bb</part-name>
+      <score-instrument id="P1-I1"><instrument-sound>wind.reed.clarinet.bflat</instrument-sound></score-instrument>
+    </score-part>
+  </part-list>
+  <part id="P1">
+    <measure number="0" implicit="yes">
+      <attributes>
+        <divisions>2</divisions>
+        <key><fifths>2</fifths><mode>minor</mode></key>
+        <time><beats>3</beats><beat-type>4</beat-type></time>
+        <clef><sign>G</sign><line>2</line></clef>
+        <transpose><diatonic>-1</diatonic><chromatic>-2</chromatic></transpose>
+      </attributes>
+      <note><pitch><step>F</step><alter>1</alter><octave>4</octave></pitch><duration>2</duration><tie type="start"/><voice>1</voice></note>
+    </measure>
+    <measure number="1">
+      <direction><direction-type><words>dolce</words></direction-type></direction>
+      <note><pitch><step>F</step><alter>1</alter><octave>4</octave></pitch><duration>2</duration><tie type="stop"/><voice>1</voice></note>
+      <note><chord/><pitch><step>A</step><octave>4</octave></pitch><duration>2</duration><voice>1</voice></note>
+      <note><rest/><duration>4</duration><voice>1</voice></note>
+      <backup><duration>6</duration></backup>
+      <note><pitch><step>D</step><octave>4</octave></pitch><duration>6</duration><voice>2</voice></note>
+      <barline location="right"><bar-style>light-heavy</bar-style></barline>
+    </measure>
+  </part>
+</score-partwise>"#;
+
+    #[test]
+    fn to_pitch_test() {
+        let pitch = to_pitch("C", 1.0, 4).unwrap();
+        assert_eq!((pitch.int, pitch.accidental), (61, Accidental::Sharp));
+        let pitch = to_pitch("D", -1.0, 4).unwrap();
+        assert_eq!((pitch.int, pitch.accidental), (61, Accidental::Flat));
+        let pitch = to_pitch("B", 1.0, 3).unwrap();
+        assert_eq!((pitch.int, pitch.accidental), (60, Accidental::Sharp));
+        let pitch = to_pitch("E", -1.5, 4).unwrap();
+        assert_eq!(
+            (pitch.int, pitch.accidental),
+            (63, Accidental::ThreeQuarterFlat)
+        );
+        assert!(to_pitch("H", 0.0, 4).is_none());
+        assert!(to_pitch("C", 0.0, 10).is_none());
+        assert!(to_pitch("C", 0.0, i16::MAX).is_none());
+    }
+
+    #[test]
+    fn parse_clef_test() {
+        let clef = |xml: &str| {
+            let document = Document::parse(xml).unwrap();
+            parse_clef(document.root_element(), 0).unwrap()
+        };
+
+        let treble_8vb = clef(
+            "<clef><sign>G</sign><line>2</line><clef-octave-change>-1</clef-octave-change></clef>",
+        );
+        assert_eq!((treble_8vb.pitch, treble_8vb.offset), (55, 2));
+
+        // out of range values are clamped rather than overflowing
+        let extreme =
+            clef("<clef><sign>F</sign><line>127</line><clef-octave-change>127</clef-octave-change></clef>");
+        assert_eq!((extreme.pitch, extreme.offset, extreme.octave), (89, -4, 3));
+    }
+
+    #[test]
+    fn match_instrument_test() {
+        let names = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| String::from(*name))
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(
+            match_instrument(&names(&["Violin I"])),
+            Some("strings.violin")
+        );
+        assert_eq!(
+            match_instrument(&names(&["Cello"])),
+            Some("strings.violoncello")
+        );
+        assert_eq!(
+            match_instrument(&names(&["Flute"])),
+            Some("woodwinds.flute")
+        );
+        assert_eq!(
+            match_instrument(&names(&["Alto Flute"])),
+            Some("woodwinds.alto-flute")
+        );
+        assert_eq!(
+            match_instrument(&names(&["Trumpet in Bb"])),
+            Some("brass.trumpet.b-flat")
+        );
+        assert_eq!(
+            match_instrument(&names(&["Clar.", "wind.reed.clarinet.bflat"])),
+            Some("woodwinds.clarinet.b-flat")
+        );
+        assert_eq!(match_instrument(&names(&["Theremin"])), None);
+    }
+
+    #[test]
+    fn parse_musicxml_test() {
+        let mut warnings = Warnings::default();
+        let score = parse_musicxml(XML, 48, &mut warnings).unwrap();
+
+        assert_eq!(score.title, Some(String::from("Sonata")));
+        assert_eq!(score.parts.len(), 1);
+
+        let part = &score.parts[0];
+        assert_eq!(part.transpose.steps, -1);
+        assert_eq!(part.transpose.semitones, -2);
+        assert_eq!(part.measures.len(), 2);
+
+        let pickup = &part.measures[0];
+        assert!(pickup.implicit);
+        assert_eq!(pickup.length, 48);
+        assert_eq!(
+            pickup.keys,
+            vec![(0, XmlKey::Standard(2, KeySignatureMode::Minor))]
+        );
+        assert_eq!(pickup.time.as_ref().unwrap().beats, 3);
+        assert!(pickup.notes[0].tie_start);
+
+        let measure = &part.measures[1];
+        assert_eq!(measure.length, 144);
+        assert_eq!(measure.notes.len(), 4);
+        assert_eq!(measure.notes[1].tick, 0); // chord
+        assert_eq!(measure.notes[2].tick, 48);
+        assert!(measure.notes[2].pitch.is_none());
+        assert_eq!(measure.notes[3].tick, 0);
+        assert_eq!(measure.notes[3].voice, "2");
+        assert_eq!(measure.barlines, vec![(false, BarlineDrawType::Final)]);
+
+        assert_eq!(warnings.entries.len(), 1);
+        assert_eq!(warnings.entries[0].element, "direction");
+        assert_eq!(warnings.entries[0].measure, Some(String::from("1")));
+    }
+
+    #[test]
+    fn parse_musicxml_divisions_test() {
+        let xml = XML.replace("<divisions>2</divisions>", "<divisions>0</divisions>");
+        let mut warnings = Warnings::default();
+        let score = parse_musicxml(&xml, 48, &mut warnings).unwrap();
+
+        // the default of 1 division per quarter is kept
+        assert_eq!(score.parts[0].measures[0].length, 96);
+        assert!(warnings
+            .entries
+            .iter()
+            .any(|warning| warning.element == "divisions"));
+    }
+
+    #[test]
+    fn parse_musicxml_range_test() {
+        let xml = XML
+            .replace("<fifths>2</fifths>", "<fifths>127</fifths>")
+            .replace(
+                "<diatonic>-1</diatonic>",
+                "<diatonic>-1</diatonic><octave-change>20</octave-change>",
+            );
+        let mut warnings = Warnings::default();
+        let score = parse_musicxml(&xml, 48, &mut warnings).unwrap();
+
+        // the key is clamped and the transposition dropped, both with a warning
+        assert_eq!(
+            score.parts[0].measures[0].keys[0].1,
+            XmlKey::Standard(7, KeySignatureMode::Minor)
+        );
+        assert!(score.parts[0].transpose.is_unison());
+        for element in ["fifths", "transpose"] {
+            assert!(warnings
+                .entries
+                .iter()
+                .any(|warning| warning.element == element));
+        }
+
+        // a clamped key on a transposing part is still written within range
+        let xml = XML.replace("<fifths>2</fifths>", "<fifths>127</fifths>");
+        let score = parse_musicxml(&xml, 48, &mut warnings).unwrap();
+        let mut engine = Engine::tester();
+        let flow_key = engine.score.flows.order[0].clone();
+        engine.build_musicxml(&flow_key, &score, &mut warnings);
+        let master_key = engine.score.flows.by_key[&flow_key].master.clone();
+        let key_signature = engine.score.tracks[&master_key]
+            .get_key_signature_at_tick(&0)
+            .unwrap();
+        assert_eq!(key_signature.offset, 5);
+    }
+
+    #[test]
+    fn parse_musicxml_error_test() {
+        let mut warnings = Warnings::default();
+        assert!(parse_musicxml("<score-timewise/>", 48, &mut warnings).is_err());
+        assert!(parse_musicxml("not xml", 48, &mut warnings).is_err());
+    }
+}
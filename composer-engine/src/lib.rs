@@ -1,5 +1,7 @@
 pub mod components;
 pub mod entries;
+pub mod export;
+pub mod import;
 pub mod parse;
 pub mod score;
 pub mod utils;
@@ -7,6 +9,7 @@ pub mod utils;
 use crate::utils::log;
 use js_sys::Function;
 use score::engrave::LayoutType;
+use score::migrations::{self, ImportError, ImportErrorKind};
 use score::Score;
 use wasm_bindgen::prelude::*;
 
@@ -31,9 +34,7 @@ impl Engine {
             score: Score::new(),
         };
 
-        let key = engine.create_flow();
-        engine.create_engrave(LayoutType::Score, "Score");
-        engine.create_engrave(LayoutType::Part, "Part");
+        let key = engine.reset_score();
 
         log(&key);
 
@@ -49,9 +50,26 @@ impl Engine {
         serde_wasm_bindgen::to_value(&self.score).unwrap()
     }
 
-    pub fn import(&mut self, state: JsValue) {
-        self.score = serde_wasm_bindgen::from_value(state).unwrap();
-        self.emit();
+    /// Replace the score with a saved document, older format versions are migrated.
+    /// Throws an `ImportError` if the document can't be read, the score is left unchanged.
+    pub fn import(&mut self, state: JsValue) -> Result<(), JsValue> {
+        let result = match migrations::from_js(&state) {
+            Some(document) => migrations::parse(document),
+            None => Err(ImportError {
+                kind: ImportErrorKind::Unreadable,
+                version: None,
+                message: String::from("the document contains values that can't be read"),
+            }),
+        };
+
+        match result {
+            Ok(score) => {
+                self.score = score;
+                self.emit();
+                Ok(())
+            }
+            Err(error) => Err(serde_wasm_bindgen::to_value(&error).unwrap()),
+        }
     }
 
     #[wasm_bindgen(getter)]
@@ -67,6 +85,15 @@ impl Default for Engine {
 }
 
 impl Engine {
+    /// Replace the score with an empty one containing a single flow, returns the flow key
+    fn reset_score(&mut self) -> String {
+        self.score = Score::new();
+        let key = self.create_flow();
+        self.create_engrave(LayoutType::Score, "Score");
+        self.create_engrave(LayoutType::Part, "Part");
+        key
+    }
+
     fn emit(&self) {
         match &self.listener {
             Some(listener) => {
@@ -78,6 +105,19 @@ impl Engine {
     }
 }
 
+#[cfg(test)]
+impl Engine {
+    /// An engine with a new score for native tests, nothing is logged to the console
+    pub fn tester() -> Self {
+        let mut engine = Self {
+            listener: None,
+            score: Score::new(),
+        };
+        engine.reset_score();
+        engine
+    }
+}
+
 #[wasm_bindgen(start)]
 pub fn run() {
     utils::set_panic_hook();
@@ -8,19 +8,26 @@ use super::Instruction;
 use crate::components::text::{Align, Justify};
 use crate::components::units::{Converter, Space};
 use crate::entries::tone::Tone;
+use crate::score::engrave::Engrave;
 use crate::score::stave::Stave;
 
+#[allow(clippy::too_many_arguments)]
 fn draw_accidental(
     x: Space,
     y: Space,
     accidental: &AccidentalNotation,
     tone: &Tone,
     tone_offsets: &ToneVerticalOffsets,
+    parentheses: bool,
     converter: &Converter,
     instructions: &mut Vec<Instruction>,
 ) {
     let left = x - ((accidental.slot as f32 - 1.0) * 1.1);
-    let glyph = tone.pitch.accidental.to_glyph();
+    let glyph = if accidental.cautionary && parentheses {
+        format!("\u{E26A}{}\u{E26B}", accidental.accidental.to_glyph())
+    } else {
+        accidental.accidental.to_glyph()
+    };
     let offset = tone_offsets.get(&tone.key).unwrap();
     let top = y + (*offset as f32 / 2.0);
 
@@ -47,6 +54,7 @@ pub fn draw_accidentals(
     tone_offsets: &ToneVerticalOffsets,
     shunts_by_track: &ShuntsByTrack,
     accidentals_by_track: &AccidentalsByTrack,
+    engrave: &Engrave,
     converter: &Converter,
     instructions: &mut Vec<Instruction>,
 ) {
@@ -76,6 +84,7 @@ pub fn draw_accidentals(
                             accidental,
                             tone,
                             tone_offsets,
+                            engrave.cautionary_accidentals.parentheses,
                             converter,
                             instructions,
                         );
@@ -15,10 +15,10 @@ pub fn draw_braces(
     instructions: &mut Vec<Instruction>,
 ) {
     for brace in &vertical_spans.braces {
-        let top = vertical_spacing.staves.get(&brace.start).unwrap();
-        let bottom = vertical_spacing.staves.get(&brace.stop).unwrap();
-        let height = (bottom.y + bottom.height) - top.y;
-        let top = y + (bottom.y + bottom.height / 2.0);
+        let (top, _) = vertical_spacing.stave_extent(&brace.start).unwrap();
+        let (bottom_top, bottom) = vertical_spacing.stave_extent(&brace.stop).unwrap();
+        let height = bottom - top;
+        let top = y + (bottom_top + bottom) / 2.0;
 
         instructions.push(Instruction::Text {
             x: converter.spaces_to_px(x - 0.25),
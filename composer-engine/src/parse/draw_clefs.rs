@@ -12,6 +12,7 @@ fn draw_clef(
     x: Space,
     y: Space,
     clef: &Clef,
+    is_initial: bool,
     converter: &Converter,
     instructions: &mut Vec<Instruction>,
 ) {
@@ -22,7 +23,7 @@ fn draw_clef(
             value: glyph,
             color: String::from("#000"),
             font: String::from("Bravura"),
-            size: converter.spaces_to_px(4.0),
+            size: converter.spaces_to_px(4.0 * clef.scale(is_initial)),
             justify: Justify::Start.as_string(),
             align: Align::Middle.as_string(),
         })
@@ -47,7 +48,15 @@ pub fn draw_clefs(
         for entry in stave_master.entries.by_key.values() {
             if let Entry::Clef(clef) = entry {
                 let left = horizontal_spacing.get(&clef.tick, &Position::Clef).unwrap();
-                draw_clef(x + left.x, y + top.y, clef, converter, instructions)
+                let is_initial = stave_master.is_initial_clef(clef);
+                draw_clef(
+                    x + left.x,
+                    y + top.y,
+                    clef,
+                    is_initial,
+                    converter,
+                    instructions,
+                )
             }
         }
     }
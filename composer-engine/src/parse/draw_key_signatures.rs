@@ -1,3 +1,4 @@
+use super::get_transpositions::{get_transposition, Transpositions};
 use super::measure_horizontal_spacing::{HorizontalSpacing, Position};
 use super::measure_vertical_spacing::VerticalSpacing;
 use super::Instruction;
@@ -5,39 +6,58 @@ use crate::components::pitch::Accidental;
 use crate::components::text::{Align, Justify};
 use crate::components::units::{Converter, Space};
 use crate::entries::clef::{Clef, ClefDrawType};
-use crate::entries::key_signature::KeySignature;
+use crate::entries::key_signature::{KeySignature, CANCELLATION_GAP};
+use crate::score::engrave::{Engrave, KeySignatureCancellation};
 use crate::score::flows::Flow;
 use crate::score::stave::Stave;
 use crate::score::tracks::Tracks;
 
+fn draw_accidentals(
+    x: Space,
+    y: Space,
+    positions: &[(Accidental, i8)],
+    converter: &Converter,
+    instructions: &mut Vec<Instruction>,
+) {
+    for (i, (accidental, offset)) in positions.iter().enumerate() {
+        instructions.push(Instruction::Text {
+            x: converter.spaces_to_px(x + (i as f32)),
+            y: converter.spaces_to_px(y + 0.5 * *offset as f32),
+            value: accidental.to_glyph(),
+            color: String::from("#000"),
+            font: String::from("Bravura"),
+            size: converter.spaces_to_px(4.0),
+            justify: Justify::Start.as_string(),
+            align: Align::Middle.as_string(),
+        })
+    }
+}
+
+/// Draw the key signature preceded by any naturals needed to cancel the previous key
+#[allow(clippy::too_many_arguments)]
 fn draw_key_signature(
     x: Space,
     y: Space,
     clef: &Clef,
     key_signature: &KeySignature,
+    previous: Option<&KeySignature>,
+    cancellation: &KeySignatureCancellation,
     converter: &Converter,
     instructions: &mut Vec<Instruction>,
-    negate: bool,
 ) {
-    let glyph = if negate {
-        Accidental::Natural.to_glyph()
+    let naturals = match previous {
+        Some(previous) => key_signature.cancellation_positions(previous, cancellation, clef),
+        None => Vec::new(),
+    };
+    draw_accidentals(x, y, &naturals, converter, instructions);
+
+    let x = if naturals.is_empty() {
+        x
     } else {
-        key_signature.glyph()
+        x + naturals.len() as f32 + CANCELLATION_GAP
     };
-    if let Some(pattern) = key_signature.pattern(clef) {
-        for i in 0..key_signature.offset.abs() {
-            instructions.push(Instruction::Text {
-                x: converter.spaces_to_px(x + (i as f32)),
-                y: converter.spaces_to_px(y + 0.5 * pattern[i as usize] as f32),
-                value: glyph.clone(),
-                color: String::from("#000"),
-                font: String::from("Bravura"),
-                size: converter.spaces_to_px(4.0),
-                justify: Justify::Start.as_string(),
-                align: Align::Middle.as_string(),
-            })
-        }
-    }
+    let positions = key_signature.positions(clef);
+    draw_accidentals(x, y, &positions, converter, instructions);
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -47,8 +67,10 @@ pub fn draw_key_signatures(
     flow: &Flow,
     staves: &Vec<&Stave>,
     tracks: &Tracks,
+    transpositions: &Transpositions,
     vertical_spacing: &VerticalSpacing,
     horizontal_spacing: &HorizontalSpacing,
+    engrave: &Engrave,
     converter: &Converter,
     instructions: &mut Vec<Instruction>,
 ) {
@@ -57,6 +79,7 @@ pub fn draw_key_signatures(
     for stave in staves {
         let stave_master = tracks.get(&stave.master).unwrap();
         let top = vertical_spacing.staves.get(&stave.key).unwrap();
+        let transposition = get_transposition(&stave.key, transpositions);
         let mut clef = &Clef::new(0, 60, 0, ClefDrawType::C);
 
         for tick in 0..flow.length {
@@ -65,29 +88,172 @@ pub fn draw_key_signatures(
             };
 
             if let Some(key_signature) = flow_master.get_key_signature_at_tick(&tick) {
-                let is_offset_zero = key_signature.offset == 0;
-                let left = horizontal_spacing
-                    .get(&tick, &Position::KeySignature)
-                    .unwrap();
-
-                let key_signature = if is_offset_zero {
-                    flow_master.get_key_signature_before_tick(tick)
-                } else {
-                    Some(key_signature)
-                };
-
-                if let Some(key_signature) = key_signature {
+                let key_signature = key_signature.transpose(transposition);
+                let previous = flow_master
+                    .get_key_signature_before_tick(tick)
+                    .map(|previous| previous.transpose(transposition));
+
+                let mut positions = vec![Position::KeySignature];
+                if engrave.courtesy_signatures && tick > 0 {
+                    positions.push(Position::Courtesy);
+                }
+
+                for position in positions {
+                    let left = horizontal_spacing.get(&tick, &position).unwrap();
                     draw_key_signature(
                         x + left.x,
                         y + top.y,
                         clef,
-                        key_signature,
+                        &key_signature,
+                        previous.as_ref(),
+                        &engrave.key_signature_cancellation,
                         converter,
                         instructions,
-                        is_offset_zero,
-                    )
+                    );
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::draw_key_signatures;
+    use crate::components::pitch::Accidental;
+    use crate::components::units::Converter;
+    use crate::entries::key_signature::KeySignatureMode;
+    use crate::parse::get_accidentals::get_accidentals;
+    use crate::parse::get_barlines::get_barlines;
+    use crate::parse::get_bars::get_bars;
+    use crate::parse::get_beams::get_beams;
+    use crate::parse::get_shunts::get_note_shunts;
+    use crate::parse::get_stem_directions::get_stem_directions;
+    use crate::parse::get_tone_offsets::get_tone_offsets;
+    use crate::parse::get_transpositions::get_transpositions;
+    use crate::parse::get_written_durations::get_written_durations;
+    use crate::parse::measure_horizontal_spacing::measure_horizontal_spacing;
+    use crate::parse::measure_vertical_spacing::measure_vertical_spacing;
+    use crate::parse::Instruction;
+    use crate::score::engrave::{KeySignatureCancellation, LayoutType};
+    use crate::score::players::PlayerType;
+    use crate::Engine;
+
+    /// D major, then F major in bar 2, then C major in bar 3
+    fn engine(
+        cancellation: KeySignatureCancellation,
+        courtesy_signatures: bool,
+    ) -> (Engine, String) {
+        let mut engine = Engine::tester();
+        let flow_key = engine.score.flows.order[0].clone();
+        let player_key = engine.create_player(PlayerType::Solo);
+        let instrument_key = engine.create_instrument("strings.violin");
+        engine.assign_instrument_to_player(&player_key, &instrument_key);
+
+        engine.create_key_signature(&flow_key, 0, KeySignatureMode::Major, 2);
+        engine.create_key_signature(&flow_key, 192, KeySignatureMode::Major, -1);
+        engine.create_key_signature(&flow_key, 384, KeySignatureMode::Major, 0);
+
+        for engrave in engine.score.engrave.by_key.values_mut() {
+            engrave.key_signature_cancellation = cancellation.clone();
+            engrave.courtesy_signatures = courtesy_signatures;
+        }
+
+        (engine, flow_key)
+    }
+
+    /// The number of each accidental drawn for the key signatures
+    fn render(engine: &Engine, flow_key: &str) -> (usize, usize, usize) {
+        let engrave = engine.get_engrave_by_type(LayoutType::Score).unwrap();
+        let converter = Converter::new(1.0, engrave.space);
+        let (flow, instruments, staves, tracks) = engine.get_flow_instruments(flow_key);
+
+        let vertical_spacing = measure_vertical_spacing(&instruments, &flow.staves, engrave);
+        let bars = get_bars(flow, &engine.score.tracks);
+        let transpositions = get_transpositions(&instruments, engrave);
+        let tone_offsets =
+            get_tone_offsets(flow.length, &staves, &engine.score.tracks, &transpositions);
+        let barlines = get_barlines(flow, &engine.score.tracks);
+        let notations = get_written_durations(flow, &tracks, &bars);
+        let beams = get_beams(&notations, &bars, flow.subdivisions);
+        let stem_directions = get_stem_directions(&notations, &tone_offsets, &beams);
+        let shunts = get_note_shunts(&notations, &tone_offsets, &stem_directions);
+        let accidentals = get_accidentals(
+            flow,
+            &instruments,
+            &engine.score.tracks,
+            &notations,
+            &bars,
+            &tone_offsets,
+            &transpositions,
+            engrave,
+        );
+        let horizontal_spacing = measure_horizontal_spacing(
+            flow,
+            &staves,
+            &engine.score.tracks,
+            &barlines,
+            &notations,
+            &shunts,
+            &beams,
+            &accidentals,
+            &transpositions,
+            engrave,
+        );
+
+        let mut instructions = Vec::new();
+        draw_key_signatures(
+            0.0,
+            0.0,
+            flow,
+            &staves,
+            &engine.score.tracks,
+            &transpositions,
+            &vertical_spacing,
+            &horizontal_spacing,
+            engrave,
+            &converter,
+            &mut instructions,
+        );
+
+        let count = |accidental: Accidental| {
+            instructions
+                .iter()
+                .filter(|instruction| match instruction {
+                    Instruction::Text { value, .. } => *value == accidental.to_glyph(),
+                    _ => false,
+                })
+                .count()
+        };
+        (
+            count(Accidental::Sharp),
+            count(Accidental::Natural),
+            count(Accidental::Flat),
+        )
+    }
+
+    #[test]
+    fn cancellation_to_c_major_test() {
+        let (engine, flow_key) = engine(KeySignatureCancellation::ToCMajor, false);
+        // naturals only when moving to C major
+        assert_eq!(render(&engine, &flow_key), (2, 1, 1));
+    }
+
+    #[test]
+    fn cancellation_always_test() {
+        let (engine, flow_key) = engine(KeySignatureCancellation::Always, false);
+        assert_eq!(render(&engine, &flow_key), (2, 3, 1));
+    }
+
+    #[test]
+    fn cancellation_never_test() {
+        let (engine, flow_key) = engine(KeySignatureCancellation::Never, false);
+        assert_eq!(render(&engine, &flow_key), (2, 0, 1));
+    }
+
+    #[test]
+    fn courtesy_signatures_test() {
+        let (engine, flow_key) = engine(KeySignatureCancellation::ToCMajor, true);
+        // changes after the start are drawn again as courtesy signatures
+        assert_eq!(render(&engine, &flow_key), (2, 2, 2));
+    }
+}
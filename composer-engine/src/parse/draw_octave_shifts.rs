@@ -0,0 +1,164 @@
+use super::get_tone_offsets::ToneVerticalOffsets;
+use super::measure_horizontal_spacing::{HorizontalSpacing, Position};
+use super::measure_vertical_spacing::VerticalSpacing;
+use super::Instruction;
+use crate::components::measurements::Point;
+use crate::components::text::{Align, Justify};
+use crate::components::units::{Converter, Space};
+use crate::entries::octave_shift::OctaveShift;
+use crate::entries::Entry;
+use crate::score::flows::Flow;
+use crate::score::stave::{Stave, STAVE_LINE_WIDTH};
+use crate::score::tracks::{Track, Tracks};
+
+const DASH_LENGTH: Space = 0.5;
+const HOOK_LENGTH: Space = 1.0;
+
+/// Get the vertical offset of the line, clear of the stave and any tones under it
+fn get_octave_shift_offset(
+    octave_shift: &OctaveShift,
+    track: &Track,
+    tone_offsets: &ToneVerticalOffsets,
+) -> i8 {
+    let end = octave_shift.tick + octave_shift.duration;
+    let mut highest: i8 = -4;
+    let mut lowest: i8 = 4;
+
+    for entry in track.entries.by_key.values() {
+        if let Entry::Tone(tone) = entry {
+            if tone.tick >= octave_shift.tick && tone.tick < end {
+                if let Some(offset) = tone_offsets.get(&tone.key) {
+                    highest = highest.min(*offset);
+                    lowest = lowest.max(*offset);
+                }
+            }
+        }
+    }
+
+    if octave_shift.is_above() {
+        highest - 3
+    } else {
+        lowest + 3
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn draw_octave_shift(
+    x: Space,
+    y: Space,
+    flow: &Flow,
+    octave_shift: &OctaveShift,
+    offset: i8,
+    horizontal_spacing: &HorizontalSpacing,
+    converter: &Converter,
+    instructions: &mut Vec<Instruction>,
+) {
+    let start = horizontal_spacing
+        .get(&octave_shift.tick, &Position::NoteSpacing)
+        .unwrap();
+    let end_tick = octave_shift.tick + octave_shift.duration;
+    let end = if end_tick < flow.length {
+        horizontal_spacing
+            .get(&end_tick, &Position::PaddingStart)
+            .unwrap()
+            .x
+    } else {
+        horizontal_spacing.width
+    };
+
+    let line_y = y + (offset as Space * 0.5);
+
+    instructions.push(Instruction::Text {
+        x: converter.spaces_to_px(x + start.x),
+        y: converter.spaces_to_px(line_y),
+        value: octave_shift.glyph(),
+        color: String::from("#000"),
+        font: String::from("Bravura"),
+        size: converter.spaces_to_px(4.0),
+        justify: Justify::Start.as_string(),
+        align: Align::Middle.as_string(),
+    });
+
+    // dashed line to the end of the shift
+    let mut left = start.x + octave_shift.glyph_width();
+    while left < end {
+        let right = (left + DASH_LENGTH).min(end);
+        instructions.push(Instruction::Line {
+            color: String::from("#000"),
+            width: converter.spaces_to_px(STAVE_LINE_WIDTH),
+            points: vec![
+                Point {
+                    x: converter.spaces_to_px(x + left),
+                    y: converter.spaces_to_px(line_y),
+                },
+                Point {
+                    x: converter.spaces_to_px(x + right),
+                    y: converter.spaces_to_px(line_y),
+                },
+            ],
+        });
+        left += DASH_LENGTH * 2.0;
+    }
+
+    // closing hook points towards the stave
+    let hook = if octave_shift.is_above() {
+        HOOK_LENGTH
+    } else {
+        -HOOK_LENGTH
+    };
+    instructions.push(Instruction::Line {
+        color: String::from("#000"),
+        width: converter.spaces_to_px(STAVE_LINE_WIDTH),
+        points: vec![
+            Point {
+                x: converter.spaces_to_px(x + end),
+                y: converter.spaces_to_px(line_y),
+            },
+            Point {
+                x: converter.spaces_to_px(x + end),
+                y: converter.spaces_to_px(line_y + hook),
+            },
+        ],
+    });
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn draw_octave_shifts(
+    x: Space,
+    y: Space,
+    flow: &Flow,
+    staves: &Vec<&Stave>,
+    tracks: &Tracks,
+    tone_offsets: &ToneVerticalOffsets,
+    vertical_spacing: &VerticalSpacing,
+    horizontal_spacing: &HorizontalSpacing,
+    converter: &Converter,
+    instructions: &mut Vec<Instruction>,
+) {
+    for stave in staves {
+        let top = vertical_spacing.staves.get(&stave.key).unwrap();
+
+        for track_key in &stave.tracks {
+            let track = tracks.get(track_key).unwrap();
+            for entry in track.entries.by_key.values() {
+                if let Entry::OctaveShift(octave_shift) = entry {
+                    if octave_shift.tick >= flow.length {
+                        continue;
+                    }
+
+                    let offset = get_octave_shift_offset(octave_shift, track, tone_offsets);
+                    draw_octave_shift(
+                        x,
+                        y + top.y,
+                        flow,
+                        octave_shift,
+                        offset,
+                        horizontal_spacing,
+                        converter,
+                        instructions,
+                    );
+                }
+            }
+        }
+    }
+}
@@ -89,9 +89,7 @@ pub fn draw_rests(
 
             for (tick, entry) in &notation.track {
                 if entry.is_rest() {
-                    let is_full_bar = bars.contains_key(tick)
-                        && (bars.contains_key(&(tick + entry.duration))
-                            || tick + entry.duration == flow.length);
+                    let is_full_bar = notation.is_full_bar_rest(tick, bars);
 
                     draw_rest(
                         x,
@@ -0,0 +1,139 @@
+use super::get_tab_positions::TabPositions;
+use super::measure_horizontal_spacing::{HorizontalSpacing, Position};
+use super::measure_vertical_spacing::{VerticalPosition, VerticalSpacing};
+use super::Instruction;
+use crate::components::measurements::Point;
+use crate::components::text::{Align, Justify};
+use crate::components::units::{Converter, Space};
+use crate::entries::Entry;
+use crate::score::flows::Flow;
+use crate::score::instruments::Instrument;
+use crate::score::stave::{Stave, STAVE_LINE_WIDTH, TAB_LINE_SPACING};
+use crate::score::tracks::Tracks;
+
+fn clef_glyph(strings: usize) -> String {
+    match strings {
+        4 => String::from("\u{E06E}"),
+        _ => String::from("\u{E06D}"),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn draw_tablature_stave(
+    x: Space,
+    y: Space,
+    width: Space,
+    flow: &Flow,
+    stave: &Stave,
+    strings: usize,
+    position: &VerticalPosition,
+    tracks: &Tracks,
+    tab_positions: &TabPositions,
+    horizontal_spacing: &HorizontalSpacing,
+    converter: &Converter,
+    instructions: &mut Vec<Instruction>,
+) {
+    let top = y + position.y - (position.height / 2.0);
+
+    for i in 0..strings {
+        let line = top + (i as Space * TAB_LINE_SPACING);
+        instructions.push(Instruction::Line {
+            color: String::from("#000"),
+            width: converter.spaces_to_px(STAVE_LINE_WIDTH),
+            points: vec![
+                Point {
+                    x: converter.spaces_to_px(x),
+                    y: converter.spaces_to_px(line),
+                },
+                Point {
+                    x: converter.spaces_to_px(x + width),
+                    y: converter.spaces_to_px(line),
+                },
+            ],
+        });
+    }
+
+    let clef = horizontal_spacing.get(&0, &Position::Clef).unwrap();
+    instructions.push(Instruction::Text {
+        x: converter.spaces_to_px(x + clef.x),
+        y: converter.spaces_to_px(y + position.y),
+        value: clef_glyph(strings),
+        color: String::from("#000"),
+        font: String::from("Bravura"),
+        size: converter.spaces_to_px(4.0),
+        justify: Justify::Start.as_string(),
+        align: Align::Middle.as_string(),
+    });
+
+    for track_key in &stave.tracks {
+        let track = tracks.get(track_key).unwrap();
+        for entry in track.entries.by_key.values() {
+            if let Entry::Tone(tone) = entry {
+                if tone.tick >= flow.length {
+                    continue;
+                }
+
+                if let Some(tab) = tab_positions.get(&tone.key) {
+                    let left = horizontal_spacing
+                        .get(&tone.tick, &Position::NoteSpacing)
+                        .unwrap();
+                    let line = top + ((tab.string - 1) as Space * TAB_LINE_SPACING);
+
+                    instructions.push(Instruction::Text {
+                        x: converter.spaces_to_px(x + left.x + 0.6),
+                        y: converter.spaces_to_px(line),
+                        value: tab.fret.to_string(),
+                        color: String::from("#000"),
+                        font: String::from("Libre Baskerville"),
+                        size: converter.spaces_to_px(1.5),
+                        justify: Justify::Middle.as_string(),
+                        align: Align::Middle.as_string(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn draw_tablature(
+    x: Space,
+    y: Space,
+    width: Space,
+    flow: &Flow,
+    instruments: &[&Instrument],
+    tracks: &Tracks,
+    tab_positions: &TabPositions,
+    vertical_spacing: &VerticalSpacing,
+    horizontal_spacing: &HorizontalSpacing,
+    converter: &Converter,
+    instructions: &mut Vec<Instruction>,
+) {
+    for instrument in instruments {
+        if !instrument.has_tablature() {
+            continue;
+        }
+
+        let strings = instrument.tuning.as_ref().unwrap().len();
+
+        for stave_key in &instrument.staves {
+            let stave = flow.staves.get(stave_key).unwrap();
+            if let Some(position) = vertical_spacing.tablatures.get(stave_key) {
+                draw_tablature_stave(
+                    x,
+                    y,
+                    width,
+                    flow,
+                    stave,
+                    strings,
+                    position,
+                    tracks,
+                    tab_positions,
+                    horizontal_spacing,
+                    converter,
+                    instructions,
+                );
+            }
+        }
+    }
+}
@@ -2,10 +2,14 @@ use super::measure_horizontal_spacing::{HorizontalSpacing, Position};
 use super::measure_vertical_spacing::VerticalSpacing;
 use super::Instruction;
 use crate::components::duration::NoteDuration;
+use crate::components::misc::Ticks;
 use crate::components::text::{Align, Justify};
 use crate::components::units::{Converter, Space};
-use crate::entries::time_signature::{TimeSignature, TimeSignatureDrawType};
+use crate::entries::time_signature::{
+    TimeSignature, TimeSignatureDrawType, TimeSignaturePart, PARENS_WIDTH, PART_GAP,
+};
 use crate::entries::Entry;
+use crate::score::engrave::Engrave;
 use crate::score::flows::Flow;
 use crate::score::stave::Stave;
 use crate::score::tracks::Tracks;
@@ -36,23 +40,85 @@ pub fn number_to_glyph(input: u8) -> String {
 }
 
 pub fn beat_type_to_glyph(input: NoteDuration) -> String {
-    let number = match input {
-        NoteDuration::Whole => 1,
-        NoteDuration::Half => 2,
-        NoteDuration::Quarter => 4,
-        NoteDuration::Eighth => 8,
-        NoteDuration::Sixteenth => 16,
-        NoteDuration::ThirtySecond => 32,
-        NoteDuration::SixtyFourth => 64,
-        NoteDuration::HudredTwentyEighth => 128,
-    };
-    number_to_glyph(number)
+    number_to_glyph(input.to_denominator())
+}
+
+/// Additive numerators joined with a plus, ie. 3+2+2
+pub fn numerators_to_glyph(input: &[u8]) -> String {
+    input
+        .iter()
+        .map(|beats| number_to_glyph(*beats))
+        .collect::<Vec<String>>()
+        .join("\u{E08C}")
+}
+
+fn draw_glyph(
+    x: Space,
+    y: Space,
+    value: String,
+    converter: &Converter,
+    instructions: &mut Vec<Instruction>,
+) {
+    instructions.push(Instruction::Text {
+        x: converter.spaces_to_px(x),
+        y: converter.spaces_to_px(y),
+        value,
+        color: String::from("#000"),
+        font: String::from("Bravura"),
+        size: converter.spaces_to_px(4.0),
+        justify: Justify::Middle.as_string(),
+        align: Align::Middle.as_string(),
+    });
+}
+
+/// Draw a list of fractions left to right from x, joined with pluses
+fn draw_parts(
+    x: Space,
+    y: Space,
+    parts: &[TimeSignaturePart],
+    converter: &Converter,
+    instructions: &mut Vec<Instruction>,
+) -> Space {
+    let mut left = x;
+    for (i, part) in parts.iter().enumerate() {
+        if i > 0 {
+            draw_glyph(
+                left + (PART_GAP / 2.0),
+                y,
+                String::from("\u{E08C}"),
+                converter,
+                instructions,
+            );
+            left += PART_GAP;
+        }
+
+        let width = part.width();
+        let numerator = numerators_to_glyph(&part.beats);
+        let denominator = beat_type_to_glyph(part.beat_type);
+        draw_glyph(
+            left + width / 2.0,
+            y - 1.0,
+            numerator,
+            converter,
+            instructions,
+        );
+        draw_glyph(
+            left + width / 2.0,
+            y + 1.0,
+            denominator,
+            converter,
+            instructions,
+        );
+        left += width;
+    }
+    left
 }
 
 fn draw_time_signature(
     x: Space,
     y: Space,
     time_signature: &TimeSignature,
+    subdivisions: Ticks,
     converter: &Converter,
     instructions: &mut Vec<Instruction>,
 ) {
@@ -88,6 +154,35 @@ fn draw_time_signature(
             justify: Justify::Middle.as_string(),
             align: Align::Middle.as_string(),
         }),
+        TimeSignatureDrawType::Regular if time_signature.is_composite() => {
+            // composite signatures are drawn left to right from the start of the box
+            let start = x - time_signature.metrics(subdivisions).width / 2.0;
+            let mut left = draw_parts(
+                start,
+                y,
+                &time_signature.display_parts(),
+                converter,
+                instructions,
+            );
+            if let Some(interchangeable) = &time_signature.interchangeable {
+                left += PARENS_WIDTH / 2.0;
+                draw_glyph(left, y, String::from("\u{E094}"), converter, instructions);
+                left = draw_parts(
+                    left + PARENS_WIDTH / 2.0,
+                    y,
+                    interchangeable,
+                    converter,
+                    instructions,
+                );
+                draw_glyph(
+                    left + PARENS_WIDTH / 2.0,
+                    y,
+                    String::from("\u{E095}"),
+                    converter,
+                    instructions,
+                );
+            }
+        }
         TimeSignatureDrawType::Regular => {
             let beats = number_to_glyph(time_signature.beats);
             let beat_type = beat_type_to_glyph(time_signature.beat_type);
@@ -124,6 +219,7 @@ pub fn draw_time_signatures(
     tracks: &Tracks,
     vertical_spacing: &VerticalSpacing,
     horizontal_spacing: &HorizontalSpacing,
+    engrave: &Engrave,
     converter: &Converter,
     instructions: &mut Vec<Instruction>,
 ) {
@@ -143,9 +239,28 @@ pub fn draw_time_signatures(
                     x + left.x + offset,
                     y + top.y,
                     time_signature,
+                    flow.subdivisions,
                     converter,
                     instructions,
-                )
+                );
+
+                // courtesy time signature follows the courtesy key signature
+                if engrave.courtesy_signatures && time_signature.tick > 0 {
+                    let courtesy = horizontal_spacing
+                        .get(&time_signature.tick, &Position::Courtesy)
+                        .unwrap();
+                    let key_signature = horizontal_spacing
+                        .get(&time_signature.tick, &Position::KeySignature)
+                        .unwrap();
+                    draw_time_signature(
+                        x + courtesy.x + key_signature.width + offset,
+                        y + top.y,
+                        time_signature,
+                        flow.subdivisions,
+                        converter,
+                        instructions,
+                    );
+                }
             }
         }
     }
@@ -153,7 +268,7 @@ pub fn draw_time_signatures(
 
 #[cfg(test)]
 mod tests {
-    use super::number_to_glyph;
+    use super::{number_to_glyph, numerators_to_glyph};
 
     #[test]
     fn number_to_glyph_test_1() {
@@ -161,6 +276,15 @@ mod tests {
         assert_eq!(result, String::from("\u{E081}"))
     }
 
+    #[test]
+    fn numerators_to_glyph_test() {
+        let result = numerators_to_glyph(&[3, 2, 2]);
+        assert_eq!(
+            result,
+            String::from("\u{E083}\u{E08C}\u{E082}\u{E08C}\u{E082}")
+        )
+    }
+
     #[test]
     fn number_to_glyph_test_2() {
         let result = number_to_glyph(145);
@@ -1,13 +1,17 @@
 use rustc_hash::{FxHashMap, FxHashSet};
 
 use super::get_bars::Bars;
+use super::get_transpositions::{get_transposition, Transpositions};
 use super::get_written_durations::{Notation, NotationTrack};
 use super::{get_tone_offsets::ToneVerticalOffsets, get_written_durations::NotationByTrack};
+use crate::components::interval::Interval;
 use crate::components::misc::Tick;
 use crate::components::pitch::{Accidental, Pitch};
 use crate::entries::key_signature::{KeySignature, KeySignatureMode};
 use crate::entries::tone::Tone;
+use crate::score::engrave::{CautionaryAccidentals, Engrave};
 use crate::score::flows::Flow;
+use crate::score::instruments::Instrument;
 use crate::score::tracks::{Track, Tracks};
 
 type SlotsByTick = FxHashMap<Tick, u8>;
@@ -16,12 +20,15 @@ type SlotsByTick = FxHashMap<Tick, u8>;
 pub struct AccidentalNotation {
     pub tone_key: String,
     pub slot: u8,
+    pub accidental: Accidental, // as written, which may differ from the tone if transposed
+    pub cautionary: bool,
 }
 
 #[derive(Debug)]
 pub struct Accidentals {
     pub by_key: FxHashMap<(Tick, String), AccidentalNotation>,
     pub slots_by_tick: SlotsByTick,
+    pub alterations: Vec<(Tick, Pitch)>, // written pitches drawn with an accidental
 }
 
 impl Accidentals {
@@ -29,6 +36,7 @@ impl Accidentals {
         Self {
             by_key: FxHashMap::default(),
             slots_by_tick: FxHashMap::default(),
+            alterations: Vec::new(),
         }
     }
 }
@@ -130,55 +138,131 @@ pub fn is_accidental_needed(
     false
 }
 
-/// returns the *unordered* tones needing accidentals
+/// Is a courtesy accidental wanted for a tone that doesn't strictly need one
+pub fn is_cautionary_needed(
+    tone: &Tone,
+    altered_pitches: &FxHashSet<Pitch>,
+    previous_bar: &FxHashSet<Pitch>,
+    other_voices: &[&Pitch],
+    rules: &CautionaryAccidentals,
+) -> bool {
+    let differs = |pitch: &Pitch| {
+        pitch.letter() == tone.pitch.letter() && pitch.accidental != tone.pitch.accidental
+    };
+
+    if rules.after_bar
+        && previous_bar
+            .iter()
+            .any(|pitch| differs(pitch) && pitch.octave() == tone.pitch.octave())
+    {
+        return true;
+    }
+
+    if rules.octaves
+        && altered_pitches
+            .iter()
+            .any(|pitch| differs(pitch) && pitch.octave() != tone.pitch.octave())
+    {
+        return true;
+    }
+
+    if rules.voices
+        && other_voices
+            .iter()
+            .any(|pitch| differs(pitch) && pitch.octave() == tone.pitch.octave())
+    {
+        return true;
+    }
+
+    false
+}
+
+/// returns the *unordered* tones needing accidentals along with the written accidental
+/// and whether it is cautionary
+#[allow(clippy::too_many_arguments)]
 pub fn get_tones_needing_accidentals(
     entry: &Notation,
     previous_tones: &mut FxHashSet<String>,
     altered_pitches: &mut FxHashSet<Pitch>,
+    previous_bar: &mut FxHashSet<Pitch>,
+    other_voices: &[&Pitch],
     key_signature: &KeySignature,
     tone_offsets: &ToneVerticalOffsets,
-) -> Vec<String> {
-    let mut output: Vec<String> = Vec::new();
+    transposition: &Interval,
+    rules: &CautionaryAccidentals,
+) -> Vec<(String, Accidental, bool)> {
+    let mut output: Vec<(String, Accidental, bool)> = Vec::new();
 
     for tone in &entry.sort_tones(tone_offsets) {
-        if is_accidental_needed(tone, previous_tones, altered_pitches, key_signature) {
-            output.push(tone.key.clone());
+        // work with the written pitch
+        let mut tone = tone.clone();
+        tone.pitch = tone.pitch.transpose(transposition);
+
+        let is_tied = previous_tones.contains(&tone.key);
+        let is_needed = is_accidental_needed(&tone, previous_tones, altered_pitches, key_signature);
+        let is_cautionary = !is_tied
+            && !is_needed
+            && is_cautionary_needed(&tone, altered_pitches, previous_bar, other_voices, rules);
+
+        if is_needed || is_cautionary {
+            output.push((tone.key.clone(), tone.pitch.accidental, is_cautionary));
             altered_pitches.retain(|pitch| -> bool {
                 !(pitch.letter() == tone.pitch.letter() && pitch.octave() == tone.pitch.octave())
             });
             altered_pitches.insert(tone.pitch.clone());
         }
+
+        // courtesy accidentals from the previous bar are only shown once
+        if !is_tied {
+            previous_bar.retain(|pitch| -> bool {
+                !(pitch.letter() == tone.pitch.letter() && pitch.octave() == tone.pitch.octave())
+            });
+        }
+
         previous_tones.insert(tone.key.clone());
     }
 
     output
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn get_accidentals_in_track(
     notation: &NotationTrack,
     master: &Track,
     barlines: &Bars,
     tone_offsets: &ToneVerticalOffsets,
+    transposition: &Interval,
+    other_alterations: &[(Tick, Pitch)],
+    rules: &CautionaryAccidentals,
 ) -> Accidentals {
     let mut output = Accidentals::new();
 
-    let mut key_signature = &KeySignature::new(0, KeySignatureMode::Major, 0);
+    let mut key_signature = KeySignature::new(0, KeySignatureMode::Major, 0);
     let mut altered_pitches: FxHashSet<Pitch> = FxHashSet::default();
+    let mut previous_bar: FxHashSet<Pitch> = FxHashSet::default();
     let mut previous_tones: FxHashSet<String> = FxHashSet::default();
+    let mut bar_start: Tick = 0;
 
     for tick in 0..notation.length {
         // look for a key signature at this tick
         let found = match master.get_key_signature_at_tick(&tick) {
             Some(entry) => {
-                key_signature = entry;
+                key_signature = entry.transpose(transposition);
                 true
             }
             None => false,
         };
 
         // clear alterations at barlines and key changes
-        if found || barlines.contains_key(&tick) {
+        if found {
             altered_pitches.clear();
+            previous_bar.clear();
+        } else if barlines.contains_key(&tick) {
+            previous_bar = std::mem::take(&mut altered_pitches);
+        }
+
+        if found || barlines.contains_key(&tick) {
+            bar_start = tick;
         }
 
         let entry = match notation.track.get(&tick) {
@@ -188,51 +272,140 @@ pub fn get_accidentals_in_track(
             }
         };
 
+        // alterations made so far this bar in the other voices of the instrument
+        let other_voices: Vec<&Pitch> = other_alterations
+            .iter()
+            .filter(|(at, _)| *at >= bar_start && *at <= tick)
+            .map(|(_, pitch)| pitch)
+            .collect();
+
         let accidentals = get_tones_needing_accidentals(
             entry,
             &mut previous_tones,
             &mut altered_pitches,
-            key_signature,
+            &mut previous_bar,
+            &other_voices,
+            &key_signature,
             tone_offsets,
+            transposition,
+            rules,
         );
 
         if accidentals.is_empty() {
             continue;
         }
 
+        for tone in &entry.tones {
+            if accidentals.iter().any(|(key, _, _)| *key == tone.key) {
+                output
+                    .alterations
+                    .push((tick, tone.pitch.transpose(transposition)));
+            }
+        }
+
+        let written: FxHashMap<String, (Accidental, bool)> = accidentals
+            .iter()
+            .map(|(key, accidental, cautionary)| (key.clone(), (*accidental, *cautionary)))
+            .collect();
+        let keys: Vec<String> = accidentals.into_iter().map(|(key, _, _)| key).collect();
+
         let mut slots: Vec<AccidentalNotation> = Vec::new();
-        for tone_key in sort_accidentals_alternate(&accidentals) {
+        for tone_key in sort_accidentals_alternate(&keys) {
             let slot = find_slot(&tone_key, &slots, tone_offsets);
-            slots.push(AccidentalNotation { tone_key, slot })
+            let (accidental, cautionary) = *written.get(&tone_key).unwrap();
+            slots.push(AccidentalNotation {
+                tone_key,
+                slot,
+                accidental,
+                cautionary,
+            })
         }
 
         let mut max_slot = 1;
+        let mut has_parentheses = false;
         for entry in slots {
             if entry.slot > max_slot {
                 max_slot = entry.slot;
             }
+            if entry.cautionary && rules.parentheses {
+                has_parentheses = true;
+            }
             output.by_key.insert((tick, entry.tone_key.clone()), entry);
         }
+
+        // parentheses need roughly an extra slot of space
+        if has_parentheses {
+            max_slot += 1;
+        }
         output.slots_by_tick.insert(tick, max_slot);
     }
 
     output
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn get_accidentals(
     flow: &Flow,
+    instruments: &[&Instrument],
     tracks: &Tracks,
     notation_by_track: &NotationByTrack,
     bars: &Bars,
     tone_offsets: &ToneVerticalOffsets,
+    transpositions: &Transpositions,
+    engrave: &Engrave,
 ) -> AccidentalsByTrack {
     let mut output: AccidentalsByTrack = FxHashMap::default();
 
     let master = tracks.get(&flow.master).unwrap();
+    let rules = &engrave.cautionary_accidentals;
+
+    for instrument in instruments {
+        let mut track_keys: Vec<(&String, &Interval)> = Vec::new();
+        for stave_key in &instrument.staves {
+            let stave = flow.staves.get(stave_key).unwrap();
+            let transposition = get_transposition(&stave.key, transpositions);
+            for track_key in &stave.tracks {
+                track_keys.push((track_key, transposition));
+            }
+        }
 
-    for (track_key, notation) in notation_by_track {
-        let accidentals = get_accidentals_in_track(notation, master, bars, tone_offsets);
-        output.insert(track_key.clone(), accidentals);
+        // first pass finds the alterations in each voice of the instrument
+        let mut alterations: FxHashMap<&String, Vec<(Tick, Pitch)>> = FxHashMap::default();
+        if rules.voices && track_keys.len() > 1 {
+            for (track_key, transposition) in &track_keys {
+                let notation = notation_by_track.get(*track_key).unwrap();
+                let accidentals = get_accidentals_in_track(
+                    notation,
+                    master,
+                    bars,
+                    tone_offsets,
+                    transposition,
+                    &[],
+                    rules,
+                );
+                alterations.insert(track_key, accidentals.alterations);
+            }
+        }
+
+        for (track_key, transposition) in &track_keys {
+            let others: Vec<(Tick, Pitch)> = alterations
+                .iter()
+                .filter(|(key, _)| **key != *track_key)
+                .flat_map(|(_, entries)| entries.iter().cloned())
+                .collect();
+
+            let notation = notation_by_track.get(*track_key).unwrap();
+            let accidentals = get_accidentals_in_track(
+                notation,
+                master,
+                bars,
+                tone_offsets,
+                transposition,
+                &others,
+                rules,
+            );
+            output.insert((*track_key).clone(), accidentals);
+        }
     }
 
     output
@@ -252,7 +425,9 @@ mod tests {
     use crate::entries::tone::Tone;
     use crate::parse::get_accidentals::find_slot;
     use crate::parse::get_accidentals::is_accidental_needed;
+    use crate::parse::get_accidentals::is_cautionary_needed;
     use crate::parse::get_accidentals::AccidentalNotation;
+    use crate::score::engrave::CautionaryAccidentals;
 
     #[test]
     fn is_accidental_needed_test_1() {
@@ -325,6 +500,7 @@ mod tests {
         altered_pitches.insert(Pitch {
             int: 68,
             accidental: Accidental::Sharp,
+            cents: 0,
         });
 
         let key_signature = KeySignature::new(0, KeySignatureMode::Major, 0);
@@ -347,6 +523,7 @@ mod tests {
         altered_pitches.insert(Pitch {
             int: 68,
             accidental: Accidental::Sharp,
+            cents: 0,
         });
         let key_signature = KeySignature::new(0, KeySignatureMode::Major, 0);
         let result = is_accidental_needed(&tone, &previous_tones, &altered_pitches, &key_signature);
@@ -405,6 +582,8 @@ mod tests {
             &[AccidentalNotation {
                 tone_key: String::from("a"),
                 slot: 1,
+                accidental: Accidental::Sharp,
+                cautionary: false,
             }],
             &tone_offsets,
         );
@@ -423,14 +602,104 @@ mod tests {
                 AccidentalNotation {
                     tone_key: String::from("a"),
                     slot: 1,
+                    accidental: Accidental::Sharp,
+                    cautionary: false,
                 },
                 AccidentalNotation {
                     tone_key: String::from("b"),
                     slot: 2,
+                    accidental: Accidental::Sharp,
+                    cautionary: false,
                 },
             ],
             &tone_offsets,
         );
         assert_eq!(result, 2);
     }
+
+    fn rules(after_bar: bool, octaves: bool, voices: bool) -> CautionaryAccidentals {
+        CautionaryAccidentals {
+            after_bar,
+            octaves,
+            voices,
+            parentheses: true,
+        }
+    }
+
+    fn natural_f() -> Tone {
+        Tone::new(
+            String::from("a"),
+            0,
+            0,
+            Pitch::new(65, Accidental::Natural),
+            Velocity::new(100),
+            Articulation::None,
+        )
+    }
+
+    #[test]
+    fn is_cautionary_needed_test_1() {
+        // F# in the previous bar, F natural now
+        let mut previous_bar = FxHashSet::default();
+        previous_bar.insert(Pitch::new(66, Accidental::Sharp));
+        let result = is_cautionary_needed(
+            &natural_f(),
+            &FxHashSet::default(),
+            &previous_bar,
+            &[],
+            &rules(true, false, false),
+        );
+        assert!(result);
+    }
+
+    #[test]
+    fn is_cautionary_needed_test_2() {
+        // F# in the previous bar but the rule is off
+        let mut previous_bar = FxHashSet::default();
+        previous_bar.insert(Pitch::new(66, Accidental::Sharp));
+        let result = is_cautionary_needed(
+            &natural_f(),
+            &FxHashSet::default(),
+            &previous_bar,
+            &[],
+            &rules(false, true, true),
+        );
+        assert!(!result);
+    }
+
+    #[test]
+    fn is_cautionary_needed_test_3() {
+        // F# an octave higher in the same bar
+        let mut altered_pitches = FxHashSet::default();
+        altered_pitches.insert(Pitch::new(78, Accidental::Sharp));
+        let tone = natural_f();
+        assert!(is_cautionary_needed(
+            &tone,
+            &altered_pitches,
+            &FxHashSet::default(),
+            &[],
+            &rules(false, true, false),
+        ));
+        assert!(!is_cautionary_needed(
+            &tone,
+            &altered_pitches,
+            &FxHashSet::default(),
+            &[],
+            &rules(true, false, true),
+        ));
+    }
+
+    #[test]
+    fn is_cautionary_needed_test_4() {
+        // F# in another voice of the same instrument
+        let sharp = Pitch::new(66, Accidental::Sharp);
+        let result = is_cautionary_needed(
+            &natural_f(),
+            &FxHashSet::default(),
+            &FxHashSet::default(),
+            &[&sharp],
+            &rules(false, false, true),
+        );
+        assert!(result);
+    }
 }
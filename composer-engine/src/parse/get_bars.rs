@@ -18,7 +18,7 @@ pub fn get_bars(flow: &Flow, tracks: &Tracks) -> Bars {
         }
 
         if time_signature.is_on_first_beat(tick, flow.subdivisions) {
-            output.insert(tick, time_signature.clone());
+            output.insert(tick, time_signature.bar_at(tick, flow.subdivisions));
         }
     }
 
@@ -0,0 +1,197 @@
+use crate::components::misc::Tick;
+use crate::entries::tone::Tone;
+use crate::entries::Entry;
+use crate::score::flows::Flow;
+use crate::score::instruments::Instrument;
+use crate::score::tracks::Tracks;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+pub const MAX_FRET: u8 = 24;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TabPosition {
+    pub string: u8, // 1 = highest string
+    pub fret: u8,
+}
+
+pub type TabPositions = FxHashMap<String, TabPosition>;
+
+/// The fret needed to play a pitch on an open string, if it's playable at all
+pub fn fret_on_string(int: u8, open: u8) -> Option<u8> {
+    if int >= open && int - open <= MAX_FRET {
+        Some(int - open)
+    } else {
+        None
+    }
+}
+
+/// Assign a string and fret to each tone sounding at the same tick.
+///
+/// User defined strings are honoured first (if playable), the remaining tones are
+/// placed lowest pitch first on the free string with the lowest fret.
+pub fn assign_tab_positions(tones: &[&Tone], tuning: &[u8]) -> TabPositions {
+    let mut output: TabPositions = FxHashMap::default();
+    let mut used: FxHashSet<u8> = FxHashSet::default();
+
+    for tone in tones {
+        if let Some(string) = tone.string {
+            if string == 0 || used.contains(&string) {
+                continue;
+            }
+            if let Some(open) = tuning.get((string - 1) as usize) {
+                if let Some(fret) = fret_on_string(tone.pitch.int, *open) {
+                    used.insert(string);
+                    output.insert(tone.key.clone(), TabPosition { string, fret });
+                }
+            }
+        }
+    }
+
+    let mut remaining: Vec<&&Tone> = tones
+        .iter()
+        .filter(|tone| !output.contains_key(&tone.key))
+        .collect();
+    remaining.sort_by_key(|tone| tone.pitch.int);
+
+    for tone in remaining {
+        let mut best: Option<TabPosition> = None;
+        for (i, open) in tuning.iter().enumerate() {
+            let string = (i + 1) as u8;
+            if used.contains(&string) {
+                continue;
+            }
+            if let Some(fret) = fret_on_string(tone.pitch.int, *open) {
+                let is_better = match &best {
+                    Some(position) => fret < position.fret,
+                    None => true,
+                };
+                if is_better {
+                    best = Some(TabPosition { string, fret });
+                }
+            }
+        }
+
+        if let Some(position) = best {
+            used.insert(position.string);
+            output.insert(tone.key.clone(), position);
+        }
+    }
+
+    output
+}
+
+/// The string and fret of every tone, by tone key, for the instruments shown as tablature.
+/// Tones that can't be played on a free string are left out and have no position.
+pub fn get_tab_positions(
+    flow: &Flow,
+    instruments: &[&Instrument],
+    tracks: &Tracks,
+) -> TabPositions {
+    let mut output: TabPositions = FxHashMap::default();
+
+    for instrument in instruments {
+        if !instrument.has_tablature() {
+            continue;
+        }
+
+        let tuning = instrument.tuning.as_ref().unwrap();
+
+        // all voices on all staves of the instrument share the same strings
+        let mut by_tick: FxHashMap<Tick, Vec<&Tone>> = FxHashMap::default();
+        for stave_key in &instrument.staves {
+            let stave = flow.staves.get(stave_key).unwrap();
+            for track_key in &stave.tracks {
+                let track = tracks.get(track_key).unwrap();
+                for entry in track.entries.by_key.values() {
+                    if let Entry::Tone(tone) = entry {
+                        by_tick.entry(tone.tick).or_default().push(tone);
+                    }
+                }
+            }
+        }
+
+        for tones in by_tick.values() {
+            output.extend(assign_tab_positions(tones, tuning));
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::assign_tab_positions;
+    use super::fret_on_string;
+    use super::TabPosition;
+    use crate::components::articulation::Articulation;
+    use crate::components::pitch::Pitch;
+    use crate::components::velocity::Velocity;
+    use crate::entries::tone::Tone;
+
+    const GUITAR: [u8; 6] = [64, 59, 55, 50, 45, 40];
+
+    fn tone(key: &str, int: u8, string: Option<u8>) -> Tone {
+        let mut tone = Tone::new(
+            String::from(key),
+            0,
+            16,
+            Pitch::from_int(int),
+            Velocity::new(100),
+            Articulation::None,
+        );
+        tone.string = string;
+        tone
+    }
+
+    #[test]
+    fn fret_on_string_test() {
+        assert_eq!(fret_on_string(40, 40), Some(0));
+        assert_eq!(fret_on_string(45, 40), Some(5));
+        assert_eq!(fret_on_string(39, 40), None);
+        assert_eq!(fret_on_string(65, 40), None);
+    }
+
+    #[test]
+    /// single tones use the lowest fret available
+    fn assign_tab_positions_test_1() {
+        let a = tone("a", 69, None);
+        let result = assign_tab_positions(&[&a], &GUITAR);
+        assert_eq!(result.get("a"), Some(&TabPosition { string: 1, fret: 5 }));
+    }
+
+    #[test]
+    /// open power chord
+    fn assign_tab_positions_test_2() {
+        let a = tone("a", 40, None);
+        let b = tone("b", 47, None);
+        let c = tone("c", 52, None);
+        let result = assign_tab_positions(&[&c, &a, &b], &GUITAR);
+        assert_eq!(result.get("a"), Some(&TabPosition { string: 6, fret: 0 }));
+        assert_eq!(result.get("b"), Some(&TabPosition { string: 5, fret: 2 }));
+        assert_eq!(result.get("c"), Some(&TabPosition { string: 4, fret: 2 }));
+    }
+
+    #[test]
+    /// user defined string is respected
+    fn assign_tab_positions_test_3() {
+        let a = tone("a", 64, Some(2));
+        let result = assign_tab_positions(&[&a], &GUITAR);
+        assert_eq!(result.get("a"), Some(&TabPosition { string: 2, fret: 5 }));
+    }
+
+    #[test]
+    /// unplayable user defined string falls back to automatic
+    fn assign_tab_positions_test_4() {
+        let a = tone("a", 45, Some(1));
+        let result = assign_tab_positions(&[&a], &GUITAR);
+        assert_eq!(result.get("a"), Some(&TabPosition { string: 5, fret: 0 }));
+    }
+
+    #[test]
+    /// tones that can't be played are left out
+    fn assign_tab_positions_test_5() {
+        let a = tone("a", 30, None);
+        let result = assign_tab_positions(&[&a], &GUITAR);
+        assert_eq!(result.get("a"), None);
+    }
+}
@@ -1,3 +1,4 @@
+use super::get_transpositions::{get_transposition, Transpositions};
 use crate::components::misc::Ticks;
 use crate::components::pitch::Pitch;
 use crate::entries::clef::{Clef, ClefDrawType};
@@ -12,11 +13,13 @@ pub fn get_tone_offsets(
     flow_length: Ticks,
     staves: &[&Stave],
     tracks: &Tracks,
+    transpositions: &Transpositions,
 ) -> ToneVerticalOffsets {
     let mut output: ToneVerticalOffsets = FxHashMap::default();
 
     for stave in staves {
         let master = tracks.get(&stave.master).unwrap();
+        let transposition = get_transposition(&stave.key, transpositions);
         for track_key in &stave.tracks {
             let mut clef = &Clef::new(0, 60, 0, ClefDrawType::C);
 
@@ -26,8 +29,15 @@ pub fn get_tone_offsets(
                 };
 
                 let track = tracks.get(track_key).unwrap();
+                // octave lines move the tones down (or up) a whole octave of steps
+                let octave_shift = match track.get_octave_shift_at_tick(&tick) {
+                    Some(octave_shift) => octave_shift.octave * 7,
+                    None => 0,
+                };
                 for tone in track.get_tones_at_tick(&tick) {
-                    let offset = Pitch::steps_between(&tone.pitch, &clef.pitch) + clef.offset;
+                    let pitch = tone.pitch.transpose(transposition);
+                    let offset =
+                        Pitch::steps_between(&pitch, &clef.pitch) + clef.offset + octave_shift;
                     output.insert(tone.key.clone(), offset);
                 }
             }
@@ -91,6 +101,7 @@ mod tests {
     use crate::components::velocity::Velocity;
     use crate::entries::clef::Clef;
     use crate::entries::clef::ClefDrawType;
+    use crate::entries::octave_shift::OctaveShift;
     use crate::entries::tone::Tone;
     use crate::entries::Entry;
     use crate::parse::get_written_durations::Notation;
@@ -99,7 +110,18 @@ mod tests {
     use crate::score::tracks::Track;
 
     fn run_get_tone_offsets(clef: Clef, tone: (&str, u8)) -> ToneVerticalOffsets {
+        run_get_tone_offsets_with_shift(clef, tone, None)
+    }
+
+    fn run_get_tone_offsets_with_shift(
+        clef: Clef,
+        tone: (&str, u8),
+        octave_shift: Option<OctaveShift>,
+    ) -> ToneVerticalOffsets {
         let mut track = Track::new();
+        if let Some(octave_shift) = octave_shift {
+            track.insert(Entry::OctaveShift(octave_shift));
+        }
         track.insert(Entry::Tone(Tone::new(
             String::from(tone.0),
             0,
@@ -126,7 +148,7 @@ mod tests {
         tracks.insert(track.key.clone(), track);
         tracks.insert(master.key.clone(), master);
 
-        get_tone_offsets(16, &[&stave], &tracks)
+        get_tone_offsets(16, &[&stave], &tracks, &FxHashMap::default())
     }
 
     #[test]
@@ -153,6 +175,42 @@ mod tests {
         assert_eq!(result, expected);
     }
 
+    #[test]
+    /// 8va line writes the tone an octave lower
+    fn get_tone_offsets_test_4() {
+        let result = run_get_tone_offsets_with_shift(
+            Clef::new(0, 67, 2, ClefDrawType::G),
+            ("a", 84),
+            Some(OctaveShift::new(0, 16, 1)),
+        );
+        let mut expected = FxHashMap::default();
+        expected.insert(String::from("a"), -1);
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    /// tones outside the line are unaffected
+    fn get_tone_offsets_test_5() {
+        let result = run_get_tone_offsets_with_shift(
+            Clef::new(0, 67, 2, ClefDrawType::G),
+            ("a", 84),
+            Some(OctaveShift::new(16, 16, 1)),
+        );
+        let mut expected = FxHashMap::default();
+        expected.insert(String::from("a"), -8);
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    /// treble 8vb clef for guitar
+    fn get_tone_offsets_test_6() {
+        let result =
+            run_get_tone_offsets(Clef::new_octave(0, 55, 2, ClefDrawType::G, -1), ("a", 52));
+        let mut expected = FxHashMap::default();
+        expected.insert(String::from("a"), 4);
+        assert_eq!(result, expected);
+    }
+
     fn run_get_tone_offset_info(tones: Vec<(&str, i8)>) -> (i8, i8, i8) {
         let mut notation = Notation {
             tick: 0,
@@ -0,0 +1,37 @@
+use crate::components::interval::Interval;
+use crate::score::engrave::Engrave;
+use crate::score::instruments::defs::get_def;
+use crate::score::instruments::Instrument;
+use rustc_hash::FxHashMap;
+
+/// The interval each stave is written at, keyed by stave key.
+/// Staves written at concert pitch are omitted.
+pub type Transpositions = FxHashMap<String, Interval>;
+
+pub fn get_transpositions(instruments: &[&Instrument], engrave: &Engrave) -> Transpositions {
+    let mut output: Transpositions = FxHashMap::default();
+
+    if engrave.concert_pitch {
+        return output;
+    }
+
+    for instrument in instruments {
+        if let Some(def) = get_def(&instrument.id) {
+            if !def.transposition.is_unison() {
+                for stave_key in &instrument.staves {
+                    output.insert(stave_key.clone(), def.transposition);
+                }
+            }
+        }
+    }
+
+    output
+}
+
+/// Get the written interval for a stave
+pub fn get_transposition<'a>(stave_key: &str, transpositions: &'a Transpositions) -> &'a Interval {
+    match transpositions.get(stave_key) {
+        Some(interval) => interval,
+        None => &Interval::UNISON,
+    }
+}
@@ -607,6 +607,34 @@ impl NotationTrack {
         }
     }
 
+    /// Irregular bars (ie. pickups) are filled with regular rests rather than a bar rest
+    pub fn split_irregular_bars(&mut self, barlines: &Bars, subdivisions: Ticks) {
+        for (tick, time_signature) in barlines {
+            if time_signature.is_irregular() {
+                for beat in 2..=time_signature.beats {
+                    let at = time_signature.get_tick_at_beat(*tick, beat, subdivisions);
+                    if let Some((_, entry)) = self.get_previous_notation(at) {
+                        if entry.is_rest() {
+                            self.split(at);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Is the entry at a tick a rest filling a whole regular bar
+    pub fn is_full_bar_rest(&self, tick: &Tick, barlines: &Bars) -> bool {
+        match self.track.get(tick) {
+            Some(entry) if entry.is_rest() => {
+                barlines.get(tick).is_some_and(|bar| !bar.is_irregular())
+                    && (barlines.contains_key(&(tick + entry.duration))
+                        || tick + entry.duration == self.length)
+            }
+            _ => false,
+        }
+    }
+
     pub fn split_unwritable(&mut self, barlines: &Bars, subdivisions: Ticks) {
         for (i, time) in barlines {
             for tick in *i..*i + time.ticks_per_bar(subdivisions) {
@@ -689,6 +717,7 @@ impl Track {
         notation.split_at_tone_events(self);
         notation.split_measures(barlines);
         notation.split_as_per_meter(barlines, subdivisions);
+        notation.split_irregular_bars(barlines, subdivisions);
         notation.split_unwritable(barlines, subdivisions);
         notation
     }
@@ -696,9 +725,14 @@ impl Track {
 
 #[cfg(test)]
 mod tests {
+    use crate::components::duration::NoteDuration;
+    use crate::entries::time_signature::TimeSignatureDrawType;
     use crate::entries::tone::Tone;
+    use crate::parse::get_bars::get_bars;
     use crate::parse::get_written_durations::Clusters;
     use crate::parse::get_written_durations::Notation;
+    use crate::score::tracks::Track;
+    use crate::Engine;
     use rustc_hash::FxHashMap;
     use rustc_hash::FxHashSet;
 
@@ -767,4 +801,32 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn irregular_bar_rests_test() {
+        let mut engine = Engine::tester();
+        let flow_key = engine.score.flows.order[0].clone();
+        engine.create_time_signature(
+            &flow_key,
+            0,
+            4,
+            NoteDuration::Quarter,
+            TimeSignatureDrawType::Regular,
+            None,
+        );
+        engine.set_pickup_bar(&flow_key, 144);
+
+        let flow = &engine.score.flows.by_key[&flow_key];
+        let bars = get_bars(flow, &engine.score.tracks);
+        let notation = Track::new().to_notation_track(&flow.length, &bars, flow.subdivisions);
+
+        // a rest per beat in the pickup, then bar rests
+        for tick in [0, 48, 96] {
+            assert_eq!(notation.track[&tick].duration, 48);
+            assert!(!notation.is_full_bar_rest(&tick, &bars));
+        }
+        assert_eq!(notation.track[&144].duration, 192);
+        assert!(notation.is_full_bar_rest(&144, &bars));
+        assert!(notation.is_full_bar_rest(&(144 + 192), &bars));
+    }
 }
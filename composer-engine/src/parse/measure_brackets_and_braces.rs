@@ -26,9 +26,9 @@ pub fn measure_brackets(
 
     // BRACES of variable widths
     for brace in &spans.braces {
-        let top = vertical_spacing.staves.get(&brace.start).unwrap();
-        let bottom = vertical_spacing.staves.get(&brace.stop).unwrap();
-        let height: Space = (bottom.y + bottom.height) - top.y;
+        let (top, _) = vertical_spacing.stave_extent(&brace.start).unwrap();
+        let (_, bottom) = vertical_spacing.stave_extent(&brace.stop).unwrap();
+        let height: Space = bottom - top;
         let width = height * 0.1;
         if width > max {
             max = width;
@@ -37,3 +37,43 @@ pub fn measure_brackets(
 
     max
 }
+
+#[cfg(test)]
+mod tests {
+    use super::measure_brackets;
+    use crate::parse::get_vertical_spans::get_vertical_spans;
+    use crate::parse::measure_vertical_spacing::measure_vertical_spacing;
+    use crate::score::engrave::LayoutType;
+    use crate::score::instruments::StaveDisplay;
+    use crate::score::players::PlayerType;
+    use crate::Engine;
+
+    #[test]
+    /// a braced instrument shown only as tablature has no notation staves
+    fn tablature_brace_test() {
+        let mut engine = Engine::tester();
+        let flow_key = engine.score.flows.order[0].clone();
+        let player_key = engine.create_player(PlayerType::Solo);
+        let instrument_key = engine.create_instrument("keyboard.piano");
+        engine
+            .score
+            .instruments
+            .get_mut(&instrument_key)
+            .unwrap()
+            .tuning = Some(vec![64, 59]);
+        engine.assign_instrument_to_player(&player_key, &instrument_key);
+        engine.set_instrument_stave_display(&instrument_key, StaveDisplay::Tablature);
+
+        let engrave = engine.get_engrave_by_type(LayoutType::Score).unwrap();
+        let (flow, instruments, _, _) = engine.get_flow_instruments(&flow_key);
+        let spacing = measure_vertical_spacing(&instruments, &flow.staves, engrave);
+        let spans = get_vertical_spans(&instruments, engrave);
+        assert!(spacing.staves.is_empty());
+        assert_eq!(spans.braces.len(), 1);
+
+        let (top, _) = spacing.stave_extent(&spans.braces[0].start).unwrap();
+        let (_, bottom) = spacing.stave_extent(&spans.braces[0].stop).unwrap();
+        let width = measure_brackets(&spacing, &spans, engrave);
+        assert_eq!(width, (bottom - top) * 0.1);
+    }
+}
@@ -1,8 +1,9 @@
 use super::get_barlines::Barlines;
 use super::get_beams::BeamsByTrack;
+use super::get_transpositions::Transpositions;
 use super::get_written_durations::NotationByTrack;
 use super::{get_accidentals::AccidentalsByTrack, get_shunts::ShuntsByTrack};
-use crate::components::measurements::BoundingBox;
+use crate::components::interval::Interval;
 use crate::components::misc::Tick;
 use crate::components::units::Space;
 use crate::entries::barline::BarlineDrawType;
@@ -21,6 +22,7 @@ pub enum Position {
     PaddingStart = 0,
     EndRepeat,
     Clef,
+    Courtesy, // key & time signatures at the end of the bar before a change
     Barline,
     KeySignature,
     TimeSignature,
@@ -45,14 +47,15 @@ impl From<usize> for Position {
             0 => Position::PaddingStart,
             1 => Position::EndRepeat,
             2 => Position::Clef,
-            3 => Position::Barline,
-            4 => Position::KeySignature,
-            5 => Position::TimeSignature,
-            6 => Position::StartRepeat,
-            7 => Position::Accidentals,
-            8 => Position::PreNoteSlot,
-            9 => Position::NoteSpacing,
-            10 => Position::PaddingEnd,
+            3 => Position::Courtesy,
+            4 => Position::Barline,
+            5 => Position::KeySignature,
+            6 => Position::TimeSignature,
+            7 => Position::StartRepeat,
+            8 => Position::Accidentals,
+            9 => Position::PreNoteSlot,
+            10 => Position::NoteSpacing,
+            11 => Position::PaddingEnd,
             _ => Position::PaddingStart,
         }
     }
@@ -112,6 +115,7 @@ pub fn measure_horizontal_spacing(
     shunts_by_track: &ShuntsByTrack,
     beams_by_track: &BeamsByTrack,
     accidentals_by_track: &AccidentalsByTrack,
+    transpositions: &Transpositions,
     engrave: &Engrave,
 ) -> HorizontalSpacing {
     let mut widths: Vec<f32> = vec![0.0; (flow.length * POSITION_COUNT) as usize];
@@ -123,16 +127,22 @@ pub fn measure_horizontal_spacing(
         match entry {
             Entry::KeySignature(key_signature) => {
                 let start = (entry.tick() * POSITION_COUNT) as usize;
-                let metrics = if key_signature.offset == 0 {
-                    // find width needed to cancel the previous key signature
-                    match flow_master.get_key_signature_before_tick(key_signature.tick) {
-                        Some(previous) => previous.metrics(),
-                        None => BoundingBox::none(),
+                // transposed staves may need a wider key signature than concert pitch
+                let intervals = std::iter::once(&Interval::UNISON).chain(transpositions.values());
+                for interval in intervals {
+                    let key_signature = key_signature.transpose(interval);
+                    let previous = flow_master
+                        .get_key_signature_before_tick(key_signature.tick)
+                        .map(|previous| previous.transpose(interval));
+                    let metrics = key_signature.metrics_with_cancellations(
+                        previous.as_ref(),
+                        &engrave.key_signature_cancellation,
+                    );
+                    let width = metrics.width + metrics.padding.right;
+                    if width > widths[start + Position::KeySignature] {
+                        widths[start + Position::KeySignature] = width;
                     }
-                } else {
-                    key_signature.metrics()
-                };
-                widths[start + Position::KeySignature] = metrics.width + metrics.padding.right;
+                }
             }
             Entry::TimeSignature(time_signature) => {
                 let start = (entry.tick() * POSITION_COUNT) as usize;
@@ -143,6 +153,19 @@ pub fn measure_horizontal_spacing(
         }
     }
 
+    if engrave.courtesy_signatures {
+        for entry in flow_master.entries.by_key.values() {
+            match entry {
+                Entry::KeySignature(_) | Entry::TimeSignature(_) if entry.tick() > 0 => {
+                    let start = (entry.tick() * POSITION_COUNT) as usize;
+                    widths[start + Position::Courtesy] = widths[start + Position::KeySignature]
+                        + widths[start + Position::TimeSignature];
+                }
+                _ => (),
+            }
+        }
+    }
+
     for (tick, def) in barlines {
         let start = (tick * POSITION_COUNT) as usize;
         let time = flow_master.get_time_signature_at_tick(tick);
@@ -181,7 +204,7 @@ pub fn measure_horizontal_spacing(
         for entry in stave_master.entries.by_key.values() {
             if let Entry::Clef(clef) = entry {
                 let start = (clef.tick * POSITION_COUNT) as usize;
-                let metrics = clef.metrics();
+                let metrics = clef.metrics(stave_master.is_initial_clef(clef));
                 widths[start + Position::Clef] = metrics.width + metrics.padding.right;
             }
         }
@@ -1,7 +1,7 @@
 use crate::components::units::Space;
 use crate::score::engrave::Engrave;
 use crate::score::instruments::Instrument;
-use crate::score::stave::Stave;
+use crate::score::stave::{Stave, TAB_LINE_SPACING};
 use rustc_hash::FxHashMap;
 
 #[derive(Debug)]
@@ -15,6 +15,7 @@ pub struct VerticalSpacing {
     pub height: Space,
     pub instruments: FxHashMap<String, VerticalPosition>,
     pub staves: FxHashMap<String, VerticalPosition>,
+    pub tablatures: FxHashMap<String, VerticalPosition>, // keyed by the stave they show
 }
 
 impl VerticalSpacing {
@@ -23,8 +24,21 @@ impl VerticalSpacing {
             height: 0.0,
             instruments: FxHashMap::default(),
             staves: FxHashMap::default(),
+            tablatures: FxHashMap::default(),
         }
     }
+
+    /// The top and bottom of a stave's notation and tablature, either may be hidden
+    pub fn stave_extent(&self, stave_key: &str) -> Option<(Space, Space)> {
+        let entries = [self.staves.get(stave_key), self.tablatures.get(stave_key)];
+        let mut entries = entries.iter().flatten();
+        let first = entries.next()?;
+        Some(
+            entries.fold((first.y, first.y + first.height), |(top, bottom), entry| {
+                (top.min(entry.y), bottom.max(entry.y + entry.height))
+            }),
+        )
+    }
 }
 
 pub fn measure_vertical_spacing(
@@ -44,23 +58,47 @@ pub fn measure_vertical_spacing(
             height: 0.0,
         };
 
-        for (ii, stave_key) in instrument.staves.iter().enumerate() {
+        let mut ii = 0;
+        for stave_key in &instrument.staves {
             let stave = staves.get(stave_key).unwrap();
 
-            if 1 > 0 && ii > 0 {
-                output.height += engrave.stave_spacing;
-                instrument_entry.height += engrave.stave_spacing;
+            if instrument.has_notation() {
+                if ii > 0 {
+                    output.height += engrave.stave_spacing;
+                    instrument_entry.height += engrave.stave_spacing;
+                }
+
+                let stave_entry = VerticalPosition {
+                    y: output.height + ((stave.lines.len() - 1) / 2) as Space,
+                    height: (stave.lines.len() - 1) as Space,
+                };
+
+                output.height += stave_entry.height;
+                instrument_entry.height += stave_entry.height;
+
+                output.staves.insert(stave_key.clone(), stave_entry);
+                ii += 1;
             }
 
-            let stave_entry = VerticalPosition {
-                y: output.height + ((stave.lines.len() - 1) / 2) as Space,
-                height: (stave.lines.len() - 1) as Space,
-            };
+            if instrument.has_tablature() {
+                if ii > 0 {
+                    output.height += engrave.stave_spacing;
+                    instrument_entry.height += engrave.stave_spacing;
+                }
 
-            output.height += stave_entry.height;
-            instrument_entry.height += stave_entry.height;
+                let strings = instrument.tuning.as_ref().unwrap().len();
+                let height = (strings.max(1) - 1) as Space * TAB_LINE_SPACING;
+                let tablature_entry = VerticalPosition {
+                    y: output.height + (height / 2.0),
+                    height,
+                };
 
-            output.staves.insert(stave_key.clone(), stave_entry);
+                output.height += tablature_entry.height;
+                instrument_entry.height += tablature_entry.height;
+
+                output.tablatures.insert(stave_key.clone(), tablature_entry);
+                ii += 1;
+            }
         }
 
         output
@@ -70,3 +108,50 @@ pub fn measure_vertical_spacing(
 
     output
 }
+
+#[cfg(test)]
+mod tests {
+    use super::measure_vertical_spacing;
+    use crate::score::engrave::LayoutType;
+    use crate::score::instruments::StaveDisplay;
+    use crate::score::players::PlayerType;
+    use crate::score::stave::TAB_LINE_SPACING;
+    use crate::Engine;
+
+    fn run(display: StaveDisplay) -> (usize, usize, f32) {
+        let mut engine = Engine::tester();
+        let flow_key = engine.score.flows.order[0].clone();
+        let player_key = engine.create_player(PlayerType::Solo);
+        let instrument_key = engine.create_instrument("guitar.acoustic");
+        engine.assign_instrument_to_player(&player_key, &instrument_key);
+        engine.set_instrument_stave_display(&instrument_key, display);
+
+        let engrave = engine.get_engrave_by_type(LayoutType::Score).unwrap();
+        let (flow, instruments, _, _) = engine.get_flow_instruments(&flow_key);
+        let spacing = measure_vertical_spacing(&instruments, &flow.staves, engrave);
+        (
+            spacing.staves.len(),
+            spacing.tablatures.len(),
+            spacing.height,
+        )
+    }
+
+    #[test]
+    fn notation_test() {
+        assert_eq!(run(StaveDisplay::Notation), (1, 0, 4.0));
+    }
+
+    #[test]
+    fn tablature_test() {
+        assert_eq!(run(StaveDisplay::Tablature), (0, 1, 5.0 * TAB_LINE_SPACING));
+    }
+
+    #[test]
+    fn notation_and_tablature_test() {
+        // six strings below the stave with the stave spacing between
+        assert_eq!(
+            run(StaveDisplay::NotationAndTablature),
+            (1, 1, 4.0 + 6.0 + 5.0 * TAB_LINE_SPACING)
+        );
+    }
+}
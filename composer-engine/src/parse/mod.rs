@@ -10,11 +10,13 @@ mod draw_key_signatures;
 mod draw_ledger_lines;
 mod draw_names;
 mod draw_noteheads;
+mod draw_octave_shifts;
 mod draw_rests;
 mod draw_staves;
 mod draw_stems;
 mod draw_sub_brackets;
 mod draw_systemic_barline;
+mod draw_tablature;
 mod draw_ties;
 mod draw_time_signatures;
 mod get_accidentals;
@@ -25,7 +27,9 @@ mod get_dots;
 mod get_shunts;
 mod get_stem_directions;
 mod get_stem_lengths;
+mod get_tab_positions;
 mod get_tone_offsets;
+mod get_transpositions;
 mod get_vertical_spans;
 pub mod get_written_durations;
 mod measure_brackets_and_braces;
@@ -36,6 +40,7 @@ mod measure_vertical_spacing;
 use crate::components::measurements::{CurvePoint, Point};
 use crate::components::units::{Converter, Space};
 use crate::score::engrave::LayoutType;
+use crate::score::stave::Stave;
 use crate::Engine;
 use draw_accidentals::draw_accidentals;
 use draw_barlines::draw_barlines;
@@ -49,11 +54,13 @@ use draw_key_signatures::draw_key_signatures;
 use draw_ledger_lines::draw_ledger_lines;
 use draw_names::draw_names;
 use draw_noteheads::draw_noteheads;
+use draw_octave_shifts::draw_octave_shifts;
 use draw_rests::draw_rests;
 use draw_staves::draw_staves;
 use draw_stems::draw_stems;
 use draw_sub_brackets::draw_sub_brackets;
 use draw_systemic_barline::draw_systemic_barline;
+use draw_tablature::draw_tablature;
 use draw_ties::draw_ties;
 use draw_time_signatures::draw_time_signatures;
 use get_accidentals::get_accidentals;
@@ -64,7 +71,9 @@ use get_dots::get_dots;
 use get_shunts::get_note_shunts;
 use get_stem_directions::get_stem_directions;
 use get_stem_lengths::get_stem_lengths;
+use get_tab_positions::get_tab_positions;
 use get_tone_offsets::get_tone_offsets;
+use get_transpositions::get_transpositions;
 use get_vertical_spans::get_vertical_spans;
 use get_written_durations::get_written_durations;
 use js_sys::Function;
@@ -129,8 +138,18 @@ impl Engine {
         let name_widths = measure_instrument_names(&instruments, engrave, &converter, measure);
         let bracket_widths = measure_brackets(&vertical_spacing, &vertical_spans, engrave);
         let bars = get_bars(flow, &self.score.tracks);
-        let tone_offsets = get_tone_offsets(flow.length, &staves, &self.score.tracks);
+        let transpositions = get_transpositions(&instruments, engrave);
+        let tone_offsets =
+            get_tone_offsets(flow.length, &staves, &self.score.tracks, &transpositions);
         let barlines = get_barlines(flow, &self.score.tracks);
+        let tab_positions = get_tab_positions(flow, &instruments, &self.score.tracks);
+
+        // staves shown only as tablature have no standard notation drawn
+        let notation_staves: Vec<&Stave> = staves
+            .iter()
+            .filter(|stave| vertical_spacing.staves.contains_key(&stave.key))
+            .cloned()
+            .collect();
 
         let notations = get_written_durations(flow, &tracks, &bars);
 
@@ -138,8 +157,16 @@ impl Engine {
         let stem_directions = get_stem_directions(&notations, &tone_offsets, &beams);
         let shunts = get_note_shunts(&notations, &tone_offsets, &stem_directions);
         let dots = get_dots(flow, &notations, &tone_offsets);
-        let accidentals =
-            get_accidentals(flow, &self.score.tracks, &notations, &bars, &tone_offsets);
+        let accidentals = get_accidentals(
+            flow,
+            &instruments,
+            &self.score.tracks,
+            &notations,
+            &bars,
+            &tone_offsets,
+            &transpositions,
+            engrave,
+        );
 
         let horizontal_spacing = measure_horizontal_spacing(
             flow,
@@ -150,6 +177,7 @@ impl Engine {
             &shunts,
             &beams,
             &accidentals,
+            &transpositions,
             engrave,
         );
 
@@ -214,11 +242,24 @@ impl Engine {
             &mut instructions,
         );
         draw_staves(
-            &staves,
+            &notation_staves,
+            padding_left + name_widths + instrument_name_gap + bracket_widths,
+            padding_top,
+            horizontal_spacing.width,
+            &vertical_spacing,
+            &converter,
+            &mut instructions,
+        );
+        draw_tablature(
             padding_left + name_widths + instrument_name_gap + bracket_widths,
             padding_top,
             horizontal_spacing.width,
+            flow,
+            &instruments,
+            &self.score.tracks,
+            &tab_positions,
             &vertical_spacing,
+            &horizontal_spacing,
             &converter,
             &mut instructions,
         );
@@ -226,7 +267,7 @@ impl Engine {
             padding_left + name_widths + instrument_name_gap + bracket_widths,
             padding_top,
             &barlines,
-            &staves,
+            &notation_staves,
             &vertical_spacing,
             &vertical_spans,
             &horizontal_spacing,
@@ -238,10 +279,12 @@ impl Engine {
             padding_left + name_widths + instrument_name_gap + bracket_widths,
             padding_top,
             flow,
-            &staves,
+            &notation_staves,
             &self.score.tracks,
+            &transpositions,
             &vertical_spacing,
             &horizontal_spacing,
+            engrave,
             &converter,
             &mut instructions,
         );
@@ -249,17 +292,18 @@ impl Engine {
             padding_left + name_widths + instrument_name_gap + bracket_widths,
             padding_top,
             flow,
-            &staves,
+            &notation_staves,
             &self.score.tracks,
             &vertical_spacing,
             &horizontal_spacing,
+            engrave,
             &converter,
             &mut instructions,
         );
         draw_clefs(
             padding_left + name_widths + instrument_name_gap + bracket_widths,
             padding_top,
-            &staves,
+            &notation_staves,
             &self.score.tracks,
             &vertical_spacing,
             &horizontal_spacing,
@@ -271,7 +315,7 @@ impl Engine {
             padding_left + name_widths + instrument_name_gap + bracket_widths,
             padding_top,
             flow,
-            &staves,
+            &notation_staves,
             &notations,
             &horizontal_spacing,
             &vertical_spacing,
@@ -283,20 +327,21 @@ impl Engine {
         draw_accidentals(
             padding_left + name_widths + instrument_name_gap + bracket_widths,
             padding_top,
-            &staves,
+            &notation_staves,
             &notations,
             &horizontal_spacing,
             &vertical_spacing,
             &tone_offsets,
             &shunts,
             &accidentals,
+            engrave,
             &converter,
             &mut instructions,
         );
         draw_ledger_lines(
             padding_left + name_widths + instrument_name_gap + bracket_widths,
             padding_top,
-            &staves,
+            &notation_staves,
             &notations,
             &horizontal_spacing,
             &vertical_spacing,
@@ -309,7 +354,7 @@ impl Engine {
             padding_left + name_widths + instrument_name_gap + bracket_widths,
             padding_top,
             flow,
-            &staves,
+            &notation_staves,
             &notations,
             &horizontal_spacing,
             &vertical_spacing,
@@ -321,7 +366,7 @@ impl Engine {
         draw_dots(
             padding_left + name_widths + instrument_name_gap + bracket_widths,
             padding_top,
-            &staves,
+            &notation_staves,
             &notations,
             &vertical_spacing,
             &horizontal_spacing,
@@ -333,7 +378,7 @@ impl Engine {
         draw_stems(
             padding_left + name_widths + instrument_name_gap + bracket_widths,
             padding_top,
-            &staves,
+            &notation_staves,
             &vertical_spacing,
             &stem_lengths,
             &converter,
@@ -343,7 +388,7 @@ impl Engine {
             padding_left + name_widths + instrument_name_gap + bracket_widths,
             padding_top,
             flow,
-            &staves,
+            &notation_staves,
             &notations,
             &stem_directions,
             &stem_lengths,
@@ -355,7 +400,7 @@ impl Engine {
         draw_beams(
             padding_left + name_widths + instrument_name_gap + bracket_widths,
             padding_top,
-            &staves,
+            &notation_staves,
             &vertical_spacing,
             &stem_lengths,
             &beams,
@@ -365,7 +410,7 @@ impl Engine {
         draw_ties(
             padding_left + name_widths + instrument_name_gap + bracket_widths,
             padding_top,
-            &staves,
+            &notation_staves,
             &notations,
             &stem_directions,
             &vertical_spacing,
@@ -375,6 +420,18 @@ impl Engine {
             &converter,
             &mut instructions,
         );
+        draw_octave_shifts(
+            padding_left + name_widths + instrument_name_gap + bracket_widths,
+            padding_top,
+            flow,
+            &notation_staves,
+            &self.score.tracks,
+            &tone_offsets,
+            &vertical_spacing,
+            &horizontal_spacing,
+            &converter,
+            &mut instructions,
+        );
 
         let width = converter.spaces_to_px(width);
         let height = converter.spaces_to_px(height);
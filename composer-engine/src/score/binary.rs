@@ -0,0 +1,545 @@
+use super::migrations::{self, ImportError, ImportErrorKind};
+use crate::Engine;
+use rustc_hash::FxHashMap;
+use serde_json::{Map, Number, Value};
+use std::convert::TryInto;
+use wasm_bindgen::prelude::{wasm_bindgen, JsValue};
+
+/// The compact binary document format.
+///
+/// The document is stored as a tree of tagged values after a table of every string in it,
+/// so the shortid keys repeated between maps and lists are only stored once and are
+/// otherwise referenced by index. Integers are varints and maps of entries are written
+/// in tick order with each tick as the distance from the one before.
+const MAGIC: &[u8; 4] = b"SCB\0";
+const REVISION: u64 = 1;
+
+const NULL: u8 = 0;
+const FALSE: u8 = 1;
+const TRUE: u8 = 2;
+const UINT: u8 = 3;
+const INT: u8 = 4; // negative integers, zigzag
+const F32: u8 = 5; // floats that are exact as f32, ie. serialized from f32 fields
+const F64: u8 = 6;
+const STRING: u8 = 7;
+const ARRAY: u8 = 8;
+const OBJECT: u8 = 9;
+const TICK_KEYS: u8 = 10; // an object keyed by tick, ie. `by_tick`
+const BY_TICK: u8 = 11; // an object of entries that each have a tick, ie. `by_key`
+
+/// Scores are only a few levels deep, this stops a malformed document exhausting the stack
+const MAX_DEPTH: usize = 128;
+
+/// Strings referenced by index can decode to more than the document's own size, but not
+/// by more than this factor, so a malformed document can't exhaust memory
+const MAX_EXPANSION: usize = 32;
+
+/// Where the tick of a value is, entries are either `{ tick }` or `{ Variant: { tick } }`
+fn tick_of(value: &Value) -> Option<(u64, bool)> {
+    let object = value.as_object()?;
+    if let Some(tick) = object.get("tick") {
+        return tick.as_u64().map(|tick| (tick, false));
+    }
+    match object.values().next() {
+        Some(Value::Object(inner)) if object.len() == 1 => {
+            inner.get("tick")?.as_u64().map(|tick| (tick, true))
+        }
+        _ => None,
+    }
+}
+
+fn without_tick(value: &Value, is_nested: bool) -> Value {
+    let mut value = value.clone();
+    let object = match is_nested {
+        true => value.as_object_mut().unwrap().values_mut().next().unwrap(),
+        false => &mut value,
+    };
+    object.as_object_mut().unwrap().remove("tick");
+    value
+}
+
+fn with_tick(mut value: Value, tick: u64, is_nested: bool) -> Option<Value> {
+    let object = match is_nested {
+        true => value.as_object_mut()?.values_mut().next()?,
+        false => &mut value,
+    };
+    object
+        .as_object_mut()?
+        .insert(String::from("tick"), tick.into());
+    Some(value)
+}
+
+fn tick_keys(object: &Map<String, Value>) -> Option<Vec<(u64, &Value)>> {
+    let mut entries = object
+        .iter()
+        .map(|(key, value)| match key.parse::<u32>() {
+            Ok(tick) if tick.to_string() == *key => Some((tick as u64, value)),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    entries.sort_by_key(|(tick, _)| *tick);
+    Some(entries)
+}
+
+fn ticked(object: &Map<String, Value>) -> Option<Vec<(&String, u64, bool, &Value)>> {
+    let mut entries = object
+        .iter()
+        .map(|(key, value)| tick_of(value).map(|(tick, is_nested)| (key, tick, is_nested, value)))
+        .collect::<Option<Vec<_>>>()?;
+    entries.sort_by_key(|(_, tick, _, _)| *tick);
+    Some(entries)
+}
+
+fn count_strings<'a>(value: &'a Value, counts: &mut FxHashMap<&'a str, usize>) {
+    match value {
+        Value::String(string) => *counts.entry(string).or_default() += 1,
+        Value::Array(values) => values.iter().for_each(|value| count_strings(value, counts)),
+        Value::Object(object) => {
+            let is_tick_keys = !object.is_empty() && tick_keys(object).is_some();
+            for (key, value) in object {
+                if !is_tick_keys {
+                    *counts.entry(key).or_default() += 1;
+                }
+                count_strings(value, counts);
+            }
+        }
+        _ => (),
+    }
+}
+
+struct Writer {
+    bytes: Vec<u8>,
+    strings: FxHashMap<String, u64>,
+}
+
+impl Writer {
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.bytes.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.bytes.push(value as u8);
+    }
+
+    fn string(&mut self, value: &str) {
+        let index = *self.strings.get(value).unwrap();
+        self.varint(index);
+    }
+
+    fn value(&mut self, value: &Value) {
+        match value {
+            Value::Null => self.bytes.push(NULL),
+            Value::Bool(false) => self.bytes.push(FALSE),
+            Value::Bool(true) => self.bytes.push(TRUE),
+            Value::Number(number) => {
+                if let Some(number) = number.as_u64() {
+                    self.bytes.push(UINT);
+                    self.varint(number);
+                } else if let Some(number) = number.as_i64() {
+                    self.bytes.push(INT);
+                    self.varint(((number << 1) ^ (number >> 63)) as u64);
+                } else {
+                    let number = number.as_f64().unwrap();
+                    if number as f32 as f64 == number {
+                        self.bytes.push(F32);
+                        self.bytes.extend((number as f32).to_le_bytes());
+                    } else {
+                        self.bytes.push(F64);
+                        self.bytes.extend(number.to_le_bytes());
+                    }
+                }
+            }
+            Value::String(string) => {
+                self.bytes.push(STRING);
+                self.string(string);
+            }
+            Value::Array(values) => {
+                self.bytes.push(ARRAY);
+                self.varint(values.len() as u64);
+                values.iter().for_each(|value| self.value(value));
+            }
+            Value::Object(object) => self.object(object),
+        }
+    }
+
+    fn object(&mut self, object: &Map<String, Value>) {
+        if object.is_empty() {
+            self.bytes.push(OBJECT);
+            self.varint(0);
+            return;
+        }
+
+        if let Some(entries) = tick_keys(object) {
+            self.bytes.push(TICK_KEYS);
+            self.varint(entries.len() as u64);
+            let mut previous = 0;
+            for (tick, value) in entries {
+                self.varint(tick - previous);
+                self.value(value);
+                previous = tick;
+            }
+        } else if let Some(entries) = ticked(object) {
+            self.bytes.push(BY_TICK);
+            self.varint(entries.len() as u64);
+            let mut previous = 0;
+            for (key, tick, is_nested, value) in entries {
+                self.string(key);
+                self.varint((tick - previous) << 1 | is_nested as u64);
+                self.value(&without_tick(value, is_nested));
+                previous = tick;
+            }
+        } else {
+            self.bytes.push(OBJECT);
+            self.varint(object.len() as u64);
+            for (key, value) in object {
+                self.string(key);
+                self.value(value);
+            }
+        }
+    }
+}
+
+/// Add the distance from the previous tick, ticks are u32 in the score
+fn add_tick(tick: u64, delta: u64) -> Result<u64, String> {
+    match tick.checked_add(delta) {
+        Some(tick) if tick <= u32::MAX as u64 => Ok(tick),
+        _ => Err(String::from("invalid tick")),
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    strings: Vec<String>,
+    depth: usize,
+    decoded: usize, // bytes of strings referenced so far
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], String> {
+        let end = self.position + length;
+        let bytes = self
+            .bytes
+            .get(self.position..end)
+            .ok_or("unexpected end of document")?;
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn varint(&mut self) -> Result<u64, String> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte < 0x80 {
+                return Ok(value);
+            }
+        }
+        Err(String::from("invalid number"))
+    }
+
+    fn length(&mut self) -> Result<usize, String> {
+        let length = self.varint()? as usize;
+        // every item takes at least a byte
+        match length <= self.bytes.len() - self.position {
+            true => Ok(length),
+            false => Err(String::from("unexpected end of document")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let index = self.varint()? as usize;
+        let string = match self.strings.get(index) {
+            Some(string) => string,
+            None => return Err(format!("unknown string {}", index)),
+        };
+        self.decoded += string.len();
+        if self.decoded > self.bytes.len().saturating_mul(MAX_EXPANSION) {
+            return Err(String::from("document is too large"));
+        }
+        Ok(string.clone())
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(String::from("document is nested too deeply"));
+        }
+
+        let value = match self.byte()? {
+            NULL => Value::Null,
+            FALSE => Value::Bool(false),
+            TRUE => Value::Bool(true),
+            UINT => self.varint()?.into(),
+            INT => {
+                let value = self.varint()?;
+                ((value >> 1) as i64 ^ -((value & 1) as i64)).into()
+            }
+            F32 => {
+                let bytes = self.take(4)?.try_into().unwrap();
+                Number::from_f64(f32::from_le_bytes(bytes) as f64)
+                    .map(Value::Number)
+                    .ok_or("invalid float")?
+            }
+            F64 => {
+                let bytes = self.take(8)?.try_into().unwrap();
+                Number::from_f64(f64::from_le_bytes(bytes))
+                    .map(Value::Number)
+                    .ok_or("invalid float")?
+            }
+            STRING => Value::String(self.string()?),
+            ARRAY => {
+                let length = self.length()?;
+                let values = (0..length)
+                    .map(|_| self.value())
+                    .collect::<Result<Vec<_>, _>>()?;
+                Value::Array(values)
+            }
+            OBJECT => {
+                let mut object = Map::new();
+                for _ in 0..self.length()? {
+                    let key = self.string()?;
+                    object.insert(key, self.value()?);
+                }
+                Value::Object(object)
+            }
+            TICK_KEYS => {
+                let mut object = Map::new();
+                let mut tick = 0;
+                for _ in 0..self.length()? {
+                    let delta = self.varint()?;
+                    tick = add_tick(tick, delta)?;
+                    object.insert(tick.to_string(), self.value()?);
+                }
+                Value::Object(object)
+            }
+            BY_TICK => {
+                let mut object = Map::new();
+                let mut tick = 0;
+                for _ in 0..self.length()? {
+                    let key = self.string()?;
+                    let delta = self.varint()?;
+                    tick = add_tick(tick, delta >> 1)?;
+                    let value =
+                        with_tick(self.value()?, tick, delta & 1 == 1).ok_or("invalid entry")?;
+                    object.insert(key, value);
+                }
+                Value::Object(object)
+            }
+            tag => return Err(format!("unknown value type {}", tag)),
+        };
+
+        self.depth -= 1;
+        Ok(value)
+    }
+}
+
+/// Encode a document in the binary format
+pub fn encode(document: &Value) -> Vec<u8> {
+    let mut counts = FxHashMap::default();
+    count_strings(document, &mut counts);
+    // the most used strings get the shortest indexes
+    let mut strings: Vec<(&str, usize)> = counts.into_iter().collect();
+    strings.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+
+    let mut writer = Writer {
+        bytes: MAGIC.to_vec(),
+        strings: FxHashMap::default(),
+    };
+    writer.varint(REVISION);
+    writer.varint(strings.len() as u64);
+    for (index, (string, _)) in strings.iter().enumerate() {
+        writer.varint(string.len() as u64);
+        writer.bytes.extend(string.as_bytes());
+        writer.strings.insert(String::from(*string), index as u64);
+    }
+    writer.value(document);
+    writer.bytes
+}
+
+/// Decode a document from the binary format
+pub fn decode(bytes: &[u8]) -> Result<Value, String> {
+    if !bytes.starts_with(MAGIC) {
+        return Err(String::from("not a binary score document"));
+    }
+
+    let mut reader = Reader {
+        bytes,
+        position: MAGIC.len(),
+        strings: Vec::new(),
+        depth: 0,
+        decoded: 0,
+    };
+    let revision = reader.varint()?;
+    if revision != REVISION {
+        return Err(format!("unsupported binary revision {}", revision));
+    }
+    for _ in 0..reader.length()? {
+        let length = reader.length()?;
+        let string =
+            std::str::from_utf8(reader.take(length)?).map_err(|error| error.to_string())?;
+        reader.strings.push(String::from(string));
+    }
+
+    let document = reader.value()?;
+    match reader.position == bytes.len() {
+        true => Ok(document),
+        false => Err(String::from("unexpected data after document")),
+    }
+}
+
+#[wasm_bindgen]
+impl Engine {
+    /// Save the score in the compact binary format
+    pub fn export_binary(&self) -> Vec<u8> {
+        encode(&serde_json::to_value(&self.score).unwrap())
+    }
+
+    /// Replace the score with a document in the compact binary format, older format
+    /// versions are migrated. Throws an `ImportError` if it can't be read.
+    pub fn import_binary(&mut self, bytes: &[u8]) -> Result<(), JsValue> {
+        let result = decode(bytes)
+            .map_err(|message| ImportError::new(ImportErrorKind::Unreadable, None, &message))
+            .and_then(migrations::parse);
+
+        match result {
+            Ok(score) => {
+                self.score = score;
+                self.emit();
+                Ok(())
+            }
+            Err(error) => Err(serde_wasm_bindgen::to_value(&error).unwrap()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode, Writer, ARRAY, BY_TICK, MAGIC, NULL, REVISION, STRING, TICK_KEYS};
+    use crate::components::articulation::Articulation;
+    use crate::components::pitch::{Accidental, Pitch};
+    use crate::components::velocity::Velocity;
+    use crate::entries::tone::Tone;
+    use crate::entries::Entry;
+    use crate::score::engrave::{Engrave, LayoutType};
+    use crate::score::tracks::Track;
+    use crate::utils::shortid;
+    use rustc_hash::FxHashMap;
+    use serde_json::json;
+
+    #[test]
+    fn round_trip_test() {
+        let document = json!({
+            "version": 1,
+            "empty": {},
+            "list": [null, true, false, -3, 0, 300, 1.75, 0.1, "a", "a"],
+            "by_tick": { "0": ["a"], "960": ["b", "c"], "48": [] },
+            "by_key": {
+                "a": { "Tone": { "tick": 960, "duration": 48 } },
+                "b": { "tick": 48, "x": { "y": 1 } },
+                "c": { "Clef": { "tick": 0 } }
+            },
+            "not_ticks": { "01": 1, "2": 2 }
+        });
+        let bytes = encode(&document);
+        assert_eq!(decode(&bytes).unwrap(), document);
+    }
+
+    #[test]
+    fn round_trip_score_test() {
+        let mut track = Track::new();
+        for tick in [0, 48, 96, 480] {
+            track.insert(Entry::Tone(Tone::new(
+                shortid(),
+                tick,
+                48,
+                Pitch::new(60, Accidental::Natural),
+                Velocity::new(80),
+                Articulation::None,
+            )));
+        }
+        let engrave = Engrave::new(LayoutType::Score, String::from("Score"));
+        let document = json!({ "tracks": [track], "engrave": engrave });
+
+        let bytes = encode(&document);
+        let decoded = decode(&bytes).unwrap();
+        assert_eq!(decoded, document);
+        let track: Track = serde_json::from_value(decoded["tracks"][0].clone()).unwrap();
+        assert_eq!(track.entries.by_key.len(), 4);
+
+        // smaller than the JSON representation
+        assert!(bytes.len() < serde_json::to_vec(&document).unwrap().len());
+    }
+
+    #[test]
+    fn invalid_test() {
+        assert!(decode(b"{}").is_err());
+        let bytes = encode(&json!({ "a": [1, 2, 3] }));
+        assert!(decode(&bytes[..bytes.len() - 1]).is_err());
+        assert!(decode(&[&bytes[..], &[0]].concat()).is_err());
+
+        // a document with the string "a" and then the body
+        let document = |body: &dyn Fn(&mut Writer)| {
+            let mut writer = Writer {
+                bytes: MAGIC.to_vec(),
+                strings: FxHashMap::default(),
+            };
+            writer.varint(REVISION);
+            writer.varint(1);
+            writer.varint(1);
+            writer.bytes.push(b'a');
+            body(&mut writer);
+            decode(&writer.bytes)
+        };
+
+        let nested = document(&|writer| {
+            for _ in 0..1000 {
+                writer.bytes.push(ARRAY);
+                writer.varint(1);
+            }
+            writer.bytes.push(NULL);
+        });
+        assert_eq!(nested.unwrap_err(), "document is nested too deeply");
+
+        for deltas in [[u32::MAX as u64, 1], [u64::MAX, 1]] {
+            let tick_keys = document(&|writer| {
+                writer.bytes.push(TICK_KEYS);
+                writer.varint(2);
+                for delta in deltas {
+                    writer.varint(delta);
+                    writer.bytes.push(NULL);
+                }
+            });
+            assert_eq!(tick_keys.unwrap_err(), "invalid tick");
+        }
+
+        let by_tick = document(&|writer| {
+            writer.bytes.push(BY_TICK);
+            writer.varint(1);
+            writer.varint(0);
+            writer.varint((u32::MAX as u64 + 1) << 1);
+            writer.bytes.push(NULL);
+        });
+        assert_eq!(by_tick.unwrap_err(), "invalid tick");
+
+        // a long string referenced many times
+        let mut writer = Writer {
+            bytes: MAGIC.to_vec(),
+            strings: FxHashMap::default(),
+        };
+        writer.varint(REVISION);
+        writer.varint(1);
+        writer.varint(10_000);
+        writer.bytes.extend([b'a'; 10_000]);
+        writer.bytes.push(ARRAY);
+        writer.varint(100_000);
+        for _ in 0..100_000 {
+            writer.bytes.extend([STRING, 0]);
+        }
+        assert_eq!(decode(&writer.bytes).unwrap_err(), "document is too large");
+    }
+}
@@ -1,3 +1,7 @@
+use crate::components::misc::Tick;
+use crate::components::pitch::Pitch;
+use crate::components::tuning::{Tuning, TuningSystem};
+use crate::entries::Entry;
 use crate::Engine;
 use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::wasm_bindgen;
@@ -33,12 +37,15 @@ impl Default for AutoCount {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
     pub auto_count: AutoCount,
+    #[serde(default)]
+    pub tuning: Tuning,
 }
 
 impl Config {
     pub fn new() -> Config {
         Config {
             auto_count: AutoCount::new(),
+            tuning: Tuning::new(),
         }
     }
 }
@@ -77,3 +84,81 @@ impl Engine {
         self.emit();
     }
 }
+
+impl Engine {
+    /// The tonic of the key active at a tick in the flow containing the track
+    fn get_tonic_at_tick(&self, track_key: &str, tick: Tick) -> Option<Pitch> {
+        self.get_flow_master_for_track(track_key)
+            .and_then(|master| master.get_key_signature_before_tick(tick + 1))
+            .and_then(|key_signature| key_signature.tonic())
+    }
+
+    /// The MIDI number of a pitch at a tick as played back in the score tuning
+    pub fn get_tuned_pitch(&self, track_key: &str, tick: Tick, pitch: &Pitch) -> f64 {
+        let tonic = self.get_tonic_at_tick(track_key, tick);
+        self.score.config.tuning.to_midi(pitch, tonic.as_ref())
+    }
+}
+
+#[wasm_bindgen]
+impl Engine {
+    #[wasm_bindgen(getter)]
+    pub fn tuning_reference(&self) -> f64 {
+        self.score.config.tuning.reference
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_tuning_reference(&mut self, value: f64) {
+        self.score.config.tuning.reference = value;
+
+        self.emit();
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn tuning_system(&self) -> TuningSystem {
+        self.score.config.tuning.system
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_tuning_system(&mut self, value: TuningSystem) {
+        self.score.config.tuning.system = value;
+
+        self.emit();
+    }
+
+    /// Cents offsets from equal temperament for each pitch class (C, C#, D ... B)
+    pub fn get_tuning_custom(&self) -> Vec<f64> {
+        self.score.config.tuning.custom.to_vec()
+    }
+
+    pub fn set_tuning_custom(&mut self, cents: Vec<f64>) {
+        for (i, value) in cents.iter().take(12).enumerate() {
+            self.score.config.tuning.custom[i] = *value;
+        }
+
+        self.emit();
+    }
+
+    /// The MIDI number of a tone for playback, fractional if tuned away from equal temperament
+    pub fn get_tone_playback_pitch(&self, track_key: &str, entry_key: &str) -> f64 {
+        let track = self.score.tracks.get(track_key).unwrap();
+        match track.entries.by_key.get(entry_key) {
+            Some(Entry::Tone(tone)) => self.get_tuned_pitch(track_key, tone.tick, &tone.pitch),
+            _ => 0.0,
+        }
+    }
+
+    pub fn get_tone_frequency(&self, track_key: &str, entry_key: &str) -> f64 {
+        let track = self.score.tracks.get(track_key).unwrap();
+        match track.entries.by_key.get(entry_key) {
+            Some(Entry::Tone(tone)) => {
+                let tonic = self.get_tonic_at_tick(track_key, tone.tick);
+                self.score
+                    .config
+                    .tuning
+                    .to_frequency(&tone.pitch, tonic.as_ref())
+            }
+            _ => 0.0,
+        }
+    }
+}
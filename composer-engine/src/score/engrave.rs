@@ -24,6 +24,15 @@ pub enum BracketStyle {
     Line,
 }
 
+/// When to draw naturals cancelling the previous key signature
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[wasm_bindgen]
+pub enum KeySignatureCancellation {
+    Always,
+    Never,
+    ToCMajor, // only when changing to a key signature with no accidentals
+}
+
 #[derive(PartialEq, Eq, Debug, Serialize, Deserialize)]
 #[wasm_bindgen]
 pub enum LayoutType {
@@ -32,6 +41,15 @@ pub enum LayoutType {
     Custom,
 }
 
+/// Rules for courtesy accidentals that aren't strictly needed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CautionaryAccidentals {
+    pub after_bar: bool, // in the bar following an alteration (or a tie across the barline)
+    pub octaves: bool,   // when the same note is altered in another octave in the bar
+    pub voices: bool,    // when altered in another voice or stave of the same instrument
+    pub parentheses: bool, // draw cautionary accidentals in parentheses
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Engraves {
     pub order: Vec<String>,
@@ -60,6 +78,7 @@ pub struct Engrave {
     pub display_name: String,
 
     pub space: Mm,
+    pub concert_pitch: bool,
 
     pub frame_padding: PaddingMm,
     pub instrument_spacing: Space,
@@ -75,6 +94,10 @@ pub struct Engrave {
     pub bracket_single_staves: bool,
     pub sub_bracket: bool,
 
+    pub key_signature_cancellation: KeySignatureCancellation,
+    pub courtesy_signatures: bool,
+    pub cautionary_accidentals: CautionaryAccidentals,
+
     pub base_note_space: Space,
     pub minimum_note_space: Space,
     pub minimum_tie_space: Space,
@@ -85,12 +108,16 @@ pub struct Engrave {
 
 impl Engrave {
     pub fn new(layout_type: LayoutType, display_name: String) -> Engrave {
+        // parts are read by the players so are transposed by default
+        let concert_pitch = layout_type != LayoutType::Part;
+
         Engrave {
             key: shortid(),
             layout_type,
             display_name,
 
             space: 2.0,
+            concert_pitch,
 
             frame_padding: PaddingMm::new(35.0, 25.0, 35.0, 25.0),
             instrument_spacing: 8.0,
@@ -118,6 +145,15 @@ impl Engrave {
             bracket_single_staves: false,
             sub_bracket: true,
 
+            key_signature_cancellation: KeySignatureCancellation::ToCMajor,
+            courtesy_signatures: false,
+            cautionary_accidentals: CautionaryAccidentals {
+                after_bar: true,
+                octaves: false,
+                voices: false,
+                parentheses: true,
+            },
+
             base_note_space: 3.0,
             minimum_note_space: 0.6,
             minimum_tie_space: 2.0,
@@ -224,6 +260,78 @@ impl Engine {
         self.emit();
     }
 
+    pub fn get_key_signature_cancellation(&self, key: &str) -> KeySignatureCancellation {
+        let config = self.score.engrave.by_key.get(key).unwrap();
+        config.key_signature_cancellation.clone()
+    }
+
+    pub fn set_key_signature_cancellation(&mut self, key: &str, value: KeySignatureCancellation) {
+        let config = self.score.engrave.by_key.get_mut(key).unwrap();
+        config.key_signature_cancellation = value;
+
+        self.emit();
+    }
+
+    pub fn get_courtesy_signatures(&self, key: &str) -> bool {
+        let config = self.score.engrave.by_key.get(key).unwrap();
+        config.courtesy_signatures
+    }
+
+    pub fn set_courtesy_signatures(&mut self, key: &str, value: bool) {
+        let config = self.score.engrave.by_key.get_mut(key).unwrap();
+        config.courtesy_signatures = value;
+
+        self.emit();
+    }
+
+    pub fn get_cautionary_after_bar(&self, key: &str) -> bool {
+        let config = self.score.engrave.by_key.get(key).unwrap();
+        config.cautionary_accidentals.after_bar
+    }
+
+    pub fn set_cautionary_after_bar(&mut self, key: &str, value: bool) {
+        let config = self.score.engrave.by_key.get_mut(key).unwrap();
+        config.cautionary_accidentals.after_bar = value;
+
+        self.emit();
+    }
+
+    pub fn get_cautionary_octaves(&self, key: &str) -> bool {
+        let config = self.score.engrave.by_key.get(key).unwrap();
+        config.cautionary_accidentals.octaves
+    }
+
+    pub fn set_cautionary_octaves(&mut self, key: &str, value: bool) {
+        let config = self.score.engrave.by_key.get_mut(key).unwrap();
+        config.cautionary_accidentals.octaves = value;
+
+        self.emit();
+    }
+
+    pub fn get_cautionary_voices(&self, key: &str) -> bool {
+        let config = self.score.engrave.by_key.get(key).unwrap();
+        config.cautionary_accidentals.voices
+    }
+
+    pub fn set_cautionary_voices(&mut self, key: &str, value: bool) {
+        let config = self.score.engrave.by_key.get_mut(key).unwrap();
+        config.cautionary_accidentals.voices = value;
+
+        self.emit();
+    }
+
+    pub fn get_cautionary_parentheses(&self, key: &str) -> bool {
+        let config = self.score.engrave.by_key.get(key).unwrap();
+        config.cautionary_accidentals.parentheses
+    }
+
+    pub fn set_cautionary_parentheses(&mut self, key: &str, value: bool) {
+        let config = self.score.engrave.by_key.get_mut(key).unwrap();
+        config.cautionary_accidentals.parentheses = value;
+
+        self.emit();
+    }
+
     pub fn get_base_note_space(&self, key: &str) -> f32 {
         let config = self.score.engrave.by_key.get(key).unwrap();
         config.base_note_space
@@ -272,6 +380,18 @@ impl Engine {
         self.emit();
     }
 
+    pub fn get_concert_pitch(&self, key: &str) -> bool {
+        let config = self.score.engrave.by_key.get(key).unwrap();
+        config.concert_pitch
+    }
+
+    pub fn set_concert_pitch(&mut self, key: &str, value: bool) {
+        let config = self.score.engrave.by_key.get_mut(key).unwrap();
+        config.concert_pitch = value;
+
+        self.emit();
+    }
+
     pub fn get_space(&self, key: &str) -> f32 {
         let config = self.score.engrave.by_key.get(key).unwrap();
         config.space
@@ -8,6 +8,7 @@ use crate::entries::clef::Clef;
 use crate::entries::key_signature::{KeySignature, KeySignatureMode};
 use crate::entries::time_signature::{TimeSignature, TimeSignatureDrawType};
 use crate::entries::Entry;
+use crate::parse::get_bars::get_bars;
 use crate::utils::shortid;
 use crate::Engine;
 use rustc_hash::{FxHashMap, FxHashSet};
@@ -102,6 +103,38 @@ impl Engine {
         }
         (flow, instruments, staves, tracks)
     }
+
+    /// The start tick of every bar in the flow, in order
+    pub fn get_bar_starts(&self, flow_key: &str) -> Vec<Tick> {
+        let flow = self.score.flows.by_key.get(flow_key).unwrap();
+        let mut starts: Vec<Tick> = get_bars(flow, &self.score.tracks).into_keys().collect();
+        starts.sort_unstable();
+        starts
+    }
+
+    /// The flow master, stave masters and voice tracks of a flow
+    pub fn get_flow_track_keys(&self, flow_key: &str) -> Vec<String> {
+        let flow = self.score.flows.by_key.get(flow_key).unwrap();
+        let mut output = vec![flow.master.clone()];
+        for stave in flow.staves.values() {
+            output.push(stave.master.clone());
+            output.extend(stave.tracks.iter().cloned());
+        }
+        output
+    }
+
+    /// Find the master track of the flow containing the track
+    pub fn get_flow_master_for_track(&self, track_key: &str) -> Option<&Track> {
+        for flow in self.score.flows.by_key.values() {
+            for stave in flow.staves.values() {
+                if stave.tracks.iter().any(|key| key == track_key) {
+                    return self.score.tracks.get(&flow.master);
+                }
+            }
+        }
+
+        None
+    }
 }
 
 #[wasm_bindgen]
@@ -191,6 +224,68 @@ impl Engine {
         self.emit();
     }
 
+    /// Insert empty bars before a bar (0 is the first bar, or anacrusis) using the meter of
+    /// the bar before, any change of meter at the bar moves with it. Bars past the end of
+    /// the flow are appended.
+    pub fn insert_bars(&mut self, flow_key: &str, at_bar: usize, count: usize) {
+        let flow = self.score.flows.by_key.get(flow_key).unwrap();
+        let master = self.score.tracks.get(&flow.master).unwrap();
+
+        let starts = self.get_bar_starts(flow_key);
+        let at = match starts.get(at_bar) {
+            Some(tick) => *tick,
+            None => flow.length,
+        };
+
+        let meter = match at {
+            0 => master.get_time_signature_at_tick(&0),
+            _ => master.get_time_signature_before_tick(at),
+        };
+        let ticks_per_bar = match meter {
+            Some(time_signature) if time_signature.beats > 0 => {
+                time_signature.ticks_per_bar(flow.subdivisions)
+            }
+            _ => flow.subdivisions * 4,
+        };
+        let ticks = ticks_per_bar * count as Ticks;
+
+        for track_key in self.get_flow_track_keys(flow_key) {
+            let track = self.score.tracks.get_mut(&track_key).unwrap();
+            track.insert_ticks(at, ticks);
+        }
+
+        let flow = self.score.flows.by_key.get_mut(flow_key).unwrap();
+        flow.length += ticks;
+
+        self.emit();
+    }
+
+    /// Delete bars starting from a bar (0 is the first bar, or anacrusis)
+    pub fn delete_bars(&mut self, flow_key: &str, from_bar: usize, count: usize) {
+        let flow = self.score.flows.by_key.get(flow_key).unwrap();
+        let length = flow.length;
+
+        let starts = self.get_bar_starts(flow_key);
+        let start = match starts.get(from_bar) {
+            Some(tick) => *tick,
+            None => return,
+        };
+        let end = match starts.get(from_bar + count) {
+            Some(tick) => *tick,
+            None => length,
+        };
+
+        for track_key in self.get_flow_track_keys(flow_key) {
+            let track = self.score.tracks.get_mut(&track_key).unwrap();
+            track.delete_ticks(start, end);
+        }
+
+        let flow = self.score.flows.by_key.get_mut(flow_key).unwrap();
+        flow.length -= end - start;
+
+        self.emit();
+    }
+
     /**
      * Assign a player to a flow
      */
@@ -221,11 +316,12 @@ impl Engine {
             let stave_def = def.staves.get(i).unwrap();
 
             let mut master = Track::new();
-            let clef = Entry::Clef(Clef::new(
+            let clef = Entry::Clef(Clef::new_octave(
                 0,
                 stave_def.clef.pitch.int,
                 stave_def.clef.offset,
                 stave_def.clef.draw_as.clone(),
+                stave_def.clef.octave,
             ));
             master.insert(clef);
 
@@ -331,30 +427,125 @@ impl Engine {
     pub fn get_timestamp(&self, flow_key: &str, at: Tick) -> String {
         let flow = self.score.flows.by_key.get(flow_key).unwrap();
         let master = self.score.tracks.get(&flow.master).unwrap();
+        let bars = get_bars(flow, &self.score.tracks);
 
-        let mut time_signature = &TimeSignature::default();
-        let mut bar: u32 = 0;
+        let mut starts: Vec<&Tick> = bars.keys().filter(|tick| **tick <= at).collect();
+        starts.sort_unstable();
 
-        for tick in 0..flow.length {
-            if let Some(entry) = master.get_time_signature_at_tick(&tick) {
-                time_signature = entry;
+        let start = match starts.last() {
+            Some(start) => **start,
+            None => return String::from("1:1:0.000"),
+        };
+
+        // an anacrusis is bar 0 and its beats are counted from the end of the bar
+        let bar = bars.get(&start).unwrap();
+        let has_anacrusis = bars.get(&0).is_some_and(|bar| bar.is_irregular());
+        let number = if has_anacrusis {
+            starts.len() - 1
+        } else {
+            starts.len()
+        };
+
+        let mut distance = (at - start) as f32;
+        let mut time_signature = bar;
+        if has_anacrusis && start == 0 {
+            if let Some(entry) = master.get_time_signature_at_tick(&0) {
+                let ticks_per_bar = entry.ticks_per_bar(flow.subdivisions);
+                if entry.pickup < ticks_per_bar {
+                    distance += (ticks_per_bar - entry.pickup) as f32;
+                    time_signature = entry;
+                }
             }
+        }
 
-            let distance = time_signature.distance_from_barline(tick, flow.subdivisions) as f32;
+        let ticks_per_beat = time_signature.beat_type.to_ticks(flow.subdivisions) as f32;
+        let beats = (distance / ticks_per_beat).floor() + 1.0;
+        let half_beats = (distance % ticks_per_beat) / (ticks_per_beat / 2.0);
 
-            if distance == 0.0 {
-                bar += 1;
-            };
+        format!("{}:{:.0}:{:.3}", number, beats.floor(), half_beats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::components::articulation::Articulation;
+    use crate::components::duration::NoteDuration;
+    use crate::components::misc::Tick;
+    use crate::entries::key_signature::KeySignatureMode;
+    use crate::entries::time_signature::TimeSignatureDrawType;
+    use crate::score::players::PlayerType;
+    use crate::Engine;
+
+    fn tick(engine: &Engine, track_key: &str, entry_key: &str) -> Tick {
+        engine.score.tracks[track_key].entries.by_key[entry_key].tick()
+    }
 
-            if at == tick {
-                let ticks_per_beat = time_signature.beat_type.to_ticks(flow.subdivisions) as f32;
-                let beats = (distance / ticks_per_beat).floor() + 1.0;
-                let half_beats = (distance % ticks_per_beat) / (ticks_per_beat / 2.0);
+    #[test]
+    fn insert_and_delete_bars_test() {
+        let mut engine = Engine::tester();
+        let flow_key = engine.score.flows.order[0].clone();
+        let player_key = engine.create_player(PlayerType::Solo);
+        let violin = engine.create_instrument("strings.violin");
+        let piano = engine.create_instrument("keyboard.piano");
+        engine.assign_instrument_to_player(&player_key, &violin);
+        engine.assign_instrument_to_player(&player_key, &piano);
+
+        let mut tones = Vec::new();
+        for instrument_key in [&violin, &piano] {
+            let stave_key = engine.score.instruments[instrument_key].staves[0].clone();
+            let track_key =
+                engine.score.flows.by_key[&flow_key].staves[&stave_key].tracks[0].clone();
+            let tone_key =
+                engine.create_tone(&track_key, 192, 48, 60, None, 100, Articulation::None);
+            tones.push((track_key, tone_key));
+        }
+        engine.create_key_signature(&flow_key, 384, KeySignatureMode::Major, 1);
+        let master = engine.score.flows.by_key[&flow_key].master.clone();
+        let key_signature = engine.score.tracks[&master]
+            .get_key_signature_at_tick(&384)
+            .unwrap()
+            .key
+            .clone();
+
+        engine.insert_bars(&flow_key, 1, 2);
+        assert_eq!(engine.score.flows.by_key[&flow_key].length, 768 + 384);
+        for (track_key, tone_key) in &tones {
+            assert_eq!(tick(&engine, track_key, tone_key), 192 + 384);
+        }
+        assert_eq!(tick(&engine, &master, &key_signature), 384 + 384);
 
-                return format!("{}:{:.0}:{:.3}", bar, beats.floor(), half_beats);
-            };
+        engine.delete_bars(&flow_key, 1, 2);
+        assert_eq!(engine.score.flows.by_key[&flow_key].length, 768);
+        for (track_key, tone_key) in &tones {
+            assert_eq!(tick(&engine, track_key, tone_key), 192);
         }
+        assert_eq!(tick(&engine, &master, &key_signature), 384);
 
-        String::from("1:1:0.000")
+        // bars inserted before a change of meter are in the meter before it
+        engine.create_time_signature(
+            &flow_key,
+            0,
+            4,
+            NoteDuration::Quarter,
+            TimeSignatureDrawType::Regular,
+            None,
+        );
+        engine.create_time_signature(
+            &flow_key,
+            384,
+            3,
+            NoteDuration::Quarter,
+            TimeSignatureDrawType::Regular,
+            None,
+        );
+        engine.insert_bars(&flow_key, 2, 1);
+        assert_eq!(
+            engine.get_bar_starts(&flow_key),
+            vec![0, 192, 384, 576, 720, 864]
+        );
+        let time_signature = engine.score.tracks[&master]
+            .get_time_signature_before_tick(577)
+            .unwrap();
+        assert_eq!((time_signature.tick, time_signature.beats), (576, 3));
     }
 }
@@ -1,3 +1,4 @@
+use crate::components::interval::Interval;
 use crate::entries::clef::{Clef, ClefDrawType};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -32,6 +33,8 @@ pub struct InstrumentDef {
     pub path: Vec<&'static str>,
     pub long_name: &'static str,
     pub short_name: &'static str,
+    pub tuning: Option<Vec<u8>>, // open strings, highest first, for fretted instruments
+    pub transposition: Interval, // written pitch relative to sounding pitch
     pub staves: Vec<StaveDef>,
     pub solo_patches: HashMap<Expression, &'static str>,
     pub section_patches: HashMap<Expression, &'static str>,
@@ -46,6 +49,8 @@ lazy_static! {
                 path: vec!["Brass", "Bass Trombone"],
                 long_name: "Bass Trombone",
                 short_name: "B. Tbn.",
+                tuning: None,
+                transposition: Interval::UNISON,
                 staves: vec![StaveDef {
                     lines: vec![1, 1, 1, 1, 1],
                     clef: Clef::new(0, 53, -2, ClefDrawType::F),
@@ -63,6 +68,8 @@ lazy_static! {
                 path: vec!["Brass", "Horn", "F"],
                 long_name: "Horn in F",
                 short_name: "F Hn.",
+                tuning: None,
+                transposition: Interval::new(4, 7),
                 staves: vec![StaveDef {
                     lines: vec![1, 1, 1, 1, 1],
                     clef: Clef::new(0, 67, 2, ClefDrawType::G),
@@ -84,6 +91,8 @@ lazy_static! {
                 path: vec!["Brass", "Trombone"],
                 long_name: "Trombone",
                 short_name: "Tbn.",
+                tuning: None,
+                transposition: Interval::UNISON,
                 staves: vec![StaveDef {
                     lines: vec![1, 1, 1, 1, 1],
                     clef: Clef::new(0, 53, -2, ClefDrawType::F),
@@ -103,6 +112,8 @@ lazy_static! {
                 path: vec!["Brass", "Trumpet", "B${flat}"],
                 long_name: "Trumpet in B${flat}",
                 short_name: "B${flat} Tpt.",
+                tuning: None,
+                transposition: Interval::new(1, 2),
                 staves: vec![StaveDef {
                     lines: vec![1, 1, 1, 1, 1],
                     clef: Clef::new(0, 67, 2, ClefDrawType::G),
@@ -124,6 +135,8 @@ lazy_static! {
                 path: vec!["Brass", "Trumpet", "C"],
                 long_name: "Trumpet in C",
                 short_name: "C Tpt.",
+                tuning: None,
+                transposition: Interval::UNISON,
                 staves: vec![StaveDef {
                     lines: vec![1, 1, 1, 1, 1],
                     clef: Clef::new(0, 67, 2, ClefDrawType::G),
@@ -145,6 +158,8 @@ lazy_static! {
                 path: vec!["Brass", "Tuba"],
                 long_name: "Tuba",
                 short_name: "Tba.",
+                tuning: None,
+                transposition: Interval::UNISON,
                 staves: vec![StaveDef {
                     lines: vec![1, 1, 1, 1, 1],
                     clef: Clef::new(0, 53, -2, ClefDrawType::F),
@@ -164,9 +179,11 @@ lazy_static! {
                 path: vec!["Guitar", "Acoustic Guitar"],
                 long_name: "Acoustic Guitar",
                 short_name: "A. Gtr.",
+                tuning: Some(vec![64, 59, 55, 50, 45, 40]),
+                transposition: Interval::UNISON,
                 staves: vec![StaveDef {
                     lines: vec![1, 1, 1, 1, 1],
-                    clef: Clef::new(0, 53, -2, ClefDrawType::F),
+                    clef: Clef::new_octave(0, 55, 2, ClefDrawType::G, -1),
                 }],
                 solo_patches: hashmap! {
                     Expression::Natural => "/patches/acoustic-guitar/natural.json",
@@ -181,9 +198,11 @@ lazy_static! {
                 path: vec!["Guitar", "Bass Guitar"],
                 long_name: "Bass Guitar",
                 short_name: "B. Gtr.",
+                tuning: Some(vec![43, 38, 33, 28]),
+                transposition: Interval::UNISON,
                 staves: vec![StaveDef {
                     lines: vec![1, 1, 1, 1, 1],
-                    clef: Clef::new(0, 53, -2, ClefDrawType::F),
+                    clef: Clef::new_octave(0, 41, -2, ClefDrawType::F, -1),
                 }],
                 solo_patches: hashmap! {
                     Expression::Natural => "/patches/bass-guitar/natural.json",
@@ -198,9 +217,11 @@ lazy_static! {
                 path: vec!["Guitar", "Distortion Guitar"],
                 long_name: "Distortion Guitar",
                 short_name: "Gtr.",
+                tuning: Some(vec![64, 59, 55, 50, 45, 40]),
+                transposition: Interval::UNISON,
                 staves: vec![StaveDef {
                     lines: vec![1, 1, 1, 1, 1],
-                    clef: Clef::new(0, 53, -2, ClefDrawType::F),
+                    clef: Clef::new_octave(0, 55, 2, ClefDrawType::G, -1),
                 }],
                 solo_patches: hashmap! {
                     Expression::Natural => "/patches/distortion-guitar/natural.json",
@@ -215,6 +236,8 @@ lazy_static! {
                 path: vec!["Unpitched Percussion", "Crash Cymbal"],
                 long_name: "Crash Cymbal",
                 short_name: "Cym.",
+                tuning: None,
+                transposition: Interval::UNISON,
                 staves: vec![StaveDef {
                     lines: vec![1],
                     clef: Clef::new(0, 60, 0, ClefDrawType::Percussion),
@@ -232,6 +255,8 @@ lazy_static! {
                 path: vec!["Unpitched Percussion", "Hi-Hat"],
                 long_name: "Hi-Hat",
                 short_name: "HH.",
+                tuning: None,
+                transposition: Interval::UNISON,
                 staves: vec![StaveDef {
                     lines: vec![1],
                     clef: Clef::new(0, 60, 0, ClefDrawType::Percussion),
@@ -249,6 +274,8 @@ lazy_static! {
                 path: vec!["Unpitched Percussion", "Kick Drum"],
                 long_name: "Kick Drum",
                 short_name: "K Drm.",
+                tuning: None,
+                transposition: Interval::UNISON,
                 staves: vec![StaveDef {
                     lines: vec![1],
                     clef: Clef::new(0, 60, 0, ClefDrawType::Percussion),
@@ -266,6 +293,8 @@ lazy_static! {
                 path: vec!["Unpitched Percussion", "Snare"],
                 long_name: "Snare",
                 short_name: "Sn.",
+                tuning: None,
+                transposition: Interval::UNISON,
                 staves: vec![StaveDef {
                     lines: vec![1],
                     clef: Clef::new(0, 60, 0, ClefDrawType::Percussion),
@@ -283,6 +312,8 @@ lazy_static! {
                 path: vec!["Pitched Percussion", "Glockenspiel"],
                 long_name: "Glokenspiel",
                 short_name: "Glock.",
+                tuning: None,
+                transposition: Interval::UNISON,
                 staves: vec![StaveDef {
                     lines: vec![1, 1, 1, 1, 1],
                     clef: Clef::new(0, 67, 2, ClefDrawType::G),
@@ -300,6 +331,8 @@ lazy_static! {
                 path: vec!["Pitched Percussion", "Harp"],
                 long_name: "Harp",
                 short_name: "Hrp.",
+                tuning: None,
+                transposition: Interval::UNISON,
                 staves: vec![
                     StaveDef {
                         lines: vec![1, 1, 1, 1, 1],
@@ -323,6 +356,8 @@ lazy_static! {
                 path: vec!["Pitched Percussion", "Marimba"],
                 long_name: "Marimba",
                 short_name: "Mrm.",
+                tuning: None,
+                transposition: Interval::UNISON,
                 staves: vec![
                     StaveDef {
                         lines: vec![1, 1, 1, 1, 1],
@@ -346,6 +381,8 @@ lazy_static! {
                 path: vec!["Pitched Percussion", "Timpani"],
                 long_name: "Timpani",
                 short_name: "Timp.",
+                tuning: None,
+                transposition: Interval::UNISON,
                 staves: vec![StaveDef {
                     lines: vec![1, 1, 1, 1, 1],
                     clef: Clef::new(0, 53, -2, ClefDrawType::F),
@@ -365,6 +402,8 @@ lazy_static! {
                 path: vec!["Pitched Percussion", "Vibraphone"],
                 long_name: "Vibraphone",
                 short_name: "Vib.",
+                tuning: None,
+                transposition: Interval::UNISON,
                 staves: vec![StaveDef {
                     lines: vec![1, 1, 1, 1, 1],
                     clef: Clef::new(0, 67, 2, ClefDrawType::G),
@@ -382,6 +421,8 @@ lazy_static! {
                 path: vec!["Pitched Percussion", "Xylophone"],
                 long_name: "Xylophone",
                 short_name: "Xyl.",
+                tuning: None,
+                transposition: Interval::UNISON,
                 staves: vec![StaveDef {
                     lines: vec![1, 1, 1, 1, 1],
                     clef: Clef::new(0, 79, 2, ClefDrawType::G),
@@ -399,6 +440,8 @@ lazy_static! {
                 path: vec!["Keyboards", "Celesta"],
                 long_name: "Celesta",
                 short_name: "Cel.",
+                tuning: None,
+                transposition: Interval::UNISON,
                 staves: vec![
                     StaveDef {
                         lines: vec![1, 1, 1, 1, 1],
@@ -422,6 +465,8 @@ lazy_static! {
                 path: vec!["Keyboards", "Piano"],
                 long_name: "Piano",
                 short_name: "Pno.",
+                tuning: None,
+                transposition: Interval::UNISON,
                 staves: vec![
                     StaveDef {
                         lines: vec![1, 1, 1, 1, 1],
@@ -445,9 +490,11 @@ lazy_static! {
                 path: vec!["Strings", "Contrabass"],
                 long_name: "Contrabass",
                 short_name: "Cb.",
+                tuning: None,
+                transposition: Interval::UNISON,
                 staves: vec![StaveDef {
                     lines: vec![1, 1, 1, 1, 1],
-                    clef: Clef::new(0, 41, -2, ClefDrawType::F),
+                    clef: Clef::new_octave(0, 41, -2, ClefDrawType::F, -1),
                 }],
                 solo_patches: hashmap! {
                     Expression::Natural => "/patches/contrabass/natural.json",
@@ -466,6 +513,8 @@ lazy_static! {
                 path: vec!["Strings", "Viola"],
                 long_name: "Viola",
                 short_name: "Vla.",
+                tuning: None,
+                transposition: Interval::UNISON,
                 staves: vec![StaveDef {
                     lines: vec![1, 1, 1, 1, 1],
                     clef: Clef::new(0, 60, 0, ClefDrawType::C),
@@ -487,6 +536,8 @@ lazy_static! {
                 path: vec!["Strings", "Violin"],
                 long_name: "Violin",
                 short_name: "Vln.",
+                tuning: None,
+                transposition: Interval::UNISON,
                 staves: vec![StaveDef {
                     lines: vec![1, 1, 1, 1, 1],
                     clef: Clef::new(0, 67, 2, ClefDrawType::G),
@@ -510,6 +561,8 @@ lazy_static! {
                 path: vec!["Strings", "Violoncello"],
                 long_name: "Violoncello",
                 short_name: "Vc.",
+                tuning: None,
+                transposition: Interval::UNISON,
                 staves: vec![StaveDef {
                     lines: vec![1, 1, 1, 1, 1],
                     clef: Clef::new(0, 53, -2, ClefDrawType::F),
@@ -531,6 +584,8 @@ lazy_static! {
                 path: vec!["Woodwinds", "Alto Flute"],
                 long_name: "Alto Flute",
                 short_name: "A. Fl.",
+                tuning: None,
+                transposition: Interval::new(3, 5),
                 staves: vec![StaveDef {
                     lines: vec![1, 1, 1, 1, 1],
                     clef: Clef::new(0, 67, 2, ClefDrawType::G),
@@ -548,6 +603,8 @@ lazy_static! {
                 path: vec!["Woodwinds", "Alto Saxophone"],
                 long_name: "Alto Saxophone",
                 short_name: "A. Sax.",
+                tuning: None,
+                transposition: Interval::new(5, 9),
                 staves: vec![StaveDef {
                     lines: vec![1, 1, 1, 1, 1],
                     clef: Clef::new(0, 67, 2, ClefDrawType::G),
@@ -567,6 +624,8 @@ lazy_static! {
                 path: vec!["Woodwinds", "Bassoon"],
                 long_name: "Bassoon",
                 short_name: "Bsn.",
+                tuning: None,
+                transposition: Interval::UNISON,
                 staves: vec![StaveDef {
                     lines: vec![1, 1, 1, 1, 1],
                     clef: Clef::new(0, 53, -2, ClefDrawType::F),
@@ -586,9 +645,11 @@ lazy_static! {
                 path: vec!["Woodwinds", "Bass Clarinet"],
                 long_name: "Bass Clarinet",
                 short_name: "B. Cl.",
+                tuning: None,
+                transposition: Interval::new(8, 14),
                 staves: vec![StaveDef {
                     lines: vec![1, 1, 1, 1, 1],
-                    clef: Clef::new(0, 53, 2, ClefDrawType::G),
+                    clef: Clef::new(0, 67, 2, ClefDrawType::G),
                 }],
                 solo_patches: hashmap! {
                     Expression::Natural => "/patches/bass-clarinet/natural.json",
@@ -605,6 +666,8 @@ lazy_static! {
                 path: vec!["Woodwinds", "Clarinet", "A"],
                 long_name: "Clarinet in A",
                 short_name: "A Cl.",
+                tuning: None,
+                transposition: Interval::new(2, 3),
                 staves: vec![StaveDef {
                     lines: vec![1, 1, 1, 1, 1],
                     clef: Clef::new(0, 67, 2, ClefDrawType::G),
@@ -624,6 +687,8 @@ lazy_static! {
                 path: vec!["Woodwinds", "Clarinet", "B Flat"],
                 long_name: "Clarinet in B${flat}",
                 short_name: "B${flat} Cl.",
+                tuning: None,
+                transposition: Interval::new(1, 2),
                 staves: vec![StaveDef {
                     lines: vec![1, 1, 1, 1, 1],
                     clef: Clef::new(0, 67, 2, ClefDrawType::G),
@@ -643,6 +708,8 @@ lazy_static! {
                 path: vec!["Woodwinds", "Contrabassoon"],
                 long_name: "Contrabasson",
                 short_name: "Cbsn.",
+                tuning: None,
+                transposition: Interval::UNISON,
                 staves: vec![StaveDef {
                     lines: vec![1, 1, 1, 1, 1],
                     clef: Clef::new(0, 53, -2, ClefDrawType::F),
@@ -660,6 +727,8 @@ lazy_static! {
                 path: vec!["Woodwinds", "English Horn"],
                 long_name: "English Horn",
                 short_name: "E Hn.",
+                tuning: None,
+                transposition: Interval::new(4, 7),
                 staves: vec![StaveDef {
                     lines: vec![1, 1, 1, 1, 1],
                     clef: Clef::new(0, 67, 2, ClefDrawType::G),
@@ -679,6 +748,8 @@ lazy_static! {
                 path: vec!["Woodwinds", "Flute"],
                 long_name: "Flute",
                 short_name: "Fl.",
+                tuning: None,
+                transposition: Interval::UNISON,
                 staves: vec![StaveDef {
                     lines: vec![1, 1, 1, 1, 1],
                     clef: Clef::new(0, 67, 2, ClefDrawType::G),
@@ -698,6 +769,8 @@ lazy_static! {
                 path: vec!["Woodwinds", "Oboe"],
                 long_name: "Oboe",
                 short_name: "Ob.",
+                tuning: None,
+                transposition: Interval::UNISON,
                 staves: vec![StaveDef {
                     lines: vec![1, 1, 1, 1, 1],
                     clef: Clef::new(0, 67, 2, ClefDrawType::G),
@@ -717,6 +790,8 @@ lazy_static! {
                 path: vec!["Woodwinds", "Piccolo"],
                 long_name: "Piccolo",
                 short_name: "Pc.",
+                tuning: None,
+                transposition: Interval::UNISON,
                 staves: vec![StaveDef {
                     lines: vec![1, 1, 1, 1, 1],
                     clef: Clef::new(0, 79, 2, ClefDrawType::G),
@@ -10,6 +10,30 @@ use rustc_hash::FxHashMap;
 use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[wasm_bindgen]
+pub enum StaveDisplay {
+    Notation,
+    Tablature,
+    NotationAndTablature,
+}
+
+impl StaveDisplay {
+    pub fn has_notation(&self) -> bool {
+        matches!(
+            self,
+            StaveDisplay::Notation | StaveDisplay::NotationAndTablature
+        )
+    }
+
+    pub fn has_tablature(&self) -> bool {
+        matches!(
+            self,
+            StaveDisplay::Tablature | StaveDisplay::NotationAndTablature
+        )
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Instrument {
     pub key: String,
@@ -18,6 +42,8 @@ pub struct Instrument {
     pub long_name: String,
     pub short_name: String,
     pub staves: Vec<String>,
+    pub tuning: Option<Vec<u8>>, // open strings, highest first
+    pub stave_display: StaveDisplay,
     pub count: Option<String>,
     pub volume: u8, // 0-127
     pub solo: bool,
@@ -41,6 +67,15 @@ impl Instrument {
             None => self.long_name.clone(),
         }
     }
+
+    /// Only fretted instruments with a tuning can be shown as tablature
+    pub fn has_tablature(&self) -> bool {
+        self.tuning.is_some() && self.stave_display.has_tablature()
+    }
+
+    pub fn has_notation(&self) -> bool {
+        self.tuning.is_none() || self.stave_display.has_notation()
+    }
 }
 
 impl Engine {
@@ -89,6 +124,8 @@ impl Engine {
             long_name: String::from(def.long_name),
             short_name: String::from(def.short_name),
             staves,
+            tuning: def.tuning.clone(),
+            stave_display: StaveDisplay::Notation,
             count: None,
             volume: 80,
             solo: false,
@@ -156,6 +193,31 @@ impl Engine {
         self.emit();
     }
 
+    pub fn get_instrument_stave_display(&self, instrument_key: &str) -> StaveDisplay {
+        let instrument = self.score.instruments.get(instrument_key).unwrap();
+        instrument.stave_display.clone()
+    }
+
+    pub fn set_instrument_stave_display(&mut self, instrument_key: &str, value: StaveDisplay) {
+        let instrument = self.score.instruments.get_mut(instrument_key).unwrap();
+        instrument.stave_display = value;
+
+        self.emit();
+    }
+
+    pub fn get_instrument_tuning(&self, instrument_key: &str) -> JsValue {
+        let instrument = self.score.instruments.get(instrument_key).unwrap();
+        serde_wasm_bindgen::to_value(&instrument.tuning).unwrap()
+    }
+
+    /// Set the open string pitches (highest string first)
+    pub fn set_instrument_tuning(&mut self, instrument_key: &str, tuning: Vec<u8>) {
+        let instrument = self.score.instruments.get_mut(instrument_key).unwrap();
+        instrument.tuning = Some(tuning);
+
+        self.emit();
+    }
+
     pub fn get_instrument_staves(&self, instrument_key: &str) -> JsValue {
         let instrument = self.score.instruments.get(instrument_key).unwrap();
         serde_wasm_bindgen::to_value(&instrument.staves).unwrap()
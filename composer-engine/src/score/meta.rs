@@ -1,5 +1,5 @@
+use crate::utils::now;
 use crate::Engine;
-use js_sys::Date;
 use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::wasm_bindgen;
 
@@ -25,7 +25,7 @@ impl Meta {
             arranger: String::from(""),
             lyricist: String::from(""),
             copyright: String::from(""),
-            created: Date::now(),
+            created: now(),
         }
     }
 }
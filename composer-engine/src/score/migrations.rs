@@ -0,0 +1,336 @@
+use super::instruments::defs::get_def;
+use super::Score;
+use js_sys::{Array, Map, Object};
+use serde::Serialize;
+use serde_json::{json, Map as JsonMap, Number, Value};
+use std::convert::TryFrom;
+use wasm_bindgen::{JsCast, JsValue};
+
+/// The version of the saved score format. When the model changes in a way
+/// that older documents can't be read, bump this and add a migration.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Upgrade a document from one version to the next, `MIGRATIONS[n]` reads version n
+type Migration = fn(&mut JsonMap<String, Value>);
+
+const MIGRATIONS: [Migration; FORMAT_VERSION as usize] = [unversioned_to_1];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum ImportErrorKind {
+    Unreadable, // not a score document
+    TooNew,     // saved by a newer version of the application
+    Invalid,    // a score document that doesn't match the model after migrating
+}
+
+/// Why a saved score couldn't be imported
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ImportError {
+    pub kind: ImportErrorKind,
+    pub version: Option<u32>,
+    pub message: String,
+}
+
+impl ImportError {
+    pub fn new(kind: ImportErrorKind, version: Option<u32>, message: &str) -> Self {
+        Self {
+            kind,
+            version,
+            message: String::from(message),
+        }
+    }
+}
+
+/// Documents saved before the format was versioned have no version field,
+/// they are missing the transposition, accidental and tablature options.
+fn unversioned_to_1(document: &mut JsonMap<String, Value>) {
+    if let Some(Value::Object(by_key)) = document
+        .get_mut("engrave")
+        .and_then(|engrave| engrave.get_mut("by_key"))
+    {
+        for engrave in by_key.values_mut() {
+            if let Value::Object(engrave) = engrave {
+                // parts are read by the players so are transposed
+                let is_part = engrave.get("layout_type") == Some(&json!("Part"));
+                engrave.entry("concert_pitch").or_insert(json!(!is_part));
+                engrave
+                    .entry("key_signature_cancellation")
+                    .or_insert(json!("ToCMajor"));
+                engrave.entry("courtesy_signatures").or_insert(json!(false));
+                engrave.entry("cautionary_accidentals").or_insert(json!({
+                    "after_bar": true,
+                    "octaves": false,
+                    "voices": false,
+                    "parentheses": true,
+                }));
+            }
+        }
+    }
+
+    if let Some(Value::Object(instruments)) = document.get_mut("instruments") {
+        for instrument in instruments.values_mut() {
+            if let Value::Object(instrument) = instrument {
+                let tuning = instrument
+                    .get("id")
+                    .and_then(Value::as_str)
+                    .and_then(get_def)
+                    .and_then(|def| def.tuning.clone());
+                instrument.entry("tuning").or_insert(json!(tuning));
+                instrument
+                    .entry("stave_display")
+                    .or_insert(json!("Notation"));
+            }
+        }
+    }
+}
+
+/// Upgrade a saved document to the current format version
+pub fn migrate(document: Value) -> Result<Value, ImportError> {
+    let mut document = match document {
+        Value::Object(document) => document,
+        _ => {
+            return Err(ImportError::new(
+                ImportErrorKind::Unreadable,
+                None,
+                "the document is not an object",
+            ))
+        }
+    };
+
+    let version = match document.get("version") {
+        None => 0,
+        Some(value) => match value
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+        {
+            Some(version) => version,
+            None => {
+                return Err(ImportError::new(
+                    ImportErrorKind::Unreadable,
+                    None,
+                    "the format version is not a number",
+                ))
+            }
+        },
+    };
+
+    if version > FORMAT_VERSION {
+        return Err(ImportError::new(
+            ImportErrorKind::TooNew,
+            Some(version),
+            &format!(
+                "format version {} is newer than the supported version {}",
+                version, FORMAT_VERSION
+            ),
+        ));
+    }
+
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(&mut document);
+    }
+    document.insert(String::from("version"), json!(FORMAT_VERSION));
+
+    Ok(Value::Object(document))
+}
+
+/// Read a saved document of any supported version
+pub fn parse(document: Value) -> Result<Score, ImportError> {
+    let version = document
+        .get("version")
+        .and_then(Value::as_u64)
+        .map(|version| version as u32)
+        .unwrap_or(0);
+
+    let document = migrate(document)?;
+    serde_json::from_value(document).map_err(|error| {
+        ImportError::new(ImportErrorKind::Invalid, Some(version), &error.to_string())
+    })
+}
+
+/// Convert a saved document to JSON, maps (ie. from `Engine::export`) become objects
+pub fn from_js(value: &JsValue) -> Option<Value> {
+    if value.is_null() || value.is_undefined() {
+        Some(Value::Null)
+    } else if let Some(value) = value.as_bool() {
+        Some(Value::Bool(value))
+    } else if let Some(value) = value.as_f64() {
+        if value.fract() == 0.0 && value.abs() <= 9007199254740991.0 {
+            match value < 0.0 {
+                true => Some(json!(value as i64)),
+                false => Some(json!(value as u64)),
+            }
+        } else {
+            Number::from_f64(value).map(Value::Number)
+        }
+    } else if let Some(value) = value.as_string() {
+        Some(Value::String(value))
+    } else if Array::is_array(value) {
+        Array::from(value)
+            .iter()
+            .map(|entry| from_js(&entry))
+            .collect::<Option<Vec<Value>>>()
+            .map(Value::Array)
+    } else if let Some(map) = value.dyn_ref::<Map>() {
+        let mut output = JsonMap::new();
+        let mut is_valid = true;
+        map.for_each(&mut |entry, key| {
+            let key = match key.as_f64() {
+                Some(key) => Some(key.to_string()),
+                None => key.as_string(),
+            };
+            match (key, from_js(&entry)) {
+                (Some(key), Some(entry)) => {
+                    output.insert(key, entry);
+                }
+                _ => is_valid = false,
+            }
+        });
+        is_valid.then_some(Value::Object(output))
+    } else if value.is_object() {
+        let mut output = JsonMap::new();
+        for entry in Object::entries(value.unchecked_ref()).iter() {
+            let entry = Array::from(&entry);
+            output.insert(entry.get(0).as_string()?, from_js(&entry.get(1))?);
+        }
+        Some(Value::Object(output))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{migrate, parse, ImportErrorKind, FORMAT_VERSION};
+    use crate::components::articulation::Articulation;
+    use crate::score::engrave::{Engrave, KeySignatureCancellation, LayoutType};
+    use crate::score::players::PlayerType;
+    use crate::Engine;
+    use serde_json::{json, Value};
+
+    /// A score saved before the format was versioned
+    fn unversioned() -> Value {
+        let mut engrave =
+            serde_json::to_value(Engrave::new(LayoutType::Part, String::from("Part"))).unwrap();
+        let engrave = engrave.as_object_mut().unwrap();
+        for field in [
+            "concert_pitch",
+            "key_signature_cancellation",
+            "courtesy_signatures",
+            "cautionary_accidentals",
+        ] {
+            engrave.remove(field);
+        }
+        engrave.insert(String::from("key"), json!("a"));
+
+        json!({
+            "meta": {
+                "application_version": "1.0.0",
+                "title": "",
+                "subtitle": "",
+                "composer": "",
+                "arranger": "",
+                "lyricist": "",
+                "copyright": "",
+                "created": 0.0
+            },
+            "config": { "auto_count": { "solo": "Roman", "section": "Roman" } },
+            "engrave": { "order": ["a"], "by_key": { "a": engrave } },
+            "players": { "order": [], "by_key": {} },
+            "instruments": {
+                "b": {
+                    "key": "b",
+                    "id": "guitar.acoustic",
+                    "instrument_type": "Melodic",
+                    "long_name": "Guitar",
+                    "short_name": "Gtr.",
+                    "staves": [],
+                    "count": null,
+                    "volume": 80,
+                    "solo": false,
+                    "mute": false
+                }
+            },
+            "flows": { "order": [], "by_key": {} },
+            "tracks": {
+                "c": { "key": "c", "entries": { "by_tick": { "0": [] }, "by_key": {} } }
+            }
+        })
+    }
+
+    #[test]
+    fn migrate_unversioned_test() {
+        let score = parse(unversioned()).unwrap();
+        assert_eq!(score.version, FORMAT_VERSION);
+
+        let engrave = score.engrave.by_key.get("a").unwrap();
+        assert!(!engrave.concert_pitch);
+        assert!(engrave.cautionary_accidentals.after_bar);
+        assert_eq!(
+            engrave.key_signature_cancellation,
+            KeySignatureCancellation::ToCMajor
+        );
+
+        let instrument = score.instruments.get("b").unwrap();
+        assert!(instrument.has_notation());
+        assert!(score
+            .tracks
+            .get("c")
+            .unwrap()
+            .entries
+            .by_tick
+            .contains_key(&0));
+    }
+
+    #[test]
+    fn migrate_current_test() {
+        let mut document = migrate(unversioned()).unwrap();
+        document["engrave"]["by_key"]["a"]["concert_pitch"] = json!(true);
+        let score = parse(document).unwrap();
+        assert!(score.engrave.by_key.get("a").unwrap().concert_pitch);
+    }
+
+    #[test]
+    fn migrate_errors_test() {
+        let error = parse(json!([])).unwrap_err();
+        assert_eq!(error.kind, ImportErrorKind::Unreadable);
+
+        let mut document = unversioned();
+        document["version"] = json!(FORMAT_VERSION + 1);
+        let error = parse(document).unwrap_err();
+        assert_eq!(error.kind, ImportErrorKind::TooNew);
+        assert_eq!(error.version, Some(FORMAT_VERSION + 1));
+
+        let mut document = unversioned();
+        document.as_object_mut().unwrap().remove("tracks");
+        let error = parse(document).unwrap_err();
+        assert_eq!(error.kind, ImportErrorKind::Invalid);
+        assert_eq!(error.message, "missing field `tracks`");
+    }
+
+    #[test]
+    fn by_tick_round_trip_test() {
+        let mut engine = Engine::tester();
+        let flow_key = engine.score.flows.order[0].clone();
+        let player_key = engine.create_player(PlayerType::Solo);
+        let instrument_key = engine.create_instrument("strings.violin");
+        engine.assign_instrument_to_player(&player_key, &instrument_key);
+        let stave_key = engine.score.instruments[&instrument_key].staves[0].clone();
+        let track_key = engine.score.flows.by_key[&flow_key].staves[&stave_key].tracks[0].clone();
+        let tone_key = engine.create_tone(&track_key, 192, 48, 60, None, 100, Articulation::None);
+
+        // ticks are map keys so are saved as strings, as `from_js` does for numeric map keys
+        let mut document = serde_json::to_value(&engine.score).unwrap();
+        let by_tick = &document["tracks"][&track_key]["entries"]["by_tick"];
+        assert_eq!(by_tick["192"], json!([tone_key]));
+
+        // both as saved now and from before the format was versioned
+        for version in [Some(FORMAT_VERSION), None] {
+            if version.is_none() {
+                document.as_object_mut().unwrap().remove("version");
+            }
+            let score = parse(document.clone()).unwrap();
+            let track = &score.tracks[&track_key];
+            assert_eq!(track.entries.by_tick[&192], vec![tone_key.clone()]);
+            assert_eq!(track.entries.by_key[&tone_key].tick(), 192);
+        }
+    }
+}
@@ -1,8 +1,10 @@
+pub mod binary;
 mod config;
 pub mod engrave;
 pub mod flows;
 pub mod instruments;
 mod meta;
+pub mod migrations;
 pub mod players;
 pub mod stave;
 pub mod tracks;
@@ -12,6 +14,7 @@ use engrave::Engraves;
 use flows::Flows;
 use instruments::Instrument;
 use meta::Meta;
+use migrations::FORMAT_VERSION;
 use players::Players;
 use rustc_hash::FxHashMap;
 use serde::{Deserialize, Serialize};
@@ -21,6 +24,7 @@ use self::tracks::Tracks;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Score {
+    pub version: u32, // saved format version, see migrations
     pub meta: Meta,
     pub config: Config,
     pub engrave: Engraves,
@@ -33,6 +37,7 @@ pub struct Score {
 impl Score {
     pub fn new() -> Self {
         Score {
+            version: FORMAT_VERSION,
             meta: Meta::new(),
             config: Config::new(),
             engrave: Engraves::new(),
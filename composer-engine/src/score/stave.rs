@@ -23,3 +23,4 @@ impl Stave {
 }
 
 pub const STAVE_LINE_WIDTH: f32 = 0.125;
+pub const TAB_LINE_SPACING: f32 = 1.5;
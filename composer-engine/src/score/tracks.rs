@@ -1,4 +1,4 @@
-use crate::components::misc::Tick;
+use crate::components::misc::{Tick, Ticks};
 use crate::entries::Entry;
 use crate::utils::shortid;
 use rustc_hash::FxHashMap;
@@ -74,8 +74,244 @@ impl Track {
     }
 }
 
+/// Is the entry a signature or clef that stays in effect until replaced
+fn is_persistent(entry: &Entry) -> bool {
+    matches!(
+        entry,
+        Entry::Clef(_) | Entry::KeySignature(_) | Entry::TimeSignature(_)
+    )
+}
+
+fn is_same_kind(a: &Entry, b: &Entry) -> bool {
+    std::mem::discriminant(a) == std::mem::discriminant(b)
+}
+
+impl Track {
+    /// Open a gap of empty ticks at a tick, moving later entries along.
+    /// Tones that straddle the tick are split, octave shifts are extended over the gap.
+    /// Entries at tick 0 stay put so the flow keeps its initial signatures.
+    pub fn insert_ticks(&mut self, at: Tick, ticks: Ticks) {
+        let mut shifts: Vec<(String, Tick)> = Vec::new();
+        let mut tails: Vec<Entry> = Vec::new();
+
+        for (key, entry) in self.entries.by_key.iter_mut() {
+            let tick = entry.tick();
+            if tick >= at && !(tick == 0 && is_persistent(entry)) {
+                shifts.push((key.clone(), tick + ticks));
+                continue;
+            }
+
+            match entry {
+                Entry::Tone(tone) if tone.tick < at && tone.tick + tone.duration > at => {
+                    let mut tail = tone.clone();
+                    tail.key = shortid();
+                    tail.tick = at + ticks;
+                    tail.duration = tone.tick + tone.duration - at;
+                    tone.duration = at - tone.tick;
+                    tails.push(Entry::Tone(tail));
+                }
+                Entry::OctaveShift(octave_shift)
+                    if octave_shift.tick < at && octave_shift.tick + octave_shift.duration > at =>
+                {
+                    octave_shift.duration += ticks;
+                }
+                _ => (),
+            }
+        }
+
+        for (key, tick) in shifts {
+            self.shift(&key, tick);
+        }
+
+        for entry in tails {
+            self.insert(entry);
+        }
+    }
+
+    /// Remove the ticks between start and end, moving later entries back.
+    /// Tones and octave shifts that straddle the range are truncated and signatures
+    /// in the range are kept at the start if nothing replaces them at the end.
+    pub fn delete_ticks(&mut self, start: Tick, end: Tick) {
+        let ticks = end - start;
+
+        // find the signatures still in effect at the end of the range
+        let mut carried: Vec<(Tick, String)> = Vec::new();
+        let mut in_range: Vec<&Entry> = self
+            .entries
+            .by_key
+            .values()
+            .filter(|entry| entry.tick() >= start && entry.tick() < end)
+            .collect();
+        in_range.sort_by_key(|entry| std::cmp::Reverse(entry.tick()));
+        for entry in in_range {
+            let replaced = self
+                .entries
+                .by_key
+                .values()
+                .any(|other| other.tick() == end && is_same_kind(entry, other))
+                || carried
+                    .iter()
+                    .any(|(_, key)| is_same_kind(entry, self.entries.by_key.get(key).unwrap()));
+            if is_persistent(entry) && !replaced {
+                carried.push((entry.tick(), entry.key()));
+            }
+        }
+
+        let mut removals: Vec<String> = Vec::new();
+        let mut shifts: Vec<(String, Tick)> = Vec::new();
+
+        for (key, entry) in self.entries.by_key.iter_mut() {
+            let tick = entry.tick();
+
+            if tick >= end {
+                shifts.push((key.clone(), tick - ticks));
+                continue;
+            }
+
+            if tick >= start {
+                match entry {
+                    // keep the part of the tone after the range
+                    Entry::Tone(tone) if tone.tick + tone.duration > end => {
+                        tone.duration = tone.tick + tone.duration - end;
+                        shifts.push((key.clone(), start));
+                    }
+                    Entry::OctaveShift(octave_shift)
+                        if octave_shift.tick + octave_shift.duration > end =>
+                    {
+                        octave_shift.duration = octave_shift.tick + octave_shift.duration - end;
+                        shifts.push((key.clone(), start));
+                    }
+                    _ => {
+                        if carried.iter().any(|(_, carried)| carried == key) {
+                            shifts.push((key.clone(), start));
+                        } else {
+                            removals.push(key.clone());
+                        }
+                    }
+                }
+                continue;
+            }
+
+            // entries starting before the range lose the deleted part
+            match entry {
+                Entry::Tone(tone) if tone.tick + tone.duration > start => {
+                    let after = (tone.tick + tone.duration).saturating_sub(end);
+                    tone.duration = start - tone.tick + after;
+                }
+                Entry::OctaveShift(octave_shift)
+                    if octave_shift.tick + octave_shift.duration > start =>
+                {
+                    let after = (octave_shift.tick + octave_shift.duration).saturating_sub(end);
+                    octave_shift.duration = start - octave_shift.tick + after;
+                }
+                _ => (),
+            }
+        }
+
+        for key in removals {
+            self.remove(&key);
+        }
+
+        for (key, tick) in shifts {
+            self.shift(&key, tick);
+        }
+    }
+}
+
 impl Default for Track {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Track;
+    use crate::components::duration::NoteDuration;
+    use crate::entries::time_signature::{TimeSignature, TimeSignatureDrawType};
+    use crate::entries::tone::Tone;
+    use crate::entries::Entry;
+
+    fn tone(key: &str, tick: u32, duration: u32) -> Entry {
+        let mut tone = Tone::tester(key);
+        tone.tick = tick;
+        tone.duration = duration;
+        Entry::Tone(tone)
+    }
+
+    fn get(track: &Track, key: &str) -> (u32, u32) {
+        match track.entries.by_key.get(key) {
+            Some(Entry::Tone(tone)) => (tone.tick, tone.duration),
+            _ => panic!("missing tone"),
+        }
+    }
+
+    #[test]
+    fn insert_ticks_test() {
+        let mut track = Track::new();
+        track.insert(tone("a", 0, 48));
+        track.insert(tone("b", 96, 48));
+        track.insert(tone("c", 48, 96)); // straddles the gap
+        track.insert_ticks(96, 192);
+
+        assert_eq!(get(&track, "a"), (0, 48));
+        assert_eq!(get(&track, "b"), (288, 48));
+        // c is split at the gap
+        assert_eq!(get(&track, "c"), (48, 48));
+        assert_eq!(track.entries.by_key.len(), 4);
+        assert_eq!(track.entries.by_tick.get(&288).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn insert_ticks_keeps_initial_signatures() {
+        let mut track = Track::new();
+        let time_signature = TimeSignature::new(
+            0,
+            4,
+            NoteDuration::Quarter,
+            TimeSignatureDrawType::Regular,
+            None,
+        );
+        let key = time_signature.key.clone();
+        track.insert(Entry::TimeSignature(time_signature));
+        track.insert(tone("a", 0, 48));
+        track.insert_ticks(0, 192);
+
+        assert_eq!(track.entries.by_key.get(&key).unwrap().tick(), 0);
+        assert_eq!(get(&track, "a"), (192, 48));
+    }
+
+    #[test]
+    fn delete_ticks_test() {
+        let mut track = Track::new();
+        track.insert(tone("a", 0, 48));
+        track.insert(tone("b", 96, 48)); // removed
+        track.insert(tone("c", 144, 96)); // straddles the end
+        track.insert(tone("d", 48, 96)); // straddles the start
+        track.insert(tone("e", 240, 48));
+        track.delete_ticks(96, 192);
+
+        assert_eq!(get(&track, "a"), (0, 48));
+        assert!(!track.entries.by_key.contains_key("b"));
+        assert_eq!(get(&track, "c"), (96, 48));
+        assert_eq!(get(&track, "d"), (48, 48));
+        assert_eq!(get(&track, "e"), (144, 48));
+    }
+
+    #[test]
+    fn delete_ticks_carries_signatures() {
+        let mut track = Track::new();
+        let time_signature = TimeSignature::new(
+            0,
+            3,
+            NoteDuration::Quarter,
+            TimeSignatureDrawType::Regular,
+            None,
+        );
+        let key = time_signature.key.clone();
+        track.insert(Entry::TimeSignature(time_signature));
+        track.delete_ticks(0, 144);
+
+        assert_eq!(track.entries.by_key.get(&key).unwrap().tick(), 0);
+    }
+}
@@ -1,4 +1,6 @@
 use rand::Rng;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{SystemTime, UNIX_EPOCH};
 use wasm_bindgen::prelude::wasm_bindgen;
 
 const ALPHABET: &str = "abcdefghijklmnopqrstuvwxyz_ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789-";
@@ -7,6 +9,28 @@ pub fn set_panic_hook() {
     console_error_panic_hook::set_once();
 }
 
+/// Milliseconds since the unix epoch, from the JS clock when running in the browser
+pub fn now() -> f64 {
+    #[cfg(target_arch = "wasm32")]
+    {
+        js_sys::Date::now()
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let elapsed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        elapsed.as_millis() as f64
+    }
+}
+
+/// The greatest common divisor, used to reduce durations to fractions
+pub fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
 pub fn shortid() -> String {
     let mut rng = rand::thread_rng();
     let mut output: String = String::with_capacity(12);